@@ -1,68 +1,633 @@
 //! Aggregator logic for evaluating arbitrage opportunities.
 
 use crate::{
-    arbitrage::{ArbitrageConfig, calculate_gas_cost_usdc, evaluate_opportunities},
+    arbitrage::{
+        ArbitrageConfig, ArbitrageOpportunity, GasEstimate, add_cex_baseline, basis_shortfall,
+        confirm_opportunities, evaluate_across_pools, evaluate_across_venues, implied_basis_bps,
+        is_book_fresh, pnl_interval, pnl_sensitivities,
+    },
+    clock::Clock,
     config::GasConfig,
     dex::PoolState,
-    models::BookDepth,
+    evaluation_log::{EvaluationLogSink, EvaluationRecord, PoolFees},
+    execution::Execution,
+    hooks::{EvaluationHook, EvaluationPass},
+    load_shed::PoolShed,
+    models::{BookDepth, BookStats, MarketSnapshot},
+    oracle::{depeg_bps, twap_divergence_bps},
+    shadow::ShadowEvaluation,
+    sink::OpportunitySink,
 };
-use tokio::sync::watch;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, oneshot, watch};
 use tracing;
 
+/// What drives an evaluation pass.
+pub enum EvalTrigger {
+    /// Evaluate on a fixed wall-clock interval.
+    Interval(tokio::time::Interval),
+    /// Evaluate once per block number published on the channel. Blocks that
+    /// arrive while a pass is still running coalesce into the latest one.
+    NewBlock(watch::Receiver<u64>),
+    /// Evaluate once per replayed event. Each event comes with an
+    /// acknowledgement, sent back once its pass is over, so the replay never
+    /// runs ahead of evaluation.
+    ReplayStep {
+        steps: mpsc::Receiver<oneshot::Sender<()>>,
+        pending: Option<oneshot::Sender<()>>,
+    },
+}
+
+impl EvalTrigger {
+    /// Interval trigger firing every `period`, starting immediately.
+    pub fn every(period: std::time::Duration) -> Self {
+        Self::Interval(tokio::time::interval(period))
+    }
+
+    /// Replay trigger stepping on the events sent over `steps`.
+    pub fn replay_steps(steps: mpsc::Receiver<oneshot::Sender<()>>) -> Self {
+        Self::ReplayStep {
+            steps,
+            pending: None,
+        }
+    }
+
+    /// Wait until the next pass is due; `false` once the block source or
+    /// replay is gone.
+    pub async fn wait(&mut self) -> bool {
+        match self {
+            Self::Interval(ticker) => {
+                ticker.tick().await;
+                true
+            }
+            Self::NewBlock(block_rx) => block_rx.changed().await.is_ok(),
+            Self::ReplayStep { steps, pending } => {
+                // Waiting again means the previous pass is over
+                if let Some(ack) = pending.take() {
+                    let _ = ack.send(());
+                }
+                *pending = steps.recv().await;
+                pending.is_some()
+            }
+        }
+    }
+}
+
+/// Market data channels of one pool, read on every evaluation pass.
+pub struct EvaluatorInputs {
+    /// Chain of the pool behind `pool_rx`
+    pub chain_id: u64,
+    /// Address of the pool behind `pool_rx`, recorded in reported leg venues
+    pub pool: String,
+    /// Book channel per CEX venue name
+    pub cex_rxs: BTreeMap<String, watch::Receiver<BookDepth>>,
+    pub pool_rx: watch::Receiver<PoolState>,
+    pub gas_rx: watch::Receiver<f64>,
+    /// Gas price before smoothing, when the gas watcher publishes it
+    pub raw_gas_rx: Option<watch::Receiver<f64>>,
+    /// Set while the CEX feed is degraded
+    pub degraded_rx: watch::Receiver<bool>,
+    /// Set while evaluation is paused by the operator
+    pub paused_rx: watch::Receiver<bool>,
+    /// Perp funding rate, when the CEX leg is a perpetual
+    pub funding_rx: Option<watch::Receiver<f64>>,
+    /// Externally updated direction bias, overriding the configured one
+    pub bias_rx: Option<watch::Receiver<f64>>,
+    /// Time source for book freshness (the replay clock when backtesting)
+    pub clock: Arc<dyn Clock>,
+    /// Id source shared by every evaluator of the run
+    pub ids: OpportunityIds,
+    /// USD price of the pool's quote token, when a reference is configured
+    pub quote_usd_rx: Option<watch::Receiver<f64>>,
+    /// The pool's TWAP in USDC per ETH, when the TWAP gate is on
+    pub twap_rx: Option<watch::Receiver<f64>>,
+    /// Candidate config evaluated beside the live one, when configured
+    pub shadow: Option<ShadowEvaluation>,
+    /// Where every pass's inputs and outputs are logged for replay, when configured
+    pub evaluation_log: Option<Arc<dyn EvaluationLogSink>>,
+    /// This pool's share of the evaluation latency budget, when one is set
+    pub load_shed: Option<PoolShed>,
+    /// Where high-confidence opportunities are handed off for execution
+    pub execution: Execution,
+    /// Capital budget shared with every other evaluator, when one is set
+    pub notional_budget: Option<NotionalBudget>,
+    /// Run after every evaluation pass, in order, on what it is about to emit
+    pub hooks: Vec<Arc<dyn EvaluationHook>>,
+    /// Other pools of the same pair, named, evaluated together with `pool`
+    /// per `pool_selection`; empty evaluates `pool` alone
+    pub sibling_pools: Vec<(String, watch::Receiver<PoolState>)>,
+}
+
+impl EvaluatorInputs {
+    /// Register `hook` after the ones already registered.
+    ///
+    /// This is how a library user plugs custom logic into the evaluator; the
+    /// binary registers none.
+    pub fn with_hook(mut self, hook: Arc<dyn EvaluationHook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+}
+
+/// Issues opportunity ids that are unique across all evaluators of a run.
+///
+/// Ids are `<run>-<sequence>`; the run label keeps them apart across
+/// restarts writing to the same sink.
+#[derive(Debug, Clone)]
+pub struct OpportunityIds {
+    run: Arc<str>,
+    next: Arc<AtomicU64>,
+}
+
+impl OpportunityIds {
+    pub fn new(run: impl Into<String>) -> Self {
+        Self {
+            run: Arc::from(run.into()),
+            next: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    /// Give `opp` the next id and its detection time, and note both in its
+    /// description.
+    pub fn stamp(&self, opp: &mut ArbitrageOpportunity, detected_at_ms: u64) {
+        let sequence = self.next.fetch_add(1, Ordering::Relaxed);
+        opp.id = format!("{}-{}", self.run, sequence);
+        opp.detected_at_ms = detected_at_ms;
+        opp.description = format!(
+            "{} [id {} at {}]",
+            opp.description, opp.id, opp.detected_at_ms
+        );
+    }
+}
+
 /// Spawn the main arbitrage evaluation loop
+///
+/// On every `trigger` the pool is evaluated against the best fresh prices
+/// across all venues in `inputs`, together with its sibling pools per
+/// `pool_selection` when it has any; the gates below watch the pool itself.
+/// While the degraded flag is set, the degraded thresholds of
+/// `arbitrage_config` apply; while the paused flag is set, `clock` is inside
+/// a blackout window or the quote token is off its USD peg by more than
+/// `max_quote_depeg_bps`, passes are skipped and nothing is emitted, but the
+/// feeds keep running. Passes whose pool price is more than
+/// `max_twap_divergence_bps` from the pool's TWAP are skipped the same way.
+/// A basis above `anomalous_basis_bps` trips a breaker that likewise
+/// suppresses emission until it recedes.
+/// Emitted opportunities are written to `sink`, with PnL also in USD.
 pub async fn spawn_arbitrage_evaluator(
-    cex_rx: watch::Receiver<BookDepth>,
-    pool_rx: watch::Receiver<PoolState>,
-    gas_rx: watch::Receiver<f64>,
+    mut trigger: EvalTrigger,
+    inputs: EvaluatorInputs,
     gas_config: GasConfig,
     arbitrage_config: ArbitrageConfig,
+    sink: Arc<dyn OpportunitySink>,
 ) -> tokio::task::JoinHandle<()> {
+    let EvaluatorInputs {
+        chain_id,
+        pool,
+        cex_rxs,
+        pool_rx,
+        gas_rx,
+        raw_gas_rx,
+        degraded_rx,
+        paused_rx,
+        funding_rx,
+        bias_rx,
+        clock,
+        ids,
+        quote_usd_rx,
+        twap_rx,
+        shadow,
+        evaluation_log,
+        load_shed,
+        execution,
+        notional_budget,
+        hooks,
+        sibling_pools,
+    } = inputs;
     tokio::spawn(async move {
-        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(1));
         let mut ticks: u64 = 0;
+        let mut hysteresis = EmissionHysteresis::default();
+        let mut persistence = ProfitPersistence::default();
+        let mut throttle = EmissionThrottle::default();
+        let mut was_degraded = false;
+        let mut was_paused = false;
+        let mut in_blackout = false;
+        let mut depegged = false;
+        let mut twap_gated = false;
+        let mut breaker_tripped = false;
+        let mut last_snapshot_ms: Option<u64> = None;
+        let mut last_fee_warning_ms: Option<u64> = None;
 
-        loop {
-            ticker.tick().await;
+        while trigger.wait().await {
             ticks += 1;
 
-            let book = cex_rx.borrow().clone();
-            let pool_state = pool_rx.borrow().clone();
+            let paused = *paused_rx.borrow();
+            if paused != was_paused {
+                tracing::warn!(
+                    paused,
+                    "[PAUSE] evaluation {}",
+                    if paused { "paused" } else { "resumed" }
+                );
+                was_paused = paused;
+                // Opportunities must re-enter from scratch after the gap
+                hysteresis = EmissionHysteresis::default();
+                persistence = ProfitPersistence::default();
+            }
+            if paused {
+                continue;
+            }
+
+            let now = clock.now_ms();
+            let blackout = arbitrage_config.blackout_windows.active_at(now);
+            if blackout.is_some() != in_blackout {
+                match blackout {
+                    Some(window) => tracing::warn!(%window, "[BLACKOUT] evaluation suppressed"),
+                    None => tracing::info!("[BLACKOUT] window over, evaluation resumed"),
+                }
+                in_blackout = blackout.is_some();
+                hysteresis = EmissionHysteresis::default();
+                persistence = ProfitPersistence::default();
+            }
+            if in_blackout {
+                continue;
+            }
+
+            // A depegged quote token looks like arbitrage against every venue
+            let usd_per_quote = quote_usd_rx.as_ref().map_or(1.0, |rx| *rx.borrow());
+            let max_depeg_bps = arbitrage_config.max_quote_depeg_bps;
+            let depeg = depeg_bps(usd_per_quote);
+            if (max_depeg_bps > 0.0 && depeg > max_depeg_bps) != depegged {
+                depegged = !depegged;
+                if depegged {
+                    tracing::warn!(
+                        usd_per_quote,
+                        depeg_bps = depeg,
+                        "[DEPEG] quote token off its peg, evaluation suppressed"
+                    );
+                } else {
+                    tracing::info!(
+                        usd_per_quote,
+                        "[DEPEG] quote token back on its peg, evaluation resumed"
+                    );
+                }
+                hysteresis = EmissionHysteresis::default();
+                persistence = ProfitPersistence::default();
+            }
+            if depegged {
+                continue;
+            }
+
+            // One span per pass, exported as a trace under the `otel` feature
+            let tick_span = tracing::info_span!(
+                "eval_tick",
+                chain_id,
+                tick = ticks,
+                basis_bps = tracing::field::Empty,
+                opportunities = tracing::field::Empty,
+                best_pnl = tracing::field::Empty,
+            );
+            let books = tracing::info_span!(parent: &tick_span, "cex_read")
+                .in_scope(|| read_books(&cex_rxs));
+            let pool_state = tracing::info_span!(parent: &tick_span, "pool_read")
+                .in_scope(|| pool_rx.borrow().clone());
             let gas_gwei = *gas_rx.borrow();
 
-            if book.bids.is_empty() || book.asks.is_empty() {
+            // A spot price far from the pool's own TWAP was likely just pushed there
+            let twap_divergence = twap_rx
+                .as_ref()
+                .and_then(|rx| twap_divergence_bps(pool_state.price_usdc_per_eth, *rx.borrow()));
+            let max_twap_bps = arbitrage_config.max_twap_divergence_bps;
+            let diverged = max_twap_bps > 0.0
+                && twap_divergence.is_some_and(|divergence| divergence > max_twap_bps);
+            if diverged != twap_gated {
+                twap_gated = diverged;
+                if twap_gated {
+                    tracing::warn!(
+                        spot = pool_state.price_usdc_per_eth,
+                        divergence_bps = twap_divergence,
+                        "[TWAP] pool spot price far from its TWAP, evaluation suppressed"
+                    );
+                } else {
+                    tracing::info!(
+                        divergence_bps = twap_divergence,
+                        "[TWAP] pool spot price back near its TWAP, evaluation resumed"
+                    );
+                }
+                hysteresis = EmissionHysteresis::default();
+                persistence = ProfitPersistence::default();
+            }
+            if twap_gated {
+                continue;
+            }
+
+            let degraded = *degraded_rx.borrow();
+            if degraded != was_degraded {
+                tracing::warn!(degraded, "[DEGRADED] CEX feed health changed");
+                was_degraded = degraded;
+            }
+            let mut active_config = if degraded {
+                arbitrage_config.degraded()
+            } else {
+                arbitrage_config.clone()
+            };
+            if let Some(funding_rx) = &funding_rx {
+                active_config.funding_rate = *funding_rx.borrow();
+            }
+            if let Some(bias_rx) = &bias_rx {
+                active_config.direction_bias = *bias_rx.borrow();
+            }
+            // Evaluate down to the exit threshold so active opportunities can be tracked
+            let eval_config = ArbitrageConfig {
+                min_pnl_usdc: active_config.min_pnl_usdc - active_config.exit_margin_usdc,
+                ..active_config.clone()
+            };
+
+            let fresh_books: Vec<&BookDepth> = books
+                .iter()
+                .map(|(_, book)| book)
+                .filter(|book| is_book_fresh(book, now, arbitrage_config.max_book_age_ms))
+                .collect();
+            if fresh_books.is_empty() {
                 if ticks % 5 == 0 {
                     tracing::info!("[HEARTBEAT] waiting for streams (dex or cex not ready)");
                 }
+                // Time in profit only counts across passes that saw the edge
+                persistence = ProfitPersistence::default();
                 continue;
             }
 
             let dex_price = pool_state.price_usdc_per_eth;
-
-            // Calculate gas cost
-            let gas_cost_usdc = calculate_gas_cost_usdc(
-                gas_gwei,
-                gas_config.gas_units,
-                gas_config.gas_multiplier,
-                pool_state.price_usdc_per_eth,
+            let max_basis_bps = fresh_books
+                .iter()
+                .filter_map(|book| implied_basis_bps(&pool_state, book))
+                .reduce(f64::max);
+            if let Some(basis_bps) = max_basis_bps {
+                tick_span.record("basis_bps", basis_bps);
+            }
+            let stats = BookStats::from_books(
+                fresh_books.iter().copied(),
+                arbitrage_config.imbalance_levels,
+            )
+            .unwrap_or_default();
+            tracing::debug!(
+                mid = stats.mid,
+                spread_bps = stats.spread_bps,
+                imbalance = stats.imbalance,
+                "[STATS] book"
             );
+            let snapshot_interval_ms = arbitrage_config.snapshot_interval_ms;
+            if snapshot_interval_ms > 0
+                && last_snapshot_ms
+                    .is_none_or(|last| now.saturating_sub(last) >= snapshot_interval_ms)
+            {
+                last_snapshot_ms = Some(now);
+                let raw_gas_gwei = raw_gas_rx.as_ref().map_or(gas_gwei, |rx| *rx.borrow());
+                let snapshot = market_snapshot(
+                    chain_id,
+                    now,
+                    &pool_state,
+                    &fresh_books,
+                    gas_gwei,
+                    raw_gas_gwei,
+                );
+                if let Err(e) = sink.record_snapshot(&snapshot) {
+                    tracing::warn!(error = %e, "[SINK] failed to record market snapshot");
+                }
+            }
+
+            // A basis this wide is almost never a real, safely arbitrageable edge
+            let anomalous_bps = arbitrage_config.anomalous_basis_bps;
+            let anomalous = anomalous_bps > 0.0
+                && max_basis_bps.is_some_and(|basis_bps| basis_bps > anomalous_bps);
+            if anomalous != breaker_tripped {
+                breaker_tripped = anomalous;
+                if breaker_tripped {
+                    tracing::error!(
+                        basis_bps = max_basis_bps,
+                        dex_price,
+                        "[BREAKER] anomalous basis, check the CEX feed and pool state; emission suppressed"
+                    );
+                } else {
+                    tracing::info!(
+                        basis_bps = max_basis_bps,
+                        "[BREAKER] basis back in range, emission resumed"
+                    );
+                }
+                hysteresis = EmissionHysteresis::default();
+                persistence = ProfitPersistence::default();
+            }
+            if breaker_tripped {
+                continue;
+            }
+
+            // Calculate gas cost; per-trade cost grows with the ticks the swap crosses
+            let gas = gas_config.estimate(gas_gwei, pool_state.price_usdc_per_eth);
+            let gas_cost_usdc = gas.base_usdc;
+            let fee_warning_interval_ms = arbitrage_config.fee_warning_interval_ms;
+            if fee_warning_interval_ms > 0
+                && last_fee_warning_ms
+                    .is_none_or(|last| now.saturating_sub(last) >= fee_warning_interval_ms)
+                && let Some((basis_bps, cost_bps)) = basis_shortfall(
+                    &pool_state,
+                    &fresh_venues(&books, now, arbitrage_config.max_book_age_ms),
+                    &active_config,
+                    gas,
+                )
+            {
+                last_fee_warning_ms = Some(now);
+                tracing::warn!(
+                    basis_bps,
+                    cost_bps,
+                    gas_cost_usdc,
+                    "[COSTS] fees and gas exceed the basis on every venue; the pair cannot clear costs"
+                );
+            }
             // Evaluate opportunities
-            let opportunities =
-                evaluate_opportunities(&pool_state, &book, &arbitrage_config, gas_cost_usdc);
+            let pools = read_pools(&pool, &pool_state, &sibling_pools);
+            let evaluate = || {
+                tracing::info_span!(parent: &tick_span, "swap_math")
+                    .in_scope(|| evaluate_pools(&pools, &books, &eval_config, gas, now))
+            };
+            let found = match &load_shed {
+                Some(shed) => match shed.run(evaluate) {
+                    Some(candidates) => candidates,
+                    None => {
+                        tracing::debug!("[SHED] over budget, low-priority pool skipped");
+                        persistence = ProfitPersistence::default();
+                        continue;
+                    }
+                },
+                None => evaluate(),
+            };
+            let (mut sources, mut candidates) = by_source(found);
+            // Written once the pass knows what it emits
+            let mut evaluation_record = evaluation_log.as_ref().map(|_| EvaluationRecord {
+                chain_id,
+                now_ms: now,
+                pool: pool_state.clone(),
+                books: books.clone(),
+                gas,
+                config: eval_config.clone(),
+                opportunities: candidates.clone(),
+                emitted: Vec::new(),
+                dex_fees: Some(PoolFees::of(&pool_state, &eval_config)),
+            });
+            if let Some(shadow) = &shadow {
+                let diffs = shadow.compare(&pool_state, &books, &eval_config, gas, now, chain_id);
+                if !diffs.is_empty() {
+                    tracing::debug!(diffs = diffs.len(), "[SHADOW] candidate config diverged");
+                }
+            }
+            if arbitrage_config.confirm_repricing && !candidates.is_empty() {
+                let pool_rxs =
+                    std::iter::once(&pool_rx).chain(sibling_pools.iter().map(|(_, rx)| rx));
+                wait_for_newer_inputs(&cex_rxs, pool_rxs, arbitrage_config.confirm_wait_ms).await;
+                let books = read_books(&cex_rxs);
+                let pool_state = pool_rx.borrow().clone();
+                let pools = read_pools(&pool, &pool_state, &sibling_pools);
+                let recheck;
+                (sources, recheck) = by_source(evaluate_pools(
+                    &pools,
+                    &books,
+                    &eval_config,
+                    gas,
+                    clock.now_ms(),
+                ));
+                let detected = candidates.len();
+                candidates = confirm_opportunities(candidates, recheck);
+                if candidates.len() < detected {
+                    tracing::debug!(
+                        discarded = detected - candidates.len(),
+                        "[CONFIRM] edge gone on second read"
+                    );
+                }
+            }
+            let profitable = hysteresis.filter(&active_config, candidates);
+            // Throttle and budget are only charged below, for what the hooks let through
+            let mut opportunities = throttle.peek(
+                arbitrage_config.min_emit_interval_ms,
+                now,
+                persistence.filter(arbitrage_config.min_time_in_profit_ms, now, profitable),
+            );
+            if let Some(budget) = &notional_budget {
+                opportunities = budget.peek(now, opportunities);
+            }
+            // Pool each opportunity trades in; none for a split across pools
+            let source_state = |opp: &ArbitrageOpportunity| {
+                let source = sources.get(&opp.signature())?;
+                pools
+                    .iter()
+                    .find(|(name, _)| name == source)
+                    .map(|(_, state)| state)
+            };
+            if arbitrage_config.report_sensitivities {
+                let gas_plus_1_gwei =
+                    gas_config.estimate(gas_gwei + 1.0, pool_state.price_usdc_per_eth);
+                for opp in &mut opportunities {
+                    if let Some(state) = source_state(opp) {
+                        (opp.pnl_per_bp_cex, opp.pnl_per_gwei) = pnl_sensitivities(
+                            state,
+                            &books,
+                            &eval_config,
+                            gas,
+                            gas_plus_1_gwei,
+                            now,
+                            opp,
+                        );
+                    }
+                }
+            }
+            if arbitrage_config.report_pnl_interval {
+                for opp in &mut opportunities {
+                    if let Some(state) = source_state(opp) {
+                        (opp.pnl_low, opp.pnl_high) =
+                            pnl_interval(state, &books, &eval_config, gas, now, opp);
+                    }
+                }
+            }
+            if arbitrage_config.report_cex_baseline {
+                for opp in &mut opportunities {
+                    add_cex_baseline(opp, &books, &eval_config);
+                }
+            }
+            for opp in &mut opportunities {
+                opp.chain_id = chain_id;
+                if let Some(legs) = &mut opp.legs {
+                    legs.chain_id = chain_id;
+                    if legs.pool.is_empty() {
+                        legs.pool.clone_from(&pool);
+                    }
+                }
+                opp.pnl_usd = opp.pnl * usd_per_quote;
+                ids.stamp(opp, now);
+            }
+            if !hooks.is_empty() {
+                let pass = EvaluationPass {
+                    chain_id,
+                    pool: &pool,
+                    tick: ticks,
+                    now_ms: now,
+                    pool_state: &pool_state,
+                    books: &books,
+                    gas,
+                    config: &eval_config,
+                };
+                let proposed: Vec<String> =
+                    opportunities.iter().map(|opp| opp.signature()).collect();
+                for hook in &hooks {
+                    hook.after_evaluation(&pass, &mut opportunities);
+                }
+                let kept: HashSet<String> =
+                    opportunities.iter().map(|opp| opp.signature()).collect();
+                for signature in proposed.iter().filter(|s| !kept.contains(*s)) {
+                    hysteresis.forget(signature);
+                }
+            }
+            if let Some(budget) = &notional_budget {
+                opportunities = budget.admit(now, opportunities);
+            }
+            throttle.record(now, &opportunities);
+            if let (Some(log), Some(record)) = (&evaluation_log, &mut evaluation_record) {
+                record.emitted.clone_from(&opportunities);
+                if let Err(e) = log.record(record) {
+                    tracing::warn!(error = %e, "[EVALLOG] failed to record evaluation");
+                }
+            }
+            tick_span.record("opportunities", opportunities.len());
+            if let Some(best_pnl) = opportunities.iter().map(|opp| opp.pnl).reduce(f64::max) {
+                tick_span.record("best_pnl", best_pnl);
+            }
 
             if !opportunities.is_empty() {
                 let opportunity_logs: Vec<String> = opportunities
                     .iter()
-                    .map(|opp| opp.description.clone())
+                    .map(|opp| format!("[{}] {}", opp.venue, opp.description))
                     .collect();
-                tracing::info!(opps = ?opportunity_logs, "[OPP] opportunities found");
+                tracing::info!(chain_id, opps = ?opportunity_logs, "[OPP] opportunities found");
+                for opp in &opportunities {
+                    if let Err(e) = sink.record(opp) {
+                        tracing::warn!(error = %e, "[SINK] failed to record opportunity");
+                    }
+                }
+                execution.submit_qualifying(&opportunities);
             } else if ticks % 5 == 0 {
-                let (bid_price, _bid_qty) = book.bids[0];
-                let (ask_price, _ask_qty) = book.asks[0];
+                let bid_price = fresh_books
+                    .iter()
+                    .map(|book| book.bids[0].0)
+                    .fold(f64::MIN, f64::max);
+                let ask_price = fresh_books
+                    .iter()
+                    .map(|book| book.asks[0].0)
+                    .fold(f64::MAX, f64::min);
+                let imbalance = stats.imbalance;
                 tracing::info!(
                     dex_price,
                     bid_price,
                     ask_price,
                     gas_gwei,
+                    imbalance,
                     arbitrage_config.dex_fee_bps,
                     arbitrage_config.cex_fee_bps,
                     gas_cost_usdc,
@@ -72,3 +637,1159 @@ pub async fn spawn_arbitrage_evaluator(
         }
     })
 }
+
+/// Flip `pause_tx` on every SIGUSR1, pausing or resuming the evaluators.
+#[cfg(unix)]
+pub fn spawn_pause_signal_listener(
+    pause_tx: watch::Sender<bool>,
+) -> crate::errors::Result<tokio::task::JoinHandle<()>> {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut usr1 = signal(SignalKind::user_defined1())?;
+    Ok(tokio::spawn(async move {
+        while usr1.recv().await.is_some() {
+            pause_tx.send_modify(|paused| *paused = !*paused);
+        }
+    }))
+}
+
+/// Snapshot of the pool against the best bid and ask across `fresh_books`,
+/// which must not be empty.
+fn market_snapshot(
+    chain_id: u64,
+    now_ms: u64,
+    pool_state: &PoolState,
+    fresh_books: &[&BookDepth],
+    gas_gwei: f64,
+    raw_gas_gwei: f64,
+) -> MarketSnapshot {
+    let cex_bid = fresh_books
+        .iter()
+        .map(|book| book.bids[0].0)
+        .fold(f64::MIN, f64::max);
+    let cex_ask = fresh_books
+        .iter()
+        .map(|book| book.asks[0].0)
+        .fold(f64::MAX, f64::min);
+    let cex_mid = (cex_bid + cex_ask) / 2.0;
+    let dex_price = pool_state.price_usdc_per_eth;
+    MarketSnapshot {
+        chain_id,
+        taken_at_ms: now_ms,
+        dex_price,
+        cex_bid,
+        cex_ask,
+        cex_mid,
+        basis_bps: if dex_price > 0.0 {
+            (cex_mid - dex_price) / dex_price * 10_000.0
+        } else {
+            0.0
+        },
+        gas_gwei,
+        raw_gas_gwei,
+        liquidity: pool_state.liquidity,
+    }
+}
+
+/// Current book of every venue.
+fn read_books(cex_rxs: &BTreeMap<String, watch::Receiver<BookDepth>>) -> Vec<(String, BookDepth)> {
+    cex_rxs
+        .iter()
+        .map(|(venue, rx)| (venue.clone(), rx.borrow().clone()))
+        .collect()
+}
+
+/// Books of `books` fresh at `now`.
+fn fresh_venues(
+    books: &[(String, BookDepth)],
+    now: u64,
+    max_age_ms: u64,
+) -> Vec<(String, BookDepth)> {
+    books
+        .iter()
+        .filter(|(_, book)| is_book_fresh(book, now, max_age_ms))
+        .cloned()
+        .collect()
+}
+
+/// The evaluator's own pool under `name`, followed by the latest state of
+/// each sibling.
+fn read_pools(
+    name: &str,
+    pool_state: &PoolState,
+    sibling_pools: &[(String, watch::Receiver<PoolState>)],
+) -> Vec<(String, PoolState)> {
+    std::iter::once((name.to_string(), pool_state.clone()))
+        .chain(
+            sibling_pools
+                .iter()
+                .map(|(name, rx)| (name.clone(), rx.borrow().clone())),
+        )
+        .collect()
+}
+
+/// Evaluate `pools` across `books`, each opportunity with the name of the pool
+/// it trades in: a single pool across venues, several per
+/// `config.pool_selection`.
+fn evaluate_pools(
+    pools: &[(String, PoolState)],
+    books: &[(String, BookDepth)],
+    config: &ArbitrageConfig,
+    gas: GasEstimate,
+    now_ms: u64,
+) -> Vec<(String, ArbitrageOpportunity)> {
+    match pools {
+        [(name, pool_state)] => evaluate_across_venues(pool_state, books, config, gas, now_ms)
+            .into_iter()
+            .map(|opp| (name.clone(), opp))
+            .collect(),
+        _ => evaluate_across_pools(pools, books, config, gas, now_ms),
+    }
+}
+
+/// Opportunities apart from their pools, which are keyed by signature.
+fn by_source(
+    found: Vec<(String, ArbitrageOpportunity)>,
+) -> (HashMap<String, String>, Vec<ArbitrageOpportunity>) {
+    found
+        .into_iter()
+        .map(|(pool, opp)| ((opp.signature(), pool), opp))
+        .unzip()
+}
+
+/// Wait up to `wait_ms` for any venue to publish a book, or any of the pools
+/// a state, newer than the one currently held.
+async fn wait_for_newer_inputs<'a>(
+    cex_rxs: &BTreeMap<String, watch::Receiver<BookDepth>>,
+    pool_rxs: impl Iterator<Item = &'a watch::Receiver<PoolState>>,
+    wait_ms: u64,
+) {
+    if wait_ms == 0 {
+        return;
+    }
+    let updates: Vec<_> = cex_rxs
+        .values()
+        .map(next_change)
+        .chain(pool_rxs.map(next_change))
+        .collect();
+    if updates.is_empty() {
+        return;
+    }
+    let _ = tokio::time::timeout(
+        std::time::Duration::from_millis(wait_ms),
+        futures::future::select_all(updates),
+    )
+    .await;
+}
+
+/// Resolves once `rx` is sent a value after the one currently held.
+fn next_change<T: Send + Sync + 'static>(
+    rx: &watch::Receiver<T>,
+) -> std::pin::Pin<Box<dyn Future<Output = bool> + Send>> {
+    let mut rx = rx.clone();
+    rx.borrow_and_update();
+    Box::pin(async move { rx.changed().await.is_ok() })
+}
+
+/// Hysteresis on opportunity emission to avoid flapping around `min_pnl_usdc`.
+///
+/// An opportunity starts emitting once its PnL reaches `min_pnl + enter_margin`
+/// and keeps emitting until it drops below `min_pnl - exit_margin` (or vanishes).
+#[derive(Debug, Default)]
+pub struct EmissionHysteresis {
+    active: HashSet<String>,
+}
+
+impl EmissionHysteresis {
+    /// Keep only the candidates that should be emitted this tick under
+    /// `config`'s thresholds, updating the per-signature state.
+    pub fn filter(
+        &mut self,
+        config: &ArbitrageConfig,
+        candidates: Vec<ArbitrageOpportunity>,
+    ) -> Vec<ArbitrageOpportunity> {
+        let enter_threshold = config.min_pnl_usdc + config.enter_margin_usdc;
+        let exit_threshold = config.min_pnl_usdc - config.exit_margin_usdc;
+        let mut next_active = HashSet::new();
+        let emitted = candidates
+            .into_iter()
+            .filter(|opp| {
+                let signature = opp.signature();
+                let emit = if self.active.contains(&signature) {
+                    opp.pnl >= exit_threshold
+                } else {
+                    opp.pnl >= enter_threshold
+                };
+                if emit {
+                    next_active.insert(signature);
+                }
+                emit
+            })
+            .collect();
+        self.active = next_active;
+        emitted
+    }
+
+    /// Treat `signature` as not emitting, so it has to clear the entry
+    /// threshold again.
+    pub fn forget(&mut self, signature: &str) {
+        self.active.remove(signature);
+    }
+}
+
+/// Minimum time an opportunity must stay profitable, over consecutive
+/// evaluations, before it is emitted, so that flickers which vanish before
+/// anything could execute on them are never reported.
+#[derive(Debug, Default)]
+pub struct ProfitPersistence {
+    first_seen_ms: HashMap<String, u64>,
+}
+
+impl ProfitPersistence {
+    /// Keep only the candidates whose signature has been profitable on every
+    /// evaluation for at least `min_duration_ms` up to `now_ms` (0 lets
+    /// everything through); a signature missing from a pass starts over.
+    pub fn filter(
+        &mut self,
+        min_duration_ms: u64,
+        now_ms: u64,
+        candidates: Vec<ArbitrageOpportunity>,
+    ) -> Vec<ArbitrageOpportunity> {
+        if min_duration_ms == 0 {
+            return candidates;
+        }
+        let mut first_seen_ms = HashMap::new();
+        let persisted = candidates
+            .into_iter()
+            .filter(|opp| {
+                let signature = opp.signature();
+                let since = self
+                    .first_seen_ms
+                    .get(&signature)
+                    .copied()
+                    .unwrap_or(now_ms);
+                first_seen_ms.insert(signature, since);
+                now_ms.saturating_sub(since) >= min_duration_ms
+            })
+            .collect();
+        self.first_seen_ms = first_seen_ms;
+        persisted
+    }
+}
+
+/// Minimum time between emissions in the same direction, whatever the price
+/// does in between, to bound downstream load.
+#[derive(Debug, Default)]
+pub struct EmissionThrottle {
+    last_emit_ms: HashMap<String, u64>,
+}
+
+impl EmissionThrottle {
+    /// Drop opportunities whose direction emitted less than `min_interval_ms`
+    /// before `now_ms` (0 lets everything through), recording the rest.
+    pub fn filter(
+        &mut self,
+        min_interval_ms: u64,
+        now_ms: u64,
+        opportunities: Vec<ArbitrageOpportunity>,
+    ) -> Vec<ArbitrageOpportunity> {
+        let kept = self.peek(min_interval_ms, now_ms, opportunities);
+        self.record(now_ms, &kept);
+        kept
+    }
+
+    /// [`filter`](Self::filter) without recording anything, so that only
+    /// what is finally emitted gets [`record`](Self::record)ed.
+    pub fn peek(
+        &self,
+        min_interval_ms: u64,
+        now_ms: u64,
+        opportunities: Vec<ArbitrageOpportunity>,
+    ) -> Vec<ArbitrageOpportunity> {
+        if min_interval_ms == 0 {
+            return opportunities;
+        }
+        let mut taken = HashSet::new();
+        opportunities
+            .into_iter()
+            .filter(|opp| {
+                let throttled = matches!(
+                    self.last_emit_ms.get(&opp.direction),
+                    Some(&last) if now_ms.saturating_sub(last) < min_interval_ms
+                );
+                !throttled && taken.insert(opp.direction.clone())
+            })
+            .collect()
+    }
+
+    /// Start the interval of every direction in `emitted` at `now_ms`.
+    pub fn record(&mut self, now_ms: u64, emitted: &[ArbitrageOpportunity]) {
+        for opp in emitted {
+            self.last_emit_ms.insert(opp.direction.clone(), now_ms);
+        }
+    }
+}
+
+/// Capital budget shared by every evaluator: the notional of opportunities
+/// emitted within the last `window_ms` may not exceed `limit_usdc`.
+#[derive(Debug, Clone)]
+pub struct NotionalBudget {
+    limit_usdc: f64,
+    window_ms: u64,
+    /// (emitted at, notional) of everything still in flight
+    in_flight: Arc<Mutex<Vec<(u64, f64)>>>,
+}
+
+impl NotionalBudget {
+    pub fn new(limit_usdc: f64, window_ms: u64) -> Self {
+        Self {
+            limit_usdc,
+            window_ms,
+            in_flight: Arc::default(),
+        }
+    }
+
+    /// Keep opportunities, highest PnL first, while the in-flight notional
+    /// stays within the limit, and record them; the rest are dropped.
+    pub fn admit(
+        &self,
+        now_ms: u64,
+        opportunities: Vec<ArbitrageOpportunity>,
+    ) -> Vec<ArbitrageOpportunity> {
+        self.select(now_ms, opportunities, true)
+    }
+
+    /// [`admit`](Self::admit) without recording anything; what is finally
+    /// emitted must still go through `admit`, which charges the budget.
+    pub fn peek(
+        &self,
+        now_ms: u64,
+        opportunities: Vec<ArbitrageOpportunity>,
+    ) -> Vec<ArbitrageOpportunity> {
+        self.select(now_ms, opportunities, false)
+    }
+
+    fn select(
+        &self,
+        now_ms: u64,
+        opportunities: Vec<ArbitrageOpportunity>,
+        record: bool,
+    ) -> Vec<ArbitrageOpportunity> {
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        in_flight.retain(|(at_ms, _)| now_ms.saturating_sub(*at_ms) < self.window_ms);
+        let mut used: f64 = in_flight.iter().map(|(_, notional)| notional).sum();
+
+        let mut by_priority: Vec<usize> = (0..opportunities.len()).collect();
+        by_priority.sort_by(|&a, &b| opportunities[b].pnl.total_cmp(&opportunities[a].pnl));
+        let mut admitted = vec![false; opportunities.len()];
+        for i in by_priority {
+            let notional = opportunities[i].notional_usdc;
+            if used + notional <= self.limit_usdc {
+                used += notional;
+                if record {
+                    in_flight.push((now_ms, notional));
+                }
+                admitted[i] = true;
+            } else {
+                tracing::debug!(
+                    notional,
+                    in_flight = used,
+                    limit = self.limit_usdc,
+                    "[BUDGET] opportunity dropped, capital budget exhausted"
+                );
+            }
+        }
+        opportunities
+            .into_iter()
+            .zip(admitted)
+            .filter_map(|(opp, admitted)| admitted.then_some(opp))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opp(pnl: f64) -> ArbitrageOpportunity {
+        ArbitrageOpportunity {
+            direction: "A".to_string(),
+            venue: "binance".to_string(),
+            pnl,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn new_block_trigger_fires_once_per_block() {
+        let (block_tx, block_rx) = watch::channel(0u64);
+        let mut trigger = EvalTrigger::NewBlock(block_rx);
+        let idle = std::time::Duration::from_millis(20);
+
+        // Nothing is due before the first block arrives
+        assert!(tokio::time::timeout(idle, trigger.wait()).await.is_err());
+        for block in 1..=3 {
+            block_tx.send(block).unwrap();
+            assert!(trigger.wait().await);
+            assert_eq!(*trigger_block(&trigger), block);
+            // Exactly one pass per block: no further wake-up until the next one
+            assert!(tokio::time::timeout(idle, trigger.wait()).await.is_err());
+        }
+
+        drop(block_tx);
+        assert!(!trigger.wait().await);
+    }
+
+    fn trigger_block(trigger: &EvalTrigger) -> watch::Ref<'_, u64> {
+        match trigger {
+            EvalTrigger::NewBlock(block_rx) => block_rx.borrow(),
+            _ => panic!("not a block trigger"),
+        }
+    }
+
+    /// Forwards every recorded opportunity and snapshot to a channel.
+    struct ChannelSink(
+        tokio::sync::mpsc::UnboundedSender<ArbitrageOpportunity>,
+        tokio::sync::mpsc::UnboundedSender<MarketSnapshot>,
+    );
+
+    impl OpportunitySink for ChannelSink {
+        fn record(&self, opportunity: &ArbitrageOpportunity) -> crate::errors::Result<()> {
+            let _ = self.0.send(opportunity.clone());
+            Ok(())
+        }
+
+        fn record_snapshot(&self, snapshot: &MarketSnapshot) -> crate::errors::Result<()> {
+            let _ = self.1.send(snapshot.clone());
+            Ok(())
+        }
+    }
+
+    /// An evaluator on a block trigger over a pool at 4000 and a book bid at
+    /// 4020, so every pass finds a Direction A opportunity.
+    struct Harness {
+        block_tx: watch::Sender<u64>,
+        pause_tx: watch::Sender<bool>,
+        opp_rx: tokio::sync::mpsc::UnboundedReceiver<ArbitrageOpportunity>,
+        snapshot_rx: tokio::sync::mpsc::UnboundedReceiver<MarketSnapshot>,
+        clock: Arc<crate::clock::MockClock>,
+        task: tokio::task::JoinHandle<()>,
+        feeds: Feeds,
+    }
+
+    /// Senders of the harness's inputs, held so the evaluator keeps running.
+    struct Feeds {
+        cex_tx: watch::Sender<BookDepth>,
+        pool_tx: watch::Sender<PoolState>,
+        _gas_tx: watch::Sender<f64>,
+        _degraded_tx: watch::Sender<bool>,
+        twap_tx: watch::Sender<f64>,
+    }
+
+    impl Harness {
+        async fn spawn() -> Self {
+            Self::spawn_with(ArbitrageConfig::default(), None).await
+        }
+
+        async fn spawn_with(
+            arbitrage_config: ArbitrageConfig,
+            quote_usd_rx: Option<watch::Receiver<f64>>,
+        ) -> Self {
+            Self::spawn_custom(arbitrage_config, |inputs| EvaluatorInputs {
+                quote_usd_rx,
+                ..inputs
+            })
+            .await
+        }
+
+        /// Spawn with the default inputs passed through `customize`.
+        async fn spawn_custom(
+            arbitrage_config: ArbitrageConfig,
+            customize: impl FnOnce(EvaluatorInputs) -> EvaluatorInputs,
+        ) -> Self {
+            use crate::clock::MockClock;
+            use crate::dex::calc::calculate_sqrt_price_with_precision_per_eth;
+
+            let sqrt_price_x96 =
+                calculate_sqrt_price_with_precision_per_eth(4_000.0, 6, 18).unwrap();
+            let pool = PoolState::new(
+                sqrt_price_x96,
+                1_800_000_000_000_000_000,
+                0,
+                6,
+                18,
+                None,
+                None,
+                4_000.0,
+            );
+            let book = BookDepth {
+                bids: vec![(4_020.0, 1e9)],
+                asks: vec![(4_030.0, 1e9)],
+                received_at_ms: 1_000,
+                ..Default::default()
+            };
+            let (cex_tx, cex_rx) = watch::channel(book);
+            let (pool_tx, pool_rx) = watch::channel(pool);
+            let (gas_tx, gas_rx) = watch::channel(0.0);
+            let (degraded_tx, degraded_rx) = watch::channel(false);
+            let (twap_tx, twap_rx) = watch::channel(4_000.0);
+            let (pause_tx, paused_rx) = watch::channel(false);
+            let (block_tx, block_rx) = watch::channel(0u64);
+            let (opp_tx, opp_rx) = tokio::sync::mpsc::unbounded_channel();
+            let (snapshot_tx, snapshot_rx) = tokio::sync::mpsc::unbounded_channel();
+            let clock = Arc::new(MockClock::new(1_000));
+
+            let task = spawn_arbitrage_evaluator(
+                EvalTrigger::NewBlock(block_rx),
+                customize(EvaluatorInputs {
+                    chain_id: 1,
+                    pool: "pool".to_string(),
+                    cex_rxs: BTreeMap::from([("binance".to_string(), cex_rx)]),
+                    pool_rx,
+                    gas_rx,
+                    raw_gas_rx: None,
+                    degraded_rx,
+                    paused_rx,
+                    funding_rx: None,
+                    bias_rx: None,
+                    clock: clock.clone(),
+                    ids: OpportunityIds::new("test"),
+                    quote_usd_rx: None,
+                    twap_rx: Some(twap_rx),
+                    shadow: None,
+                    evaluation_log: None,
+                    load_shed: None,
+                    execution: Execution::default(),
+                    notional_budget: None,
+                    hooks: Vec::new(),
+                    sibling_pools: Vec::new(),
+                }),
+                GasConfig {
+                    gas_units: 0.0,
+                    gas_multiplier: 1.0,
+                    mode: crate::config::GasCostMode::FixedUsd(0.0),
+                    units_by_chain: BTreeMap::new(),
+                    per_tick_gas_units: 0.0,
+                    priority_bid_gwei: 0.0,
+                    precision: crate::config::PrecisionMode::Fast,
+                },
+                arbitrage_config,
+                Arc::new(ChannelSink(opp_tx, snapshot_tx)),
+            )
+            .await;
+            Self {
+                block_tx,
+                pause_tx,
+                opp_rx,
+                snapshot_rx,
+                clock,
+                task,
+                feeds: Feeds {
+                    cex_tx,
+                    pool_tx,
+                    _gas_tx: gas_tx,
+                    _degraded_tx: degraded_tx,
+                    twap_tx,
+                },
+            }
+        }
+
+        /// Trigger one pass and return the first opportunity it emitted.
+        async fn pass(&mut self, block: u64) -> Option<ArbitrageOpportunity> {
+            self.block_tx.send(block).unwrap();
+            let idle = std::time::Duration::from_millis(50);
+            tokio::time::timeout(idle, self.opp_rx.recv())
+                .await
+                .ok()
+                .flatten()
+        }
+
+        /// Trigger one pass and report whether it emitted anything.
+        async fn pass_emits(&mut self, block: u64) -> bool {
+            self.pass(block).await.is_some()
+        }
+    }
+
+    #[test]
+    fn stamped_ids_are_unique_and_detection_times_monotonic() {
+        use crate::clock::MockClock;
+
+        let clock = MockClock::new(1_000);
+        let ids = OpportunityIds::new("run");
+        let other_pool = ids.clone();
+        let mut batch = Vec::new();
+        for pass in 0..3 {
+            for source in [&ids, &other_pool] {
+                let mut opp = opp(10.0 + pass as f64);
+                source.stamp(&mut opp, clock.now_ms());
+                batch.push(opp);
+            }
+            clock.advance(250);
+        }
+
+        let unique: HashSet<&str> = batch.iter().map(|opp| opp.id.as_str()).collect();
+        assert_eq!(unique.len(), batch.len());
+        assert!(
+            batch
+                .windows(2)
+                .all(|pair| pair[0].detected_at_ms <= pair[1].detected_at_ms)
+        );
+        assert_eq!(batch[0].id, "run-1");
+        assert_eq!(batch[5].detected_at_ms, 1_500);
+        assert!(batch[5].description.ends_with("[id run-6 at 1500]"));
+    }
+
+    #[tokio::test]
+    async fn pause_toggle_stops_and_restarts_emission() {
+        let mut harness = Harness::spawn().await;
+        assert!(harness.pass_emits(1).await);
+
+        harness.pause_tx.send_modify(|paused| *paused = !*paused);
+        assert!(!harness.pass_emits(2).await);
+
+        harness.pause_tx.send_modify(|paused| *paused = !*paused);
+        assert!(harness.pass_emits(3).await);
+        harness.task.abort();
+    }
+
+    #[tokio::test]
+    async fn hooks_see_every_pass_and_may_filter_it() {
+        /// Ids of the opportunities it was shown, per tick
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<(u64, Vec<String>)>>);
+        impl EvaluationHook for Recorder {
+            fn after_evaluation(
+                &self,
+                pass: &EvaluationPass<'_>,
+                opportunities: &mut Vec<ArbitrageOpportunity>,
+            ) {
+                assert_eq!(pass.pool, "pool");
+                assert_eq!(pass.books.len(), 1);
+                let ids = opportunities.iter().map(|opp| opp.id.clone()).collect();
+                self.0.lock().unwrap().push((pass.tick, ids));
+            }
+        }
+        /// Drops everything on the second tick
+        struct Veto;
+        impl EvaluationHook for Veto {
+            fn after_evaluation(
+                &self,
+                pass: &EvaluationPass<'_>,
+                opportunities: &mut Vec<ArbitrageOpportunity>,
+            ) {
+                if pass.tick == 2 {
+                    opportunities.clear();
+                }
+            }
+        }
+
+        let recorder = Arc::new(Recorder::default());
+        let hooks: Vec<Arc<dyn EvaluationHook>> = vec![recorder.clone(), Arc::new(Veto)];
+        let mut harness = Harness::spawn_custom(ArbitrageConfig::default(), |inputs| {
+            EvaluatorInputs { hooks, ..inputs }
+        })
+        .await;
+        let mut emitted = Vec::new();
+        for block in 1..=3 {
+            emitted.push(harness.pass(block).await.map(|opp| opp.id));
+        }
+        harness.task.abort();
+
+        let seen = recorder.0.lock().unwrap().clone();
+        assert_eq!(
+            seen.iter().map(|(tick, _)| *tick).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(seen.iter().all(|(_, ids)| ids.len() == 1));
+        // The recorder runs first and sees the vetoed tick; only the others emit
+        assert_eq!(emitted[0].as_ref(), Some(&seen[0].1[0]));
+        assert_eq!(emitted[1], None);
+        assert_eq!(emitted[2].as_ref(), Some(&seen[2].1[0]));
+    }
+
+    #[tokio::test]
+    async fn stale_book_gap_restarts_the_time_in_profit() {
+        let config = ArbitrageConfig {
+            min_time_in_profit_ms: 500,
+            max_book_age_ms: 5_000,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_with(config, None).await;
+        let refresh_book = |harness: &Harness| {
+            let now = harness.clock.now_ms();
+            harness
+                .feeds
+                .cex_tx
+                .send_modify(|book| book.received_at_ms = now);
+        };
+        assert!(!harness.pass_emits(1).await);
+
+        // The book goes stale for 10 s, then comes back with the same edge
+        harness.clock.advance(10_000);
+        assert!(!harness.pass_emits(2).await);
+        refresh_book(&harness);
+        assert!(!harness.pass_emits(3).await);
+
+        // In profit for the minimum time since the book came back
+        harness.clock.advance(500);
+        refresh_book(&harness);
+        assert!(harness.pass_emits(4).await);
+        harness.task.abort();
+    }
+
+    #[tokio::test]
+    async fn confirmation_rereads_the_pool_update_it_waited_for() {
+        let config = ArbitrageConfig {
+            confirm_repricing: true,
+            confirm_wait_ms: 60_000,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_with(config, None).await;
+        let update_pool_soon = |harness: &Harness, price: f64| {
+            let pool_tx = harness.feeds.pool_tx.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                pool_tx.send_replace(pool_at(price));
+            })
+        };
+
+        // The pool catches up with the CEX while the detection waits
+        update_pool_soon(&harness, 4_025.0);
+        assert!(!harness.pass_emits(1).await);
+
+        // With the edge back, an update that keeps it confirms it
+        harness.feeds.pool_tx.send_replace(pool_at(4_000.0));
+        update_pool_soon(&harness, 4_000.0);
+        assert!(harness.pass_emits(2).await);
+        harness.task.abort();
+    }
+
+    #[tokio::test]
+    async fn vetoed_pass_charges_neither_throttle_nor_budget() {
+        /// Drops everything on the first tick
+        struct VetoFirst;
+        impl EvaluationHook for VetoFirst {
+            fn after_evaluation(
+                &self,
+                pass: &EvaluationPass<'_>,
+                opportunities: &mut Vec<ArbitrageOpportunity>,
+            ) {
+                if pass.tick == 1 {
+                    opportunities.clear();
+                }
+            }
+        }
+
+        let mut harness = Harness::spawn().await;
+        let notional = harness.pass(1).await.expect("pass emits").notional_usdc;
+        harness.task.abort();
+
+        // Room for one opportunity, and one emission per direction per minute
+        let budget = NotionalBudget::new(notional * 1.5, 60_000);
+        let config = ArbitrageConfig {
+            min_emit_interval_ms: 60_000,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_custom(config, |inputs| {
+            EvaluatorInputs {
+                notional_budget: Some(budget),
+                ..inputs
+            }
+            .with_hook(Arc::new(VetoFirst))
+        })
+        .await;
+        assert!(!harness.pass_emits(1).await);
+        assert!(harness.pass_emits(2).await);
+        // The emitted one is charged to both
+        assert!(!harness.pass_emits(3).await);
+        harness.task.abort();
+    }
+
+    #[tokio::test]
+    async fn evaluation_log_records_what_the_pass_emitted() {
+        #[derive(Default)]
+        struct Records(std::sync::Mutex<Vec<EvaluationRecord>>);
+        impl EvaluationLogSink for Records {
+            fn record(&self, record: &EvaluationRecord) -> crate::errors::Result<()> {
+                self.0.lock().unwrap().push(record.clone());
+                Ok(())
+            }
+        }
+
+        let log = Arc::new(Records::default());
+        let config = ArbitrageConfig {
+            min_emit_interval_ms: 60_000,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_custom(config, |inputs| EvaluatorInputs {
+            evaluation_log: Some(log.clone()),
+            ..inputs
+        })
+        .await;
+        let emitted = harness.pass(1).await.expect("pass emits");
+        // Throttled: evaluated again, but nothing emitted
+        assert!(!harness.pass_emits(2).await);
+        harness.task.abort();
+
+        let records = log.0.lock().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].opportunities.len(), 1);
+        assert_eq!(records[0].emitted.len(), 1);
+        assert_eq!(records[0].emitted[0].id, emitted.id);
+        assert_eq!(records[1].opportunities.len(), 1);
+        assert!(records[1].emitted.is_empty());
+        // The candidates still replay; what was emitted is there to compare
+        assert_eq!(records[1].replay().len(), 1);
+    }
+
+    /// Pool quoting `price`, as deep as the harness's own.
+    fn pool_at(price: f64) -> PoolState {
+        use crate::dex::calc::calculate_sqrt_price_with_precision_per_eth;
+
+        let sqrt_price_x96 = calculate_sqrt_price_with_precision_per_eth(price, 6, 18).unwrap();
+        PoolState::new(
+            sqrt_price_x96,
+            1_800_000_000_000_000_000,
+            0,
+            6,
+            18,
+            None,
+            None,
+            price,
+        )
+    }
+
+    #[tokio::test]
+    async fn sibling_pool_quoting_a_better_price_is_traded_instead() {
+        // ETH is cheaper in the sibling, so buying it there clears more
+        let (_sibling_tx, sibling_rx) = watch::channel(pool_at(3_990.0));
+        let config = ArbitrageConfig {
+            report_leg_venues: true,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_custom(config, |inputs| EvaluatorInputs {
+            sibling_pools: vec![("sibling".to_string(), sibling_rx)],
+            ..inputs
+        })
+        .await;
+        let opp = harness.pass(1).await.expect("pass emits");
+        harness.task.abort();
+        assert_eq!(opp.direction, "A");
+        assert_eq!(opp.legs.expect("legs reported").pool, "sibling");
+        assert!(opp.description.contains("pool sibling"));
+    }
+
+    #[tokio::test]
+    async fn split_selection_trades_across_the_pools() {
+        let (_sibling_tx, sibling_rx) = watch::channel(pool_at(4_000.0));
+        let config = ArbitrageConfig {
+            pool_selection: crate::arbitrage::PoolSelection::Split,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_custom(config, |inputs| EvaluatorInputs {
+            sibling_pools: vec![("sibling".to_string(), sibling_rx)],
+            ..inputs
+        })
+        .await;
+        let opp = harness.pass(1).await.expect("pass emits");
+        harness.task.abort();
+        assert!(
+            opp.description.contains("split pool"),
+            "{}",
+            opp.description
+        );
+        assert!(opp.description.contains("sibling"));
+    }
+
+    #[tokio::test]
+    async fn depegged_quote_adjusts_pnl_and_optionally_gates() {
+        // USDC at 97 cents: PnL in USDC is worth 3% less in USD
+        let (usd_tx, usd_rx) = watch::channel(0.97);
+        let mut harness = Harness::spawn_with(ArbitrageConfig::default(), Some(usd_rx)).await;
+        let opp = harness.pass(1).await.expect("ungated pass emits");
+        assert!(opp.pnl > 0.0);
+        assert!((opp.pnl_usd - opp.pnl * 0.97).abs() < 1e-9);
+        harness.task.abort();
+        drop(usd_tx);
+
+        let gated = ArbitrageConfig {
+            max_quote_depeg_bps: 100.0,
+            ..Default::default()
+        };
+        let (usd_tx, usd_rx) = watch::channel(0.97);
+        let mut harness = Harness::spawn_with(gated, Some(usd_rx)).await;
+        assert!(!harness.pass_emits(1).await);
+
+        // Back within 100 bps of the peg
+        usd_tx.send(0.995).unwrap();
+        let opp = harness.pass(2).await.expect("repegged pass emits");
+        assert!((opp.pnl_usd - opp.pnl * 0.995).abs() < 1e-9);
+        harness.task.abort();
+    }
+
+    #[tokio::test]
+    async fn spot_far_from_the_twap_gates_evaluation() {
+        let config = ArbitrageConfig {
+            max_twap_divergence_bps: 50.0,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_with(config, None).await;
+        assert!(harness.pass_emits(1).await);
+
+        // The TWAP says 4100: the 4000 spot was just pushed ~244 bps away
+        harness.feeds.twap_tx.send(4_100.0).unwrap();
+        assert!(!harness.pass_emits(2).await);
+
+        // Within 25 bps of the TWAP again
+        harness.feeds.twap_tx.send(4_010.0).unwrap();
+        assert!(harness.pass_emits(3).await);
+        harness.task.abort();
+    }
+
+    #[tokio::test]
+    async fn anomalous_basis_trips_the_breaker() {
+        // The harness basis of 62.5 bps is past a 50 bps breaker
+        let config = ArbitrageConfig {
+            anomalous_basis_bps: 50.0,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_with(config, None).await;
+        assert!(!harness.pass_emits(1).await);
+        assert!(!harness.pass_emits(2).await);
+
+        // A 37.5 bps basis is back in range
+        harness.feeds.cex_tx.send_modify(|book| {
+            book.bids = vec![(4_014.0, 1e9)];
+            book.asks = vec![(4_016.0, 1e9)];
+        });
+        assert!(harness.pass_emits(3).await);
+        harness.task.abort();
+    }
+
+    #[tokio::test]
+    async fn market_snapshot_is_recorded_every_interval() {
+        let config = ArbitrageConfig {
+            snapshot_interval_ms: 500,
+            ..Default::default()
+        };
+        let mut harness = Harness::spawn_with(config, None).await;
+        assert!(harness.pass_emits(1).await);
+        let snapshot = harness
+            .snapshot_rx
+            .try_recv()
+            .expect("first pass snapshots");
+        assert_eq!(
+            snapshot,
+            MarketSnapshot {
+                chain_id: 1,
+                taken_at_ms: 1_000,
+                dex_price: 4_000.0,
+                cex_bid: 4_020.0,
+                cex_ask: 4_030.0,
+                cex_mid: 4_025.0,
+                basis_bps: 62.5,
+                gas_gwei: 0.0,
+                raw_gas_gwei: 0.0,
+                liquidity: 1_800_000_000_000_000_000,
+            }
+        );
+        let record = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(record["record"], "market_snapshot");
+
+        // Within the interval nothing new is recorded, at its end the next one is
+        harness.clock.advance(200);
+        assert!(harness.pass_emits(2).await);
+        assert!(harness.snapshot_rx.try_recv().is_err());
+        harness.clock.advance(300);
+        assert!(harness.pass_emits(3).await);
+        let snapshot = harness.snapshot_rx.try_recv().expect("interval elapsed");
+        assert_eq!(snapshot.taken_at_ms, 1_500);
+        harness.task.abort();
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test]
+    async fn evaluation_pass_exports_tick_span_with_children() {
+        use opentelemetry::Value;
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+        use tracing_subscriber::prelude::*;
+
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber =
+            tracing_subscriber::registry().with(crate::telemetry::otel_layer(&provider));
+        // The current-thread test runtime polls the evaluator on this thread
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut harness = Harness::spawn().await;
+        assert!(harness.pass_emits(1).await);
+        harness.task.abort();
+        provider.force_flush().unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let tick = spans
+            .iter()
+            .find(|span| span.name == "eval_tick")
+            .expect("tick span");
+        let attribute = |key: &str| {
+            tick.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        };
+        assert_eq!(
+            attribute("opportunities").map(|v| v.to_string()),
+            Some("1".to_string())
+        );
+        assert!(matches!(attribute("best_pnl"), Some(Value::F64(pnl)) if pnl > 0.0));
+        assert!(matches!(attribute("basis_bps"), Some(Value::F64(bps)) if bps > 0.0));
+
+        for child in ["cex_read", "pool_read", "swap_math"] {
+            let span = spans
+                .iter()
+                .find(|span| span.name == child)
+                .unwrap_or_else(|| panic!("{child} span"));
+            assert_eq!(span.parent_span_id, tick.span_context.span_id());
+        }
+    }
+
+    #[test]
+    fn hysteresis_enters_above_margin_and_exits_below_margin() {
+        let cfg = ArbitrageConfig {
+            min_pnl_usdc: 10.0,
+            enter_margin_usdc: 2.0,
+            exit_margin_usdc: 3.0,
+            ..Default::default()
+        };
+        let mut hysteresis = EmissionHysteresis::default();
+
+        // (pnl, expected emission): rises through 12 to enter, falls through 7 to exit
+        let path = [
+            (10.5, false),
+            (11.9, false),
+            (12.0, true),
+            (9.0, true),
+            (7.0, true),
+            (6.9, false),
+            (11.0, false),
+            (12.5, true),
+        ];
+        for (pnl, expected) in path {
+            let emitted = hysteresis.filter(&cfg, vec![opp(pnl)]);
+            assert_eq!(!emitted.is_empty(), expected, "pnl {}", pnl);
+        }
+
+        // An opportunity that disappears must re-enter from scratch
+        assert!(hysteresis.filter(&cfg, vec![]).is_empty());
+        assert!(hysteresis.filter(&cfg, vec![opp(11.0)]).is_empty());
+    }
+
+    #[test]
+    fn throttle_spaces_same_direction_emissions() {
+        use crate::clock::{Clock, MockClock};
+
+        let clock = MockClock::new(0);
+        let mut throttle = EmissionThrottle::default();
+        let direction_b = || ArbitrageOpportunity {
+            direction: "B".to_string(),
+            pnl: 20.0,
+            ..Default::default()
+        };
+
+        // A profitable tick every 100ms for 2s with a 500ms interval
+        let mut emitted_a = Vec::new();
+        let mut emitted_b = 0;
+        for _ in 0..20 {
+            let now = clock.now_ms();
+            let candidates = if now == 300 {
+                vec![opp(20.0), direction_b()]
+            } else {
+                vec![opp(20.0)]
+            };
+            for opp in throttle.filter(500, now, candidates) {
+                match opp.direction.as_str() {
+                    "A" => emitted_a.push(now),
+                    _ => emitted_b += 1,
+                }
+            }
+            clock.advance(100);
+        }
+
+        assert_eq!(emitted_a, vec![0, 500, 1_000, 1_500]);
+        // Direction B is throttled separately
+        assert_eq!(emitted_b, 1);
+        assert_eq!(throttle.filter(0, 1_600, vec![opp(20.0)]).len(), 1);
+    }
+
+    #[test]
+    fn persistence_requires_the_minimum_time_in_profit() {
+        use crate::clock::{Clock, MockClock};
+
+        let clock = MockClock::new(0);
+        let mut persistence = ProfitPersistence::default();
+
+        // Profitable for 300ms, under the 500ms minimum, then gone
+        for _ in 0..4 {
+            assert!(
+                persistence
+                    .filter(500, clock.now_ms(), vec![opp(20.0)])
+                    .is_empty()
+            );
+            clock.advance(100);
+        }
+        assert!(persistence.filter(500, clock.now_ms(), vec![]).is_empty());
+        clock.advance(100);
+
+        // Back again: the clock starts over and emission waits the full 500ms
+        let mut emitted = Vec::new();
+        for _ in 0..8 {
+            let now = clock.now_ms();
+            if !persistence.filter(500, now, vec![opp(20.0)]).is_empty() {
+                emitted.push(now);
+            }
+            clock.advance(100);
+        }
+        assert_eq!(emitted, vec![1_000, 1_100, 1_200]);
+
+        // No minimum emits at once
+        let mut fresh = ProfitPersistence::default();
+        assert_eq!(fresh.filter(0, 0, vec![opp(20.0)]).len(), 1);
+    }
+
+    #[test]
+    fn notional_budget_drops_lowest_priority_over_the_cap() {
+        let sized = |pnl: f64, notional_usdc: f64| ArbitrageOpportunity {
+            notional_usdc,
+            ..opp(pnl)
+        };
+        let budget = NotionalBudget::new(10_000.0, 60_000);
+        // Another pool's evaluator shares the budget
+        let other_pool = budget.clone();
+
+        // 4k + 5k fit, the 3k with the lowest PnL would exceed the cap
+        let admitted = budget.admit(
+            0,
+            vec![
+                sized(10.0, 4_000.0),
+                sized(5.0, 3_000.0),
+                sized(30.0, 5_000.0),
+            ],
+        );
+        let pnls: Vec<f64> = admitted.iter().map(|opp| opp.pnl).collect();
+        assert_eq!(pnls, vec![10.0, 30.0]);
+
+        // 1k left in flight: the small one fits, the bigger better one does not
+        let admitted = other_pool.admit(1_000, vec![sized(50.0, 2_000.0), sized(1.0, 1_000.0)]);
+        assert_eq!(admitted.len(), 1);
+        assert_eq!(admitted[0].pnl, 1.0);
+        assert!(budget.admit(2_000, vec![sized(99.0, 1.0)]).is_empty());
+
+        // Once the window has passed the capital is free again
+        assert_eq!(budget.admit(60_000, vec![sized(50.0, 2_000.0)]).len(), 1);
+    }
+}
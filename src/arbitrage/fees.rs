@@ -0,0 +1,142 @@
+//! Exchange fee schedules tiered by trailing 30-day volume.
+//!
+//! A schedule file maps each venue to its tiers, e.g.
+//!
+//! ```json
+//! {"binance": {"tiers": [
+//!     {"min_volume_usdc": 0, "maker_bps": 10.0, "taker_bps": 10.0},
+//!     {"min_volume_usdc": 1000000, "maker_bps": 9.0, "taker_bps": 10.0},
+//!     {"min_volume_usdc": 5000000, "maker_bps": 8.0, "taker_bps": 9.0}
+//! ]}}
+//! ```
+
+use crate::errors::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Which side of the book the CEX leg is charged as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FeeRole {
+    /// Crossing the spread (what the evaluator models)
+    #[default]
+    Taker,
+    /// Resting orders, e.g. when the hedge is worked passively
+    Maker,
+}
+
+impl std::str::FromStr for FeeRole {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "taker" => Ok(Self::Taker),
+            "maker" => Ok(Self::Maker),
+            other => Err(AppError::Config(format!(
+                "CEX_FEE_ROLE must be `taker` or `maker`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// One volume tier; negative fees are rebates.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeeTier {
+    /// 30-day volume from which this tier applies
+    pub min_volume_usdc: f64,
+    pub maker_bps: f64,
+    pub taker_bps: f64,
+}
+
+impl FeeTier {
+    pub fn fee_bps(&self, role: FeeRole) -> f64 {
+        match role {
+            FeeRole::Taker => self.taker_bps,
+            FeeRole::Maker => self.maker_bps,
+        }
+    }
+}
+
+/// Fee tiers of one venue, sorted by `min_volume_usdc`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeSchedule {
+    tiers: Vec<FeeTier>,
+}
+
+impl FeeSchedule {
+    /// Build a schedule, rejecting empty or nonsensical tiers.
+    pub fn new(mut tiers: Vec<FeeTier>) -> Result<Self> {
+        if tiers.is_empty() {
+            return Err(AppError::Config("fee schedule has no tiers".to_string()));
+        }
+        for tier in &tiers {
+            let fees_ok = [tier.maker_bps, tier.taker_bps]
+                .iter()
+                .all(|bps| *bps > -10_000.0 && *bps < 10_000.0);
+            if !fees_ok || tier.min_volume_usdc.is_nan() || tier.min_volume_usdc < 0.0 {
+                return Err(AppError::Config(format!(
+                    "invalid fee tier {:?}: volume must be non-negative and fees in (-10000, 10000) bps",
+                    tier
+                )));
+            }
+        }
+        tiers.sort_by(|a, b| a.min_volume_usdc.total_cmp(&b.min_volume_usdc));
+        Ok(Self { tiers })
+    }
+
+    /// Highest tier whose threshold `volume_usdc` reaches; the lowest tier
+    /// when it reaches none.
+    pub fn tier(&self, volume_usdc: f64) -> &FeeTier {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|tier| tier.min_volume_usdc <= volume_usdc)
+            .unwrap_or(&self.tiers[0])
+    }
+
+    /// Fee in bps charged at `volume_usdc` for `role`.
+    pub fn fee_bps(&self, volume_usdc: f64, role: FeeRole) -> f64 {
+        self.tier(volume_usdc).fee_bps(role)
+    }
+}
+
+/// Load per-venue schedules from a JSON file; venue names are lowercased to
+/// match the evaluator's venue labels.
+pub fn load_fee_schedules(path: impl AsRef<Path>) -> Result<BTreeMap<String, FeeSchedule>> {
+    let raw = std::fs::read_to_string(path)?;
+    let parsed: BTreeMap<String, FeeSchedule> = serde_json::from_str(&raw)?;
+    parsed
+        .into_iter()
+        .map(|(venue, schedule)| Ok((venue.to_lowercase(), FeeSchedule::new(schedule.tiers)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_file_picks_tier_by_volume() {
+        let path = std::env::temp_dir().join(format!("fee-schedule-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"Binance": {"tiers": [
+                {"min_volume_usdc": 5000000, "maker_bps": 6.0, "taker_bps": 8.0},
+                {"min_volume_usdc": 0, "maker_bps": 10.0, "taker_bps": 10.0},
+                {"min_volume_usdc": 1000000, "maker_bps": 9.0, "taker_bps": 10.0}
+            ]}}"#,
+        )
+        .unwrap();
+        let schedules = load_fee_schedules(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let binance = &schedules["binance"];
+        assert_eq!(binance.fee_bps(0.0, FeeRole::Taker), 10.0);
+        assert_eq!(binance.fee_bps(2_000_000.0, FeeRole::Maker), 9.0);
+        assert_eq!(binance.fee_bps(5_000_000.0, FeeRole::Taker), 8.0);
+        assert_eq!(binance.fee_bps(50_000_000.0, FeeRole::Maker), 6.0);
+
+        assert!(FeeSchedule::new(Vec::new()).is_err());
+    }
+}
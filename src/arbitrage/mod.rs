@@ -1,5 +1,18 @@
 pub mod evaluator;
+pub mod fees;
+pub mod sizing;
 pub mod types;
 
-pub use evaluator::{calculate_gas_cost_usdc, evaluate_opportunities};
-pub use types::{ArbitrageConfig, ArbitrageOpportunity};
+pub use evaluator::{
+    add_cex_baseline, basis_shortfall, calculate_gas_cost_usdc, calculate_gas_cost_usdc_exact,
+    confirm_opportunities, evaluate_across_pools, evaluate_across_venues, evaluate_direction,
+    evaluate_opportunities, evaluate_route, implied_basis_bps, is_book_fresh, pnl_interval,
+    pnl_sensitivities, round_trip_cost_bps,
+};
+pub use fees::{FeeRole, FeeSchedule, FeeTier, load_fee_schedules};
+pub use sizing::{SizeConstraint, SizeLimit, cex_depth_eth, dex_slippage_bps, size_limit};
+pub use types::{
+    ArbitrageConfig, ArbitrageOpportunity, DepthGuard, DoubleEdgePolicy, ExecutionLegs,
+    GasEstimate, OPPORTUNITY_SCHEMA_VERSION, PoolLeg, PoolSelection, ScoreFn,
+    check_opportunity_schema_version,
+};
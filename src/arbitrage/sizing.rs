@@ -0,0 +1,115 @@
+//! Trade sizing bounded by CEX depth and DEX slippage together.
+//!
+//! With `max_slippage_bps` set, both legs get the same tolerance: the CEX
+//! leg may walk the book while its average price stays within it of the
+//! touch, and the DEX leg may trade while its price impact stays within it of
+//! the pool price. The size is the largest one satisfying both.
+
+use super::evaluator::{fill_for_size, lp_fee_bps};
+use super::types::ArbitrageConfig;
+use crate::dex::PoolState;
+use crate::models::{BookDepth, SwapDirection, SwapResult};
+use serde::{Deserialize, Serialize};
+
+/// What capped an opportunity's size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeConstraint {
+    /// The CEX book ran out of quantity within the slippage tolerance
+    CexDepth,
+    /// The DEX leg's price impact reached the slippage tolerance
+    DexSlippage,
+    /// Neither: the PnL optimum (or the pool depth guard) stopped short
+    Edge,
+}
+
+/// Largest size both legs can trade, and which limit set it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeLimit {
+    pub size_eth: f64,
+    pub binding: SizeConstraint,
+}
+
+/// ETH the bid (`bid`) or ask side of `book` fills before its average price
+/// moves more than `max_slippage_bps` from the touch.
+pub fn cex_depth_eth(book: &BookDepth, bid: bool, max_slippage_bps: f64) -> f64 {
+    let levels = if bid { &book.bids } else { &book.asks };
+    let Some(&(touch, _)) = levels.first() else {
+        return 0.0;
+    };
+    let tolerance = max_slippage_bps / 10_000.0;
+    let bound = if bid {
+        touch * (1.0 - tolerance)
+    } else {
+        touch * (1.0 + tolerance)
+    };
+    let (mut qty, mut value) = (0.0, 0.0);
+    for &(price, level_qty) in levels {
+        let worse = if bid { price < bound } else { price > bound };
+        if worse {
+            // Take only what keeps the average at the bound
+            let extra = (bound * qty - value) / (price - bound);
+            return qty + extra.clamp(0.0, level_qty);
+        }
+        qty += level_qty;
+        value += price * level_qty;
+    }
+    qty
+}
+
+/// Price impact of a DEX fill on `pool_state` in bps, the LP fee taken out
+/// of its average price.
+pub fn dex_slippage_bps(
+    pool_state: &PoolState,
+    direction: SwapDirection,
+    res: &SwapResult,
+    config: &ArbitrageConfig,
+) -> f64 {
+    let spot = pool_state.price_usdc_per_eth;
+    let fee = lp_fee_bps(pool_state, direction, config) / 10_000.0;
+    // The fee is charged on the input: USDC when buying ETH, ETH when selling
+    let price = match direction {
+        SwapDirection::Token0ToToken1 => res.avg_price * (1.0 - fee),
+        SwapDirection::Token1ToToken0 => res.avg_price / (1.0 - fee),
+    };
+    (price - spot).abs() / spot * 10_000.0
+}
+
+/// Largest size in `direction` within both the CEX depth and the DEX
+/// slippage tolerance of `config.max_slippage_bps`; `None` when nothing fits.
+pub fn size_limit(
+    pool_state: &PoolState,
+    book: &BookDepth,
+    direction: SwapDirection,
+    config: &ArbitrageConfig,
+) -> Option<SizeLimit> {
+    let tolerance = config.max_slippage_bps;
+    // Buying ETH on the DEX sells it into the CEX bids, and vice versa
+    let cex_eth = cex_depth_eth(book, direction == SwapDirection::Token0ToToken1, tolerance);
+    if cex_eth <= 0.0 || cex_eth.is_nan() {
+        return None;
+    }
+    let within = |size_eth: f64| {
+        fill_for_size(pool_state, direction, size_eth, config)
+            .is_some_and(|res| dex_slippage_bps(pool_state, direction, &res, config) <= tolerance)
+    };
+    if within(cex_eth) {
+        return Some(SizeLimit {
+            size_eth: cex_eth,
+            binding: SizeConstraint::CexDepth,
+        });
+    }
+    let (mut near, mut far) = (0.0, cex_eth);
+    for _ in 0..32 {
+        let mid = (near + far) / 2.0;
+        if within(mid) {
+            near = mid;
+        } else {
+            far = mid;
+        }
+    }
+    (near > 0.0).then_some(SizeLimit {
+        size_eth: near,
+        binding: SizeConstraint::DexSlippage,
+    })
+}
@@ -1,15 +1,971 @@
+use super::fees::{FeeRole, FeeSchedule};
+use super::sizing::SizeConstraint;
+use crate::blackout::BlackoutWindows;
+use crate::dex::{FeeModel, SwapOptions};
+use crate::errors::{AppError, Result};
+use crate::models::SwapDirection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
 /// Configuration for arbitrage calculations
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ArbitrageConfig {
     pub min_pnl_usdc: f64,
+    /// Minimum PnL as a fraction of trade notional, in bps (0 disables)
+    pub min_pnl_bps: f64,
+    /// Never emit a trade whose PnL is not strictly positive, whatever the thresholds
+    pub require_positive_net_edge: bool,
+    /// Drop trades whose gas eats more than this fraction of the gross profit (0 disables)
+    pub max_gas_fraction: f64,
     pub dex_fee_bps: f64,
+    /// Where the DEX swap fee comes from; `Fixed` charges `dex_fee_bps`
+    /// (a `Dynamic` model is code, so it is not serialized)
+    #[serde(skip)]
+    pub dex_fee_model: FeeModel,
+    /// CEX fee (taker, or maker in the maker model); negative values model a rebate
     pub cex_fee_bps: f64,
+    /// Skip the swap math when the CEX mid / DEX price basis is below this (0 disables)
+    pub min_basis_bps: f64,
+    /// Basis treated as a broken feed or pool rather than an edge: above it the
+    /// breaker trips and nothing is emitted (0 disables)
+    pub anomalous_basis_bps: f64,
+    /// Ignore CEX venues whose book is older than this (0 disables)
+    pub max_book_age_ms: u64,
+    /// Extra PnL above `min_pnl_usdc` required before an opportunity starts emitting
+    pub enter_margin_usdc: f64,
+    /// PnL below `min_pnl_usdc` tolerated before an emitting opportunity stops
+    pub exit_margin_usdc: f64,
+    /// Book levels per side used for the imbalance statistic (0 means all)
+    pub imbalance_levels: usize,
+    /// Cap on segment boundaries a single DEX swap may cross (0 = unlimited)
+    pub max_ticks_traversed: usize,
+    /// Cap on how far a solved target may move the pool price, in bps (0 = unlimited)
+    pub max_target_move_bps: f64,
+    /// Trade sizes in ETH to evaluate, reporting the one with the highest PnL
+    /// (empty = the continuous optimum up to the CEX price)
+    pub candidate_sizes_eth: Vec<f64>,
+    /// Clamp on the CEX quantity a DEX swap is capped by, in the swap's input
+    /// token (0 = none); guards the sizing against absurd reported quantities
+    pub max_amount_clamp: f64,
+    /// Multiple of the trade's ETH size the pool must hold up to the target
+    /// price (0 = off); a thinner pool is handled per `depth_guard`
+    pub min_depth_ratio: f64,
+    /// Whether a trade failing `min_depth_ratio` is down-sized or skipped
+    pub depth_guard: DepthGuard,
+    /// Slippage tolerance in bps for both legs (0 = off): the CEX leg may walk
+    /// the book while its average stays this close to the touch, and the DEX
+    /// leg may move its average this far from the pool price
+    pub max_slippage_bps: f64,
+    /// Size DEX swaps only within the current tick (conservative sizing)
+    pub current_tick_only: bool,
+    /// Tightening of the PnL thresholds while the CEX feed is degraded, see
+    /// [`ArbitrageConfig::degraded`] (≤ 1 disables)
+    pub degraded_pnl_factor: f64,
+    /// Minimum CEX order notional per venue (e.g. Binance `MIN_NOTIONAL`)
+    pub cex_min_notional_usdc: BTreeMap<String, f64>,
+    /// Minimum USDC notional of the DEX leg
+    pub dex_min_notional_usdc: f64,
+    /// Order in which opportunities are returned, best first
+    pub score_fn: ScoreFn,
+    /// Preference between directions in [-1, 1] (positive favours A, negative
+    /// B): the favoured direction's score is raised by this fraction of itself
+    /// when ranking and when picking the larger of a double edge
+    pub direction_bias: f64,
+    /// What to do when directions A and B are profitable at the same time
+    pub double_edge_policy: DoubleEdgePolicy,
+    /// Under `EmitBoth`, price the smaller direction against the pool as the
+    /// larger one's DEX leg leaves it, as if both landed in the same block
+    pub sequence_double_edge: bool,
+    /// Report each emitted opportunity's PnL sensitivity to the CEX price and gas
+    pub report_sensitivities: bool,
+    /// Report each emitted opportunity against a CEX-only round trip of its size
+    pub report_cex_baseline: bool,
+    /// Record on each opportunity the CEX venue and pool each leg executes on
+    /// (`legs`), for executors routing the legs
+    pub report_leg_venues: bool,
+    /// Report each emitted opportunity's PnL as an interval over its input
+    /// uncertainty (`pnl_low`, `pnl_high`)
+    pub report_pnl_interval: bool,
+    /// Plausible CEX price drift per second of book age, in bps, that the
+    /// PnL interval allows for
+    pub pnl_interval_drift_bps_per_s: f64,
+    /// Fraction of the pool's liquidity that may be taken or added before the
+    /// trade lands, for the PnL interval
+    pub pnl_interval_liquidity_fraction: f64,
+    /// How one pool is picked when several trade the pair
+    pub pool_selection: PoolSelection,
+    /// Funding rate per interval when the CEX leg is a perpetual (0 for spot);
+    /// refreshed from the mark price feed on every pass
+    pub funding_rate: f64,
+    /// Funding intervals the perp hedge is expected to be held for
+    pub funding_periods: f64,
+    /// Re-read the book and pool after a detection and emit only what still clears
+    pub confirm_repricing: bool,
+    /// How long the confirmation waits for a newer CEX book or pool state
+    /// before re-reading (0 = re-read now, on the inputs just evaluated)
+    pub confirm_wait_ms: u64,
+    /// Volume-tiered fee schedules per venue, overriding `cex_fee_bps` there
+    pub cex_fee_schedules: BTreeMap<String, FeeSchedule>,
+    /// Trailing 30-day volume that selects the schedule tier
+    pub cex_volume_30d_usdc: f64,
+    /// Whether the schedule's maker or taker fee applies
+    pub cex_fee_role: FeeRole,
+    /// UTC windows during which nothing is evaluated
+    pub blackout_windows: BlackoutWindows,
+    /// Minimum time between two emissions in the same direction (0 = no limit)
+    pub min_emit_interval_ms: u64,
+    /// How long an opportunity must stay profitable across consecutive
+    /// evaluations before it is emitted (0 = emit at once)
+    pub min_time_in_profit_ms: u64,
+    /// Record a market snapshot to the sink this often, edge or not (0 = never)
+    pub snapshot_interval_ms: u64,
+    /// Warn at most this often while fees and gas exceed every venue's basis
+    /// (0 = never)
+    pub fee_warning_interval_ms: u64,
+    /// Skip evaluation while the quote token's USD reference is further than
+    /// this from $1, in bps (0 = never)
+    pub max_quote_depeg_bps: f64,
+    /// Skip evaluation while the pool's spot price is further than this from
+    /// its own TWAP, in bps, as a sign of a manipulated spot (0 = never)
+    pub max_twap_divergence_bps: f64,
+    /// Probability that a resting CEX order fills; when set the CEX leg is a
+    /// maker order at the near touch (maker fee) and the DEX leg the taker
+    pub cex_maker_fill_probability: Option<f64>,
+}
+
+impl ArbitrageConfig {
+    /// Reject fee settings that would make the fee arithmetic meaningless.
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..10_000.0).contains(&self.dex_fee_bps) {
+            return Err(AppError::Config(format!(
+                "DEX_FEE_BPS must be in [0, 10000), got {}",
+                self.dex_fee_bps
+            )));
+        }
+        if !(self.cex_fee_bps > -10_000.0 && self.cex_fee_bps < 10_000.0) {
+            return Err(AppError::Config(format!(
+                "CEX_FEE_BPS must be in (-10000, 10000), got {}",
+                self.cex_fee_bps
+            )));
+        }
+        if self.max_amount_clamp < 0.0 || self.max_amount_clamp.is_nan() {
+            return Err(AppError::Config(format!(
+                "MAX_AMOUNT_CLAMP must be non-negative, got {}",
+                self.max_amount_clamp
+            )));
+        }
+        if self.anomalous_basis_bps < 0.0 || self.anomalous_basis_bps.is_nan() {
+            return Err(AppError::Config(format!(
+                "ANOMALOUS_BASIS_BPS must be non-negative, got {}",
+                self.anomalous_basis_bps
+            )));
+        }
+        if self.pnl_interval_drift_bps_per_s < 0.0 || self.pnl_interval_drift_bps_per_s.is_nan() {
+            return Err(AppError::Config(format!(
+                "PNL_INTERVAL_DRIFT_BPS_PER_S must be non-negative, got {}",
+                self.pnl_interval_drift_bps_per_s
+            )));
+        }
+        if !(0.0..1.0).contains(&self.pnl_interval_liquidity_fraction) {
+            return Err(AppError::Config(format!(
+                "PNL_INTERVAL_LIQUIDITY_FRACTION must be in [0, 1), got {}",
+                self.pnl_interval_liquidity_fraction
+            )));
+        }
+        if self.max_twap_divergence_bps < 0.0 || self.max_twap_divergence_bps.is_nan() {
+            return Err(AppError::Config(format!(
+                "MAX_TWAP_DIVERGENCE_BPS must be non-negative, got {}",
+                self.max_twap_divergence_bps
+            )));
+        }
+        if self.min_depth_ratio < 0.0 || self.min_depth_ratio.is_nan() {
+            return Err(AppError::Config(format!(
+                "MIN_DEPTH_RATIO must be non-negative, got {}",
+                self.min_depth_ratio
+            )));
+        }
+        if self.max_slippage_bps < 0.0 || self.max_slippage_bps.is_nan() {
+            return Err(AppError::Config(format!(
+                "MAX_SLIPPAGE_BPS must be non-negative, got {}",
+                self.max_slippage_bps
+            )));
+        }
+        if self.max_target_move_bps < 0.0 || self.max_target_move_bps.is_nan() {
+            return Err(AppError::Config(format!(
+                "MAX_TARGET_MOVE_BPS must be non-negative, got {}",
+                self.max_target_move_bps
+            )));
+        }
+        if let Some(size) = self
+            .candidate_sizes_eth
+            .iter()
+            .find(|size| !(**size > 0.0 && size.is_finite()))
+        {
+            return Err(AppError::Config(format!(
+                "CANDIDATE_SIZES_ETH must all be positive, got {}",
+                size
+            )));
+        }
+        if let Some(fill_probability) = self.cex_maker_fill_probability
+            && !(fill_probability > 0.0 && fill_probability <= 1.0)
+        {
+            return Err(AppError::Config(format!(
+                "CEX_MAKER_FILL_PROBABILITY must be in (0, 1], got {}",
+                fill_probability
+            )));
+        }
+        if self.max_gas_fraction < 0.0 || self.max_gas_fraction.is_nan() {
+            return Err(AppError::Config(format!(
+                "MAX_GAS_FRACTION must be non-negative, got {}",
+                self.max_gas_fraction
+            )));
+        }
+        if !(-1.0..=1.0).contains(&self.direction_bias) {
+            return Err(AppError::Config(format!(
+                "DIRECTION_BIAS must be in [-1, 1], got {}",
+                self.direction_bias
+            )));
+        }
+        if self.enter_margin_usdc < 0.0 || self.exit_margin_usdc < 0.0 {
+            return Err(AppError::Config(
+                "HYSTERESIS_ENTER_USDC and HYSTERESIS_EXIT_USDC must be non-negative".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// CEX fee charged on `venue`: its schedule's tier at `cex_volume_30d_usdc`
+    /// when one is loaded, `cex_fee_bps` otherwise.
+    pub fn cex_fee_bps_for(&self, venue: &str) -> f64 {
+        self.cex_fee_schedules
+            .get(venue)
+            .map_or(self.cex_fee_bps, |schedule| {
+                let role = if self.cex_leg_rests() {
+                    FeeRole::Maker
+                } else {
+                    self.cex_fee_role
+                };
+                schedule.fee_bps(self.cex_volume_30d_usdc, role)
+            })
+    }
+
+    /// Whether the CEX leg is a resting maker order rather than a taker fill.
+    pub fn cex_leg_rests(&self) -> bool {
+        self.cex_maker_fill_probability.is_some()
+    }
+
+    /// PnL of a trade from its CEX/DEX revenue and cost, gas and funding.
+    ///
+    /// In the maker model the DEX leg and its gas are certain but the hedge
+    /// only fills with `cex_maker_fill_probability`; an unfilled hedge is
+    /// assumed to be unwound flat, so the edge is discounted and gas is not.
+    pub fn expected_pnl(&self, revenue: f64, cost: f64, gas: f64, funding: f64) -> f64 {
+        match self.cex_maker_fill_probability {
+            None => revenue - cost - gas - funding,
+            Some(fill_probability) => fill_probability * (revenue - cost - funding) - gas,
+        }
+    }
+
+    /// `fees` of a trade as expected in the maker model, where they are only
+    /// paid when the resting order fills.
+    pub fn expected_fees(&self, fees: f64) -> f64 {
+        match self.cex_maker_fill_probability {
+            None => fees,
+            Some(fill_probability) => fill_probability * fees,
+        }
+    }
+
+    /// This config with `cex_fee_bps` resolved for `venue`.
+    pub fn for_venue(&self, venue: &str) -> Self {
+        Self {
+            cex_fee_bps: self.cex_fee_bps_for(venue),
+            ..self.clone()
+        }
+    }
+
+    /// Thresholds to apply while the CEX feed is degraded: `min_pnl_usdc`
+    /// and `min_pnl_bps` each rise by `factor - 1` times their magnitude, so
+    /// a negative threshold tightens too, and the trade must also make money
+    /// outright, which is what tightens a zero threshold.
+    pub fn degraded(&self) -> Self {
+        if self.degraded_pnl_factor <= 1.0 {
+            return self.clone();
+        }
+        let tighten =
+            |threshold: f64| threshold + threshold.abs() * (self.degraded_pnl_factor - 1.0);
+        Self {
+            min_pnl_usdc: tighten(self.min_pnl_usdc),
+            min_pnl_bps: tighten(self.min_pnl_bps),
+            require_positive_net_edge: true,
+            ..self.clone()
+        }
+    }
+
+    /// Smallest PnL worth acting on for a trade of `notional_usdc`:
+    /// `max(min_pnl_usdc, min_pnl_bps × notional / 10_000)`.
+    ///
+    /// With a bps threshold set, a trade without positive notional can never
+    /// clear it, so its hurdle is infinite.
+    pub fn pnl_hurdle_usdc(&self, notional_usdc: f64) -> f64 {
+        if self.min_pnl_bps <= 0.0 {
+            self.min_pnl_usdc
+        } else if notional_usdc > 0.0 {
+            self.min_pnl_usdc
+                .max(self.min_pnl_bps * notional_usdc / 10_000.0)
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Net edge of a trade after all costs, relative to the hurdle:
+    ///
+    /// ```text
+    /// pnl      = CEX leg (net of CEX fee/rebate) − DEX leg (incl. LP fee) − gas
+    /// net_edge = pnl − pnl_hurdle_usdc(notional)
+    /// ```
+    pub fn net_edge_usdc(&self, pnl: f64, notional_usdc: f64) -> f64 {
+        pnl - self.pnl_hurdle_usdc(notional_usdc)
+    }
+
+    /// The single "is this worth doing" gate: the net edge is non-negative and,
+    /// under `require_positive_net_edge`, the trade also makes money outright
+    /// (guarding against a zero or negative `min_pnl_usdc`).
+    pub fn clears_net_edge(&self, pnl: f64, notional_usdc: f64) -> bool {
+        self.net_edge_usdc(pnl, notional_usdc) >= 0.0
+            && (!self.require_positive_net_edge || pnl > 0.0)
+    }
+
+    /// Whether gas stays within `max_gas_fraction` of the gross profit
+    /// (`pnl + gas_cost_usdc`). Trades mostly paying for gas turn into losses
+    /// on a small gas spike, whatever their net PnL.
+    pub fn gas_fraction_ok(&self, pnl: f64, gas_cost_usdc: f64) -> bool {
+        if self.max_gas_fraction <= 0.0 {
+            return true;
+        }
+        let gross = pnl + gas_cost_usdc;
+        gross > 0.0 && gas_cost_usdc / gross <= self.max_gas_fraction
+    }
+
+    /// Funding paid (positive) or received (negative) in USDC on a perp leg
+    /// of `notional_usdc` held for `funding_periods`. Longs pay shorts when
+    /// the rate is positive; direction A shorts the perp, direction B buys it.
+    pub fn funding_carry_usdc(&self, short_perp: bool, notional_usdc: f64) -> f64 {
+        let carry = self.funding_rate * notional_usdc * self.funding_periods;
+        if short_perp { -carry } else { carry }
+    }
+
+    /// `value` of an opportunity in `direction`, raised by `direction_bias`
+    /// of its magnitude when that direction is the favoured one.
+    pub fn biased(&self, direction: &str, value: f64) -> f64 {
+        let favoured = match direction {
+            "A" => self.direction_bias > 0.0,
+            "B" => self.direction_bias < 0.0,
+            _ => false,
+        };
+        if favoured {
+            value + self.direction_bias.abs() * value.abs()
+        } else {
+            value
+        }
+    }
+
+    /// `opp`'s `score_fn` score with the direction bias applied.
+    pub fn ranking_score(&self, opp: &ArbitrageOpportunity) -> f64 {
+        self.biased(&opp.direction, self.score_fn.score(opp))
+    }
+
+    /// Sort `opportunities` best first by `score_fn`, with the direction bias.
+    pub fn rank(&self, opportunities: &mut [ArbitrageOpportunity]) {
+        opportunities.sort_by(|a, b| self.ranking_score(b).total_cmp(&self.ranking_score(a)));
+    }
+
+    /// Swap limits derived from this config.
+    pub fn swap_options(&self) -> SwapOptions {
+        SwapOptions {
+            max_ticks_traversed: self.max_ticks_traversed,
+            max_target_move_bps: self.max_target_move_bps,
+            current_tick_only: self.current_tick_only,
+            fee_model: self.dex_fee_model.clone(),
+            max_amount_clamp: self.max_amount_clamp,
+        }
+    }
+}
+
+/// Gas cost of one trade in USDC, optionally growing with the ticks the DEX
+/// swap crosses: `base_usdc + per_tick_usdc × ticks_crossed`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct GasEstimate {
+    pub base_usdc: f64,
+    /// Extra cost per initialized tick crossed (0 = flat gas model)
+    pub per_tick_usdc: f64,
+}
+
+impl GasEstimate {
+    /// Cost of a swap crossing `ticks_crossed` initialized ticks.
+    pub fn for_ticks(&self, ticks_crossed: usize) -> f64 {
+        self.base_usdc + self.per_tick_usdc * ticks_crossed as f64
+    }
+}
+
+impl From<f64> for GasEstimate {
+    /// A flat cost regardless of ticks crossed.
+    fn from(base_usdc: f64) -> Self {
+        Self {
+            base_usdc,
+            per_tick_usdc: 0.0,
+        }
+    }
+}
+
+/// Handling of a book crossed around the pool, where both directions profit.
+///
+/// That usually means stale or invalid inputs rather than a real double edge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DoubleEdgePolicy {
+    /// Emit both directions
+    EmitBoth,
+    /// Emit only the direction with the larger PnL
+    #[default]
+    EmitLarger,
+    /// Treat it as a sanity failure and emit neither
+    Suppress,
+}
+
+impl std::str::FromStr for DoubleEdgePolicy {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "both" => Ok(Self::EmitBoth),
+            "larger" => Ok(Self::EmitLarger),
+            "suppress" => Ok(Self::Suppress),
+            other => Err(AppError::Config(format!(
+                "DOUBLE_EDGE_POLICY must be `both`, `larger` or `suppress`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// How one pool is picked per direction when several trade the same pair.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PoolSelection {
+    /// The pool with the best marginal price for the direction
+    #[default]
+    BestPrice,
+    /// The pool with the highest executable PnL at the intended size, so a
+    /// marginally better price on a thin pool loses to a deep pool once the
+    /// trade is large enough for its impact to matter
+    Liquidity,
+    /// Every pool at once, the trade split so their marginal prices meet
+    Split,
+}
+
+impl std::str::FromStr for PoolSelection {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "price" => Ok(Self::BestPrice),
+            "liquidity" => Ok(Self::Liquidity),
+            "split" => Ok(Self::Split),
+            other => Err(AppError::Config(format!(
+                "POOL_SELECTION must be `price`, `liquidity` or `split`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// What happens to a trade the pool is too thin for under `min_depth_ratio`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepthGuard {
+    /// Shrink the trade to the size the pool depth still covers
+    #[default]
+    DownSize,
+    /// Drop the trade
+    Skip,
+}
+
+impl std::str::FromStr for DepthGuard {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "downsize" => Ok(Self::DownSize),
+            "skip" => Ok(Self::Skip),
+            other => Err(AppError::Config(format!(
+                "DEPTH_GUARD must be `downsize` or `skip`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// How opportunities are ranked when several are found at once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScoreFn {
+    /// Absolute PnL in USDC
+    #[default]
+    Pnl,
+    /// PnL per USDC of gas, favouring cheap-to-execute trades
+    PnlPerGas,
+    /// PnL in bps of notional, i.e. return on capital
+    PnlBps,
+}
+
+impl ScoreFn {
+    pub fn score(&self, opp: &ArbitrageOpportunity) -> f64 {
+        match self {
+            Self::Pnl => opp.pnl,
+            Self::PnlPerGas => opp.pnl / opp.gas_cost_usdc.max(1e-9),
+            Self::PnlBps if opp.notional_usdc > 0.0 => opp.pnl / opp.notional_usdc * 10_000.0,
+            Self::PnlBps => f64::MIN,
+        }
+    }
+
+    /// Sort `opportunities` by descending score.
+    pub fn rank(&self, opportunities: &mut [ArbitrageOpportunity]) {
+        opportunities.sort_by(|a, b| self.score(b).total_cmp(&self.score(a)));
+    }
+}
+
+impl std::str::FromStr for ScoreFn {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "pnl" => Ok(Self::Pnl),
+            "pnl_per_gas" => Ok(Self::PnlPerGas),
+            "pnl_bps" => Ok(Self::PnlBps),
+            other => Err(AppError::Config(format!(
+                "SCORE_FN must be `pnl`, `pnl_per_gas` or `pnl_bps`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// Version of the [`ArbitrageOpportunity`] record written by this build.
+///
+/// - 1: `direction`, `venue`, `description`, `pnl`, `size_eth`, `notional_usdc`
+/// - 2: adds `schema_version`, `chain_id`, `id`, `detected_at_ms`, `pnl_usd`,
+///   `gas_cost_usdc` and `dex_vwap`
+/// - 3: adds `pnl_per_bp_cex` and `pnl_per_gwei`
+/// - 4: adds `cex_only_pnl` and `incremental_pnl` (omitted unless reported)
+/// - 5: adds `gross_pnl_usdc`, `fees_usdc` and `net_pnl_usdc`
+/// - 6: adds `pnl_low` and `pnl_high`
+/// - 7: adds `size_constraint` (omitted unless slippage-bounded sizing is on)
+/// - 8: adds `legs` (omitted unless leg venues are reported)
+///
+/// Fields added after v1 are `#[serde(default)]`, so records of any earlier
+/// version still deserialize.
+pub const OPPORTUNITY_SCHEMA_VERSION: u32 = 8;
+
+/// Fields each schema version added, oldest first.
+const SCHEMA_FIELDS: [&[&str]; OPPORTUNITY_SCHEMA_VERSION as usize] = [
+    &[
+        "direction",
+        "venue",
+        "description",
+        "pnl",
+        "size_eth",
+        "notional_usdc",
+    ],
+    &[
+        "schema_version",
+        "chain_id",
+        "id",
+        "detected_at_ms",
+        "pnl_usd",
+        "gas_cost_usdc",
+        "dex_vwap",
+    ],
+    &["pnl_per_bp_cex", "pnl_per_gwei"],
+    &["cex_only_pnl", "incremental_pnl"],
+    &["gross_pnl_usdc", "fees_usdc", "net_pnl_usdc"],
+    &["pnl_low", "pnl_high"],
+    &["size_constraint"],
+    &["legs"],
+];
+
+/// Records without a `schema_version` predate it, i.e. are v1.
+fn schema_v1() -> u32 {
+    1
+}
+
+/// `version` if this build can write opportunities in it.
+pub fn check_opportunity_schema_version(version: u32) -> Result<u32> {
+    if (1..=OPPORTUNITY_SCHEMA_VERSION).contains(&version) {
+        Ok(version)
+    } else {
+        Err(AppError::Config(format!(
+            "OPPORTUNITY_SCHEMA_VERSION must be between 1 and {}, got {}",
+            OPPORTUNITY_SCHEMA_VERSION, version
+        )))
+    }
 }
 
 /// Result of arbitrage opportunity evaluation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ArbitrageOpportunity {
+    /// Schema the record was written in (see [`OPPORTUNITY_SCHEMA_VERSION`])
+    #[serde(default = "schema_v1")]
+    pub schema_version: u32,
     pub direction: String,
+    /// CEX venue that supplied the winning price (empty for single-book evaluation)
+    pub venue: String,
+    /// Chain of the DEX pool (0 until stamped by the aggregator)
+    #[serde(default)]
+    pub chain_id: u64,
+    /// Unique within a run, `<run>-<sequence>` (empty until stamped by the aggregator)
+    #[serde(default)]
+    pub id: String,
+    /// When the opportunity was detected, Unix ms (0 until stamped)
+    #[serde(default)]
+    pub detected_at_ms: u64,
     pub description: String,
     pub pnl: f64,
+    /// `pnl` in USD at the quote token's reference price (`pnl` when unreferenced)
+    #[serde(default)]
+    pub pnl_usd: f64,
+    /// ETH traded on each leg
+    pub size_eth: f64,
+    /// USDC notional of the CEX leg at the quoted price
+    pub notional_usdc: f64,
+    /// Gas cost charged against `pnl`
+    #[serde(default)]
+    pub gas_cost_usdc: f64,
+    /// PnL before fees and gas: `net_pnl_usdc + gas_cost_usdc + fees_usdc`
+    #[serde(default)]
+    pub gross_pnl_usdc: f64,
+    /// CEX fee (negative for a rebate) plus DEX LP fee, in USDC
+    #[serde(default)]
+    pub fees_usdc: f64,
+    /// PnL after fees and gas, the same as `pnl`
+    #[serde(default)]
+    pub net_pnl_usdc: f64,
+    /// Low end of the PnL interval over input uncertainty (`pnl` unless the
+    /// interval is reported)
+    #[serde(default)]
+    pub pnl_low: f64,
+    /// High end of the PnL interval over input uncertainty (`pnl` unless the
+    /// interval is reported)
+    #[serde(default)]
+    pub pnl_high: f64,
+    /// Average USDC-per-ETH price of the DEX leg across every crossed
+    /// segment, LP fee included, comparable to the CEX leg's price
+    #[serde(default)]
+    pub dex_vwap: f64,
+    /// PnL change per 1 bp rise in the CEX price (0 unless sensitivities are reported)
+    #[serde(default)]
+    pub pnl_per_bp_cex: f64,
+    /// PnL change per 1 gwei rise in the gas price (0 unless sensitivities are reported)
+    #[serde(default)]
+    pub pnl_per_gwei: f64,
+    /// PnL of the same size round-tripped on the CEX alone, the baseline
+    /// without a DEX leg (set when the best execution report is on)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cex_only_pnl: Option<f64>,
+    /// `pnl` over `cex_only_pnl`: what the arbitrage adds over trading the CEX
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incremental_pnl: Option<f64>,
+    /// Which limit capped `size_eth` under `max_slippage_bps` sizing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_constraint: Option<SizeConstraint>,
+    /// Where each leg executes (set when leg venues are reported)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub legs: Option<ExecutionLegs>,
+}
+
+/// The concrete venues of an opportunity's two legs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionLegs {
+    /// CEX venue the CEX leg trades on
+    pub cex_venue: String,
+    /// Chain of the pool
+    pub chain_id: u64,
+    /// Pool the DEX leg swaps in: its address, its name among several
+    /// evaluated pools, or `split` when spread across `pool_legs`
+    pub pool: String,
+    /// LP fee of the pool in bps, i.e. its fee tier; for a split, the mean
+    /// over `pool_legs` weighted by the ETH each trades
+    pub pool_fee_bps: f64,
+    /// Swap in each pool of a split DEX leg (empty unless `pool` is `split`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pool_legs: Vec<PoolLeg>,
+}
+
+/// The part of a split DEX leg swapped in one pool.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PoolLeg {
+    pub pool: String,
+    /// ETH the pool trades
+    pub size_eth: f64,
+    /// Input of the pool's swap, LP fee included: USDC for A, ETH for B
+    pub amount_in: f64,
+    /// LP fee of the pool in bps
+    pub pool_fee_bps: f64,
+}
+
+impl ArbitrageOpportunity {
+    /// Stable key identifying "the same" opportunity across evaluation ticks.
+    pub fn signature(&self) -> String {
+        format!("{}@{}", self.direction, self.venue)
+    }
+
+    /// Direction and input of the DEX leg, LP fee included: the USDC paid for
+    /// A, the ETH sold for B.
+    pub fn dex_swap(&self) -> (SwapDirection, f64) {
+        if self.direction == "A" {
+            (SwapDirection::Token0ToToken1, self.size_eth * self.dex_vwap)
+        } else {
+            (SwapDirection::Token1ToToken0, self.size_eth)
+        }
+    }
+
+    /// This record as JSON in schema `version`, without the fields added
+    /// after it, so consumers pinned to an older schema keep their shape.
+    pub fn to_schema(&self, version: u32) -> Result<serde_json::Value> {
+        check_opportunity_schema_version(version)?;
+        let known = SCHEMA_FIELDS[..version as usize].concat();
+        let mut value = serde_json::to_value(self)?;
+        if let serde_json::Value::Object(fields) = &mut value {
+            fields.retain(|key, _| known.contains(&key.as_str()));
+            if version > 1 {
+                fields.insert("schema_version".to_string(), version.into());
+            }
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn net_edge_gate_matches_component_thresholds_at_boundaries() {
+        let cfg = ArbitrageConfig {
+            min_pnl_usdc: 5.0,
+            min_pnl_bps: 10.0,
+            ..Default::default()
+        };
+        // Below 5_000 USDC notional the absolute floor binds, above it the bps one
+        for (pnl, notional) in [
+            (5.0, 1_000.0),
+            (4.999, 1_000.0),
+            (5.0, 5_000.0),
+            (4.999, 5_000.0),
+            (20.0, 20_000.0),
+            (19.999, 20_000.0),
+            (1.0, 0.0),
+        ] {
+            let components = pnl >= cfg.min_pnl_usdc
+                && notional > 0.0
+                && pnl >= cfg.min_pnl_bps * notional / 10_000.0;
+            assert_eq!(
+                cfg.clears_net_edge(pnl, notional),
+                components,
+                "{pnl} on {notional}"
+            );
+        }
+        assert_eq!(cfg.net_edge_usdc(25.0, 20_000.0), 5.0);
+
+        // A permissive threshold still lets losing trades through unless required positive
+        let permissive = ArbitrageConfig {
+            min_pnl_usdc: -1.0,
+            ..Default::default()
+        };
+        assert!(permissive.clears_net_edge(-0.5, 1_000.0));
+        let strict = ArbitrageConfig {
+            require_positive_net_edge: true,
+            ..permissive
+        };
+        assert!(!strict.clears_net_edge(-0.5, 1_000.0));
+        assert!(!strict.clears_net_edge(0.0, 1_000.0));
+        assert!(strict.clears_net_edge(0.01, 1_000.0));
+    }
+
+    #[test]
+    fn v1_record_deserializes_with_defaults_for_newer_fields() {
+        let v1 = r#"{"direction":"A","venue":"binance","description":"A: Buy","pnl":12.5,"size_eth":1.5,"notional_usdc":6000.0}"#;
+        let opp: ArbitrageOpportunity = serde_json::from_str(v1).unwrap();
+        assert_eq!(opp.schema_version, 1);
+        assert_eq!(
+            (opp.direction.as_str(), opp.venue.as_str()),
+            ("A", "binance")
+        );
+        assert_eq!(
+            (opp.pnl, opp.size_eth, opp.notional_usdc),
+            (12.5, 1.5, 6_000.0)
+        );
+        assert_eq!(
+            (opp.chain_id, opp.id.as_str(), opp.detected_at_ms),
+            (0, "", 0)
+        );
+        assert_eq!(
+            (opp.pnl_usd, opp.gas_cost_usdc, opp.dex_vwap),
+            (0.0, 0.0, 0.0)
+        );
+
+        // Written back pinned to v1 it is the same record
+        let pinned = opp.to_schema(1).unwrap();
+        assert_eq!(
+            pinned,
+            serde_json::from_str::<serde_json::Value>(v1).unwrap()
+        );
+
+        let current = ArbitrageOpportunity {
+            id: "run-1".to_string(),
+            chain_id: 1,
+            ..opp
+        };
+        let written = current.to_schema(OPPORTUNITY_SCHEMA_VERSION).unwrap();
+        assert_eq!(written["schema_version"], OPPORTUNITY_SCHEMA_VERSION);
+        let read: ArbitrageOpportunity = serde_json::from_value(written).unwrap();
+        assert_eq!(
+            (read.schema_version, read.id.as_str()),
+            (OPPORTUNITY_SCHEMA_VERSION, "run-1")
+        );
+        // Pinned to v2, fields from later versions are left out
+        let v2 = current.to_schema(2).unwrap();
+        assert_eq!(
+            (&v2["schema_version"], &v2["id"]),
+            (&2.into(), &"run-1".into())
+        );
+        assert!(v2.get("pnl_per_gwei").is_none());
+        assert!(current.to_schema(0).is_err());
+        assert!(current.to_schema(OPPORTUNITY_SCHEMA_VERSION + 1).is_err());
+    }
+
+    #[test]
+    fn pnl_and_pnl_per_gas_rank_differently() {
+        let opp = |direction: &str, pnl: f64, gas_cost_usdc: f64, notional_usdc: f64| {
+            ArbitrageOpportunity {
+                direction: direction.to_string(),
+                pnl,
+                gas_cost_usdc,
+                notional_usdc,
+                ..Default::default()
+            }
+        };
+        // Big but gas-hungry, small but cheap, and a high-return small trade
+        let set = vec![
+            opp("big", 50.0, 25.0, 100_000.0),
+            opp("cheap", 20.0, 2.0, 20_000.0),
+            opp("lean", 10.0, 4.0, 1_000.0),
+        ];
+        let ranked = |score_fn: ScoreFn| {
+            let mut opps = set.clone();
+            score_fn.rank(&mut opps);
+            opps.into_iter().map(|o| o.direction).collect::<Vec<_>>()
+        };
+
+        assert_eq!(ranked(ScoreFn::Pnl), ["big", "cheap", "lean"]);
+        assert_eq!(ranked(ScoreFn::PnlPerGas), ["cheap", "lean", "big"]);
+        assert_eq!(ranked(ScoreFn::PnlBps), ["lean", "cheap", "big"]);
+        assert_eq!(
+            "pnl_per_gas".parse::<ScoreFn>().unwrap(),
+            ScoreFn::PnlPerGas
+        );
+        assert!("sharpe".parse::<ScoreFn>().is_err());
+    }
+
+    #[test]
+    fn direction_bias_prefers_the_favoured_direction() {
+        let opp = |direction: &str, pnl: f64| ArbitrageOpportunity {
+            direction: direction.to_string(),
+            pnl,
+            ..Default::default()
+        };
+        let ranked = |direction_bias: f64, set: &[ArbitrageOpportunity]| {
+            let config = ArbitrageConfig {
+                direction_bias,
+                ..Default::default()
+            };
+            let mut opps = set.to_vec();
+            config.rank(&mut opps);
+            opps.into_iter().map(|o| o.direction).collect::<Vec<_>>()
+        };
+
+        // Otherwise equal, the bias decides; without one the order is kept
+        let equal = [opp("A", 10.0), opp("B", 10.0)];
+        assert_eq!(ranked(0.0, &equal), ["A", "B"]);
+        assert_eq!(ranked(-0.1, &equal), ["B", "A"]);
+        assert_eq!(ranked(0.1, &equal), ["A", "B"]);
+
+        // It only tips marginal cases: 10% does not beat a 50% better edge
+        let apart = [opp("A", 15.0), opp("B", 10.0)];
+        assert_eq!(ranked(-0.1, &apart), ["A", "B"]);
+        assert_eq!(ranked(-0.6, &apart), ["B", "A"]);
+
+        let out_of_range = ArbitrageConfig {
+            direction_bias: 1.5,
+            ..Default::default()
+        };
+        assert!(out_of_range.validate().is_err());
+    }
+
+    #[test]
+    fn validate_accepts_rebates_and_rejects_absurd_fees() {
+        let rebate = ArbitrageConfig {
+            cex_fee_bps: -2.5,
+            dex_fee_bps: 5.0,
+            ..Default::default()
+        };
+        assert!(rebate.validate().is_ok());
+
+        for cex_fee_bps in [10_000.0, 25_000.0, -10_000.0, f64::NAN] {
+            let cfg = ArbitrageConfig {
+                cex_fee_bps,
+                ..Default::default()
+            };
+            assert!(matches!(cfg.validate(), Err(AppError::Config(_))));
+        }
+    }
+
+    #[test]
+    fn degraded_tightens_both_pnl_thresholds() {
+        let cfg = ArbitrageConfig {
+            min_pnl_usdc: 4.0,
+            degraded_pnl_factor: 2.5,
+            ..Default::default()
+        };
+        assert_eq!(cfg.degraded().min_pnl_usdc, 10.0);
+
+        // A negative threshold rises rather than loosening further
+        let negative = ArbitrageConfig {
+            min_pnl_usdc: -4.0,
+            ..cfg.clone()
+        };
+        assert_eq!(negative.degraded().min_pnl_usdc, 2.0);
+
+        // A zero threshold stops admitting break-even trades
+        let zero = ArbitrageConfig {
+            min_pnl_usdc: 0.0,
+            ..cfg.clone()
+        };
+        assert!(zero.clears_net_edge(0.0, 1_000.0));
+        assert!(!zero.degraded().clears_net_edge(0.0, 1_000.0));
+        assert!(zero.degraded().clears_net_edge(0.01, 1_000.0));
+
+        // The bps threshold is raised alongside
+        let bps = ArbitrageConfig {
+            min_pnl_bps: 10.0,
+            ..cfg.clone()
+        };
+        assert_eq!(bps.degraded().min_pnl_bps, 25.0);
+        assert!(bps.clears_net_edge(15.0, 10_000.0));
+        assert!(!bps.degraded().clears_net_edge(15.0, 10_000.0));
+
+        let disabled = ArbitrageConfig {
+            degraded_pnl_factor: 0.0,
+            ..bps
+        };
+        assert_eq!(disabled.degraded().min_pnl_usdc, 4.0);
+        assert_eq!(disabled.degraded().min_pnl_bps, 10.0);
+        assert!(!disabled.degraded().require_positive_net_edge);
+    }
 }
@@ -0,0 +1,548 @@
+//! Replay of captured CEX books through the live evaluation pipeline.
+//!
+//! A capture is a JSONL file of [`BookDepth`] snapshots, each stamped with its
+//! original `received_at_ms`. Replaying publishes them on the same watch
+//! channel the live feed uses, while a [`ReplayClock`] stands in for wall time
+//! so freshness checks see the captured timing.
+//!
+//! The pool and gas channels can be captured and replayed next to the books
+//! as [`Captured`] values, so all three inputs of an incident are reproduced.
+//! The live replay steps every evaluator once per event, waiting for its
+//! pass before publishing the next; [`replay_evaluations`] re-runs the
+//! evaluation itself in lockstep with the merged inputs, without the pipeline.
+
+use crate::arbitrage::{ArbitrageConfig, ArbitrageOpportunity, evaluate_across_venues};
+use crate::clock::Clock;
+use crate::config::GasConfig;
+use crate::dex::PoolState;
+use crate::errors::{AppError, Result};
+use crate::models::BookDepth;
+use crate::sink::{JsonlSink, OpportunitySink};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot, watch};
+
+/// How fast a capture is replayed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Publish every book immediately, jumping the clock to its timestamp.
+    Instant,
+    /// Follow the captured timestamps on the wall clock, sped up by this
+    /// factor (1.0 = real time).
+    Scaled(f64),
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, Self::Err> {
+        let raw = raw.trim().to_lowercase();
+        if raw == "instant" {
+            return Ok(Self::Instant);
+        }
+        match raw.parse::<f64>() {
+            Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(Self::Scaled(factor)),
+            _ => Err(AppError::Config(format!(
+                "REPLAY_SPEED must be `instant` or a positive factor, got `{}`",
+                raw
+            ))),
+        }
+    }
+}
+
+/// Clock of a replay, reading in capture time.
+///
+/// Instant replays only move it forward as books are published; scaled
+/// replays let it run with the wall clock times the speed factor.
+#[derive(Debug)]
+pub struct ReplayClock {
+    speed: ReplaySpeed,
+    /// Capture time at `origin` (scaled) or the current time (instant)
+    base_ms: AtomicU64,
+    origin: Instant,
+}
+
+impl ReplayClock {
+    pub fn new(start_ms: u64, speed: ReplaySpeed) -> Self {
+        Self {
+            speed,
+            base_ms: AtomicU64::new(start_ms),
+            origin: Instant::now(),
+        }
+    }
+
+    /// Wait until the clock reads at least `at_ms`.
+    pub async fn wait_until(&self, at_ms: u64) {
+        match self.speed {
+            ReplaySpeed::Instant => {
+                self.base_ms.fetch_max(at_ms, Ordering::SeqCst);
+            }
+            ReplaySpeed::Scaled(factor) => {
+                let ahead_ms = at_ms.saturating_sub(self.now_ms());
+                if ahead_ms > 0 {
+                    tokio::time::sleep(Duration::from_secs_f64(ahead_ms as f64 / 1_000.0 / factor))
+                        .await;
+                }
+            }
+        }
+    }
+}
+
+impl Clock for ReplayClock {
+    fn now_ms(&self) -> u64 {
+        let base = self.base_ms.load(Ordering::SeqCst);
+        match self.speed {
+            ReplaySpeed::Instant => base,
+            ReplaySpeed::Scaled(factor) => {
+                base + (self.origin.elapsed().as_secs_f64() * 1_000.0 * factor).ceil() as u64
+            }
+        }
+    }
+}
+
+/// Load a JSONL capture of books, in capture order.
+pub fn load_capture(path: impl AsRef<Path>) -> Result<Vec<BookDepth>> {
+    load_jsonl(path)
+}
+
+/// Load a JSONL capture of one channel's values, in capture order.
+pub fn load_channel_capture<T: DeserializeOwned>(
+    path: impl AsRef<Path>,
+) -> Result<Vec<Captured<T>>> {
+    load_jsonl(path)
+}
+
+fn load_jsonl<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<Vec<T>> {
+    let file = std::fs::File::open(path)?;
+    std::io::BufReader::new(file)
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// A value a channel took at `at_ms`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Captured<T> {
+    pub at_ms: u64,
+    pub value: T,
+}
+
+/// Append every value `rx` takes to `capture`, stamped with `clock`, until the
+/// channel closes. The current value is captured first.
+pub fn spawn_channel_capture<T>(
+    mut rx: watch::Receiver<T>,
+    capture: JsonlSink,
+    clock: Arc<dyn Clock>,
+) -> tokio::task::JoinHandle<()>
+where
+    T: Serialize + Clone + Send + Sync + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            let value = rx.borrow_and_update().clone();
+            let record = Captured {
+                at_ms: clock.now_ms(),
+                value,
+            };
+            // Flushed per value: the capture matters most when the process dies
+            if let Err(e) = capture
+                .append(&record)
+                .and_then(|_| OpportunitySink::flush(&capture))
+            {
+                tracing::warn!(error = %e, "[CAPTURE] failed to record a channel value");
+            }
+            if rx.changed().await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// One input of a replay.
+#[derive(Debug, Clone)]
+pub enum ReplayEvent {
+    Pool(PoolState),
+    Gas(f64),
+    Book(BookDepth),
+}
+
+/// Captured books, pool states and gas prices merged into one sequence in
+/// time order. At equal times pool and gas come before the book, so a book
+/// is evaluated against the chain state captured with it.
+pub fn merge_replay(
+    books: Vec<BookDepth>,
+    pools: Vec<Captured<PoolState>>,
+    gas: Vec<Captured<f64>>,
+) -> Vec<(u64, ReplayEvent)> {
+    let mut events: Vec<(u64, ReplayEvent)> = pools
+        .into_iter()
+        .map(|c| (c.at_ms, ReplayEvent::Pool(c.value)))
+        .chain(
+            gas.into_iter()
+                .map(|c| (c.at_ms, ReplayEvent::Gas(c.value))),
+        )
+        .chain(
+            books
+                .into_iter()
+                .map(|book| (book.received_at_ms, ReplayEvent::Book(book))),
+        )
+        .collect();
+    // Stable, so each channel keeps its captured order
+    events.sort_by_key(|(at_ms, _)| *at_ms);
+    events
+}
+
+/// Channels a replay publishes on; a channel left `None` keeps its live
+/// source and its captured values are skipped.
+pub struct ReplayChannels {
+    pub cex_tx: watch::Sender<BookDepth>,
+    pub pool_tx: Option<watch::Sender<PoolState>>,
+    pub gas_tx: Option<watch::Sender<f64>>,
+    /// Step channel of each evaluator's [`EvalTrigger::ReplayStep`](crate::aggregator::EvalTrigger::ReplayStep)
+    pub steps: Vec<mpsc::Sender<oneshot::Sender<()>>>,
+}
+
+/// Publish merged `events` on `channels` as `clock` reaches each one, and
+/// step every evaluator once per event, waiting for its pass to finish before
+/// moving on.
+pub fn spawn_replay(
+    events: Vec<(u64, ReplayEvent)>,
+    mut channels: ReplayChannels,
+    clock: Arc<ReplayClock>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let total = events.len();
+        for (at_ms, event) in events {
+            clock.wait_until(at_ms).await;
+            let sent = match event {
+                ReplayEvent::Book(book) => channels.cex_tx.send(book).is_ok(),
+                ReplayEvent::Pool(pool) => channels
+                    .pool_tx
+                    .as_ref()
+                    .is_none_or(|tx| tx.send(pool).is_ok()),
+                ReplayEvent::Gas(gwei) => channels
+                    .gas_tx
+                    .as_ref()
+                    .is_none_or(|tx| tx.send(gwei).is_ok()),
+            };
+            if !sent {
+                break;
+            }
+            let mut acks = Vec::new();
+            let mut stepped = Vec::new();
+            for step in channels.steps.drain(..) {
+                let (ack_tx, ack_rx) = oneshot::channel();
+                if step.send(ack_tx).await.is_ok() {
+                    acks.push(ack_rx);
+                    stepped.push(step);
+                }
+            }
+            channels.steps = stepped;
+            for ack in acks {
+                let _ = ack.await;
+            }
+        }
+        tracing::info!(events = total, "[REPLAY] capture finished");
+    })
+}
+
+/// Evaluate `venue`'s book against the pool and gas after every merged event,
+/// once all three inputs have been seen, at the event's time: the
+/// opportunities in the order the replay produces them, each with the time
+/// of the event that produced it.
+pub fn replay_evaluations(
+    events: &[(u64, ReplayEvent)],
+    venue: &str,
+    config: &ArbitrageConfig,
+    gas_config: &GasConfig,
+) -> Vec<(u64, ArbitrageOpportunity)> {
+    let (mut pool, mut gwei, mut book) = (None, None, None);
+    let mut found = Vec::new();
+    for (at_ms, event) in events {
+        match event {
+            ReplayEvent::Pool(state) => pool = Some(state),
+            ReplayEvent::Gas(price) => gwei = Some(*price),
+            ReplayEvent::Book(depth) => book = Some(depth),
+        }
+        let (Some(pool), Some(gwei), Some(book)) = (pool, gwei, book) else {
+            continue;
+        };
+        let gas = gas_config.estimate(gwei, pool.price_usdc_per_eth);
+        let books = [(venue.to_string(), book.clone())];
+        found.extend(
+            evaluate_across_venues(pool, &books, config, gas, *at_ms)
+                .into_iter()
+                .map(|opp| (*at_ms, opp)),
+        );
+    }
+    found
+}
+
+/// Publish `books` on `cex_tx` as `clock` reaches each book's
+/// `received_at_ms`, like the live feed would have.
+pub fn spawn_capture_replay(
+    books: Vec<BookDepth>,
+    cex_tx: watch::Sender<BookDepth>,
+    clock: Arc<ReplayClock>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let total = books.len();
+        for book in books {
+            clock.wait_until(book.received_at_ms).await;
+            if cex_tx.send(book).is_err() {
+                break;
+            }
+        }
+        tracing::info!(books = total, "[REPLAY] capture finished");
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage::is_book_fresh;
+
+    fn book(received_at_ms: u64) -> BookDepth {
+        BookDepth {
+            bids: vec![(4_000.0, 1.0)],
+            asks: vec![(4_001.0, 1.0)],
+            received_at_ms,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn replay_speed_parses_instant_and_factors() {
+        assert_eq!(
+            "instant".parse::<ReplaySpeed>().unwrap(),
+            ReplaySpeed::Instant
+        );
+        assert_eq!(
+            " 10 ".parse::<ReplaySpeed>().unwrap(),
+            ReplaySpeed::Scaled(10.0)
+        );
+        assert!("0".parse::<ReplaySpeed>().is_err());
+        assert!("fast".parse::<ReplaySpeed>().is_err());
+    }
+
+    #[tokio::test]
+    async fn scaled_replay_keeps_captured_timing() {
+        // 3s of capture with a 2.5s gap, replayed 50x faster
+        let start = 1_000_000;
+        let books = vec![book(start), book(start + 500), book(start + 3_000)];
+        let clock = Arc::new(ReplayClock::new(start, ReplaySpeed::Scaled(50.0)));
+        let (cex_tx, mut cex_rx) = watch::channel(BookDepth::default());
+
+        let began = Instant::now();
+        let task = spawn_capture_replay(books, cex_tx, clock.clone());
+        let mut seen = Vec::new();
+        while cex_rx.changed().await.is_ok() {
+            let (received_at_ms, fresh) = {
+                let book = cex_rx.borrow_and_update();
+                (
+                    book.received_at_ms,
+                    is_book_fresh(&book, clock.now_ms(), 2_000),
+                )
+            };
+            // Never published ahead of its capture time
+            assert!(clock.now_ms() >= received_at_ms);
+            seen.push((received_at_ms, fresh));
+            if seen.len() == 3 {
+                // Sat through the gap: the previous book has aged past 2s
+                assert!(!is_book_fresh(&book(start + 500), clock.now_ms(), 2_000));
+            }
+        }
+        task.await.unwrap();
+
+        assert_eq!(seen.len(), 3);
+        assert!(seen.iter().all(|&(_, fresh)| fresh));
+        // 3s of capture at 50x takes at least 60ms of wall time, not 3s
+        let elapsed = began.elapsed();
+        assert!(elapsed >= Duration::from_millis(55), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+    }
+
+    #[tokio::test]
+    async fn instant_replay_jumps_the_clock() {
+        let clock = Arc::new(ReplayClock::new(0, ReplaySpeed::Instant));
+        let (cex_tx, cex_rx) = watch::channel(BookDepth::default());
+        spawn_capture_replay(vec![book(5_000), book(60_000)], cex_tx, clock.clone())
+            .await
+            .unwrap();
+        assert_eq!(clock.now_ms(), 60_000);
+        assert_eq!(cex_rx.borrow().received_at_ms, 60_000);
+    }
+
+    fn write_capture<T: Serialize>(path: &Path, records: &[T]) {
+        let capture = JsonlSink::open(path).unwrap();
+        for record in records {
+            capture.append(record).unwrap();
+        }
+        OpportunitySink::flush(&capture).unwrap();
+    }
+
+    fn pool(price: f64) -> PoolState {
+        let sqrt_price_x96 =
+            crate::dex::calc::calculate_sqrt_price_with_precision_per_eth(price, 6, 18).unwrap();
+        PoolState::new(
+            sqrt_price_x96,
+            1_800_000_000_000_000_000,
+            0,
+            6,
+            18,
+            None,
+            None,
+            price,
+        )
+    }
+
+    #[tokio::test]
+    async fn replaying_all_three_channels_reproduces_the_opportunities() {
+        let quote = |received_at_ms: u64, bid: f64, ask: f64| BookDepth {
+            bids: vec![(bid, 2.0)],
+            asks: vec![(ask, 2.0)],
+            received_at_ms,
+            ..Default::default()
+        };
+        // Buying at 4050 to sell into a 4100 pool pays, until gas spikes at
+        // 200 and the pool drops below the ask at 400; a lower book at 500
+        // reopens the edge
+        let books = vec![quote(100, 4_049.0, 4_050.0), quote(500, 3_940.0, 3_950.0)];
+        let pools = vec![
+            Captured {
+                at_ms: 0,
+                value: pool(4_100.0),
+            },
+            Captured {
+                at_ms: 400,
+                value: pool(4_000.0),
+            },
+        ];
+        let gas = vec![
+            Captured {
+                at_ms: 0,
+                value: 1.0,
+            },
+            Captured {
+                at_ms: 200,
+                value: 10_000.0,
+            },
+            Captured {
+                at_ms: 300,
+                value: 1.0,
+            },
+        ];
+
+        // Round-trip every channel through a capture file
+        let dir = std::env::temp_dir();
+        let path = |name: &str| {
+            dir.join(format!(
+                "replay-{}-{}-{}.jsonl",
+                name,
+                std::process::id(),
+                crate::utils::now_ms()
+            ))
+        };
+        let (book_path, pool_path, gas_path) = (path("books"), path("pool"), path("gas"));
+        write_capture(&book_path, &books);
+        write_capture(&pool_path, &pools);
+        write_capture(&gas_path, &gas);
+        let loaded = merge_replay(
+            load_capture(&book_path).unwrap(),
+            load_channel_capture(&pool_path).unwrap(),
+            load_channel_capture(&gas_path).unwrap(),
+        );
+        for path in [book_path, pool_path, gas_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let config = ArbitrageConfig {
+            min_pnl_usdc: 1.0,
+            dex_fee_bps: 5.0,
+            cex_fee_bps: 1.0,
+            ..Default::default()
+        };
+        let gas_config = GasConfig {
+            gas_units: 10_000.0,
+            gas_multiplier: 1.0,
+            mode: crate::config::GasCostMode::Dynamic,
+            units_by_chain: Default::default(),
+            per_tick_gas_units: 0.0,
+            priority_bid_gwei: 0.0,
+            precision: crate::config::PrecisionMode::Fast,
+        };
+        let recorded = replay_evaluations(
+            &merge_replay(books, pools, gas),
+            "binance",
+            &config,
+            &gas_config,
+        );
+        let replayed = replay_evaluations(&loaded, "binance", &config, &gas_config);
+
+        let sequence = |found: &[(u64, ArbitrageOpportunity)]| {
+            found
+                .iter()
+                .map(|(at_ms, opp)| (*at_ms, opp.direction.clone()))
+                .collect::<Vec<_>>()
+        };
+        let expected = [(100, "B"), (300, "B"), (500, "B")]
+            .map(|(at_ms, direction)| (at_ms, direction.to_string()))
+            .to_vec();
+        assert_eq!(sequence(&recorded), expected);
+        assert_eq!(sequence(&replayed), expected);
+        // Exactly, down to every figure
+        assert_eq!(
+            serde_json::to_value(&replayed).unwrap(),
+            serde_json::to_value(&recorded).unwrap()
+        );
+
+        // The live replay drives all three channels to their last values
+        let clock = Arc::new(ReplayClock::new(0, ReplaySpeed::Instant));
+        let (cex_tx, cex_rx) = watch::channel(BookDepth::default());
+        let (pool_tx, pool_rx) = watch::channel(pool(1.0));
+        let (gas_tx, gas_rx) = watch::channel(0.0);
+        let channels = ReplayChannels {
+            cex_tx,
+            pool_tx: Some(pool_tx),
+            gas_tx: Some(gas_tx),
+            steps: Vec::new(),
+        };
+        spawn_replay(loaded.clone(), channels, clock.clone())
+            .await
+            .unwrap();
+        assert_eq!(clock.now_ms(), 500);
+        assert_eq!(cex_rx.borrow().received_at_ms, 500);
+        assert_eq!(pool_rx.borrow().price_usdc_per_eth, 4_000.0);
+        assert_eq!(*gas_rx.borrow(), 1.0);
+
+        // A stepped evaluator gets one pass per event, however fast the replay
+        let clock = Arc::new(ReplayClock::new(0, ReplaySpeed::Instant));
+        let (cex_tx, cex_rx) = watch::channel(BookDepth::default());
+        let (step_tx, step_rx) = mpsc::channel(1);
+        let evaluator = tokio::spawn(async move {
+            let mut trigger = crate::aggregator::EvalTrigger::replay_steps(step_rx);
+            let mut passes = Vec::new();
+            while trigger.wait().await {
+                passes.push(cex_rx.borrow().received_at_ms);
+            }
+            passes
+        });
+        let channels = ReplayChannels {
+            cex_tx,
+            pool_tx: None,
+            gas_tx: None,
+            steps: vec![step_tx],
+        };
+        spawn_replay(loaded.clone(), channels, clock).await.unwrap();
+        let passes = evaluator.await.unwrap();
+        assert_eq!(passes.len(), loaded.len());
+        assert_eq!(passes.last(), Some(&500));
+    }
+}
@@ -0,0 +1,59 @@
+//! External preference for one trade direction over the other.
+//!
+//! An inventory system can skew which direction wins when both are close,
+//! e.g. favouring Direction B to sell ETH on the DEX while the CEX account
+//! runs low on it. The bias is a number in [-1, 1]: positive favours A,
+//! negative favours B (see `ArbitrageConfig::direction_bias`). It comes from
+//! `DIRECTION_BIAS`, or from a file holding just that number, re-read
+//! periodically so another process can update it.
+
+use crate::errors::{AppError, Result};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Parse a direction bias, rejecting values outside [-1, 1].
+pub fn parse_direction_bias(raw: &str) -> Result<f64> {
+    let bias: f64 = raw.trim().parse()?;
+    if !(-1.0..=1.0).contains(&bias) {
+        return Err(AppError::Config(format!(
+            "direction bias must be in [-1, 1], got {}",
+            bias
+        )));
+    }
+    Ok(bias)
+}
+
+/// Re-read the bias from `path` every `interval` and publish it on `tx`
+/// when it changes. An unreadable or invalid file keeps the last bias.
+pub fn spawn_direction_bias_watcher(
+    path: impl Into<PathBuf>,
+    interval: Duration,
+    tx: watch::Sender<f64>,
+) -> tokio::task::JoinHandle<()> {
+    let path = path.into();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let read = std::fs::read_to_string(&path)
+                .map_err(AppError::from)
+                .and_then(|raw| parse_direction_bias(&raw));
+            match read {
+                Ok(bias) => {
+                    tx.send_if_modified(|current| {
+                        let changed = *current != bias;
+                        if changed {
+                            tracing::info!(bias, "[BIAS] direction bias updated");
+                            *current = bias;
+                        }
+                        changed
+                    });
+                }
+                Err(e) => {
+                    tracing::warn!(path = %path.display(), error = %e, "[BIAS] keeping the last direction bias");
+                }
+            }
+        }
+    })
+}
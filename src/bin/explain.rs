@@ -0,0 +1,18 @@
+//! Explain the evaluation of a hypothetical price pair, without any network.
+//!
+//! Usage: `explain --cex-bid X --cex-ask Y --dex-price Z --liquidity L`, plus
+//! the optional flags listed in [`USAGE`].
+
+use anyhow::Result;
+use arbitrage_detector::explain::{ExplainInputs, USAGE, explain};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let inputs = ExplainInputs::from_args(&args)?;
+    print!("{}", explain(&inputs)?);
+    Ok(())
+}
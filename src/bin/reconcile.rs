@@ -0,0 +1,35 @@
+//! Reconcile an opportunity log against a fills file.
+//!
+//! Usage: `reconcile <opportunities.jsonl> <fills.jsonl> [report.json]`; the
+//! report is printed as JSON when no output path is given.
+
+use anyhow::{Result, bail};
+use arbitrage_detector::arbitrage::ArbitrageOpportunity;
+use arbitrage_detector::reconcile::{Fill, load_jsonl, reconcile};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (opportunities_path, fills_path, output) = match args.as_slice() {
+        [opps, fills] => (opps, fills, None),
+        [opps, fills, output] => (opps, fills, Some(output)),
+        _ => bail!("usage: reconcile <opportunities.jsonl> <fills.jsonl> [report.json]"),
+    };
+    let opportunities: Vec<ArbitrageOpportunity> = load_jsonl(opportunities_path)?;
+    let fills: Vec<Fill> = load_jsonl(fills_path)?;
+    let report = reconcile(&opportunities, &fills);
+
+    eprintln!(
+        "acted on {}/{} opportunities, predicted {:.2}, realized {:.2}, {} unknown fills",
+        report.acted_on.len(),
+        opportunities.len(),
+        report.predicted_pnl,
+        report.realized_pnl,
+        report.unknown_fills.len()
+    );
+    let json = serde_json::to_string_pretty(&report)?;
+    match output {
+        Some(path) => std::fs::write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
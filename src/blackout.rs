@@ -0,0 +1,155 @@
+//! UTC time windows during which evaluation is suppressed.
+//!
+//! Windows are either recurring daily (`HH:MM-HH:MM`, wrapping past midnight
+//! when the end is earlier than the start) or one-off ranges of Unix seconds
+//! (`start-end`), e.g. an exchange maintenance or a CPI release.
+
+use crate::errors::AppError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+const DAY_MS: u64 = 86_400_000;
+const MINUTE_MS: u64 = 60_000;
+
+/// One blackout window; the start is inclusive and the end exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlackoutWindow {
+    /// Every day between these minutes after UTC midnight
+    Daily { start_min: u32, end_min: u32 },
+    /// Once, between these Unix times in ms
+    Once { start_ms: u64, end_ms: u64 },
+}
+
+impl BlackoutWindow {
+    pub fn contains(&self, now_ms: u64) -> bool {
+        match *self {
+            Self::Daily { start_min, end_min } => {
+                let minute = ((now_ms % DAY_MS) / MINUTE_MS) as u32;
+                if start_min <= end_min {
+                    (start_min..end_min).contains(&minute)
+                } else {
+                    minute >= start_min || minute < end_min
+                }
+            }
+            Self::Once { start_ms, end_ms } => (start_ms..end_ms).contains(&now_ms),
+        }
+    }
+}
+
+impl fmt::Display for BlackoutWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Daily { start_min, end_min } => write!(
+                f,
+                "{:02}:{:02}-{:02}:{:02} UTC",
+                start_min / 60,
+                start_min % 60,
+                end_min / 60,
+                end_min % 60
+            ),
+            Self::Once { start_ms, end_ms } => {
+                write!(f, "{}-{}", start_ms / 1_000, end_ms / 1_000)
+            }
+        }
+    }
+}
+
+fn parse_minute_of_day(raw: &str) -> Option<u32> {
+    let (hours, minutes) = raw.split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+impl FromStr for BlackoutWindow {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            AppError::Config(format!(
+                "blackout window must be `HH:MM-HH:MM` (UTC) or `start-end` in Unix seconds, got `{}`",
+                raw
+            ))
+        };
+        let (start, end) = raw.trim().split_once('-').ok_or_else(invalid)?;
+        let (start, end) = (start.trim(), end.trim());
+        if start.contains(':') {
+            let start_min = parse_minute_of_day(start).ok_or_else(invalid)?;
+            let end_min = parse_minute_of_day(end).ok_or_else(invalid)?;
+            if start_min == end_min {
+                return Err(invalid());
+            }
+            return Ok(Self::Daily { start_min, end_min });
+        }
+        let start_s: u64 = start.parse().map_err(|_| invalid())?;
+        let end_s: u64 = end.parse().map_err(|_| invalid())?;
+        if end_s <= start_s {
+            return Err(invalid());
+        }
+        Ok(Self::Once {
+            start_ms: start_s * 1_000,
+            end_ms: end_s * 1_000,
+        })
+    }
+}
+
+/// All configured windows, parsed from a comma-separated list.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlackoutWindows(pub Vec<BlackoutWindow>);
+
+impl BlackoutWindows {
+    /// The window covering `now_ms`, if any.
+    pub fn active_at(&self, now_ms: u64) -> Option<&BlackoutWindow> {
+        self.0.iter().find(|window| window.contains(now_ms))
+    }
+}
+
+impl FromStr for BlackoutWindows {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+
+    #[test]
+    fn windows_suppress_only_while_covering_now() {
+        let windows: BlackoutWindows = "23:55-00:10, 1700000000-1700003600".parse().unwrap();
+        // 2023-11-14 22:13:20 UTC
+        let clock = MockClock::new(1_700_000_000_000);
+
+        assert_eq!(windows.active_at(clock.now_ms()), Some(&windows.0[1]));
+        clock.advance(3_600_000);
+        assert_eq!(windows.active_at(clock.now_ms()), None);
+
+        // 23:58 and 00:05 fall in the daily window across midnight, 00:10 does not
+        let midnight = 1_700_006_400_000;
+        assert_eq!(
+            windows.active_at(midnight - 2 * MINUTE_MS),
+            Some(&windows.0[0])
+        );
+        assert_eq!(
+            windows.active_at(midnight + 5 * MINUTE_MS),
+            Some(&windows.0[0])
+        );
+        assert_eq!(windows.active_at(midnight + 10 * MINUTE_MS), None);
+        assert_eq!(windows.0[0].to_string(), "23:55-00:10 UTC");
+
+        assert!("25:00-01:00".parse::<BlackoutWindows>().is_err());
+        assert!("100-50".parse::<BlackoutWindows>().is_err());
+        assert_eq!(
+            "".parse::<BlackoutWindows>().unwrap(),
+            BlackoutWindows::default()
+        );
+    }
+}
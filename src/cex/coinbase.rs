@@ -0,0 +1,278 @@
+//! Coinbase Exchange order book from the `level2` websocket channel.
+//!
+//! The channel opens with a full `snapshot` of the product's book and then
+//! sends `l2update` messages listing only the levels that changed, a size of
+//! zero removing the level. The book is kept locally and a fresh
+//! [`BookDepth`] of its top levels is emitted on every change.
+
+use crate::cex::binance::StreamOptions;
+use crate::cex::events::FeedEvents;
+use crate::cex::feed::{CexFeed, spawn_feed_watcher};
+use crate::cex::health::ReconnectMonitor;
+use crate::cex::local_book::LocalBook;
+use crate::cex::skew::ClockSkewEstimator;
+use crate::errors::Result;
+use crate::models::BookDepth;
+use crate::utils::now_ms;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{SinkExt, Stream, StreamExt};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::warn;
+use url::Url;
+
+const COINBASE_WS_ENDPOINT: &str = "wss://ws-feed.exchange.coinbase.com";
+
+/// Coinbase Exchange clock, `{"iso": ..., "epoch": seconds}`
+const COINBASE_SERVER_TIME_URL: &str = "https://api.exchange.coinbase.com/time";
+
+/// Levels per side of the emitted books, as many as Binance `@depth20`
+const BOOK_LEVELS: usize = 20;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum Level2Msg {
+    #[serde(rename = "snapshot")]
+    Snapshot {
+        bids: Vec<[String; 2]>,
+        asks: Vec<[String; 2]>,
+    },
+    /// Changed levels as `[side, price, size]`, side `buy` or `sell`, and
+    /// when the exchange made the change
+    #[serde(rename = "l2update")]
+    L2Update {
+        changes: Vec<[String; 3]>,
+        #[serde(default)]
+        time: Option<String>,
+    },
+    #[serde(rename = "error")]
+    Error { message: String },
+    /// Subscription acknowledgements, heartbeats and the like
+    #[serde(other)]
+    Other,
+}
+
+/// Local book of one product, fed the channel's messages in order.
+#[derive(Debug, Default)]
+pub struct Level2Book {
+    book: Option<LocalBook>,
+}
+
+impl Level2Book {
+    /// Fold one message into the book; the book's top levels when it
+    /// changed and both sides are populated.
+    pub fn handle(&mut self, txt: &str) -> Option<BookDepth> {
+        let msg: Level2Msg = match serde_json::from_str(txt) {
+            Ok(msg) => msg,
+            Err(e) => {
+                warn!(error = %e, "[CEX] coinbase JSON parse failed");
+                return None;
+            }
+        };
+        let mut event_time_ms = 0;
+        match msg {
+            Level2Msg::Snapshot { bids, asks } => {
+                self.book = Some(LocalBook::from_snapshot(
+                    0,
+                    &parse_levels(&bids),
+                    &parse_levels(&asks),
+                ));
+            }
+            Level2Msg::L2Update { changes, time } => {
+                // Updates before the snapshot have nothing to apply to
+                let book = self.book.as_mut()?;
+                event_time_ms = time.as_deref().and_then(parse_time_ms).unwrap_or(0);
+                let (mut bids, mut asks) = (Vec::new(), Vec::new());
+                for [side, price, size] in &changes {
+                    let Some(level) = price.parse().ok().zip(size.parse().ok()) else {
+                        continue;
+                    };
+                    match side.as_str() {
+                        "buy" => bids.push(level),
+                        "sell" => asks.push(level),
+                        _ => {}
+                    }
+                }
+                book.apply_levels(&bids, &asks);
+            }
+            Level2Msg::Error { message } => {
+                warn!(%message, "[CEX] coinbase error");
+                return None;
+            }
+            Level2Msg::Other => return None,
+        }
+        let depth = BookDepth {
+            event_time_ms,
+            ..self.book.as_ref()?.to_depth(BOOK_LEVELS, now_ms())
+        };
+        (!depth.bids.is_empty() && !depth.asks.is_empty()).then_some(depth)
+    }
+}
+
+/// Unix ms of a UTC timestamp as Coinbase sends it, e.g.
+/// `2026-01-01T00:00:00.123456Z`; `None` when it is not in that form.
+fn parse_time_ms(time: &str) -> Option<u64> {
+    let (date, clock) = time.strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (clock, fraction) = clock.split_once('.').unwrap_or((clock, "0"));
+    let mut clock = clock.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hour, minute, second) = (clock.next()??, clock.next()??, clock.next()??);
+    let millis: i64 = format!("{:0<3}", fraction).get(..3)?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days since 1970-01-01 in the proleptic Gregorian calendar, years
+    // starting in March so the leap day comes last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    let ms = ((days * 24 + hour) * 60 + minute) * 60_000 + second * 1_000 + millis;
+    u64::try_from(ms).ok()
+}
+
+/// `[price, size]` string levels as numbers, dropping those that do not parse.
+fn parse_levels(side: &[[String; 2]]) -> Vec<(f64, f64)> {
+    side.iter()
+        .filter_map(|[price, size]| price.parse().ok().zip(size.parse().ok()))
+        .collect()
+}
+
+/// Returns an asynchronous stream of `BookDepth`s for the given Coinbase
+/// product, e.g. "ETH-USD".
+pub async fn connect_and_stream(product_id: &str) -> Result<impl Stream<Item = BookDepth> + use<>> {
+    let url = Url::parse(COINBASE_WS_ENDPOINT)?;
+    let (mut ws_stream, _resp) = connect_async(url).await?;
+    let subscribe = serde_json::json!({
+        "type": "subscribe",
+        "product_ids": [product_id],
+        "channels": ["level2"],
+    });
+    ws_stream.send(Message::Text(subscribe.to_string())).await?;
+
+    let mut book = Level2Book::default();
+    Ok(ws_stream.filter_map(move |msg_res| {
+        let depth = match msg_res {
+            Ok(msg) if msg.is_text() => match msg.into_text() {
+                Ok(txt) => book.handle(&txt),
+                Err(e) => {
+                    warn!(error = %e, "[CEX] text extraction failed");
+                    None
+                }
+            },
+            Err(e) => {
+                warn!(error = %e, "[CEX] websocket message error");
+                None
+            }
+            _ => None,
+        };
+        futures::future::ready(depth)
+    }))
+}
+
+/// Coinbase Exchange books.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CoinbaseFeed;
+
+#[async_trait]
+impl CexFeed for CoinbaseFeed {
+    fn venue(&self) -> &'static str {
+        "coinbase"
+    }
+
+    fn symbol(&self, base: &str, quote: &str) -> String {
+        format!("{}-{}", base, quote).to_uppercase()
+    }
+
+    async fn stream(&self, symbol: &str) -> Result<BoxStream<'static, BookDepth>> {
+        Ok(connect_and_stream(symbol).await?.boxed())
+    }
+
+    fn server_time_url(&self) -> Option<&'static str> {
+        Some(COINBASE_SERVER_TIME_URL)
+    }
+}
+
+/// Spawn CEX stream watcher task
+///
+/// [`spawn_feed_watcher`] over the Coinbase `level2` channel of `product_id`;
+/// takes the same arguments as the Binance watcher, the Binance stream
+/// `options` being unused.
+pub async fn spawn_cex_stream_watcher(
+    product_id: &str,
+    _options: StreamOptions,
+    cex_tx: watch::Sender<BookDepth>,
+    monitor: ReconnectMonitor,
+    degraded_tx: watch::Sender<bool>,
+    events: FeedEvents,
+    skew: Option<ClockSkewEstimator>,
+) -> Result<tokio::task::JoinHandle<()>> {
+    spawn_feed_watcher(
+        Arc::new(CoinbaseFeed),
+        product_id,
+        cex_tx,
+        monitor,
+        degraded_tx,
+        events,
+        skew,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_then_updates_maintain_the_book() {
+        let mut book = Level2Book::default();
+
+        // Updates before the snapshot and control messages yield nothing
+        let early =
+            r#"{"type":"l2update","product_id":"ETH-USD","changes":[["buy","3999.00","1.0"]]}"#;
+        assert!(book.handle(early).is_none());
+        assert!(
+            book.handle(r#"{"type":"subscriptions","channels":[]}"#)
+                .is_none()
+        );
+
+        let snapshot = r#"{"type":"snapshot","product_id":"ETH-USD",
+            "bids":[["4000.00","1.5"],["3999.50","2.0"]],
+            "asks":[["4000.50","0.7"],["4001.00","3.0"]]}"#;
+        let depth = book.handle(snapshot).unwrap();
+        assert_eq!(depth.bids, vec![(4_000.0, 1.5), (3_999.5, 2.0)]);
+        assert_eq!(depth.asks, vec![(4_000.5, 0.7), (4_001.0, 3.0)]);
+
+        // A new best bid, a removed ask and a resized ask
+        let update = r#"{"type":"l2update","product_id":"ETH-USD",
+            "time":"2026-01-01T00:00:00.000000Z",
+            "changes":[["buy","4000.25","0.4"],["sell","4000.50","0"],["sell","4001.00","2.5"]]}"#;
+        let depth = book.handle(update).unwrap();
+        assert_eq!(
+            depth.bids,
+            vec![(4_000.25, 0.4), (4_000.0, 1.5), (3_999.5, 2.0)]
+        );
+        assert_eq!(depth.asks, vec![(4_001.0, 2.5)]);
+        assert!(depth.timestamp > 0 && depth.received_at_ms > 0);
+        // Stamped with the exchange's time of the change, for clock skew correction
+        assert_eq!(depth.event_time_ms, 1_767_225_600_000);
+        assert_eq!(
+            parse_time_ms("2024-02-29T23:59:58.5Z"),
+            Some(1_709_251_198_500)
+        );
+        assert_eq!(parse_time_ms("2024-02-29 23:59:58Z"), None);
+
+        // Emptying a side leaves no book to trade on
+        let drained =
+            r#"{"type":"l2update","product_id":"ETH-USD","changes":[["sell","4001.00","0"]]}"#;
+        assert!(book.handle(drained).is_none());
+        assert_eq!(CoinbaseFeed.symbol("eth", "usd"), "ETH-USD");
+    }
+}
@@ -0,0 +1,150 @@
+//! Consolidated best-bid-best-offer book across CEX venues.
+
+use crate::arbitrage::is_book_fresh;
+use crate::models::BookDepth;
+use crate::utils::now_ms;
+use std::collections::BTreeMap;
+use tokio::sync::watch;
+
+/// Merges per-venue books into one virtual book.
+///
+/// Levels at the same price are combined by summing quantities. Venues whose
+/// book is stale (older than `max_age_ms`, or flagged by a reconnecting feed)
+/// are left out of the merge.
+#[derive(Debug, Clone)]
+pub struct ConsolidatedBook {
+    max_age_ms: u64,
+}
+
+impl ConsolidatedBook {
+    pub fn new(max_age_ms: u64) -> Self {
+        Self { max_age_ms }
+    }
+
+    /// Merge the fresh books among `books` as of `now_ms`.
+    ///
+    /// The result carries the oldest receive time of the books it includes, and
+    /// is empty (hence never fresh) when no venue is fresh.
+    pub fn merge<'a>(
+        &self,
+        books: impl IntoIterator<Item = &'a BookDepth>,
+        now_ms: u64,
+    ) -> BookDepth {
+        let fresh: Vec<&BookDepth> = books
+            .into_iter()
+            .filter(|book| is_book_fresh(book, now_ms, self.max_age_ms))
+            .collect();
+        if fresh.is_empty() {
+            return BookDepth::default();
+        }
+
+        let mut bids = merge_levels(fresh.iter().flat_map(|book| book.bids.iter().copied()));
+        bids.reverse();
+        let asks = merge_levels(fresh.iter().flat_map(|book| book.asks.iter().copied()));
+        let oldest = fresh.iter().min_by_key(|book| book.received_at_ms);
+        BookDepth {
+            timestamp: fresh.iter().map(|book| book.timestamp).max().unwrap_or(0),
+            bids,
+            asks,
+            received_at_ms: oldest.map_or(0, |book| book.received_at_ms),
+            event_time_ms: oldest.map_or(0, |book| book.event_time_ms),
+            clock_skew_ms: oldest.and_then(|book| book.clock_skew_ms),
+            stale: false,
+            exact: None,
+        }
+    }
+
+    /// Spawn a task that re-merges whenever any venue publishes, and return
+    /// the channel carrying the consolidated book.
+    pub fn spawn(
+        self,
+        mut venue_rxs: BTreeMap<String, watch::Receiver<BookDepth>>,
+    ) -> (watch::Receiver<BookDepth>, tokio::task::JoinHandle<()>) {
+        let (tx, rx) = watch::channel(BookDepth::default());
+        let handle = tokio::spawn(async move {
+            loop {
+                let changes = venue_rxs.values_mut().map(|rx| Box::pin(rx.changed()));
+                let (changed, _, _) = futures::future::select_all(changes).await;
+                if changed.is_err() {
+                    // A venue feed is gone for good; stop rather than merge a partial set
+                    break;
+                }
+                let books: Vec<BookDepth> =
+                    venue_rxs.values().map(|rx| rx.borrow().clone()).collect();
+                let _ = tx.send(self.merge(&books, now_ms()));
+            }
+        });
+        (rx, handle)
+    }
+}
+
+/// Sort levels by ascending price and sum the quantities of equal prices.
+fn merge_levels(levels: impl Iterator<Item = (f64, f64)>) -> Vec<(f64, f64)> {
+    let mut levels: Vec<(f64, f64)> = levels.collect();
+    levels.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut merged: Vec<(f64, f64)> = Vec::with_capacity(levels.len());
+    for (price, qty) in levels {
+        match merged.last_mut() {
+            Some(last) if last.0 == price => last.1 += qty,
+            _ => merged.push((price, qty)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_levels_and_drops_stale_venues() {
+        let now = 100_000;
+        let binance = BookDepth {
+            timestamp: 7,
+            bids: vec![(4200.0, 1.0), (4199.0, 2.0)],
+            asks: vec![(4201.0, 1.5), (4202.0, 1.0)],
+            received_at_ms: now - 100,
+            ..Default::default()
+        };
+        let coinbase = BookDepth {
+            timestamp: 9,
+            bids: vec![(4200.5, 0.5), (4200.0, 3.0)],
+            asks: vec![(4201.0, 0.5), (4203.0, 2.0)],
+            received_at_ms: now - 50,
+            ..Default::default()
+        };
+        let stale = BookDepth {
+            bids: vec![(4300.0, 10.0)],
+            asks: vec![(4100.0, 10.0)],
+            received_at_ms: now - 60_000,
+            ..Default::default()
+        };
+
+        let book = ConsolidatedBook::new(5_000).merge([&binance, &coinbase, &stale], now);
+        assert_eq!(book.bids, vec![(4200.5, 0.5), (4200.0, 4.0), (4199.0, 2.0)]);
+        assert_eq!(book.asks, vec![(4201.0, 2.0), (4202.0, 1.0), (4203.0, 2.0)]);
+        assert_eq!(book.received_at_ms, now - 100);
+        assert_eq!(book.timestamp, 9);
+
+        let none_fresh = ConsolidatedBook::new(5_000).merge([&stale], now);
+        assert!(!is_book_fresh(&none_fresh, now, 5_000));
+    }
+
+    #[tokio::test]
+    async fn publishes_merged_book_when_a_venue_updates() {
+        let (a_tx, a_rx) = watch::channel(BookDepth::default());
+        let (_b_tx, b_rx) = watch::channel(BookDepth::default());
+        let venues = BTreeMap::from([("a".to_string(), a_rx), ("b".to_string(), b_rx)]);
+        let (mut merged_rx, _handle) = ConsolidatedBook::new(0).spawn(venues);
+
+        a_tx.send(BookDepth {
+            bids: vec![(10.0, 1.0)],
+            asks: vec![(11.0, 1.0)],
+            received_at_ms: now_ms(),
+            ..Default::default()
+        })
+        .unwrap();
+        merged_rx.changed().await.unwrap();
+        assert_eq!(merged_rx.borrow().bids, vec![(10.0, 1.0)]);
+    }
+}
@@ -0,0 +1,92 @@
+//! Structured feed health transitions, published next to the `[CEX]` logs so
+//! dashboards can follow a feed without parsing log lines.
+
+use crate::errors::Result;
+use crate::utils::now_ms;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use tokio::sync::broadcast;
+
+/// A health transition of one feed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum FeedEvent {
+    /// The connection is up (books may not have arrived yet)
+    Connected,
+    /// An established connection dropped
+    Disconnected,
+    /// About to retry the connection; `attempt` counts from 1 since the last success
+    Reconnecting { attempt: u32 },
+    /// The last book is kept but no longer counts as fresh
+    Stale,
+    /// The first book since connecting arrived
+    Fresh,
+}
+
+/// A [`FeedEvent`] stamped with its feed and time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FeedUpdate {
+    pub feed: String,
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub event: FeedEvent,
+}
+
+/// Publisher of one feed's events on a shared broadcast channel.
+#[derive(Debug, Clone)]
+pub struct FeedEvents {
+    feed: String,
+    tx: broadcast::Sender<FeedUpdate>,
+}
+
+impl FeedEvents {
+    pub fn new(feed: impl Into<String>, tx: broadcast::Sender<FeedUpdate>) -> Self {
+        Self {
+            feed: feed.into(),
+            tx,
+        }
+    }
+
+    pub fn feed(&self) -> &str {
+        &self.feed
+    }
+
+    /// Publish `event`; nobody listening is not an error.
+    pub fn publish(&self, event: FeedEvent) {
+        let _ = self.tx.send(FeedUpdate {
+            feed: self.feed.clone(),
+            at_ms: now_ms(),
+            event,
+        });
+    }
+}
+
+/// Append every update received on `rx` to the JSONL file at `path`.
+pub fn spawn_feed_event_log(
+    mut rx: broadcast::Receiver<FeedUpdate>,
+    path: impl AsRef<Path>,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    Ok(tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(update) => {
+                    let written = serde_json::to_writer(&mut file, &update)
+                        .map_err(std::io::Error::from)
+                        .and_then(|()| file.write_all(b"\n"));
+                    if let Err(e) = written {
+                        tracing::warn!(error = %e, "[FEED] failed to write feed event");
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "[FEED] event log fell behind, events dropped");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }))
+}
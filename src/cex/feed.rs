@@ -0,0 +1,374 @@
+//! Exchange-agnostic CEX book feeds: the [`CexFeed`] every exchange
+//! implements, the [`CexExchange`] picked from config, and the watcher that
+//! keeps a feed streaming into a channel across reconnects.
+
+use crate::cex::binance::{BinanceFeed, StreamOptions};
+use crate::cex::coinbase::CoinbaseFeed;
+use crate::cex::events::{FeedEvent, FeedEvents};
+use crate::cex::health::ReconnectMonitor;
+use crate::cex::skew::ClockSkewEstimator;
+use crate::errors::{AppError, Result};
+use crate::models::BookDepth;
+use crate::utils::now_ms;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Order-book stream of one exchange.
+#[async_trait]
+pub trait CexFeed: std::fmt::Debug + Send + Sync {
+    /// Venue the books are evaluated and reported under
+    fn venue(&self) -> &'static str;
+
+    /// The exchange's symbol for the `base`/`quote` pair, e.g. `ethusdc`
+    fn symbol(&self, base: &str, quote: &str) -> String;
+
+    /// Connect and stream books of `symbol` until the connection ends.
+    async fn stream(&self, symbol: &str) -> Result<BoxStream<'static, BookDepth>>;
+
+    /// REST endpoint returning the exchange's clock, for calibrating its
+    /// clock skew (see [`spawn_server_time_calibration`](crate::cex::skew::spawn_server_time_calibration))
+    fn server_time_url(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Exchange the spot book is read from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CexExchange {
+    #[default]
+    Binance,
+    /// Coinbase Exchange `level2` channel
+    Coinbase,
+}
+
+impl CexExchange {
+    /// This exchange's feed, read per `options`.
+    pub fn feed(self, options: StreamOptions) -> Arc<dyn CexFeed> {
+        match self {
+            Self::Binance => Arc::new(BinanceFeed { options }),
+            Self::Coinbase => Arc::new(CoinbaseFeed),
+        }
+    }
+}
+
+impl std::str::FromStr for CexExchange {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "binance" => Ok(Self::Binance),
+            "coinbase" => Ok(Self::Coinbase),
+            other => Err(AppError::Config(format!(
+                "CEX_EXCHANGE must be `binance` or `coinbase`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// Delay before the first reconnect attempt, doubled on every failed one
+const RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Longest delay between reconnect attempts
+const RECONNECT_MAX_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Exponential delays between reconnect attempts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    pub initial: std::time::Duration,
+    pub max: std::time::Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: RECONNECT_DELAY,
+            max: RECONNECT_MAX_DELAY,
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay before reconnect `attempt` (1-based): `initial` doubled per
+    /// earlier attempt, capped at `max`.
+    pub fn delay(&self, attempt: u32) -> std::time::Duration {
+        let doublings = attempt.saturating_sub(1).min(31);
+        self.initial.saturating_mul(1 << doublings).min(self.max)
+    }
+}
+
+/// Spawn a task streaming `feed`'s books of `symbol` into `cex_tx`.
+///
+/// Reconnects whenever the stream ends or the connect fails, backing off
+/// exponentially from 1s to 30s until a connect succeeds; reconnects are
+/// fed to `monitor` and its degraded flag is published on `degraded_tx`.
+/// While reconnecting, the last book stays in `cex_tx` but is flagged stale.
+/// Every connection transition is also published on `events`. With `skew`,
+/// each book carrying a venue timestamp is stamped with the venue's clock
+/// skew, so its age is measured from when the venue produced it.
+pub async fn spawn_feed_watcher(
+    feed: Arc<dyn CexFeed>,
+    symbol: &str,
+    cex_tx: watch::Sender<BookDepth>,
+    monitor: ReconnectMonitor,
+    degraded_tx: watch::Sender<bool>,
+    events: FeedEvents,
+    skew: Option<ClockSkewEstimator>,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let symbol = symbol.to_string();
+    let connect = move || {
+        let (feed, symbol) = (feed.clone(), symbol.clone());
+        async move { feed.stream(&symbol).await }
+    };
+    Ok(tokio::spawn(watch_stream(
+        connect,
+        cex_tx,
+        monitor,
+        degraded_tx,
+        events,
+        skew,
+        Backoff::default(),
+    )))
+}
+
+/// Reconnect loop of [`spawn_feed_watcher`] over any book stream.
+async fn watch_stream<C, F, S>(
+    mut connect: C,
+    cex_tx: watch::Sender<BookDepth>,
+    mut monitor: ReconnectMonitor,
+    degraded_tx: watch::Sender<bool>,
+    events: FeedEvents,
+    mut skew: Option<ClockSkewEstimator>,
+    backoff: Backoff,
+) where
+    C: FnMut() -> F,
+    F: std::future::Future<Output = Result<S>>,
+    S: Stream<Item = BookDepth>,
+{
+    let symbol = events.feed().to_string();
+    let mut attempt = 0;
+    loop {
+        if attempt > 0 {
+            events.publish(FeedEvent::Reconnecting { attempt });
+        }
+        match connect().await {
+            Ok(stream) => {
+                attempt = 0;
+                events.publish(FeedEvent::Connected);
+                futures::pin_mut!(stream);
+                let mut fresh = false;
+                while let Some(mut book) = stream.next().await {
+                    if let Some(skew) = &mut skew {
+                        skew.stamp(&mut book);
+                    }
+                    let _ = cex_tx.send(book);
+                    if !fresh {
+                        fresh = true;
+                        events.publish(FeedEvent::Fresh);
+                    }
+                    degraded_tx.send_if_modified(|degraded| {
+                        let now = monitor.is_degraded(now_ms());
+                        std::mem::replace(degraded, now) != now
+                    });
+                }
+                warn!(symbol = %symbol, "[CEX] stream ended");
+                events.publish(FeedEvent::Disconnected);
+            }
+            Err(e) => {
+                warn!(symbol = %symbol, error = %e, "[CEX] connect failed");
+            }
+        }
+        if mark_stale(&cex_tx) {
+            events.publish(FeedEvent::Stale);
+        }
+        let degraded = monitor.record_reconnect(now_ms());
+        if degraded && !*degraded_tx.borrow() {
+            warn!(symbol = %symbol, "[CEX] too many reconnects, entering degraded mode");
+        }
+        let _ = degraded_tx.send(degraded);
+        attempt += 1;
+        let delay = backoff.delay(attempt);
+        warn!(
+            symbol = %symbol,
+            attempt,
+            delay_ms = delay.as_millis() as u64,
+            "[CEX] reconnecting"
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Keep the last book readable but flag it stale until the next snapshot;
+/// `true` when it was fresh until now.
+fn mark_stale(cex_tx: &watch::Sender<BookDepth>) -> bool {
+    cex_tx.send_if_modified(|book| !std::mem::replace(&mut book.stale, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn book_is_stale_during_reconnect_window() {
+        use crate::arbitrage::is_book_fresh;
+
+        let (cex_tx, cex_rx) = watch::channel(BookDepth::default());
+        let now = 1_000_000;
+        cex_tx
+            .send(BookDepth {
+                bids: vec![(100.0, 1.0)],
+                asks: vec![(101.0, 1.0)],
+                received_at_ms: now,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(is_book_fresh(&cex_rx.borrow(), now, 5_000));
+
+        // Disconnect: the pre-disconnect levels remain, but no longer count as fresh
+        mark_stale(&cex_tx);
+        let during = cex_rx.borrow().clone();
+        assert_eq!(during.bids, vec![(100.0, 1.0)]);
+        assert!(!is_book_fresh(&during, now, 5_000));
+        assert!(!is_book_fresh(&during, now, 0));
+
+        // The first snapshot after reconnecting clears the flag
+        cex_tx
+            .send(BookDepth {
+                bids: vec![(100.5, 1.0)],
+                asks: vec![(101.5, 1.0)],
+                received_at_ms: now + 2_000,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(is_book_fresh(&cex_rx.borrow(), now + 2_000, 5_000));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let delays: Vec<u64> = (1..=8)
+            .map(|attempt| Backoff::default().delay(attempt).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30, 30, 30]);
+        assert_eq!(Backoff::default().delay(u32::MAX).as_secs(), 30);
+    }
+
+    #[tokio::test]
+    async fn disconnect_and_reconnect_publish_feed_events_in_order() {
+        use crate::cex::events::FeedUpdate;
+        use crate::errors::AppError;
+        use futures::stream::{self, BoxStream};
+        use std::collections::VecDeque;
+
+        let book = |price: f64| BookDepth {
+            bids: vec![(price, 1.0)],
+            asks: vec![(price + 1.0, 1.0)],
+            ..Default::default()
+        };
+        // Two books then a drop, a refused connect, then a connection that stays up
+        let mut script: VecDeque<Result<BoxStream<'static, BookDepth>>> = VecDeque::from([
+            Ok(stream::iter([book(100.0), book(100.5)]).boxed()),
+            Err(AppError::Other("connection refused".to_string())),
+            Ok(stream::iter([book(101.0)]).chain(stream::pending()).boxed()),
+        ]);
+        let connect = move || {
+            let next = script.pop_front();
+            async move {
+                match next {
+                    Some(result) => result,
+                    None => futures::future::pending().await,
+                }
+            }
+        };
+
+        let (events_tx, mut events_rx) = tokio::sync::broadcast::channel(16);
+        let (cex_tx, cex_rx) = watch::channel(BookDepth::default());
+        let (degraded_tx, _degraded_rx) = watch::channel(false);
+        let task = tokio::spawn(watch_stream(
+            connect,
+            cex_tx,
+            ReconnectMonitor::new(60_000, 0, 0),
+            degraded_tx,
+            FeedEvents::new("ethusdc", events_tx),
+            None,
+            Backoff {
+                initial: std::time::Duration::from_millis(1),
+                max: std::time::Duration::from_millis(4),
+            },
+        ));
+
+        let mut seen = Vec::new();
+        while seen.len() < 8 {
+            let update: FeedUpdate =
+                tokio::time::timeout(std::time::Duration::from_secs(1), events_rx.recv())
+                    .await
+                    .expect("event published")
+                    .unwrap();
+            assert_eq!(update.feed, "ethusdc");
+            seen.push(update.event);
+        }
+        task.abort();
+
+        assert_eq!(
+            seen,
+            vec![
+                FeedEvent::Connected,
+                FeedEvent::Fresh,
+                FeedEvent::Disconnected,
+                FeedEvent::Stale,
+                FeedEvent::Reconnecting { attempt: 1 },
+                FeedEvent::Reconnecting { attempt: 2 },
+                FeedEvent::Connected,
+                FeedEvent::Fresh,
+            ]
+        );
+        assert!(!cex_rx.borrow().stale);
+        assert_eq!(cex_rx.borrow().bids, vec![(101.0, 1.0)]);
+    }
+
+    #[tokio::test]
+    async fn streamed_books_are_stamped_with_the_venue_clock_skew() {
+        use futures::stream;
+
+        // The venue clock runs 500 ms ahead of ours
+        let (_server_skew_tx, server_skew_rx) = watch::channel(Some(500));
+        let skew = ClockSkewEstimator::new(8).with_server_time(server_skew_rx);
+        let book = BookDepth {
+            bids: vec![(100.0, 1.0)],
+            asks: vec![(101.0, 1.0)],
+            event_time_ms: 10_500,
+            received_at_ms: 10_040,
+            ..Default::default()
+        };
+        let connect = move || {
+            let book = book.clone();
+            async move { Ok(stream::iter([book]).chain(stream::pending())) }
+        };
+
+        let (events_tx, _events_rx) = tokio::sync::broadcast::channel(16);
+        let (cex_tx, mut cex_rx) = watch::channel(BookDepth::default());
+        let (degraded_tx, _degraded_rx) = watch::channel(false);
+        let task = tokio::spawn(watch_stream(
+            connect,
+            cex_tx,
+            ReconnectMonitor::new(60_000, 0, 0),
+            degraded_tx,
+            FeedEvents::new("ethusdc", events_tx),
+            Some(skew),
+            Backoff::default(),
+        ));
+        tokio::time::timeout(std::time::Duration::from_secs(1), cex_rx.changed())
+            .await
+            .expect("book streamed")
+            .unwrap();
+        task.abort();
+
+        // Produced at our 10_000, so 100 ms old at 10_100 rather than 60 ms
+        let book = cex_rx.borrow().clone();
+        assert_eq!(book.clock_skew_ms, Some(500));
+        assert_eq!(book.age_ms(10_100), 100);
+    }
+}
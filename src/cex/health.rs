@@ -0,0 +1,81 @@
+//! Feed health tracking for CEX websocket connections.
+
+use std::collections::VecDeque;
+
+/// Tracks reconnects and flags a feed as degraded after too many in a window.
+///
+/// The feed enters degraded mode once `max_reconnects` reconnects happen within
+/// `window_ms`, and leaves it after `cooldown_ms` without any reconnect.
+#[derive(Debug, Clone)]
+pub struct ReconnectMonitor {
+    window_ms: u64,
+    max_reconnects: usize,
+    cooldown_ms: u64,
+    reconnects: VecDeque<u64>,
+    degraded: bool,
+}
+
+impl ReconnectMonitor {
+    pub fn new(window_ms: u64, max_reconnects: usize, cooldown_ms: u64) -> Self {
+        Self {
+            window_ms,
+            max_reconnects,
+            cooldown_ms,
+            reconnects: VecDeque::new(),
+            degraded: false,
+        }
+    }
+
+    /// Record a reconnect at `now_ms` and return whether the feed is degraded.
+    pub fn record_reconnect(&mut self, now_ms: u64) -> bool {
+        self.reconnects.push_back(now_ms);
+        while let Some(&oldest) = self.reconnects.front() {
+            if now_ms.saturating_sub(oldest) > self.window_ms {
+                self.reconnects.pop_front();
+            } else {
+                break;
+            }
+        }
+        if self.max_reconnects > 0 && self.reconnects.len() >= self.max_reconnects {
+            self.degraded = true;
+        }
+        self.degraded
+    }
+
+    /// Whether the feed is degraded at `now_ms`, clearing the flag once the
+    /// connection has been stable for the cooldown.
+    pub fn is_degraded(&mut self, now_ms: u64) -> bool {
+        if self.degraded {
+            let last = self.reconnects.back().copied().unwrap_or(0);
+            if now_ms.saturating_sub(last) >= self.cooldown_ms {
+                self.degraded = false;
+                self.reconnects.clear();
+            }
+        }
+        self.degraded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rapid_reconnects_degrade_until_cooldown() {
+        let mut monitor = ReconnectMonitor::new(10_000, 3, 30_000);
+
+        assert!(!monitor.record_reconnect(1_000));
+        assert!(!monitor.record_reconnect(2_000));
+        assert!(monitor.record_reconnect(3_000));
+
+        // Still degraded before the cooldown elapses
+        assert!(monitor.is_degraded(20_000));
+        // Stable for the full cooldown since the last reconnect
+        assert!(!monitor.is_degraded(33_000));
+
+        // Reconnects spread wider than the window never degrade
+        assert!(!monitor.record_reconnect(50_000));
+        assert!(!monitor.record_reconnect(61_000));
+        assert!(!monitor.record_reconnect(72_000));
+    }
+}
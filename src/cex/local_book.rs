@@ -0,0 +1,165 @@
+//! Local order book maintained from Binance diff-depth (`@depth`) updates,
+//! or from any other feed of changed levels such as Coinbase `l2update`.
+//!
+//! Unlike the `@depth20` snapshots, a diff update only lists the levels that
+//! changed; a quantity of zero removes the level. Depth can therefore shrink,
+//! and levels missing from an update are not implicitly removed.
+
+use crate::errors::{AppError, Result};
+use crate::models::BookDepth;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// One `depthUpdate` event.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DiffDepthUpdate {
+    /// First update id in the event
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    /// Final update id in the event
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "b")]
+    pub bids: Vec<[String; 2]>,
+    #[serde(rename = "a")]
+    pub asks: Vec<[String; 2]>,
+}
+
+/// Book sorted by price; keys are the bit patterns of positive prices, which
+/// order the same way as the prices themselves.
+#[derive(Debug, Clone, Default)]
+pub struct LocalBook {
+    last_update_id: u64,
+    bids: BTreeMap<Reverse<u64>, f64>,
+    asks: BTreeMap<u64, f64>,
+}
+
+impl LocalBook {
+    /// Start from a REST depth snapshot taken at `last_update_id`.
+    pub fn from_snapshot(last_update_id: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> Self {
+        let mut book = Self {
+            last_update_id,
+            ..Default::default()
+        };
+        for &(price, qty) in bids {
+            set_level(&mut book.bids, Reverse(price.to_bits()), qty);
+        }
+        for &(price, qty) in asks {
+            set_level(&mut book.asks, price.to_bits(), qty);
+        }
+        book
+    }
+
+    pub fn last_update_id(&self) -> u64 {
+        self.last_update_id
+    }
+
+    /// Apply a diff update: changed levels are replaced and zero-quantity
+    /// levels removed.
+    ///
+    /// Updates already covered by the book are ignored (`Ok(false)`); a gap
+    /// in update ids means the book can no longer be trusted and must be
+    /// rebuilt from a new snapshot.
+    pub fn apply(&mut self, update: &DiffDepthUpdate) -> Result<bool> {
+        if update.final_update_id <= self.last_update_id {
+            return Ok(false);
+        }
+        if update.first_update_id > self.last_update_id + 1 {
+            return Err(AppError::Other(format!(
+                "diff depth gap: book at {}, update starts at {}",
+                self.last_update_id, update.first_update_id
+            )));
+        }
+        for [price, qty] in &update.bids {
+            let price: f64 = price.parse()?;
+            set_level(&mut self.bids, Reverse(price.to_bits()), qty.parse()?);
+        }
+        for [price, qty] in &update.asks {
+            let price: f64 = price.parse()?;
+            set_level(&mut self.asks, price.to_bits(), qty.parse()?);
+        }
+        self.last_update_id = update.final_update_id;
+        Ok(true)
+    }
+
+    /// Apply changed levels from a feed without update ids, counting them as
+    /// the next update; zero-quantity levels are removed.
+    pub fn apply_levels(&mut self, bids: &[(f64, f64)], asks: &[(f64, f64)]) {
+        for &(price, qty) in bids {
+            set_level(&mut self.bids, Reverse(price.to_bits()), qty);
+        }
+        for &(price, qty) in asks {
+            set_level(&mut self.asks, price.to_bits(), qty);
+        }
+        self.last_update_id += 1;
+    }
+
+    /// The top `levels` per side as currently held (0 = all); a side that
+    /// has shrunk reports only what is left.
+    pub fn to_depth(&self, levels: usize, received_at_ms: u64) -> BookDepth {
+        let take = if levels == 0 { usize::MAX } else { levels };
+        BookDepth {
+            timestamp: self.last_update_id,
+            bids: self
+                .bids
+                .iter()
+                .take(take)
+                .map(|(Reverse(bits), &qty)| (f64::from_bits(*bits), qty))
+                .collect(),
+            asks: self
+                .asks
+                .iter()
+                .take(take)
+                .map(|(bits, &qty)| (f64::from_bits(*bits), qty))
+                .collect(),
+            received_at_ms,
+            event_time_ms: 0,
+            clock_skew_ms: None,
+            stale: false,
+            exact: None,
+        }
+    }
+}
+
+fn set_level<K: Ord>(side: &mut BTreeMap<K, f64>, key: K, qty: f64) {
+    if qty > 0.0 {
+        side.insert(key, qty);
+    } else {
+        side.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zeroed_level_is_removed_and_depth_shrinks() {
+        let mut book = LocalBook::from_snapshot(
+            100,
+            &[(4_000.0, 1.0), (3_999.0, 2.0), (3_998.0, 3.0)],
+            &[(4_001.0, 1.0), (4_002.0, 2.0)],
+        );
+        let raw = r#"{"e":"depthUpdate","E":1,"s":"ETHUSDC","U":101,"u":102,
+            "b":[["3999.00","0.00000000"],["4000.00","1.50000000"]],
+            "a":[["4002.00","0"]]}"#;
+        let update: DiffDepthUpdate = serde_json::from_str(raw).unwrap();
+        assert!(book.apply(&update).unwrap());
+
+        let depth = book.to_depth(20, 7);
+        assert_eq!(depth.bids, vec![(4_000.0, 1.5), (3_998.0, 3.0)]);
+        assert_eq!(depth.asks, vec![(4_001.0, 1.0)]);
+        assert_eq!(depth.timestamp, 102);
+
+        // Replays are ignored and gaps are reported
+        assert!(!book.apply(&update).unwrap());
+        let gap = DiffDepthUpdate {
+            first_update_id: 110,
+            final_update_id: 111,
+            bids: vec![],
+            asks: vec![],
+        };
+        assert!(book.apply(&gap).is_err());
+    }
+}
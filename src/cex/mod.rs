@@ -1,5 +1,27 @@
 //! CEX (Centralized Exchange) integration.
 
 pub mod binance;
+pub mod coinbase;
+pub mod consolidated;
+pub mod events;
+pub mod feed;
+pub mod health;
+pub mod local_book;
+pub mod mock;
+pub mod perp;
+pub mod skew;
 
-pub use binance::{connect_and_stream, spawn_cex_stream_watcher};
+pub use binance::{
+    BinanceFeed, BookStream, StreamOptions, connect_and_stream, spawn_cex_stream_watcher,
+};
+pub use coinbase::{CoinbaseFeed, Level2Book};
+pub use consolidated::ConsolidatedBook;
+pub use events::{FeedEvent, FeedEvents, FeedUpdate, spawn_feed_event_log};
+pub use feed::{CexExchange, CexFeed, spawn_feed_watcher};
+pub use health::ReconnectMonitor;
+pub use local_book::{DiffDepthUpdate, LocalBook};
+pub use mock::{MockBookGenerator, spawn_mock_book_feed};
+pub use perp::{
+    BINANCE_FUTURES_SERVER_TIME_URL, MarkPrice, parse_mark_price, spawn_perp_mark_watcher,
+};
+pub use skew::{ClockSkewEstimator, spawn_server_time_calibration};
@@ -0,0 +1,184 @@
+//! Binance USD-M perpetual mark price and funding feed.
+
+use crate::cex::events::{FeedEvent, FeedEvents};
+use crate::cex::feed::Backoff;
+use crate::cex::skew::ClockSkewEstimator;
+use crate::errors::Result;
+use crate::models::BookDepth;
+use crate::utils::now_ms;
+use futures::StreamExt;
+use serde::Deserialize;
+use tokio::sync::watch;
+use tokio_tungstenite::connect_async;
+use tracing::warn;
+use url::Url;
+
+const BINANCE_FUTURES_WS_ENDPOINT: &str = "wss://fstream.binance.com/ws";
+
+/// Binance USD-M futures clock, `{"serverTime": ms}`
+pub const BINANCE_FUTURES_SERVER_TIME_URL: &str = "https://fapi.binance.com/fapi/v1/time";
+
+#[derive(Debug, Deserialize)]
+struct MarkPriceMsg {
+    #[serde(rename = "E")]
+    event_time: u64,
+    #[serde(rename = "p")]
+    mark_price: String,
+    #[serde(rename = "i")]
+    index_price: String,
+    #[serde(rename = "r")]
+    funding_rate: String,
+    #[serde(rename = "T")]
+    next_funding_time: u64,
+}
+
+/// One `<symbol>@markPrice` update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkPrice {
+    pub event_time_ms: u64,
+    pub mark_price: f64,
+    pub index_price: f64,
+    /// Funding rate of the current interval, as a fraction (0.0001 = 1 bp);
+    /// longs pay shorts when positive
+    pub funding_rate: f64,
+    pub next_funding_time_ms: u64,
+}
+
+impl MarkPrice {
+    /// Single-level book at the mark price. The mark carries no depth, so
+    /// the size is unbounded and the trade is sized by the DEX leg alone.
+    pub fn to_book(&self, received_at_ms: u64) -> BookDepth {
+        BookDepth {
+            timestamp: self.event_time_ms,
+            bids: vec![(self.mark_price, f64::INFINITY)],
+            asks: vec![(self.mark_price, f64::INFINITY)],
+            received_at_ms,
+            event_time_ms: self.event_time_ms,
+            clock_skew_ms: None,
+            stale: false,
+            exact: None,
+        }
+    }
+}
+
+/// Parse a `markPriceUpdate` message.
+pub fn parse_mark_price(raw: &str) -> Result<MarkPrice> {
+    let msg: MarkPriceMsg = serde_json::from_str(raw)?;
+    Ok(MarkPrice {
+        event_time_ms: msg.event_time,
+        mark_price: msg.mark_price.parse()?,
+        index_price: msg.index_price.parse()?,
+        funding_rate: msg.funding_rate.parse()?,
+        next_funding_time_ms: msg.next_funding_time,
+    })
+}
+
+/// Spawn the perp mark price watcher
+///
+/// Publishes each mark price as a book on `cex_tx` and the funding rate on
+/// `funding_tx`, reconnecting whenever the stream ends or the connect fails.
+/// Connection transitions are published on `events`. With `skew`, each
+/// book carries the venue's estimated clock skew, so its age is measured
+/// from the mark's event time.
+pub async fn spawn_perp_mark_watcher(
+    symbol: &str,
+    cex_tx: watch::Sender<BookDepth>,
+    funding_tx: watch::Sender<f64>,
+    mut skew: Option<ClockSkewEstimator>,
+    events: FeedEvents,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let url = Url::parse(&format!(
+        "{}/{}@markPrice@1s",
+        BINANCE_FUTURES_WS_ENDPOINT,
+        symbol.to_lowercase()
+    ))?;
+
+    let handle = tokio::spawn(async move {
+        let mut attempt = 0;
+        loop {
+            if attempt > 0 {
+                events.publish(FeedEvent::Reconnecting { attempt });
+            }
+            match connect_async(url.as_str()).await {
+                Ok((mut ws_stream, _resp)) => {
+                    attempt = 0;
+                    events.publish(FeedEvent::Connected);
+                    let mut fresh = false;
+                    while let Some(msg) = ws_stream.next().await {
+                        let txt = match msg {
+                            Ok(msg) if msg.is_text() => match msg.into_text() {
+                                Ok(txt) => txt,
+                                Err(e) => {
+                                    warn!(error = %e, "[PERP] text extraction failed");
+                                    continue;
+                                }
+                            },
+                            Ok(_) => continue,
+                            Err(e) => {
+                                warn!(error = %e, "[PERP] websocket message error");
+                                break;
+                            }
+                        };
+                        match parse_mark_price(&txt) {
+                            Ok(mark) => {
+                                let _ = funding_tx.send(mark.funding_rate);
+                                let mut book = mark.to_book(now_ms());
+                                if let Some(skew) = &mut skew {
+                                    skew.stamp(&mut book);
+                                }
+                                let _ = cex_tx.send(book);
+                                if !fresh {
+                                    fresh = true;
+                                    events.publish(FeedEvent::Fresh);
+                                }
+                            }
+                            Err(e) => warn!(error = %e, "[PERP] mark price parse failed"),
+                        }
+                    }
+                    warn!("[PERP] stream ended");
+                    events.publish(FeedEvent::Disconnected);
+                }
+                Err(e) => warn!(error = %e, "[PERP] connect failed"),
+            }
+            if cex_tx.send_if_modified(|book| !std::mem::replace(&mut book.stale, true)) {
+                events.publish(FeedEvent::Stale);
+            }
+            attempt += 1;
+            let delay = Backoff::default().delay(attempt);
+            warn!(
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "[PERP] reconnecting"
+            );
+            tokio::time::sleep(delay).await;
+        }
+    });
+
+    Ok(handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mark_price_update() {
+        let raw = r#"{"e":"markPriceUpdate","E":1562305380000,"s":"ETHUSDT","p":"4011.25000000","i":"4010.62659091","P":"4010.25641265","r":"0.00038167","T":1562306400000}"#;
+        let mark = parse_mark_price(raw).expect("valid message");
+        assert_eq!(
+            mark,
+            MarkPrice {
+                event_time_ms: 1_562_305_380_000,
+                mark_price: 4011.25,
+                index_price: 4010.62659091,
+                funding_rate: 0.00038167,
+                next_funding_time_ms: 1_562_306_400_000,
+            }
+        );
+        let book = mark.to_book(7);
+        assert_eq!(book.bids[0].0, 4011.25);
+        assert_eq!(book.asks[0].0, 4011.25);
+
+        assert!(parse_mark_price(r#"{"E":1,"p":"x","i":"1","r":"0","T":2}"#).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Per-venue clock skew between the venue's timestamps and local time.
+//!
+//! A message stamped `E` by the venue arrives `latency` later in local time,
+//! so each message yields the sample `E − received_at = skew − latency`. The
+//! largest sample over a window of recent messages is the one with the least
+//! latency, i.e. the closest to the skew itself. With the skew known, a
+//! book's age can be measured from when the venue produced it, not only from
+//! when it reached us (see `BookDepth::age_ms`).
+//!
+//! Where the venue publishes its clock over REST, the skew can instead be
+//! calibrated against it periodically (see [`spawn_server_time_calibration`]):
+//! the server time is compared with the midpoint of the request's round trip,
+//! which takes the latency out of the estimate.
+
+use crate::errors::{AppError, Result};
+use crate::models::BookDepth;
+use crate::utils::now_ms;
+use std::collections::VecDeque;
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Rolling estimate of one venue's clock minus the local clock, in ms.
+#[derive(Debug, Clone)]
+pub struct ClockSkewEstimator {
+    samples: VecDeque<i64>,
+    window: usize,
+    /// Latest skew calibrated against the venue's server time
+    server_skew_rx: Option<watch::Receiver<Option<i64>>>,
+}
+
+impl ClockSkewEstimator {
+    /// Estimate over the last `window` messages (at least one).
+    pub fn new(window: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(window.max(1)),
+            window: window.max(1),
+            server_skew_rx: None,
+        }
+    }
+
+    /// Prefer the skew calibrated against the venue's server time, once
+    /// `server_skew_rx` holds one, over the estimate from message timestamps.
+    pub fn with_server_time(mut self, server_skew_rx: watch::Receiver<Option<i64>>) -> Self {
+        self.server_skew_rx = Some(server_skew_rx);
+        self
+    }
+
+    /// Record a message the venue stamped `venue_ms` that arrived at `local_ms`.
+    pub fn observe(&mut self, venue_ms: u64, local_ms: u64) {
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(venue_ms as i64 - local_ms as i64);
+    }
+
+    /// Venue clock minus local clock; `None` before the first sample.
+    pub fn skew_ms(&self) -> Option<i64> {
+        self.server_skew_rx
+            .as_ref()
+            .and_then(|rx| *rx.borrow())
+            .or_else(|| self.samples.iter().copied().max())
+    }
+
+    /// Feed `book`'s venue timestamp into the estimate and stamp the book with
+    /// it. Books without a venue timestamp are left as they are.
+    pub fn stamp(&mut self, book: &mut BookDepth) {
+        if book.event_time_ms == 0 {
+            return;
+        }
+        self.observe(book.event_time_ms, book.received_at_ms);
+        book.clock_skew_ms = self.skew_ms();
+    }
+}
+
+/// Venue clock minus local clock from one call to `url`, a `serverTime`
+/// endpoint: the server's time against the midpoint of the round trip.
+pub async fn fetch_server_time_skew(client: &reqwest::Client, url: &str) -> Result<i64> {
+    let sent_ms = now_ms();
+    let body = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| AppError::Other(format!("server time: {}", e)))?
+        .bytes()
+        .await
+        .map_err(|e| AppError::Other(format!("server time: {}", e)))?;
+    let received_ms = now_ms();
+    let server_ms = parse_server_time(&body)?;
+    let midpoint_ms = sent_ms + (received_ms.saturating_sub(sent_ms)) / 2;
+    Ok(server_ms as i64 - midpoint_ms as i64)
+}
+
+/// Server time in Unix ms from a Binance `{"serverTime": ms}` or Coinbase
+/// `{"epoch": seconds}` response.
+fn parse_server_time(body: &[u8]) -> Result<u64> {
+    let value: serde_json::Value = serde_json::from_slice(body)?;
+    value
+        .get("serverTime")
+        .and_then(|ms| ms.as_u64())
+        .or_else(|| {
+            value
+                .get("epoch")
+                .and_then(|secs| secs.as_f64())
+                .map(|secs| (secs * 1_000.0).round() as u64)
+        })
+        .ok_or_else(|| AppError::Other(format!("server time missing from {}", value)))
+}
+
+/// Spawn a task calibrating the venue's clock skew against its `serverTime`
+/// endpoint `url` every `interval`, for [`ClockSkewEstimator::with_server_time`].
+/// A failed call keeps the last calibration.
+pub fn spawn_server_time_calibration(
+    url: &str,
+    interval: std::time::Duration,
+) -> (watch::Receiver<Option<i64>>, tokio::task::JoinHandle<()>) {
+    let (skew_tx, skew_rx) = watch::channel(None);
+    let url = url.to_string();
+    let handle = tokio::spawn(async move {
+        let client = reqwest::Client::new();
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match fetch_server_time_skew(&client, &url).await {
+                Ok(skew_ms) => {
+                    let _ = skew_tx.send(Some(skew_ms));
+                }
+                Err(e) => warn!(%url, error = %e, "[CEX] clock skew calibration failed"),
+            }
+        }
+    });
+    (skew_rx, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_skew_corrects_the_book_age() {
+        // The venue clock runs 750 ms ahead; messages take 20–60 ms to arrive
+        let mut skew = ClockSkewEstimator::new(4);
+        for (sent_local, latency) in [(1_000, 60), (1_100, 20), (1_200, 45)] {
+            skew.observe(sent_local + 750, sent_local + latency);
+        }
+        assert_eq!(skew.skew_ms(), Some(730));
+
+        // Produced at local 2_000, received at 2_030, evaluated at 2_100
+        let mut book = BookDepth {
+            bids: vec![(4_000.0, 1.0)],
+            asks: vec![(4_001.0, 1.0)],
+            event_time_ms: 2_750,
+            received_at_ms: 2_030,
+            ..Default::default()
+        };
+        assert_eq!(book.age_ms(2_100), 70);
+        skew.stamp(&mut book);
+        assert_eq!(book.clock_skew_ms, Some(730));
+        assert_eq!(book.age_ms(2_100), 80);
+
+        // Uncorrected, the venue clock alone would put the book in the future
+        assert_eq!(
+            BookDepth {
+                clock_skew_ms: Some(0),
+                ..book.clone()
+            }
+            .age_ms(2_100),
+            0
+        );
+
+        // The window forgets the low-latency sample
+        for sent_local in [3_000, 3_100, 3_200, 3_300] {
+            skew.observe(sent_local + 750, sent_local + 50);
+        }
+        assert_eq!(skew.skew_ms(), Some(700));
+    }
+
+    #[tokio::test]
+    async fn server_time_calibration_overrides_message_samples() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // A venue clock 5 s ahead answering one `serverTime` call
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/v3/time", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            let body = format!("{{\"serverTime\":{}}}", now_ms() + 5_000);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        let skew_ms = fetch_server_time_skew(&reqwest::Client::new(), &url)
+            .await
+            .unwrap();
+        server.await.unwrap();
+        assert!((4_900..=5_100).contains(&skew_ms), "{skew_ms}");
+
+        // Until calibrated the messages decide; after, the server time does
+        let (skew_tx, skew_rx) = watch::channel(None);
+        let mut skew = ClockSkewEstimator::new(4).with_server_time(skew_rx);
+        skew.observe(10_700, 10_000);
+        assert_eq!(skew.skew_ms(), Some(700));
+        skew_tx.send(Some(skew_ms)).unwrap();
+        assert_eq!(skew.skew_ms(), Some(skew_ms));
+
+        assert_eq!(
+            parse_server_time(br#"{"iso":"2015-01-07T23:47:25.201Z","epoch":1420674445.201}"#)
+                .unwrap(),
+            1_420_674_445_201
+        );
+        assert!(parse_server_time(b"{}").is_err());
+    }
+}
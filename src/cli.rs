@@ -7,3 +7,14 @@
 pub fn parse_args() {
     todo!("Implement CLI parsing");
 }
+
+/// Flag streaming every emitted opportunity to stdout as one JSON object per
+/// line, with the logs moved to stderr.
+pub const STDOUT_JSONL_FLAG: &str = "--stdout-jsonl";
+
+/// Whether `args` (without the program name) or `STDOUT_JSONL=true` ask for
+/// the stdout JSONL stream.
+pub fn stdout_jsonl_requested(args: impl IntoIterator<Item = String>) -> bool {
+    args.into_iter().any(|arg| arg == STDOUT_JSONL_FLAG)
+        || std::env::var("STDOUT_JSONL").is_ok_and(|raw| raw.trim().eq_ignore_ascii_case("true"))
+}
@@ -0,0 +1,46 @@
+//! Time source abstraction so time-dependent logic can be tested deterministically.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Source of wall-clock time in milliseconds since the Unix epoch.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now_ms(&self) -> u64;
+}
+
+/// The real system clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        crate::utils::now_ms()
+    }
+}
+
+/// Manually driven clock for tests and replays.
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now_ms: AtomicU64,
+}
+
+impl MockClock {
+    pub fn new(start_ms: u64) -> Self {
+        Self {
+            now_ms: AtomicU64::new(start_ms),
+        }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now_ms.store(now_ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, delta_ms: u64) {
+        self.now_ms.fetch_add(delta_ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+}
@@ -1,24 +1,154 @@
 //! Configuration loader and application settings.
 
-use crate::arbitrage::ArbitrageConfig;
+use crate::arbitrage::{
+    ArbitrageConfig, DepthGuard, DoubleEdgePolicy, FeeRole, GasEstimate,
+    OPPORTUNITY_SCHEMA_VERSION, PoolSelection, ScoreFn, calculate_gas_cost_usdc,
+    calculate_gas_cost_usdc_exact, check_opportunity_schema_version, load_fee_schedules,
+};
+use crate::backtest::ReplaySpeed;
+use crate::bias::parse_direction_bias;
+use crate::blackout::BlackoutWindows;
+use crate::cex::{BookStream, CexExchange, ReconnectMonitor, StreamOptions};
+use crate::dex::FeeModel;
+use crate::errors::AppError;
+use crate::health::{HealthConfig, HealthWeights};
+use crate::oracle::DivergenceConfig;
+use crate::rng::time_based_seed;
+use crate::shadow::ConfigOverrides;
+use crate::utils::{GasSmoothing, MissingBaseFee};
+use std::collections::BTreeMap;
+use std::str::FromStr;
 
 /// Consolidated application configuration.
 #[derive(Debug, Clone)]
 pub struct AppConfig {
-    /// RPC endpoint for the Ethereum-compatible node.
-    pub rpc_url: String,
+    /// Pools to watch; the first one comes from `CHAIN_ID`/`RPC_URL`/`POOL_ADDRESS`.
+    pub pools: Vec<PoolConfig>,
     /// WebSocket endpoint for the chosen CEX public feed.
     pub cex_ws_url: String,
     /// Trading pair symbol (e.g., "ETH/USDC").
     //pub pair: String,
-    /// Pool address
-    pub pool_address: String,
     /// Minimum PnL threshold to log opportunities
     pub min_pnl_usdc: f64,
     /// Gas configuration
     pub gas_config: GasConfig,
     /// Arbitrage config
     pub arbitrage_config: ArbitrageConfig,
+    /// CEX feed health thresholds
+    pub feed_health_config: FeedHealthConfig,
+    /// Weights and limits of the system health score
+    pub health_config: HealthConfig,
+    /// What drives an evaluation pass
+    pub eval_trigger: EvalTriggerMode,
+    /// Silence on the `newHeads` subscription after which the pool is re-read
+    /// to check the subscription is alive, in ms (0 = never)
+    pub max_head_silence_ms: u64,
+    /// Evaluation pass duration above which low-priority pools are shed (0 = never)
+    pub eval_budget_ms: u64,
+    /// Cap on the notional of opportunities emitted across all pools within
+    /// `inflight_window_ms`, in quote units (0 = unlimited)
+    pub max_inflight_notional_usdc: f64,
+    /// How long an emitted opportunity counts against the notional cap
+    pub inflight_window_ms: u64,
+    /// Outbound RPC requests-per-second budget per endpoint (0 = unlimited)
+    pub rpc_max_rps: f64,
+    /// Optional JSON file caching pool/token metadata across runs
+    pub metadata_cache_path: Option<String>,
+    /// Optional JSONL file receiving every emitted opportunity
+    pub opportunity_log_path: Option<String>,
+    /// Schema version of the records written to `opportunity_log_path`
+    pub opportunity_schema_version: u32,
+    /// Optional JSONL file receiving the periodic market snapshots, kept
+    /// apart from the opportunity log
+    pub market_snapshot_log_path: Option<String>,
+    /// Optional JSONL file receiving every CEX feed health transition
+    pub feed_event_log_path: Option<String>,
+    /// Optional JSONL file receiving every system health report
+    pub health_log_path: Option<String>,
+    /// File holding the direction bias, re-read every few seconds; overrides
+    /// `DIRECTION_BIAS` once read
+    pub direction_bias_file: Option<String>,
+    /// Candidate config evaluated beside the live one, as `key=value` overrides
+    pub shadow_overrides: Option<ConfigOverrides>,
+    /// JSONL file receiving where the shadow config diverges from the live one
+    pub shadow_log_path: Option<String>,
+    /// Append every evaluation pass's inputs and outputs here, for offline replay
+    pub evaluation_log_path: Option<String>,
+    /// Records each sink accepts per clock hour before suppressing (0 = unlimited)
+    pub sink_max_per_hour: usize,
+    /// Evaluate against one merged book of all CEX venues instead of per venue
+    pub consolidated_book: bool,
+    /// Log the basis between quote assets implied by pools of the same base
+    pub cross_quote_basis: bool,
+    /// Seed for every randomized component; time-based unless `SEED` is set
+    pub seed: u64,
+    /// Replace the live CEX feed with generated books (seeded by `seed`)
+    pub mock_cex_feed: bool,
+    /// Start with evaluation paused until the first SIGUSR1
+    pub start_paused: bool,
+    /// Static gas price used when a gas watcher cannot start (unset = fail startup)
+    pub gas_fallback_gwei: Option<f64>,
+    /// Gas price reported for blocks without a base fee (pre-1559 chains, some L2s)
+    pub gas_missing_base_fee: MissingBaseFee,
+    /// Smoothing of the per-block gas price fed to PnL
+    pub gas_smoothing: GasSmoothing,
+    /// Blocks the gas smoothing spans
+    pub gas_smoothing_blocks: usize,
+    /// Stablecoin pool (e.g. USDC/USDT) on the first pool's chain pricing the
+    /// USDC quote in USD, for depeg awareness
+    pub usdc_reference_pool: Option<String>,
+    /// ETH/USD pool on the first pool's chain whose price the first pool and
+    /// its CEX feed are checked against
+    pub oracle_pool: Option<String>,
+    /// When a gap to the oracle price counts as a persistent divergence
+    pub oracle_divergence: DivergenceConfig,
+    /// Window of the pool TWAP the spot price is checked against, in seconds
+    pub twap_window_secs: u32,
+    /// Ticks on each side of the current one to load as DEX segments (0 = active range only)
+    pub segment_window_ticks: u32,
+    /// Uniswap `TickLens` reading each tick bitmap word's initialized ticks in
+    /// one call (unset = read them from the pool)
+    pub tick_lens_address: Option<String>,
+    /// Startup sqrtPriceX96 round-trip tolerance in bps (0 = skip the check)
+    pub sqrt_round_trip_tolerance_bps: f64,
+    /// Exchange the spot book is streamed from
+    pub cex_exchange: CexExchange,
+    /// Further exchanges streamed beside `cex_exchange`, each evaluated as
+    /// its own venue
+    pub cex_extra_exchanges: Vec<CexExchange>,
+    /// Which Binance market the CEX leg trades
+    pub cex_market: CexMarket,
+    /// How the Binance book stream is read: stream kind, update id jump
+    /// warning and level precision
+    pub cex_stream: StreamOptions,
+    /// Recent messages a venue's clock skew is estimated over, correcting
+    /// book ages measured from venue timestamps (0 = off, ages from receipt)
+    pub clock_skew_window: usize,
+    /// Calibrate each venue's clock skew against its REST server time this
+    /// often, in seconds, instead of estimating it from messages (0 = off)
+    pub clock_skew_server_time_secs: u64,
+    /// Backtest: replay this JSONL book capture instead of the live CEX feed
+    pub replay_capture_path: Option<String>,
+    /// Pace of the capture replay
+    pub replay_speed: ReplaySpeed,
+    /// Backtest: replay this JSONL pool state capture instead of the pool watcher
+    pub replay_pool_path: Option<String>,
+    /// Backtest: replay this JSONL gas price capture instead of the gas watcher
+    pub replay_gas_path: Option<String>,
+    /// Capture the first pool's state channel to this JSONL file, for replay
+    pub capture_pool_path: Option<String>,
+    /// Capture the first pool's gas price channel to this JSONL file, for replay
+    pub capture_gas_path: Option<String>,
+    /// Executor opportunities are POSTed to (`execution` feature; unset = none)
+    pub execution_endpoint: Option<String>,
+    /// HMAC-SHA256 key signing the bodies sent to `execution_endpoint`
+    pub execution_hmac_key: Option<String>,
+    /// Minimum PnL in quote units for an opportunity to be submitted
+    pub execution_min_pnl_usdc: f64,
+    /// Simulate each opportunity's DEX leg before submitting it for execution
+    pub simulate_execution: bool,
+    /// How far in bps the simulated DEX leg may fill short of the evaluated price
+    pub simulation_tolerance_bps: f64,
 }
 
 impl AppConfig {
@@ -30,24 +160,605 @@ impl AppConfig {
         let min_pnl_usdc: f64 = std::env::var("MIN_PNL_USDC")?.parse()?;
         let gas_units: f64 = std::env::var("GAS_UNITS")?.parse()?;
         let gas_multiplier: f64 = std::env::var("GAS_MULTIPLIER")?.parse()?;
+        let gas_mode = match std::env::var("GAS_FIXED_USD") {
+            Ok(raw) => {
+                let fixed: f64 = raw.parse()?;
+                if !(fixed >= 0.0 && fixed.is_finite()) {
+                    return Err(AppError::Config(format!(
+                        "GAS_FIXED_USD must be a non-negative number, got {}",
+                        fixed
+                    )));
+                }
+                GasCostMode::FixedUsd(fixed)
+            }
+            Err(std::env::VarError::NotPresent) => GasCostMode::Dynamic,
+            Err(e) => return Err(e.into()),
+        };
         let dex_fee_bps: f64 = std::env::var("DEX_FEE_BPS")?.parse()?;
         let cex_fee_bps: f64 = std::env::var("CEX_FEE_BPS")?.parse()?;
-        Ok(Self {
+        let min_pnl_bps: f64 = env_or("MIN_PNL_BPS", 0.0)?;
+        let require_positive_net_edge: bool = env_or("REQUIRE_POSITIVE_NET_EDGE", false)?;
+        let max_gas_fraction: f64 = env_or("MAX_GAS_FRACTION", 0.0)?;
+        let min_basis_bps: f64 = env_or("MIN_BASIS_BPS", 0.0)?;
+        let anomalous_basis_bps: f64 = env_or("ANOMALOUS_BASIS_BPS", 0.0)?;
+        let max_book_age_ms: u64 = env_or("MAX_BOOK_AGE_MS", 5_000)?;
+        let enter_margin_usdc: f64 = env_or("HYSTERESIS_ENTER_USDC", 0.0)?;
+        let exit_margin_usdc: f64 = env_or("HYSTERESIS_EXIT_USDC", 0.0)?;
+        let imbalance_levels: usize = env_or("IMBALANCE_LEVELS", 10)?;
+        let max_ticks_traversed: usize = env_or("MAX_TICKS_TRAVERSED", 0)?;
+        let max_target_move_bps: f64 = env_or("MAX_TARGET_MOVE_BPS", 0.0)?;
+        let max_amount_clamp: f64 = env_or("MAX_AMOUNT_CLAMP", 0.0)?;
+        let min_depth_ratio: f64 = env_or("MIN_DEPTH_RATIO", 0.0)?;
+        let depth_guard: DepthGuard = env_or("DEPTH_GUARD", DepthGuard::DownSize)?;
+        let max_slippage_bps: f64 = env_or("MAX_SLIPPAGE_BPS", 0.0)?;
+        let current_tick_only: bool = env_or("CURRENT_TICK_ONLY", false)?;
+        let candidate_sizes_eth: Vec<f64> = match std::env::var("CANDIDATE_SIZES_ETH") {
+            Ok(raw) => raw
+                .split(',')
+                .map(str::trim)
+                .filter(|size| !size.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+            Err(std::env::VarError::NotPresent) => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let degraded_pnl_factor: f64 = env_or("DEGRADED_PNL_FACTOR", 2.0)?;
+        let cex_min_notional_usdc = match std::env::var("CEX_MIN_NOTIONAL_USDC") {
+            Ok(raw) => parse_venue_map(&raw)?,
+            Err(std::env::VarError::NotPresent) => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let dex_min_notional_usdc: f64 = env_or("DEX_MIN_NOTIONAL_USDC", 0.0)?;
+        let score_fn: ScoreFn = env_or("SCORE_FN", ScoreFn::Pnl)?;
+        let direction_bias = match std::env::var("DIRECTION_BIAS") {
+            Ok(raw) => parse_direction_bias(&raw)?,
+            Err(std::env::VarError::NotPresent) => 0.0,
+            Err(e) => return Err(e.into()),
+        };
+        let double_edge_policy: DoubleEdgePolicy =
+            env_or("DOUBLE_EDGE_POLICY", DoubleEdgePolicy::EmitLarger)?;
+        let sequence_double_edge: bool = env_or("SEQUENCE_DOUBLE_EDGE", false)?;
+        let report_sensitivities: bool = env_or("REPORT_SENSITIVITIES", false)?;
+        let report_cex_baseline: bool = env_or("REPORT_CEX_BASELINE", false)?;
+        let report_leg_venues: bool = env_or("REPORT_LEG_VENUES", false)?;
+        let report_pnl_interval: bool = env_or("REPORT_PNL_INTERVAL", false)?;
+        let pnl_interval_drift_bps_per_s: f64 = env_or("PNL_INTERVAL_DRIFT_BPS_PER_S", 2.0)?;
+        let pnl_interval_liquidity_fraction: f64 = env_or("PNL_INTERVAL_LIQUIDITY_FRACTION", 0.05)?;
+        let pool_selection: PoolSelection = env_or("POOL_SELECTION", PoolSelection::BestPrice)?;
+        let funding_periods: f64 = env_or("PERP_FUNDING_PERIODS", 1.0)?;
+        let confirm_repricing: bool = env_or("CONFIRM_REPRICING", false)?;
+        let confirm_wait_ms: u64 = env_or("CONFIRM_WAIT_MS", 500)?;
+        let cex_fee_schedules = match std::env::var("CEX_FEE_SCHEDULE_PATH") {
+            Ok(path) => load_fee_schedules(&path)?,
+            Err(std::env::VarError::NotPresent) => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let cex_volume_30d_usdc: f64 = env_or("CEX_VOLUME_30D_USDC", 0.0)?;
+        let cex_fee_role: FeeRole = env_or("CEX_FEE_ROLE", FeeRole::Taker)?;
+        let cex_maker_fill_probability: Option<f64> =
+            match std::env::var("CEX_MAKER_FILL_PROBABILITY") {
+                Ok(raw) => Some(raw.parse()?),
+                Err(std::env::VarError::NotPresent) => None,
+                Err(e) => return Err(e.into()),
+            };
+        let blackout_windows: BlackoutWindows =
+            env_or("BLACKOUT_WINDOWS", BlackoutWindows::default())?;
+        let min_emit_interval_ms: u64 = env_or("MIN_EMIT_INTERVAL_MS", 0)?;
+        let min_time_in_profit_ms: u64 = env_or("MIN_TIME_IN_PROFIT_MS", 0)?;
+        let snapshot_interval_ms: u64 = env_or("MARKET_SNAPSHOT_INTERVAL_MS", 0)?;
+        let fee_warning_interval_ms: u64 = env_or("FEE_WARNING_INTERVAL_MS", 300_000)?;
+        let max_quote_depeg_bps: f64 = env_or("MAX_USDC_DEPEG_BPS", 0.0)?;
+        let max_twap_divergence_bps: f64 = env_or("MAX_TWAP_DIVERGENCE_BPS", 0.0)?;
+        let feed_health_config = FeedHealthConfig {
+            reconnect_window_ms: env_or("RECONNECT_WINDOW_MS", 60_000)?,
+            max_reconnects: env_or("RECONNECT_MAX_IN_WINDOW", 3)?,
+            degraded_cooldown_ms: env_or("DEGRADED_COOLDOWN_MS", 120_000)?,
+        };
+        let health_config = HealthConfig {
+            weights: env_or("HEALTH_WEIGHTS", HealthWeights::default())?,
+            cex_max_age_ms: max_book_age_ms,
+            pool_max_age_ms: env_or("HEALTH_POOL_MAX_AGE_MS", 30_000)?,
+            gas_max_age_ms: env_or("HEALTH_GAS_MAX_AGE_MS", 60_000)?,
+            max_rpc_error_rate: env_or("HEALTH_MAX_RPC_ERROR_RATE", 0.5)?,
+            max_reconnects_per_min: env_or("HEALTH_MAX_RECONNECTS_PER_MIN", 3.0)?,
+            warn_below: env_or("HEALTH_WARN_BELOW", 0.5)?,
+        };
+        let arbitrage_config = ArbitrageConfig {
+            min_pnl_usdc,
+            min_pnl_bps,
+            require_positive_net_edge,
+            max_gas_fraction,
+            dex_fee_bps,
+            dex_fee_model: FeeModel::Fixed,
+            cex_fee_bps,
+            min_basis_bps,
+            anomalous_basis_bps,
+            max_book_age_ms,
+            enter_margin_usdc,
+            exit_margin_usdc,
+            imbalance_levels,
+            max_ticks_traversed,
+            max_target_move_bps,
+            candidate_sizes_eth,
+            max_amount_clamp,
+            min_depth_ratio,
+            depth_guard,
+            max_slippage_bps,
+            current_tick_only,
+            degraded_pnl_factor,
+            cex_min_notional_usdc,
+            dex_min_notional_usdc,
+            score_fn,
+            direction_bias,
+            double_edge_policy,
+            sequence_double_edge,
+            report_sensitivities,
+            report_cex_baseline,
+            report_leg_venues,
+            report_pnl_interval,
+            pnl_interval_drift_bps_per_s,
+            pnl_interval_liquidity_fraction,
+            pool_selection,
+            funding_rate: 0.0,
+            funding_periods,
+            confirm_repricing,
+            confirm_wait_ms,
+            cex_fee_schedules,
+            cex_volume_30d_usdc,
+            cex_fee_role,
+            blackout_windows,
+            min_emit_interval_ms,
+            min_time_in_profit_ms,
+            snapshot_interval_ms,
+            fee_warning_interval_ms,
+            max_quote_depeg_bps,
+            max_twap_divergence_bps,
+            cex_maker_fill_probability,
+        };
+        arbitrage_config.validate()?;
+        let eval_trigger: EvalTriggerMode = env_or("EVAL_TRIGGER", EvalTriggerMode::Interval)?;
+        let max_head_silence_ms: u64 = env_or("MAX_HEAD_SILENCE_MS", 60_000)?;
+        let eval_budget_ms: u64 = env_or("EVAL_BUDGET_MS", 0)?;
+        let max_inflight_notional_usdc: f64 = env_or("MAX_INFLIGHT_NOTIONAL_USDC", 0.0)?;
+        let inflight_window_ms: u64 = env_or("INFLIGHT_WINDOW_MS", 60_000)?;
+        let rpc_max_rps: f64 = env_or("RPC_MAX_RPS", 0.0)?;
+        let mut pools = vec![PoolConfig {
+            chain_id: env_or("CHAIN_ID", 1)?,
             rpc_url,
-            cex_ws_url,
+            ws_rpc_url: std::env::var("WS_RPC_URL").ok(),
             pool_address,
+            quote: QuoteAsset {
+                symbol: std::env::var("QUOTE_SYMBOL")
+                    .unwrap_or_else(|_| DEFAULT_QUOTE.to_string())
+                    .to_uppercase(),
+                token: std::env::var("QUOTE_TOKEN_ADDRESS").ok(),
+            },
+        }];
+        if let Ok(raw) = std::env::var("EXTRA_POOLS") {
+            pools.extend(parse_pool_list(&raw)?);
+        }
+        pools.extend(parse_pair_groups(std::env::vars())?);
+        let gas_units_by_chain = match std::env::var("GAS_UNITS_BY_CHAIN") {
+            Ok(raw) => parse_venue_map(&raw)?
+                .into_iter()
+                .map(|(chain, units)| Ok((chain.parse::<u64>()?, units)))
+                .collect::<crate::errors::Result<_>>()?,
+            Err(std::env::VarError::NotPresent) => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let per_tick_gas_units: f64 = env_or("GAS_PER_TICK_UNITS", 0.0)?;
+        let priority_bid_gwei: f64 = env_or("PRIORITY_BID_GWEI", 0.0)?;
+        let precision: PrecisionMode = env_or("GAS_PRECISION", PrecisionMode::Fast)?;
+        let metadata_cache_path = std::env::var("METADATA_CACHE_PATH").ok();
+        let opportunity_log_path = std::env::var("OPPORTUNITY_LOG_PATH").ok();
+        let market_snapshot_log_path = std::env::var("MARKET_SNAPSHOT_LOG_PATH").ok();
+        let opportunity_schema_version = check_opportunity_schema_version(env_or(
+            "OPPORTUNITY_SCHEMA_VERSION",
+            OPPORTUNITY_SCHEMA_VERSION,
+        )?)?;
+        let feed_event_log_path = std::env::var("FEED_EVENT_LOG_PATH").ok();
+        let health_log_path = std::env::var("HEALTH_LOG_PATH").ok();
+        let direction_bias_file = std::env::var("DIRECTION_BIAS_FILE").ok();
+        let shadow_overrides: Option<ConfigOverrides> = match std::env::var("SHADOW_OVERRIDES") {
+            Ok(raw) => Some(raw.parse()?),
+            Err(std::env::VarError::NotPresent) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let shadow_log_path = std::env::var("SHADOW_LOG_PATH").ok();
+        let evaluation_log_path = std::env::var("EVALUATION_LOG_PATH").ok();
+        if shadow_overrides.is_some() && shadow_log_path.is_none() {
+            return Err(AppError::Config(
+                "SHADOW_OVERRIDES requires SHADOW_LOG_PATH".to_string(),
+            ));
+        }
+        let sink_max_per_hour: usize = env_or("SINK_MAX_PER_HOUR", 0)?;
+        let consolidated_book: bool = env_or("CONSOLIDATED_BOOK", false)?;
+        let cross_quote_basis: bool = env_or("CROSS_QUOTE_BASIS", false)?;
+        let seed: u64 = match std::env::var("SEED") {
+            Ok(raw) => raw.parse()?,
+            Err(std::env::VarError::NotPresent) => time_based_seed(),
+            Err(e) => return Err(e.into()),
+        };
+        let mock_cex_feed: bool = env_or("MOCK_CEX_FEED", false)?;
+        let start_paused: bool = env_or("START_PAUSED", false)?;
+        let gas_fallback_gwei: Option<f64> = match std::env::var("GAS_FALLBACK_GWEI") {
+            Ok(raw) => Some(raw.parse()?),
+            Err(std::env::VarError::NotPresent) => None,
+            Err(e) => return Err(e.into()),
+        };
+        let gas_missing_base_fee: MissingBaseFee =
+            env_or("GAS_MISSING_BASE_FEE", MissingBaseFee::LegacyGasPrice)?;
+        let gas_smoothing: GasSmoothing = env_or("GAS_SMOOTHING", GasSmoothing::Raw)?;
+        let gas_smoothing_blocks: usize = env_or("GAS_SMOOTHING_BLOCKS", 10)?;
+        let usdc_reference_pool = std::env::var("USDC_REFERENCE_POOL").ok();
+        let oracle_pool = std::env::var("ORACLE_POOL").ok();
+        let oracle_divergence = DivergenceConfig {
+            tolerance_bps: env_or("ORACLE_TOLERANCE_BPS", 100.0)?,
+            min_duration_ms: env_or("ORACLE_MIN_DIVERGENCE_MS", 60_000)?,
+        };
+        let twap_window_secs: u32 = env_or("TWAP_WINDOW_SECS", 600)?;
+        if twap_window_secs == 0 {
+            return Err(AppError::Config(
+                "TWAP_WINDOW_SECS must be at least 1".to_string(),
+            ));
+        }
+        let segment_window_ticks: u32 = env_or("SEGMENT_WINDOW_TICKS", 200)?;
+        let tick_lens_address = std::env::var("TICK_LENS_ADDRESS").ok();
+        let sqrt_round_trip_tolerance_bps: f64 = env_or("SQRT_ROUND_TRIP_TOLERANCE_BPS", 1.0)?;
+        let cex_exchange: CexExchange = env_or("CEX_EXCHANGE", CexExchange::Binance)?;
+        let cex_market: CexMarket = env_or("CEX_MARKET", CexMarket::Spot)?;
+        if cex_market == CexMarket::Perp && cex_exchange != CexExchange::Binance {
+            return Err(AppError::Config(
+                "CEX_MARKET=perp requires CEX_EXCHANGE=binance".to_string(),
+            ));
+        }
+        let cex_extra_exchanges: Vec<CexExchange> = match std::env::var("CEX_EXTRA_EXCHANGES") {
+            Ok(raw) => raw
+                .split(',')
+                .map(str::trim)
+                .filter(|exchange| !exchange.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+            Err(std::env::VarError::NotPresent) => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        for (i, exchange) in cex_extra_exchanges.iter().enumerate() {
+            if *exchange == cex_exchange || cex_extra_exchanges[..i].contains(exchange) {
+                return Err(AppError::Config(format!(
+                    "CEX_EXTRA_EXCHANGES lists {:?} twice or repeats CEX_EXCHANGE",
+                    exchange
+                )));
+            }
+        }
+        if cex_market == CexMarket::Perp && !cex_extra_exchanges.is_empty() {
+            return Err(AppError::Config(
+                "CEX_EXTRA_EXCHANGES requires CEX_MARKET=spot".to_string(),
+            ));
+        }
+        let cex_stream = StreamOptions {
+            kind: env_or("CEX_BOOK_STREAM", BookStream::Depth)?,
+            depth_levels: env_or("CEX_DEPTH_LEVELS", 20)?,
+            update_ms: env_or("CEX_DEPTH_UPDATE_MS", 100)?,
+            max_update_id_jump: env_or("CEX_MAX_UPDATE_ID_JUMP", 0)?,
+            precision: env_or("BOOK_PRECISION", PrecisionMode::Fast)?,
+        };
+        cex_stream.validate()?;
+        let clock_skew_window: usize = env_or("CLOCK_SKEW_WINDOW", 0)?;
+        let clock_skew_server_time_secs: u64 = env_or("CLOCK_SKEW_SERVER_TIME_SECS", 0)?;
+        let replay_capture_path = std::env::var("REPLAY_CAPTURE_PATH").ok();
+        let replay_speed: ReplaySpeed = env_or("REPLAY_SPEED", ReplaySpeed::Instant)?;
+        let replay_pool_path = std::env::var("REPLAY_POOL_PATH").ok();
+        let replay_gas_path = std::env::var("REPLAY_GAS_PATH").ok();
+        let capture_pool_path = std::env::var("CAPTURE_POOL_PATH").ok();
+        let capture_gas_path = std::env::var("CAPTURE_GAS_PATH").ok();
+        let execution_endpoint = std::env::var("EXECUTION_ENDPOINT").ok();
+        let execution_hmac_key = std::env::var("EXECUTION_HMAC_KEY").ok();
+        if execution_endpoint.is_some() && execution_hmac_key.is_none() {
+            return Err(AppError::Config(
+                "EXECUTION_ENDPOINT requires EXECUTION_HMAC_KEY".to_string(),
+            ));
+        }
+        let execution_min_pnl_usdc: f64 = env_or("EXECUTION_MIN_PNL_USDC", min_pnl_usdc)?;
+        let simulate_execution: bool = env_or("SIMULATE_EXECUTION", false)?;
+        let simulation_tolerance_bps: f64 = env_or("SIMULATION_TOLERANCE_BPS", 10.0)?;
+        if !(simulation_tolerance_bps >= 0.0 && simulation_tolerance_bps.is_finite()) {
+            return Err(AppError::Config(format!(
+                "SIMULATION_TOLERANCE_BPS must be a non-negative number, got {}",
+                simulation_tolerance_bps
+            )));
+        }
+        if (replay_pool_path.is_some() || replay_gas_path.is_some())
+            && (replay_capture_path.is_none() || pools.len() != 1)
+        {
+            return Err(AppError::Config(
+                "REPLAY_POOL_PATH and REPLAY_GAS_PATH require REPLAY_CAPTURE_PATH and a single pool"
+                    .to_string(),
+            ));
+        }
+        if !(rpc_max_rps >= 0.0 && rpc_max_rps.is_finite()) {
+            return Err(AppError::Config(format!(
+                "RPC_MAX_RPS must be a non-negative number, got {}",
+                rpc_max_rps
+            )));
+        }
+        if eval_trigger == EvalTriggerMode::NewBlock
+            && pools.iter().any(|pool| pool.ws_rpc_url.is_none())
+        {
+            return Err(AppError::Config(
+                "EVAL_TRIGGER=block requires a WebSocket RPC for every pool".to_string(),
+            ));
+        }
+        Ok(Self {
+            pools,
+            cex_ws_url,
             min_pnl_usdc,
             gas_config: GasConfig {
                 gas_units,
                 gas_multiplier,
+                mode: gas_mode,
+                units_by_chain: gas_units_by_chain,
+                per_tick_gas_units,
+                priority_bid_gwei,
+                precision,
             },
-            arbitrage_config: ArbitrageConfig {
-                min_pnl_usdc,
-                dex_fee_bps,
-                cex_fee_bps,
-            },
+            arbitrage_config,
+            feed_health_config,
+            health_config,
+            eval_trigger,
+            max_head_silence_ms,
+            eval_budget_ms,
+            max_inflight_notional_usdc,
+            inflight_window_ms,
+            rpc_max_rps,
+            metadata_cache_path,
+            opportunity_log_path,
+            opportunity_schema_version,
+            market_snapshot_log_path,
+            feed_event_log_path,
+            health_log_path,
+            direction_bias_file,
+            shadow_overrides,
+            shadow_log_path,
+            evaluation_log_path,
+            sink_max_per_hour,
+            consolidated_book,
+            cross_quote_basis,
+            seed,
+            mock_cex_feed,
+            start_paused,
+            gas_fallback_gwei,
+            gas_missing_base_fee,
+            gas_smoothing,
+            gas_smoothing_blocks,
+            usdc_reference_pool,
+            oracle_pool,
+            oracle_divergence,
+            twap_window_secs,
+            segment_window_ticks,
+            tick_lens_address,
+            sqrt_round_trip_tolerance_bps,
+            cex_exchange,
+            cex_extra_exchanges,
+            cex_market,
+            cex_stream,
+            clock_skew_window,
+            clock_skew_server_time_secs,
+            replay_capture_path,
+            replay_speed,
+            replay_pool_path,
+            replay_gas_path,
+            capture_pool_path,
+            capture_gas_path,
+            execution_endpoint,
+            execution_hmac_key,
+            execution_min_pnl_usdc,
+            simulate_execution,
+            simulation_tolerance_bps,
+        })
+    }
+}
+
+/// Read an optional environment variable, falling back to `default` when unset.
+fn env_or<T>(key: &str, default: T) -> crate::errors::Result<T>
+where
+    T: FromStr,
+    AppError: From<T::Err>,
+{
+    match std::env::var(key) {
+        Ok(raw) => Ok(raw.parse()?),
+        Err(std::env::VarError::NotPresent) => Ok(default),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Parse a `venue=value` list such as `binance=5,coinbase=1`.
+fn parse_venue_map(raw: &str) -> crate::errors::Result<BTreeMap<String, f64>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (venue, value) = entry.split_once('=').ok_or_else(|| {
+                AppError::Config(format!("expected venue=value, got `{}`", entry))
+            })?;
+            Ok((venue.trim().to_lowercase(), value.trim().parse()?))
+        })
+        .collect()
+}
+
+/// Cadence of arbitrage evaluation passes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalTriggerMode {
+    /// Evaluate every second.
+    Interval,
+    /// Evaluate once per new block (`newHeads` subscription).
+    NewBlock,
+}
+
+impl FromStr for EvalTriggerMode {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_lowercase().as_str() {
+            "interval" => Ok(Self::Interval),
+            "block" => Ok(Self::NewBlock),
+            other => Err(AppError::Config(format!(
+                "EVAL_TRIGGER must be `interval` or `block`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// Market the CEX leg is evaluated against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CexMarket {
+    /// Spot order book (`@depth20`).
+    Spot,
+    /// USD-M perpetual at its mark price (`@markPrice`), funding charged as carry.
+    Perp,
+}
+
+impl FromStr for CexMarket {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_lowercase().as_str() {
+            "spot" => Ok(Self::Spot),
+            "perp" => Ok(Self::Perp),
+            other => Err(AppError::Config(format!(
+                "CEX_MARKET must be `spot` or `perp`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// Quote asset of pools that do not name one.
+pub const DEFAULT_QUOTE: &str = "USDC";
+
+/// Parse `EXTRA_POOLS`: `;`-separated `[QUOTE@]chain_id,pool_address,rpc_url[,ws_rpc_url]`
+/// entries, the quote defaulting to [`DEFAULT_QUOTE`].
+fn parse_pool_list(raw: &str) -> crate::errors::Result<Vec<PoolConfig>> {
+    raw.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (quote, fields) = match entry.split_once('@') {
+                Some((quote, rest)) => (quote.trim().to_uppercase(), rest),
+                None => (DEFAULT_QUOTE.to_string(), entry),
+            };
+            let fields: Vec<&str> = fields.split(',').map(str::trim).collect();
+            match fields.as_slice() {
+                [chain_id, pool_address, rpc_url, rest @ ..] if rest.len() <= 1 => Ok(PoolConfig {
+                    chain_id: chain_id.parse()?,
+                    rpc_url: rpc_url.to_string(),
+                    ws_rpc_url: rest.first().map(|ws| ws.to_string()),
+                    pool_address: pool_address.to_string(),
+                    quote: QuoteAsset {
+                        symbol: quote,
+                        token: None,
+                    },
+                }),
+                _ => Err(AppError::Config(format!(
+                    "expected [QUOTE@]chain_id,pool_address,rpc_url[,ws_rpc_url], got `{}`",
+                    entry
+                ))),
+            }
         })
+        .collect()
+}
+
+/// Parse the numbered `PAIR_<n>_*` groups among `vars`, the env-native form
+/// of `EXTRA_POOLS` for running many pairs without a file.
+///
+/// `SYMBOL` (`ETH/<quote>`), `POOL`, `CHAIN_ID` and `RPC_URL` are required,
+/// `WS_RPC_URL` and `QUOTE_TOKEN` optional. Groups are returned in index
+/// order; an incomplete group or an unknown key is an error.
+fn parse_pair_groups(
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> crate::errors::Result<Vec<PoolConfig>> {
+    let mut groups: BTreeMap<u32, BTreeMap<String, String>> = BTreeMap::new();
+    for (key, value) in vars {
+        let Some((index, field)) = key
+            .strip_prefix("PAIR_")
+            .and_then(|rest| rest.split_once('_'))
+        else {
+            continue;
+        };
+        let Ok(index) = index.parse::<u32>() else {
+            continue;
+        };
+        groups
+            .entry(index)
+            .or_default()
+            .insert(field.to_string(), value.trim().to_string());
     }
+
+    groups
+        .into_iter()
+        .map(|(index, mut fields)| {
+            let optional = |fields: &mut BTreeMap<String, String>, field: &str| {
+                fields.remove(field).filter(|value| !value.is_empty())
+            };
+            let required = |fields: &mut BTreeMap<String, String>, field: &str| {
+                optional(fields, field).ok_or_else(|| {
+                    AppError::Config(format!("PAIR_{}_{} is required", index, field))
+                })
+            };
+            let symbol = required(&mut fields, "SYMBOL")?;
+            let quote = match symbol.to_uppercase().split_once('/') {
+                Some(("ETH", quote)) if !quote.is_empty() => quote.to_string(),
+                _ => {
+                    return Err(AppError::Config(format!(
+                        "PAIR_{}_SYMBOL must be ETH/<quote>, got `{}`",
+                        index, symbol
+                    )));
+                }
+            };
+            let pool = PoolConfig {
+                chain_id: required(&mut fields, "CHAIN_ID")?.parse()?,
+                rpc_url: required(&mut fields, "RPC_URL")?,
+                ws_rpc_url: optional(&mut fields, "WS_RPC_URL"),
+                pool_address: required(&mut fields, "POOL")?,
+                quote: QuoteAsset {
+                    symbol: quote,
+                    token: optional(&mut fields, "QUOTE_TOKEN"),
+                },
+            };
+            if let Some(field) = fields.keys().next() {
+                return Err(AppError::Config(format!(
+                    "PAIR_{}_{} is not a pair setting",
+                    index, field
+                )));
+            }
+            Ok(pool)
+        })
+        .collect()
+}
+
+/// A pool to watch on a specific chain.
+///
+/// Pools are identified by `(chain_id, pool_address)`, so the same address
+/// on two chains is two different pools.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolConfig {
+    pub chain_id: u64,
+    /// HTTP RPC endpoint serving `chain_id`
+    pub rpc_url: String,
+    /// WebSocket RPC endpoint, required for per-block evaluation
+    pub ws_rpc_url: Option<String>,
+    pub pool_address: String,
+    pub quote: QuoteAsset,
+}
+
+/// The asset a pool's ETH is priced in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteAsset {
+    /// Symbol (e.g. `USDC`), selecting the CEX pair `ETH<symbol>`
+    pub symbol: String,
+    /// Token address, checked against the pool at startup; it may be either
+    /// token0 or token1 (without it the side is told from the decimals)
+    pub token: Option<String>,
 }
 
 /// Gas configuration loaded from environment variables
@@ -55,4 +766,267 @@ impl AppConfig {
 pub struct GasConfig {
     pub gas_units: f64,
     pub gas_multiplier: f64,
+    pub mode: GasCostMode,
+    /// Per-chain override of `gas_units` (an L2 swap costs a different amount of gas)
+    pub units_by_chain: BTreeMap<u64, f64>,
+    /// Extra gas per initialized tick the DEX swap crosses, on top of
+    /// `gas_units` as the base (0 = flat `gas_units` per trade)
+    pub per_tick_gas_units: f64,
+    /// Priority fee bid per gas unit on top of the network gas price, the
+    /// cost of winning inclusion against competing searchers (0 = none)
+    pub priority_bid_gwei: f64,
+    /// Arithmetic used to convert gas to USDC
+    pub precision: PrecisionMode,
+}
+
+/// Reconnect thresholds that put a CEX feed into degraded mode.
+#[derive(Debug, Clone)]
+pub struct FeedHealthConfig {
+    pub reconnect_window_ms: u64,
+    /// Reconnects within the window that trigger degraded mode (0 disables)
+    pub max_reconnects: usize,
+    /// Stable time without reconnects required to leave degraded mode
+    pub degraded_cooldown_ms: u64,
+}
+
+impl FeedHealthConfig {
+    pub fn monitor(&self) -> ReconnectMonitor {
+        ReconnectMonitor::new(
+            self.reconnect_window_ms,
+            self.max_reconnects,
+            self.degraded_cooldown_ms,
+        )
+    }
+}
+
+/// Arithmetic used for the gas term of the break-even decision, and for the
+/// CEX book levels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrecisionMode {
+    /// Plain f64 products
+    #[default]
+    Fast,
+    /// BigDecimal products rounded to f64 once, for thresholds finer than
+    /// the f64 rounding of tiny gwei × large price; book levels are kept as
+    /// the exchange's decimals and converted to f64 only for the math
+    Exact,
+}
+
+impl FromStr for PrecisionMode {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw.trim().to_lowercase().as_str() {
+            "fast" => Ok(Self::Fast),
+            "exact" => Ok(Self::Exact),
+            other => Err(AppError::Config(format!(
+                "precision must be `fast` or `exact`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// How the per-trade gas cost is derived.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GasCostMode {
+    /// Live gwei × gas units × multiplier, converted to USDC at the pool price
+    Dynamic,
+    /// Flat USD budget per trade; the gas watcher value is ignored
+    FixedUsd(f64),
+}
+
+impl GasConfig {
+    /// Gas cost of one trade in USDC under the configured mode.
+    pub fn cost_usdc(&self, gas_gwei: f64, price_usdc_per_eth: f64) -> f64 {
+        self.estimate(gas_gwei, price_usdc_per_eth).base_usdc
+    }
+
+    /// Base and per-tick gas cost in USDC, including the priority bid; the
+    /// per-tick part is always 0 for a fixed USD budget, which is charged the
+    /// bid on `gas_units`.
+    pub fn estimate(&self, gas_gwei: f64, price_usdc_per_eth: f64) -> GasEstimate {
+        let cost = |gwei: f64, units: f64| match self.precision {
+            PrecisionMode::Fast => {
+                calculate_gas_cost_usdc(gwei, units, self.gas_multiplier, price_usdc_per_eth)
+            }
+            PrecisionMode::Exact => {
+                calculate_gas_cost_usdc_exact(gwei, units, self.gas_multiplier, price_usdc_per_eth)
+                    .unwrap_or(f64::NAN)
+            }
+        };
+        match self.mode {
+            GasCostMode::Dynamic => {
+                let gwei = gas_gwei + self.priority_bid_gwei;
+                GasEstimate {
+                    base_usdc: cost(gwei, self.gas_units),
+                    per_tick_usdc: cost(gwei, self.per_tick_gas_units),
+                }
+            }
+            GasCostMode::FixedUsd(usd) => {
+                GasEstimate::from(usd + cost(self.priority_bid_gwei, self.gas_units))
+            }
+        }
+    }
+
+    /// This config with the gas-unit override of `chain_id` applied, if any.
+    pub fn for_chain(&self, chain_id: u64) -> Self {
+        Self {
+            gas_units: self
+                .units_by_chain
+                .get(&chain_id)
+                .copied()
+                .unwrap_or(self.gas_units),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn venue_map_parses_pairs_and_rejects_garbage() {
+        let map = parse_venue_map("binance=5, Coinbase=1.5,").unwrap();
+        assert_eq!(map.get("binance"), Some(&5.0));
+        assert_eq!(map.get("coinbase"), Some(&1.5));
+        assert!(parse_venue_map("binance").is_err());
+        assert!(parse_venue_map("binance=abc").is_err());
+    }
+
+    #[test]
+    fn pool_list_keeps_same_address_on_different_chains_apart() {
+        let pools = parse_pool_list(
+            "1,0x88e6,https://eth.example; 42161,0x88e6,https://arb.example,wss://arb.example",
+        )
+        .unwrap();
+        assert_eq!(pools.len(), 2);
+        assert_eq!(
+            (pools[0].chain_id, pools[0].ws_rpc_url.as_deref()),
+            (1, None)
+        );
+        assert_eq!(pools[1].chain_id, 42_161);
+        assert_eq!(pools[1].ws_rpc_url.as_deref(), Some("wss://arb.example"));
+        assert_ne!(pools[0], pools[1]);
+        assert!(parse_pool_list("1,0x88e6").is_err());
+        assert_eq!(pools[0].quote.symbol, "USDC");
+
+        let usdt = parse_pool_list("usdt@1,0x11b8,https://eth.example").unwrap();
+        assert_eq!(usdt[0].quote.symbol, "USDT");
+
+        let gas = GasConfig {
+            gas_units: 200_000.0,
+            gas_multiplier: 1.0,
+            mode: GasCostMode::Dynamic,
+            units_by_chain: [(42_161, 1_000_000.0)].into(),
+            per_tick_gas_units: 0.0,
+            priority_bid_gwei: 0.0,
+            precision: PrecisionMode::Fast,
+        };
+        assert_eq!(gas.for_chain(1).gas_units, 200_000.0);
+        assert_eq!(gas.for_chain(42_161).gas_units, 1_000_000.0);
+    }
+
+    #[test]
+    fn numbered_pair_groups_load_in_index_order() {
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let pools = parse_pair_groups(vars(&[
+            ("PAIR_1_SYMBOL", "eth/usdt"),
+            ("PAIR_1_POOL", "0x11b8"),
+            ("PAIR_1_CHAIN_ID", "1"),
+            ("PAIR_1_RPC_URL", "https://eth.example"),
+            ("PAIR_0_SYMBOL", "ETH/USDC"),
+            ("PAIR_0_POOL", "0xC696"),
+            ("PAIR_0_CHAIN_ID", "42161"),
+            ("PAIR_0_RPC_URL", "https://arb.example"),
+            ("PAIR_0_WS_RPC_URL", "wss://arb.example"),
+            ("RPC_URL", "https://unrelated.example"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            pools,
+            vec![
+                PoolConfig {
+                    chain_id: 42_161,
+                    rpc_url: "https://arb.example".to_string(),
+                    ws_rpc_url: Some("wss://arb.example".to_string()),
+                    pool_address: "0xC696".to_string(),
+                    quote: QuoteAsset {
+                        symbol: "USDC".to_string(),
+                        token: None,
+                    },
+                },
+                PoolConfig {
+                    chain_id: 1,
+                    rpc_url: "https://eth.example".to_string(),
+                    ws_rpc_url: None,
+                    pool_address: "0x11b8".to_string(),
+                    quote: QuoteAsset {
+                        symbol: "USDT".to_string(),
+                        token: None,
+                    },
+                },
+            ]
+        );
+
+        // Incomplete groups, bad symbols and typos are rejected
+        let missing_rpc = vars(&[
+            ("PAIR_0_SYMBOL", "ETH/USDC"),
+            ("PAIR_0_POOL", "0xC696"),
+            ("PAIR_0_CHAIN_ID", "1"),
+        ]);
+        let err = parse_pair_groups(missing_rpc).unwrap_err().to_string();
+        assert!(err.contains("PAIR_0_RPC_URL"), "{err}");
+        let not_eth = vars(&[("PAIR_0_SYMBOL", "BTC/USDC")]);
+        assert!(parse_pair_groups(not_eth).is_err());
+        let typo = vars(&[
+            ("PAIR_0_SYMBOL", "ETH/USDC"),
+            ("PAIR_0_POOL", "0xC696"),
+            ("PAIR_0_CHAIN_ID", "1"),
+            ("PAIR_0_RPC_URL", "https://eth.example"),
+            ("PAIR_0_WS_URL", "wss://eth.example"),
+        ]);
+        assert!(parse_pair_groups(typo).is_err());
+        assert!(parse_pair_groups(Vec::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn eval_trigger_mode_parses_known_values() {
+        assert_eq!(
+            "interval".parse::<EvalTriggerMode>().unwrap(),
+            EvalTriggerMode::Interval
+        );
+        assert_eq!(
+            " Block ".parse::<EvalTriggerMode>().unwrap(),
+            EvalTriggerMode::NewBlock
+        );
+        assert!("hourly".parse::<EvalTriggerMode>().is_err());
+    }
+
+    #[test]
+    fn fixed_gas_mode_ignores_watcher_gwei() {
+        let dynamic = GasConfig {
+            gas_units: 200_000.0,
+            gas_multiplier: 1.0,
+            mode: GasCostMode::Dynamic,
+            units_by_chain: BTreeMap::new(),
+            per_tick_gas_units: 0.0,
+            priority_bid_gwei: 0.0,
+            precision: PrecisionMode::Fast,
+        };
+        let fixed = GasConfig {
+            mode: GasCostMode::FixedUsd(5.0),
+            ..dynamic.clone()
+        };
+
+        assert!((dynamic.cost_usdc(30.0, 4000.0) - 24.0).abs() < 1e-9);
+        assert_eq!(fixed.cost_usdc(30.0, 4000.0), 5.0);
+        assert_eq!(fixed.cost_usdc(300.0, 4000.0), 5.0);
+    }
 }
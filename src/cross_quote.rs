@@ -0,0 +1,123 @@
+//! Basis between two quote assets implied by the same base priced in each.
+//!
+//! With ETH at 4000 USDC and 4004 USDT, 1 USDC buys 1.001 USDT: USDT trades
+//! 10 bps below USDC. A DEX-implied basis that departs from the CEX-implied
+//! one is an edge across the two pools rather than against either CEX pair.
+
+use crate::dex::PoolState;
+use crate::models::BookDepth;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Basis of `quote_b` against `quote_a` in bps, from the base price in each:
+/// positive when the base costs more in `quote_b`, i.e. `quote_b` is cheaper.
+pub fn cross_quote_basis_bps(base_in_a: f64, base_in_b: f64) -> Option<f64> {
+    if !(base_in_a > 0.0 && base_in_b > 0.0) {
+        return None;
+    }
+    Some((base_in_b / base_in_a - 1.0) * 10_000.0)
+}
+
+/// Price feeds of the base in one quote asset.
+#[derive(Clone)]
+pub struct QuoteFeeds {
+    pub quote: String,
+    pub pool_rx: watch::Receiver<PoolState>,
+    pub cex_rx: watch::Receiver<BookDepth>,
+}
+
+impl QuoteFeeds {
+    fn dex_price(&self) -> f64 {
+        self.pool_rx.borrow().price_usdc_per_eth
+    }
+
+    fn cex_mid(&self) -> f64 {
+        let book = self.cex_rx.borrow();
+        match (book.bids.first(), book.asks.first()) {
+            (Some(&(bid, _)), Some(&(ask, _))) => (bid + ask) / 2.0,
+            _ => 0.0,
+        }
+    }
+}
+
+/// DEX- and CEX-implied basis of `b.quote` against `a.quote`, when priced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrossQuoteBasis {
+    pub dex_bps: Option<f64>,
+    pub cex_bps: Option<f64>,
+}
+
+impl CrossQuoteBasis {
+    pub fn observe(a: &QuoteFeeds, b: &QuoteFeeds) -> Self {
+        Self {
+            dex_bps: cross_quote_basis_bps(a.dex_price(), b.dex_price()),
+            cex_bps: cross_quote_basis_bps(a.cex_mid(), b.cex_mid()),
+        }
+    }
+}
+
+/// Log the basis of `b` against `a` every `interval` as `[XQUOTE]`.
+pub fn spawn_cross_quote_monitor(
+    a: QuoteFeeds,
+    b: QuoteFeeds,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let basis = CrossQuoteBasis::observe(&a, &b);
+            if basis.dex_bps.is_none() && basis.cex_bps.is_none() {
+                continue;
+            }
+            tracing::info!(
+                quote_a = %a.quote,
+                quote_b = %b.quote,
+                dex_bps = ?basis.dex_bps,
+                cex_bps = ?basis.cex_bps,
+                "[XQUOTE] cross-quote basis"
+            );
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feeds(quote: &str, dex_price: f64, bid: f64, ask: f64) -> QuoteFeeds {
+        let pool = PoolState::new(
+            alloy_primitives::U256::ZERO,
+            0,
+            0,
+            6,
+            18,
+            None,
+            None,
+            dex_price,
+        );
+        let book = BookDepth {
+            bids: vec![(bid, 1.0)],
+            asks: vec![(ask, 1.0)],
+            ..Default::default()
+        };
+        QuoteFeeds {
+            quote: quote.to_string(),
+            pool_rx: watch::channel(pool).1,
+            cex_rx: watch::channel(book).1,
+        }
+    }
+
+    #[test]
+    fn eth_usdc_and_eth_usdt_imply_stablecoin_basis() {
+        let usdc = feeds("USDC", 4_000.0, 3_999.0, 4_001.0);
+        let usdt = feeds("USDT", 4_004.0, 4_001.0, 4_003.0);
+
+        let basis = CrossQuoteBasis::observe(&usdc, &usdt);
+        assert!((basis.dex_bps.unwrap() - 10.0).abs() < 1e-9);
+        assert!((basis.cex_bps.unwrap() - 5.0).abs() < 1e-9);
+
+        let unpriced = feeds("USDT", 0.0, 4_001.0, 4_003.0);
+        assert_eq!(CrossQuoteBasis::observe(&usdc, &unpriced).dex_bps, None);
+    }
+}
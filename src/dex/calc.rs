@@ -1,12 +1,97 @@
 use crate::dex::state::PoolState;
+use crate::errors::SwapMathError;
 use crate::models::{SwapDirection, SwapResult};
 use alloy_primitives::U256;
-use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive, Zero};
+use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive, Zero, num_bigint::BigInt};
 use std::str::FromStr;
-use uniswap_v3_math::{
-    error::UniswapV3MathError,
-    sqrt_price_math::{_get_amount_0_delta, _get_amount_1_delta},
-};
+use std::sync::Arc;
+use uniswap_v3_math::sqrt_price_math::{_get_amount_0_delta, _get_amount_1_delta};
+
+/// Optional limits applied while solving a swap.
+#[derive(Debug, Clone, Default)]
+pub struct SwapOptions {
+    /// Maximum number of segment boundaries the swap may cross (0 = unlimited).
+    pub max_ticks_traversed: usize,
+    /// Furthest the solved target may move the pool price, in bps of the
+    /// current price (0 = unlimited). Farther targets are capped to this move.
+    pub max_target_move_bps: f64,
+    /// Only fill within the current tick, stopping (and flagging the boundary)
+    /// at `limit_lower_sqrt_price_x96` / `limit_upper_sqrt_price_x96`.
+    pub current_tick_only: bool,
+    /// Where the swap fee comes from; the `fee_bps` argument by default
+    pub fee_model: FeeModel,
+    /// Largest `max_amount` taken at face value, in input token units
+    /// (0 = no clamp). Absurd CEX quantities above it are clamped to it.
+    pub max_amount_clamp: f64,
+}
+
+impl SwapOptions {
+    /// `max_amount` clamped to `[0, max_amount_clamp]`; NaN caps the fill at 0.
+    fn clamp_max_amount(&self, max_amount: f64) -> f64 {
+        if max_amount.is_nan() {
+            return 0.0;
+        }
+        let bound = if self.max_amount_clamp > 0.0 {
+            self.max_amount_clamp
+        } else {
+            f64::INFINITY
+        };
+        max_amount.clamp(0.0, bound)
+    }
+}
+
+/// Largest integer up to which every f64 integer is exact (2^53)
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// `amount` human units of a token with `decimals`, in raw units.
+///
+/// Past f64's exact-integer range (2^53 raw, about 9 ETH at 18 decimals) the
+/// scaling is done in BigDecimal and rounded once; an amount too large for
+/// f64 in raw units caps nothing.
+fn to_raw_amount(amount: f64, decimals: u8) -> f64 {
+    let scaled = amount * 10f64.powi(decimals as i32);
+    if scaled <= MAX_SAFE_INTEGER || amount.is_infinite() {
+        return scaled;
+    }
+    BigDecimal::from_f64(amount)
+        .map(|amount| amount * BigDecimal::new(BigInt::from(1), -(decimals as i64)))
+        .and_then(|raw| raw.to_f64())
+        .filter(|raw| raw.is_finite())
+        .unwrap_or(f64::INFINITY)
+}
+
+/// Fee in bps for one swap on `pool` in `direction`.
+pub type FeeFn = dyn Fn(&PoolState, SwapDirection) -> f64 + Send + Sync;
+
+/// How the pool's swap fee is determined.
+#[derive(Clone, Default)]
+pub enum FeeModel {
+    /// The fixed fee tier passed with each swap
+    #[default]
+    Fixed,
+    /// A fee computed per swap from the pool state, for dynamic-fee pools
+    /// (e.g. Uniswap V4 hooks, Algebra); the passed fee is ignored
+    Dynamic(Arc<FeeFn>),
+}
+
+impl FeeModel {
+    /// Fee charged on a swap on `pool` in `direction` when the tier is `fixed_bps`.
+    pub fn fee_bps(&self, pool: &PoolState, direction: SwapDirection, fixed_bps: f64) -> f64 {
+        match self {
+            Self::Fixed => fixed_bps,
+            Self::Dynamic(fee) => fee(pool, direction),
+        }
+    }
+}
+
+impl std::fmt::Debug for FeeModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fixed => f.write_str("Fixed"),
+            Self::Dynamic(_) => f.write_str("Dynamic(..)"),
+        }
+    }
+}
 
 /// Calculate swap using Uniswap V3 math library with high precision
 /// This function calculates the optimal swap amounts to reach a target price
@@ -17,16 +102,37 @@ pub fn calculate_swap_with_library(
     direction: SwapDirection,
     fee_bps: f64,
     max_amount: f64,
-) -> Result<SwapResult, UniswapV3MathError> {
+) -> Result<SwapResult, SwapMathError> {
+    calculate_swap_with_options(
+        pool,
+        target_price,
+        direction,
+        fee_bps,
+        max_amount,
+        &SwapOptions::default(),
+    )
+}
+
+/// Same as [`calculate_swap_with_library`] with explicit [`SwapOptions`].
+pub fn calculate_swap_with_options(
+    pool: &PoolState,
+    target_price: f64,
+    direction: SwapDirection,
+    fee_bps: f64,
+    max_amount: f64,
+    options: &SwapOptions,
+) -> Result<SwapResult, SwapMathError> {
+    let fee_bps = options.fee_model.fee_bps(pool, direction, fee_bps);
+    if !(0.0..10_000.0).contains(&fee_bps) {
+        return Err(SwapMathError::InvalidFee(fee_bps));
+    }
     // Convert current sqrtPriceX96 to U256
     let sqrt_price_start = U256::from_str_radix(&pool.sqrt_price_x96.to_string(), 10)
-        .map_err(|_| UniswapV3MathError::SqrtPriceIsZero)?;
+        .map_err(|_| SwapMathError::Overflow(pool.sqrt_price_x96.to_string()))?;
 
-    // Convert liquidity to u128
-    let liquidity = pool.liquidity;
-
-    // Calculate amounts using library functions
-    let (amount_in, amount_out) = match direction {
+    // Calculate amounts using library functions; hit_boundary is set when the
+    // target lies beyond the last loaded segment
+    let (amount_in, amount_out, hit_boundary, ticks_crossed) = match direction {
         SwapDirection::Token0ToToken1 => {
             // USDC in, ETH out (price UP). Human price up
             // CEX price > DEX price: buy ETH on DEX to profit
@@ -43,38 +149,39 @@ pub fn calculate_swap_with_library(
                     amount_in: 0.0,
                     amount_out: 0.0,
                     hit_boundary: false,
+                    avg_price: 0.0,
+                    ticks_crossed: 0,
                 });
             }
+            let sqrt_price_target =
+                cap_target_move(sqrt_price_start, sqrt_price_target, true, options)?;
 
-            let amount0_in = _get_amount_0_delta(
-                sqrt_price_start,
-                sqrt_price_target,
-                liquidity,
-                true, // round up
-            )?;
-
-            let amount1_out = _get_amount_1_delta(
+            // Walk the active range and any loaded segments below it
+            let (amount0_in, amount1_out, hit_boundary, ticks_crossed) = walk_ranges(
+                pool,
                 sqrt_price_start,
                 sqrt_price_target,
-                liquidity,
-                false, // round down
+                direction,
+                options,
             )?;
 
             // Apply fee: Uniswap V3 applies fee to input amount
             // amount_in_with_fee = amount_in / (1 - fee_fraction)
             let fee_fraction = BigDecimal::from_f64(fee_bps / 10_000.0)
-                .ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+                .ok_or(SwapMathError::DecimalConversion("fee fraction"))?;
             let one_minus_fee = BigDecimal::from_f64(1.0).unwrap() - fee_fraction;
 
             let amount0_in_bd = BigDecimal::from_u128(amount0_in.try_into().unwrap_or(0u128))
-                .ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+                .ok_or(SwapMathError::DecimalConversion("amount0 in"))?;
             let amount0_in_with_fee = (amount0_in_bd / one_minus_fee)
                 .to_f64()
-                .ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+                .ok_or(SwapMathError::DecimalConversion("amount0 in with fee"))?;
 
             (
                 amount0_in_with_fee,
                 amount1_out.try_into().unwrap_or(0u128) as f64,
+                hit_boundary,
+                ticks_crossed,
             )
         }
         SwapDirection::Token1ToToken0 => {
@@ -93,38 +200,39 @@ pub fn calculate_swap_with_library(
                     amount_in: 0.0,
                     amount_out: 0.0,
                     hit_boundary: false,
+                    avg_price: 0.0,
+                    ticks_crossed: 0,
                 });
             }
+            let sqrt_price_target =
+                cap_target_move(sqrt_price_start, sqrt_price_target, false, options)?;
 
-            let amount1_in = _get_amount_1_delta(
-                sqrt_price_target,
+            // Walk the active range and any loaded segments above it
+            let (amount1_in, amount0_out, hit_boundary, ticks_crossed) = walk_ranges(
+                pool,
                 sqrt_price_start,
-                liquidity,
-                true, // round up
-            )?;
-
-            let amount0_out = _get_amount_0_delta(
                 sqrt_price_target,
-                sqrt_price_start,
-                liquidity,
-                false, // round down
+                direction,
+                options,
             )?;
 
             // include fee to amount1_in
             // amount_1_in = x * (1 - fee_bps_adjusted)
             // x = amount_1_in / (1 - fee_bps_adjusted)
             let amount1_in_bd = BigDecimal::from_u128(amount1_in.try_into().unwrap_or(0u128))
-                .ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+                .ok_or(SwapMathError::DecimalConversion("amount1 in"))?;
             let fee_fraction_bd = BigDecimal::from_f64(fee_bps_adjusted)
-                .ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+                .ok_or(SwapMathError::DecimalConversion("fee fraction"))?;
             let one_minus_fee_adjusted = BigDecimal::from_f64(1.0).unwrap() - fee_fraction_bd;
             let amount1_in_with_fee = (amount1_in_bd / one_minus_fee_adjusted)
                 .to_f64()
-                .ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+                .ok_or(SwapMathError::DecimalConversion("amount1 in with fee"))?;
 
             (
                 amount1_in_with_fee,
                 amount0_out.try_into().unwrap_or(0u128) as f64,
+                hit_boundary,
+                ticks_crossed,
             )
         }
     };
@@ -134,18 +242,13 @@ pub fn calculate_swap_with_library(
     let mut final_amount_out = amount_out; // RAW units
 
     // Convert human max_amount to RAW units for the input token
-    let max_in_raw: f64 = match direction {
+    let input_decimals = match direction {
         // Token0ToToken1: input is token0 (USDC), 6 decimals
-        SwapDirection::Token0ToToken1 => {
-            let scale = 10f64.powi(pool.token0_decimals as i32);
-            max_amount * scale
-        }
+        SwapDirection::Token0ToToken1 => pool.token0_decimals,
         // Token1ToToken0: input is token1 (ETH), 18 decimals
-        SwapDirection::Token1ToToken0 => {
-            let scale = 10f64.powi(pool.token1_decimals as i32);
-            max_amount * scale
-        }
+        SwapDirection::Token1ToToken0 => pool.token1_decimals,
     };
+    let max_in_raw = to_raw_amount(options.clamp_max_amount(max_amount), input_decimals);
 
     if amount_in > max_in_raw {
         let scale = max_in_raw / amount_in;
@@ -167,8 +270,9 @@ pub fn calculate_swap_with_library(
         }
     };
 
-    // Calculate execution price directly from human units
-    let _execution_price = match direction {
+    // Calculate execution price directly from human units; across several
+    // segments this is the notional-weighted average of the fill
+    let execution_price = match direction {
         SwapDirection::Token0ToToken1 => {
             if final_out_human > 0.0 {
                 final_in_human / final_out_human
@@ -188,10 +292,131 @@ pub fn calculate_swap_with_library(
     Ok(SwapResult {
         amount_in: final_in_human,
         amount_out: final_out_human,
-        hit_boundary: false,
+        hit_boundary,
+        avg_price: execution_price,
+        ticks_crossed,
+    })
+}
+
+/// Pull `sqrt_target` back so the human price moves at most
+/// `options.max_target_move_bps` away from the price at `sqrt_start`.
+///
+/// Moving the sqrt price down raises the USDC/ETH price, so the bound there is
+/// `sqrt_start / sqrt(1 + move)`; moving up lowers it, bounded by
+/// `sqrt_start / sqrt(1 - move)`.
+fn cap_target_move(
+    sqrt_start: U256,
+    sqrt_target: U256,
+    moving_down: bool,
+    options: &SwapOptions,
+) -> Result<U256, SwapMathError> {
+    let max_move = options.max_target_move_bps / 10_000.0;
+    if max_move <= 0.0 || (!moving_down && max_move >= 1.0) {
+        return Ok(sqrt_target);
+    }
+    let price_factor = if moving_down {
+        1.0 + max_move
+    } else {
+        1.0 - max_move
+    };
+    let start = sqrt_start
+        .to_string()
+        .parse::<f64>()
+        .map_err(|_| SwapMathError::Overflow(sqrt_start.to_string()))?;
+    let bound_str = format!("{:.0}", start / price_factor.sqrt());
+    let bound =
+        U256::from_str_radix(&bound_str, 10).map_err(|_| SwapMathError::Overflow(bound_str))?;
+    Ok(if moving_down {
+        sqrt_target.max(bound)
+    } else {
+        sqrt_target.min(bound)
     })
 }
 
+/// Walk from `sqrt_start` toward `sqrt_target` across the active range and the
+/// pool's loaded segments, returning raw (amount_in, amount_out, hit_boundary,
+/// ticks_crossed).
+///
+/// The active range uses `pool.liquidity` and ends where the first segment on
+/// the swap side begins; with no segments loaded it is treated as unbounded.
+/// The walk also stops (flagging the boundary) once `max_ticks_traversed`
+/// segment boundaries have been crossed, or at the current tick's limit on
+/// the swap side under `current_tick_only`. Amounts exclude the LP fee.
+fn walk_ranges(
+    pool: &PoolState,
+    sqrt_start: U256,
+    sqrt_target: U256,
+    direction: SwapDirection,
+    options: &SwapOptions,
+) -> Result<(U256, U256, bool, usize), SwapMathError> {
+    let (segments, moving_down) = match direction {
+        SwapDirection::Token0ToToken1 => (&pool.segments_down, true),
+        SwapDirection::Token1ToToken0 => (&pool.segments_up, false),
+    };
+    // (liquidity, far edge) in traversal order
+    let mut active_edge = segments.first().map(|seg| seg.near_edge(moving_down));
+    let mut range_count = usize::MAX;
+    if options.current_tick_only {
+        let tick_limit = if moving_down {
+            pool.limit_lower_sqrt_price_x96
+        } else {
+            pool.limit_upper_sqrt_price_x96
+        };
+        active_edge = tick_limit.or(active_edge);
+        range_count = 1;
+    }
+    let ranges = std::iter::once((pool.liquidity, active_edge))
+        .chain(
+            segments
+                .iter()
+                .map(|seg| (seg.liquidity, Some(seg.far_edge(moving_down)))),
+        )
+        .take(range_count);
+
+    let mut cursor = sqrt_start;
+    let mut amount_in = U256::ZERO;
+    let mut amount_out = U256::ZERO;
+    let mut ticks_crossed = 0;
+    for (crossed, (liquidity, far_edge)) in ranges.enumerate() {
+        if options.max_ticks_traversed > 0 && crossed > options.max_ticks_traversed {
+            break;
+        }
+        ticks_crossed = crossed;
+        let stop = match far_edge {
+            Some(edge)
+                if (moving_down && edge > sqrt_target) || (!moving_down && edge < sqrt_target) =>
+            {
+                edge
+            }
+            _ => sqrt_target,
+        };
+        if liquidity > 0 && stop != cursor {
+            if moving_down {
+                amount_in += _get_amount_0_delta(cursor, stop, liquidity, true)?;
+                amount_out += _get_amount_1_delta(cursor, stop, liquidity, false)?;
+            } else {
+                amount_in += _get_amount_1_delta(stop, cursor, liquidity, true)?;
+                amount_out += _get_amount_0_delta(stop, cursor, liquidity, false)?;
+            }
+        }
+        cursor = stop;
+        if cursor == sqrt_target {
+            return Ok((amount_in, amount_out, false, ticks_crossed));
+        }
+    }
+    // Ran out of loaded segments (or traversal budget) before reaching the target
+    Ok((amount_in, amount_out, true, ticks_crossed))
+}
+
+/// Exact `10^(token1_decimals - token0_decimals)`.
+///
+/// Built as a BigDecimal with an integer mantissa so large decimal differences
+/// (e.g. 0 vs 18) do not pick up f64 representation error.
+pub fn decimals_factor(token0_decimals: u8, token1_decimals: u8) -> BigDecimal {
+    let decimals_diff = token1_decimals as i64 - token0_decimals as i64;
+    BigDecimal::new(BigInt::from(1), -decimals_diff)
+}
+
 /// Calculate sqrt price using BigDecimal for high precision
 ///
 /// Converts a human-readable price to sqrtPriceX96
@@ -199,27 +424,26 @@ pub fn calculate_sqrt_price_with_precision_per_eth(
     price: f64,
     token0_decimals: u8,
     token1_decimals: u8,
-) -> Result<U256, UniswapV3MathError> {
-    if price <= 0.0 {
-        return Err(UniswapV3MathError::SqrtPriceIsZero);
+) -> Result<U256, SwapMathError> {
+    if !(price > 0.0 && price.is_finite()) {
+        return Err(SwapMathError::InvalidPrice(price));
     }
 
     // Calculate decimals factor: 10^(token1_decimals - token0_decimals)
-    let decimals_diff = token1_decimals as i32 - token0_decimals as i32;
-    let decimals_factor_f64 = 10.0_f64.powi(decimals_diff);
-    let decimals_factor =
-        BigDecimal::from_f64(decimals_factor_f64).ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+    let decimals_factor = decimals_factor(token0_decimals, token1_decimals);
 
     // Calculate ratio: decimals_factor / target_price
-    let price_bd = BigDecimal::from_f64(price).ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+    let price_bd = BigDecimal::from_f64(price).ok_or(SwapMathError::DecimalConversion("price"))?;
     let ratio = decimals_factor / price_bd;
 
     // Calculate sqrt of ratio using f64 for better compatibility
-    let ratio_f64 = ratio.to_f64().ok_or(UniswapV3MathError::SqrtPriceIsZero)?;
+    let ratio_f64 = ratio
+        .to_f64()
+        .ok_or(SwapMathError::DecimalConversion("price ratio"))?;
     let sqrt_ratio_f64 = ratio_f64.sqrt();
 
     if sqrt_ratio_f64.is_nan() || sqrt_ratio_f64 <= 0.0 {
-        return Err(UniswapV3MathError::SqrtPriceIsZero);
+        return Err(SwapMathError::NonPositiveSqrt(sqrt_ratio_f64));
     }
 
     // Multiply by 2^96 to get Q96 format
@@ -228,13 +452,142 @@ pub fn calculate_sqrt_price_with_precision_per_eth(
 
     // Convert to U256 using string conversion for precision
     let sqrt_price_str = format!("{:.0}", sqrt_price_q96_f64);
-    U256::from_str_radix(&sqrt_price_str, 10).map_err(|_| UniswapV3MathError::SqrtPriceIsZero)
+    U256::from_str_radix(&sqrt_price_str, 10).map_err(|_| SwapMathError::Overflow(sqrt_price_str))
+}
+
+/// Calculate human-readable price from sqrtPriceX96
+///
+/// Converts sqrtPriceX96 back to human-readable price (USDC per ETH);
+/// the inverse of [`calculate_sqrt_price_with_precision_per_eth`].
+pub fn calculate_human_price_from_sqrt_x96(
+    sqrt_price_x96: U256,
+    token0_decimals: u8,
+    token1_decimals: u8,
+) -> f64 {
+    let sqrt_price_str = sqrt_price_x96.to_string();
+    let sqrt_price_bd =
+        BigDecimal::from_str(&sqrt_price_str).unwrap_or_else(|_| BigDecimal::zero());
+
+    // Divide by 2^96 to get sqrt ratio
+    let two_pow_96_f64 = 2.0_f64.powi(96);
+    let two_pow_96 = BigDecimal::from_f64(two_pow_96_f64).unwrap();
+    let sqrt_ratio = sqrt_price_bd / two_pow_96;
+
+    // Square to get ratio
+    let ratio = &sqrt_ratio * &sqrt_ratio;
+
+    // Calculate price: decimals_factor / ratio
+    let price_bd = decimals_factor(token0_decimals, token1_decimals) / ratio;
+
+    price_bd.to_f64().unwrap_or(0.0)
+}
+
+/// Relative gap between `sqrt_price_x96` and the sqrtPriceX96 recomputed from
+/// `price` with the given decimals, in bps.
+///
+/// When `price` was derived from `sqrt_price_x96` under the same decimals the
+/// gap is rounding noise; decimals that differ from those behind `price` show
+/// up as a gap of orders of magnitude.
+pub fn sqrt_price_round_trip_bps(
+    sqrt_price_x96: U256,
+    price: f64,
+    token0_decimals: u8,
+    token1_decimals: u8,
+) -> Result<f64, SwapMathError> {
+    let recomputed =
+        calculate_sqrt_price_with_precision_per_eth(price, token0_decimals, token1_decimals)?;
+    let original = sqrt_price_x96
+        .to_string()
+        .parse::<f64>()
+        .map_err(|_| SwapMathError::DecimalConversion("sqrt price"))?;
+    if original <= 0.0 {
+        return Err(SwapMathError::NonPositiveSqrt(original));
+    }
+    let recomputed = recomputed
+        .to_string()
+        .parse::<f64>()
+        .map_err(|_| SwapMathError::DecimalConversion("sqrt price"))?;
+    Ok((recomputed - original).abs() / original * 10_000.0)
+}
+
+/// ETH traded by a DEX fill in `direction`.
+pub fn fill_eth(direction: SwapDirection, res: &SwapResult) -> f64 {
+    match direction {
+        SwapDirection::Token0ToToken1 => res.amount_out,
+        SwapDirection::Token1ToToken0 => res.amount_in,
+    }
+}
+
+/// Target-price doublings (halvings when selling) tried before the loaded
+/// liquidity is treated as exhausted
+const MAX_BRACKET_STEPS: usize = 64;
+/// Bisection steps narrowing in on the target for an exact size
+const MAX_BISECTION_STEPS: usize = 100;
+
+/// Fill trading `size_eth` in `direction`, found by bisecting the target
+/// price it is walked to from the marginal price `start`. `at` quotes the
+/// fill at a target, `measure` reports the ETH a fill trades and whether it
+/// ran past the loaded liquidity.
+///
+/// Returns the smallest fill found that covers the size, or the one where
+/// the liquidity ran out when that comes first.
+pub fn solve_for_size<T>(
+    start: f64,
+    direction: SwapDirection,
+    size_eth: f64,
+    at: impl Fn(f64) -> Result<T, SwapMathError>,
+    measure: impl Fn(&T) -> (f64, bool),
+) -> Result<T, SwapMathError> {
+    if !(start > 0.0 && start.is_finite()) {
+        return Err(SwapMathError::InvalidPrice(start));
+    }
+    // Buying ETH walks the price up, selling walks it down
+    let step = match direction {
+        SwapDirection::Token0ToToken1 => 2.0,
+        SwapDirection::Token1ToToken0 => 0.5,
+    };
+
+    // Widen the target until the fill covers the size
+    let mut near = start;
+    let mut far = start * step;
+    let mut fill = at(far)?;
+    for _ in 1..MAX_BRACKET_STEPS {
+        let (eth, hit_boundary) = measure(&fill);
+        if eth >= size_eth || hit_boundary {
+            break;
+        }
+        near = far;
+        far *= step;
+        fill = at(far)?;
+    }
+    if measure(&fill).0 < size_eth {
+        return Ok(fill);
+    }
+    // Narrow in on the smallest target still covering it
+    for _ in 0..MAX_BISECTION_STEPS {
+        if measure(&fill).0 <= size_eth * (1.0 + 1e-9) {
+            break;
+        }
+        let mid = (near * far).sqrt();
+        if mid == near || mid == far {
+            break;
+        }
+        let res = at(mid)?;
+        if measure(&res).0 >= size_eth {
+            far = mid;
+            fill = res;
+        } else {
+            near = mid;
+        }
+    }
+    Ok(fill)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::dex::state::PoolState;
+    use crate::errors::SwapMathError;
 
     fn make_pool(price_usdc_per_eth: f64, liquidity: u128) -> PoolState {
         let token0_decimals = 6; // USDC
@@ -254,38 +607,10 @@ mod tests {
             limit_lower_sqrt_price_x96: None,
             limit_upper_sqrt_price_x96: None,
             price_usdc_per_eth,
+            segments_down: Vec::new(),
+            segments_up: Vec::new(),
         }
     }
-    /// Calculate human-readable price from sqrtPriceX96
-    ///
-    /// Converts sqrtPriceX96 back to human-readable price (USDC per ETH)
-    /// for debugging and logging purposes.
-    fn calculate_human_price_from_sqrt_x96(
-        sqrt_price_x96: U256,
-        token0_decimals: u8,
-        token1_decimals: u8,
-    ) -> f64 {
-        let sqrt_price_str = sqrt_price_x96.to_string();
-        let sqrt_price_bd =
-            BigDecimal::from_str(&sqrt_price_str).unwrap_or_else(|_| BigDecimal::zero());
-
-        // Divide by 2^96 to get sqrt ratio
-        let two_pow_96_f64 = 2.0_f64.powi(96);
-        let two_pow_96 = BigDecimal::from_f64(two_pow_96_f64).unwrap();
-        let sqrt_ratio = sqrt_price_bd / two_pow_96;
-
-        // Square to get ratio
-        let ratio = &sqrt_ratio * &sqrt_ratio;
-
-        // Calculate price: decimals_factor / ratio
-        let decimals_diff = token1_decimals as i32 - token0_decimals as i32;
-        let decimals_factor_f64 = 10.0_f64.powi(decimals_diff);
-        let decimals_factor = BigDecimal::from_f64(decimals_factor_f64).unwrap();
-        let price_bd = decimals_factor / ratio;
-
-        price_bd.to_f64().unwrap_or(0.0)
-    }
-
     #[test]
     fn test_calculate_sqrt_price_with_precision() {
         let price = 9.0;
@@ -358,6 +683,71 @@ mod tests {
         assert!(res.amount_out <= 0.0);
     }
 
+    #[test]
+    fn dynamic_fee_callback_is_applied_per_swap() {
+        // A fee that widens as liquidity thins, like a volatility-scaled pool
+        let options = SwapOptions {
+            fee_model: FeeModel::Dynamic(Arc::new(|pool: &PoolState, direction| {
+                let base = if pool.liquidity < 1_000_000_000_000_000_000 {
+                    100.0
+                } else {
+                    5.0
+                };
+                match direction {
+                    SwapDirection::Token0ToToken1 => base,
+                    SwapDirection::Token1ToToken0 => base * 2.0,
+                }
+            })),
+            ..Default::default()
+        };
+        let swap = |pool: &PoolState, direction, fee_bps, options: &SwapOptions| {
+            let res = calculate_swap_with_options(pool, 4_100.0, direction, fee_bps, 1e12, options)
+                .unwrap();
+            (res.amount_in, res.amount_out)
+        };
+
+        let deep = make_pool(4000.0, 1_800_000_000_000_000_000);
+        let thin = make_pool(4000.0, 900_000_000_000_000_000);
+        let fixed = SwapOptions::default();
+        let dir = SwapDirection::Token0ToToken1;
+        // The passed 30 bps is ignored in favor of the state-dependent fee
+        assert_eq!(
+            swap(&deep, dir, 30.0, &options),
+            swap(&deep, dir, 5.0, &fixed)
+        );
+        assert_eq!(
+            swap(&thin, dir, 30.0, &options),
+            swap(&thin, dir, 100.0, &fixed)
+        );
+        assert_ne!(
+            swap(&deep, dir, 30.0, &options),
+            swap(&deep, dir, 30.0, &fixed)
+        );
+
+        let up = |fee_bps, options: &SwapOptions| {
+            calculate_swap_with_options(
+                &deep,
+                3_900.0,
+                SwapDirection::Token1ToToken0,
+                fee_bps,
+                1e12,
+                options,
+            )
+            .map(|res| (res.amount_in, res.amount_out))
+            .unwrap()
+        };
+        assert_eq!(up(0.0, &options), up(10.0, &fixed));
+
+        let broken = SwapOptions {
+            fee_model: FeeModel::Dynamic(Arc::new(|_: &PoolState, _| -1.0)),
+            ..Default::default()
+        };
+        assert!(matches!(
+            calculate_swap_with_options(&deep, 4_100.0, dir, 5.0, 1e12, &broken),
+            Err(SwapMathError::InvalidFee(_))
+        ));
+    }
+
     #[test]
     fn caps_max_input_and_scales_output() {
         let pool = make_pool(4200.0, 1_800_000_000_000_000_000);
@@ -367,4 +757,342 @@ mod tests {
                 .unwrap();
         assert!(res.amount_in <= 0.5 + 1e-9);
     }
+
+    #[test]
+    fn pathological_max_amounts_still_cap_correctly() {
+        let pool = make_pool(4200.0, 1_800_000_000_000_000_000);
+        let dir = SwapDirection::Token1ToToken0;
+        let swap = |max_amount: f64, options: &SwapOptions| {
+            let res =
+                calculate_swap_with_options(&pool, 4_100.0, dir, 5.0, max_amount, options).unwrap();
+            (res.amount_in, res.amount_out)
+        };
+        let options = SwapOptions::default();
+        let uncapped = swap(f64::INFINITY, &options);
+        // Hundreds of ETH: far past 2^53 wei
+        assert!(uncapped.0 * 1e18 > MAX_SAFE_INTEGER);
+
+        // Quantities no pool could fill leave the swap uncapped, even when
+        // their raw amount overflows f64
+        for qty in [1e30, 1e300, f64::MAX] {
+            assert_eq!(swap(qty, &options), uncapped);
+        }
+        // A cap past 2^53 wei still cuts the input exactly there
+        let half = uncapped.0 / 2.0;
+        let (amount_in, amount_out) = swap(half, &options);
+        assert!((amount_in - half).abs() <= half * 1e-15);
+        assert!((amount_out - uncapped.1 / 2.0).abs() <= uncapped.1 * 1e-12);
+
+        // With a clamp, an absurd quantity trades at most the clamp
+        let clamped = SwapOptions {
+            max_amount_clamp: 10.0,
+            ..Default::default()
+        };
+        assert_eq!(swap(1e300, &clamped), swap(10.0, &options));
+        assert!((swap(1e300, &clamped).0 - 10.0).abs() < 1e-12);
+        assert_eq!(swap(f64::NAN, &options).0, 0.0);
+    }
+
+    /// sqrt price (in Q96 units) that a human price maps to for a 6/18 pool.
+    fn sqrt_units(price: f64) -> f64 {
+        let sqrt = calculate_sqrt_price_with_precision_per_eth(price, 6, 18).unwrap();
+        sqrt.to_string().parse::<f64>().unwrap() / 2f64.powi(96)
+    }
+
+    #[test]
+    fn multi_segment_down_sums_amount_out_across_segments() {
+        use crate::dex::state::fixtures::three_segments_down;
+
+        let pool = three_segments_down();
+        // Target inside the last segment: sqrt 15975 => price 1e12 / 15975^2
+        let target_price = 1e12 / (15_975.0f64 * 15_975.0);
+        let res = calculate_swap_with_library(
+            &pool,
+            target_price,
+            SwapDirection::Token0ToToken1,
+            0.0,
+            1e12,
+        )
+        .unwrap();
+
+        // amount1 = L * Δsqrt (Q96 units cancel), summed over the three ranges
+        let t = sqrt_units(target_price);
+        let expected_raw =
+            1e18 * (16_000.0 - 15_990.0) + 2e18 * (15_990.0 - 15_980.0) + 0.5e18 * (15_980.0 - t);
+        let expected = expected_raw / 1e18;
+        assert!(
+            (res.amount_out - expected).abs() < 1e-9,
+            "{} vs {}",
+            res.amount_out,
+            expected
+        );
+        assert!(!res.hit_boundary);
+        assert_eq!(res.ticks_crossed, 2);
+
+        // The fill's average price lies between the start and end prices
+        let start_price = 1e12 / (16_000.0f64 * 16_000.0);
+        assert!(
+            res.avg_price > start_price && res.avg_price < target_price,
+            "{} not in ({}, {})",
+            res.avg_price,
+            start_price,
+            target_price
+        );
+        // ...and is exactly the fill's USDC paid per ETH received
+        assert!((res.avg_price - res.amount_in / res.amount_out).abs() < 1e-9);
+    }
+
+    #[test]
+    fn multi_segment_flags_boundary_when_target_beyond_loaded_segments() {
+        use crate::dex::state::fixtures::three_segments_down;
+
+        let pool = three_segments_down();
+        // sqrt 15900 is below the last loaded edge at 15970
+        let target_price = 1e12 / (15_900.0f64 * 15_900.0);
+        let res = calculate_swap_with_library(
+            &pool,
+            target_price,
+            SwapDirection::Token0ToToken1,
+            0.0,
+            1e12,
+        )
+        .unwrap();
+
+        let expected = (1e18 * 10.0 + 2e18 * 10.0 + 0.5e18 * 10.0) / 1e18;
+        assert!((res.amount_out - expected).abs() < 1e-9);
+        assert!(res.hit_boundary);
+    }
+
+    #[test]
+    fn multi_segment_up_sums_amount_in_across_segments() {
+        use crate::dex::state::fixtures::two_segments_up;
+
+        let pool = two_segments_up();
+        // Target inside the upper segment: sqrt 16015
+        let target_price = 1e12 / (16_015.0f64 * 16_015.0);
+        let res = calculate_swap_with_library(
+            &pool,
+            target_price,
+            SwapDirection::Token1ToToken0,
+            0.0,
+            1e12,
+        )
+        .unwrap();
+
+        // amount1 in = L * Δsqrt over the active range then the upper segment
+        let t = sqrt_units(target_price);
+        let expected = (1e18 * 10.0 + 3e18 * (t - 16_010.0)) / 1e18;
+        assert!(
+            (res.amount_in - expected).abs() < 1e-9,
+            "{} vs {}",
+            res.amount_in,
+            expected
+        );
+        assert!(!res.hit_boundary);
+    }
+
+    #[test]
+    fn traversal_stops_at_max_ticks_and_flags_boundary() {
+        use crate::dex::state::PriceSegment;
+        use crate::dex::state::fixtures::{pool_with_segments, q96};
+
+        // Active range down to 15_999 then 50 one-unit segments below it
+        let liquidity = 1_000_000_000_000_000_000u128;
+        let segments: Vec<PriceSegment> = (0..50u64)
+            .map(|i| PriceSegment::new(q96(15_998 - i), q96(15_999 - i), liquidity))
+            .collect();
+        let pool = pool_with_segments(q96(16_000), liquidity, segments, vec![]);
+        let target_price = 1e12 / (15_900.0f64 * 15_900.0);
+
+        let options = SwapOptions {
+            max_ticks_traversed: 5,
+            ..Default::default()
+        };
+        let res = calculate_swap_with_options(
+            &pool,
+            target_price,
+            SwapDirection::Token0ToToken1,
+            0.0,
+            1e12,
+            &options,
+        )
+        .unwrap();
+        // Active range (1 unit) plus five crossed segments (1 unit each)
+        assert!((res.amount_out - 6.0).abs() < 1e-9, "{}", res.amount_out);
+        assert!(res.hit_boundary);
+
+        let unlimited = calculate_swap_with_library(
+            &pool,
+            target_price,
+            SwapDirection::Token0ToToken1,
+            0.0,
+            1e12,
+        )
+        .unwrap();
+        assert!((unlimited.amount_out - 51.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn current_tick_only_clamps_at_tick_limit_and_flags_boundary() {
+        use crate::dex::state::fixtures::{q96, three_segments_down};
+
+        // Current tick spans sqrt 15_995..16_005, inside the active range
+        let mut pool = three_segments_down();
+        pool.limit_lower_sqrt_price_x96 = Some(q96(15_995));
+        pool.limit_upper_sqrt_price_x96 = Some(q96(16_005));
+        let options = SwapOptions {
+            current_tick_only: true,
+            ..Default::default()
+        };
+        let solve = |target_sqrt: f64, options: &SwapOptions| {
+            calculate_swap_with_options(
+                &pool,
+                1e12 / (target_sqrt * target_sqrt),
+                SwapDirection::Token0ToToken1,
+                0.0,
+                1e12,
+                options,
+            )
+            .unwrap()
+        };
+
+        // Target well past the tick: only the 5 units down to its lower limit fill
+        let clamped = solve(15_975.0, &options);
+        assert!(
+            (clamped.amount_out - 5.0).abs() < 1e-9,
+            "{}",
+            clamped.amount_out
+        );
+        assert!(clamped.hit_boundary);
+        assert_eq!(clamped.ticks_crossed, 0);
+
+        // Without the mode the same target walks the loaded segments
+        let walked = solve(15_975.0, &SwapOptions::default());
+        assert!(walked.amount_out > clamped.amount_out);
+        assert!(!walked.hit_boundary);
+
+        // A target inside the tick is unaffected
+        let inside = solve(15_998.0, &options);
+        assert!(
+            (inside.amount_out - 2.0).abs() < 1e-6,
+            "{}",
+            inside.amount_out
+        );
+        assert!(!inside.hit_boundary);
+    }
+
+    #[test]
+    fn far_off_target_is_capped_to_max_move() {
+        let pool = make_pool(4000.0, 1_800_000_000_000_000_000);
+        let options = SwapOptions {
+            max_target_move_bps: 100.0,
+            ..Default::default()
+        };
+        let solve = |target: f64, direction: SwapDirection, options: &SwapOptions| {
+            calculate_swap_with_options(&pool, target, direction, 0.0, 1e12, options).unwrap()
+        };
+
+        // A 10x target would imply an enormous swap; the cap solves to +1% instead
+        let capped = solve(40_000.0, SwapDirection::Token0ToToken1, &options);
+        let at_cap = solve(
+            4040.0,
+            SwapDirection::Token0ToToken1,
+            &SwapOptions::default(),
+        );
+        let uncapped = solve(
+            40_000.0,
+            SwapDirection::Token0ToToken1,
+            &SwapOptions::default(),
+        );
+        assert!((capped.amount_out / at_cap.amount_out - 1.0).abs() < 1e-6);
+        assert!(uncapped.amount_out > 10.0 * capped.amount_out);
+
+        // Same on the way down: a 90% crash is capped to -1%
+        let capped = solve(400.0, SwapDirection::Token1ToToken0, &options);
+        let at_cap = solve(
+            3960.0,
+            SwapDirection::Token1ToToken0,
+            &SwapOptions::default(),
+        );
+        assert!((capped.amount_in / at_cap.amount_in - 1.0).abs() < 1e-6);
+
+        // Targets inside the cap are untouched
+        let near = solve(4020.0, SwapDirection::Token0ToToken1, &options);
+        let free = solve(
+            4020.0,
+            SwapDirection::Token0ToToken1,
+            &SwapOptions::default(),
+        );
+        assert_eq!(near.amount_out, free.amount_out);
+    }
+
+    #[test]
+    fn each_math_failure_has_its_own_error_variant() {
+        use uniswap_v3_math::error::UniswapV3MathError;
+
+        assert!(matches!(
+            calculate_sqrt_price_with_precision_per_eth(0.0, 6, 18),
+            Err(SwapMathError::InvalidPrice(_))
+        ));
+        assert!(matches!(
+            calculate_sqrt_price_with_precision_per_eth(f64::NAN, 6, 18),
+            Err(SwapMathError::InvalidPrice(_))
+        ));
+        // 10^-255 / 1e100 underflows f64, leaving a zero sqrt
+        assert!(matches!(
+            calculate_sqrt_price_with_precision_per_eth(1e100, 255, 0),
+            Err(SwapMathError::NonPositiveSqrt(_))
+        ));
+        // sqrt(10^255) * 2^96 is far beyond U256
+        assert!(matches!(
+            calculate_sqrt_price_with_precision_per_eth(1.0, 0, 255),
+            Err(SwapMathError::Overflow(_))
+        ));
+
+        // A zero pool price surfaces the library's own error, not a sentinel
+        let mut pool = make_pool(4200.0, 1_800_000_000_000_000_000);
+        pool.sqrt_price_x96 = U256::ZERO;
+        let res =
+            calculate_swap_with_library(&pool, 4100.0, SwapDirection::Token1ToToken0, 0.0, 5.0);
+        assert!(matches!(
+            res,
+            Err(SwapMathError::Library(UniswapV3MathError::SqrtPriceIsZero))
+        ));
+    }
+
+    #[test]
+    fn extreme_decimal_pairs_round_trip_accurately() {
+        assert_eq!(
+            decimals_factor(0, 18),
+            BigDecimal::from_str("1000000000000000000").unwrap()
+        );
+        assert_eq!(
+            decimals_factor(18, 0),
+            BigDecimal::from_str("1e-18").unwrap()
+        );
+
+        for (token0_decimals, token1_decimals, price) in [
+            (0u8, 18u8, 4_223.17),
+            (18, 0, 0.000_237),
+            (18, 0, 4_223.17),
+            (0, 18, 1e-6),
+        ] {
+            let sqrt = calculate_sqrt_price_with_precision_per_eth(
+                price,
+                token0_decimals,
+                token1_decimals,
+            )
+            .unwrap();
+            let back = calculate_human_price_from_sqrt_x96(sqrt, token0_decimals, token1_decimals);
+            let rel_err = ((back - price) / price).abs();
+            assert!(
+                rel_err < 1e-12,
+                "{}/{} price {} came back as {}",
+                token0_decimals,
+                token1_decimals,
+                price,
+                back
+            );
+        }
+    }
 }
@@ -1,11 +1,22 @@
-use crate::dex::state::PoolState;
-use crate::errors::Result;
+use crate::config::PoolConfig;
+use crate::dex::calc::{calculate_human_price_from_sqrt_x96, sqrt_price_round_trip_bps};
+use crate::dex::metadata::{
+    EXPECTED_DECIMALS, MetadataCache, PoolMetadata, QuoteSide, TokenMetadata,
+};
+use crate::dex::state::{PoolState, PriceSegment, segments_from_ticks};
+use crate::errors::{AppError, Result};
+use crate::models::{SwapDirection, SwapResult};
+use crate::rate_limit::{RateLimiter, RpcProvider, rate_limited_provider};
 use alloy_primitives::U256;
 use ethers::{
-    contract::abigen,
-    providers::{Http, Provider},
-    types::Address,
+    abi::Detokenize,
+    contract::{ContractCall, abigen},
+    providers::{Middleware, Provider, RawCall, RpcError, Ws, spoof},
+    types::{Address, BlockId},
 };
+use futures::StreamExt;
+use std::collections::BTreeMap;
+use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::watch;
 use tracing::warn;
@@ -19,35 +30,273 @@ abigen!(
         function liquidity() view returns (uint128)
         function fee() view returns (uint24)
         function tickSpacing() view returns (int24)
+        function token0() view returns (address)
+        function token1() view returns (address)
+        function tickBitmap(int16 wordPosition) view returns (uint256)
+        function swap(address recipient, bool zeroForOne, int256 amountSpecified, uint160 sqrtPriceLimitX96, bytes data) returns (int256 amount0, int256 amount1)
+        function ticks(int24 tick) view returns (uint128 liquidityGross, int128 liquidityNet, uint256 feeGrowthOutside0X128, uint256 feeGrowthOutside1X128, int56 tickCumulativeOutside, uint160 secondsPerLiquidityOutsideX128, uint32 secondsOutside, bool initialized)
+        function observe(uint32[] secondsAgos) view returns (int56[] tickCumulatives, uint160[] secondsPerLiquidityCumulativeX128s)
+    ]",
+);
+
+abigen!(
+    TickLens,
+    r"[
+        struct PopulatedTick { int24 tick; int128 liquidityNet; uint128 liquidityGross; }
+        function getPopulatedTicksInWord(address pool, int16 tickBitmapIndex) view returns (PopulatedTick[] populatedTicks)
+    ]",
+);
+
+abigen!(
+    Erc20,
+    r"[
+        function decimals() view returns (uint8)
+        function symbol() view returns (string)
     ]",
 );
 
-/// Handle for interacting with a specific Uniswap V3 pool.
+/// Handle for interacting with a specific Uniswap V3 pool on one chain.
 #[derive(Clone)]
-pub struct Dex {
-    pool: UniswapV3Pool<Provider<Http>>,
+pub struct Dex<M = RpcProvider> {
+    pool: UniswapV3Pool<M>,
+    chain_id: u64,
+    metadata: Option<PoolMetadata>,
+    segment_window_ticks: u32,
+    tick_lens: Option<TickLens<M>>,
+    quote_side: QuoteSide,
 }
 
 impl Dex {
-    pub async fn new(rpc_url: &str, pool_addr: Address) -> Result<Self> {
-        let provider = Arc::new(Provider::<Http>::try_from(rpc_url)?);
-        let pool = UniswapV3Pool::new(pool_addr, provider);
-        pool.slot_0().call().await?; // sanity-check
-        Ok(Self { pool })
+    /// Connect to `pool` over its HTTP RPC; every call draws from `limiter`.
+    ///
+    /// Pool metadata comes from `cache` when present there for the pool's
+    /// chain; otherwise it is fetched, which doubles as a sanity check. The
+    /// pool's decimals are then checked against what the pricing math
+    /// assumes, and the side its quote token is on recorded.
+    pub async fn new(
+        pool: &PoolConfig,
+        limiter: Arc<RateLimiter>,
+        cache: Option<&MetadataCache>,
+    ) -> Result<Self> {
+        let pool_addr = Address::from_str(&pool.pool_address).map_err(|e| {
+            AppError::Config(format!("invalid pool address {}: {}", pool.pool_address, e))
+        })?;
+        let quote_token = pool
+            .quote
+            .token
+            .as_deref()
+            .map(|raw| {
+                Address::from_str(raw).map_err(|e| {
+                    AppError::Config(format!("invalid quote token address {}: {}", raw, e))
+                })
+            })
+            .transpose()?;
+        let provider = Arc::new(rate_limited_provider(&pool.rpc_url, limiter)?);
+        let mut dex = Self::connect(provider, pool_addr, pool.chain_id, cache).await?;
+        if let Some(meta) = dex.metadata() {
+            dex.quote_side = meta.check_orientation(quote_token)?;
+        }
+        Ok(dex)
+    }
+}
+
+impl<M: Middleware + 'static> Dex<M> {
+    /// Wrap an existing mainnet client without any RPC round-trip.
+    pub fn from_client(client: Arc<M>, pool_addr: Address) -> Self {
+        Self {
+            pool: UniswapV3Pool::new(pool_addr, client),
+            chain_id: 1,
+            metadata: None,
+            segment_window_ticks: 0,
+            tick_lens: None,
+            quote_side: QuoteSide::Token0,
+        }
+    }
+
+    /// Load initialized ticks up to `ticks` away from the current one on each
+    /// refresh (0 keeps the single-range snapshot).
+    pub fn with_segment_window_ticks(mut self, ticks: u32) -> Self {
+        self.segment_window_ticks = ticks;
+        self
+    }
+
+    /// Read each bitmap word's initialized ticks from the Uniswap `TickLens`
+    /// deployed at `lens`, one call per word, instead of from the pool.
+    pub fn with_tick_lens(mut self, lens: Option<Address>) -> Self {
+        self.tick_lens = lens.map(|lens| TickLens::new(lens, self.pool.client()));
+        self
+    }
+
+    /// Wrap an existing client and load the pool's metadata.
+    pub async fn connect(
+        client: Arc<M>,
+        pool_addr: Address,
+        chain_id: u64,
+        cache: Option<&MetadataCache>,
+    ) -> Result<Self> {
+        let mut dex = Self::from_client(client, pool_addr);
+        dex.chain_id = chain_id;
+        dex.metadata = Some(dex.fetch_token_metadata(chain_id, cache).await?);
+        Ok(dex)
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Identity of the pool: the same address on two chains is two pools.
+    pub fn key(&self) -> (u64, Address) {
+        (self.chain_id, self.pool.address())
+    }
+
+    /// Metadata loaded by [`Dex::connect`], if any.
+    pub fn metadata(&self) -> Option<&PoolMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Address of the pool.
+    pub fn address(&self) -> Address {
+        self.pool.address()
+    }
+
+    /// Which of the pool's tokens is the quote, token0 unless
+    /// [`Dex::new`] found it on the other side.
+    pub fn quote_side(&self) -> QuoteSide {
+        self.quote_side
     }
 
-    /// Build a `PoolState` snapshot for pricing (single tick only).
+    /// Decimals of (quote, base), the token0 and token1 of the pool states
+    /// read, defaulting to USDC/WETH without metadata.
+    pub fn token_decimals(&self) -> (u8, u8) {
+        self.metadata.as_ref().map_or(EXPECTED_DECIMALS, |m| {
+            let quote = m.quote_token(self.quote_side);
+            let base = match self.quote_side {
+                QuoteSide::Token0 => &m.token1,
+                QuoteSide::Token1 => &m.token0,
+            };
+            (quote.decimals, base.decimals)
+        })
+    }
+
+    /// `sqrtPriceX96` of the pool with the quote as token0: inverted when
+    /// the quote is token1.
+    fn quote_sqrt_price_x96(&self, sqrt_price_x96: U256) -> U256 {
+        match self.quote_side {
+            QuoteSide::Token1 if !sqrt_price_x96.is_zero() => {
+                (U256::from(1u8) << 192) / sqrt_price_x96
+            }
+            _ => sqrt_price_x96,
+        }
+    }
+
+    /// Fetch the pool's immutables and token metadata, consulting `cache` first.
+    pub async fn fetch_token_metadata(
+        &self,
+        chain_id: u64,
+        cache: Option<&MetadataCache>,
+    ) -> Result<PoolMetadata> {
+        let pool_addr = self.pool.address();
+        if let Some(cached) = cache.and_then(|c| c.pool(chain_id, pool_addr)) {
+            return Ok(cached);
+        }
+
+        let token0 = self.pool.token_0().call().await?;
+        let token1 = self.pool.token_1().call().await?;
+        let fee = self.pool.fee().call().await?;
+        let tick_spacing = self.pool.tick_spacing().call().await?;
+        let metadata = PoolMetadata {
+            chain_id,
+            token0: self.token_metadata(chain_id, token0, cache).await?,
+            token1: self.token_metadata(chain_id, token1, cache).await?,
+            fee,
+            tick_spacing,
+        };
+
+        if let Some(cache) = cache {
+            cache.insert_pool(pool_addr, &metadata)?;
+        }
+        Ok(metadata)
+    }
+
+    async fn token_metadata(
+        &self,
+        chain_id: u64,
+        token: Address,
+        cache: Option<&MetadataCache>,
+    ) -> Result<TokenMetadata> {
+        if let Some(cached) = cache.and_then(|c| c.token(chain_id, token)) {
+            return Ok(cached);
+        }
+        let erc20 = Erc20::new(token, self.pool.client());
+        Ok(TokenMetadata {
+            address: token,
+            decimals: erc20.decimals().call().await?,
+            symbol: erc20.symbol().call().await?,
+        })
+    }
+
+    /// Build a `PoolState` snapshot for pricing, with segments loaded when a
+    /// segment window is configured.
+    ///
+    /// The state always has the quote as token0 (`token0_decimals` being the
+    /// quote's): a pool quoting in token1 is read as its mirror image, see
+    /// [`PoolState::invert_tokens`].
     pub async fn get_pool_state(
         &self,
         token0_decimals: u8,
         token1_decimals: u8,
         current_tick_lower_sqrt_q96: Option<U256>,
         current_tick_upper_sqrt_q96: Option<U256>,
+    ) -> Result<PoolState> {
+        self.read_pool_state(
+            None,
+            token0_decimals,
+            token1_decimals,
+            current_tick_lower_sqrt_q96,
+            current_tick_upper_sqrt_q96,
+        )
+        .await
+    }
+
+    /// Build a `PoolState` snapshot as of a past block.
+    ///
+    /// Every read is pinned to `block_number`, which requires an archive node
+    /// for anything older than the last ~128 blocks; a node without that state
+    /// yields [`AppError::HistoricalState`].
+    pub async fn get_pool_state_at_block(
+        &self,
+        block_number: u64,
+        token0_decimals: u8,
+        token1_decimals: u8,
+        current_tick_lower_sqrt_q96: Option<U256>,
+        current_tick_upper_sqrt_q96: Option<U256>,
+    ) -> Result<PoolState> {
+        self.read_pool_state(
+            Some(BlockId::from(block_number)),
+            token0_decimals,
+            token1_decimals,
+            current_tick_lower_sqrt_q96,
+            current_tick_upper_sqrt_q96,
+        )
+        .await
+        .map_err(|e| AppError::HistoricalState {
+            block: block_number,
+            reason: e.to_string(),
+        })
+    }
+
+    async fn read_pool_state(
+        &self,
+        block: Option<BlockId>,
+        token0_decimals: u8,
+        token1_decimals: u8,
+        current_tick_lower_sqrt_q96: Option<U256>,
+        current_tick_upper_sqrt_q96: Option<U256>,
     ) -> Result<PoolState> {
         let (sqrt_price_x96, tick, _, _, _, _fee_protocol, _unlocked) =
-            self.pool.slot_0().call().await?;
-        let liquidity = self.pool.liquidity().call().await?;
-        let tick_spacing = self.pool.tick_spacing().call().await?;
+            pinned(self.pool.slot_0(), block).call().await?;
+        let liquidity = pinned(self.pool.liquidity(), block).call().await?;
+        let tick_spacing = pinned(self.pool.tick_spacing(), block).call().await?;
 
         // Convert ethers U256 to alloy U256
         let sqrt_price_x96_alloy =
@@ -58,8 +307,9 @@ impl Dex {
             match (current_tick_lower_sqrt_q96, current_tick_upper_sqrt_q96) {
                 (Some(l), Some(u)) => (Some(l), Some(u)),
                 _ => {
-                    let ts = tick_spacing as i32;
-                    let base = tick - (tick % ts);
+                    let ts = (tick_spacing as i32).max(1);
+                    // Round down, also for the negative ticks of token1-quoted pools
+                    let base = tick.div_euclid(ts) * ts;
                     let lower_tick = base;
                     let upper_tick = base + ts;
                     (
@@ -70,23 +320,217 @@ impl Dex {
             };
 
         let price_usdc_per_eth = price_usdc_per_eth(sqrt_price_x96_alloy);
+        // The pool's own token order; the decimals given are the quote's first
+        let (raw0_decimals, raw1_decimals) = match self.quote_side {
+            QuoteSide::Token0 => (token0_decimals, token1_decimals),
+            QuoteSide::Token1 => (token1_decimals, token0_decimals),
+        };
 
-        Ok(PoolState::new(
+        let state = PoolState::new(
             sqrt_price_x96_alloy,
             liquidity,
             tick as i32,
-            token0_decimals,
-            token1_decimals,
+            raw0_decimals,
+            raw1_decimals,
             lower_q96,
             upper_q96,
             price_usdc_per_eth,
+        );
+        if self.segment_window_ticks == 0 {
+            return Ok(self.quote_oriented(state));
+        }
+        let (segments_down, segments_up) = self
+            .load_tick_segments(
+                block,
+                tick,
+                tick_spacing as i32,
+                liquidity,
+                self.segment_window_ticks,
+            )
+            .await?;
+        Ok(self.quote_oriented(state.with_segments(segments_down, segments_up)))
+    }
+
+    /// `state`, read in the pool's own token order, with the quote as token0.
+    fn quote_oriented(&self, state: PoolState) -> PoolState {
+        match self.quote_side {
+            QuoteSide::Token0 => state,
+            QuoteSide::Token1 => state.invert_tokens(),
+        }
+    }
+
+    /// Load the initialized ticks within `window_ticks` of `tick` and build
+    /// the price segments on either side of the active range.
+    ///
+    /// Reads the initialized ticks of one bitmap word per 256 tick spacings
+    /// covered, see [`Dex::get_populated_ticks_in_word`]. A wider window
+    /// prices larger swaps at the cost of more calls per refresh.
+    pub async fn load_tick_segments(
+        &self,
+        block: Option<BlockId>,
+        tick: i32,
+        tick_spacing: i32,
+        liquidity: u128,
+        window_ticks: u32,
+    ) -> Result<(Vec<PriceSegment>, Vec<PriceSegment>)> {
+        let spacing = tick_spacing.max(1);
+        let window = window_ticks as i32;
+        let lower = tick.saturating_sub(window).div_euclid(spacing) * spacing;
+        let upper = tick.saturating_add(window).div_euclid(spacing) * spacing;
+        let (first_word, last_word) = (
+            bitmap_position(lower, spacing).0,
+            bitmap_position(upper, spacing).0,
+        );
+
+        let mut initialized = BTreeMap::new();
+        for word in first_word..=last_word {
+            for (initialized_tick, liquidity_net) in self
+                .get_populated_ticks_in_word(block, word, spacing)
+                .await?
+            {
+                if (lower..=upper).contains(&initialized_tick) {
+                    initialized.insert(initialized_tick, liquidity_net);
+                }
+            }
+        }
+
+        Ok(segments_from_ticks(
+            tick,
+            liquidity,
+            &initialized,
+            window_ticks,
         ))
     }
 
-    /// Reads the Uniswap V3 pool fee (in basis points, e.g., 500 = 0.05%).
-    pub async fn get_pool_fee_bps(&self) -> Result<u32> {
-        let fee_raw: u32 = self.pool.fee().call().await?;
-        Ok(fee_raw)
+    /// The initialized ticks of `tickBitmap` word `word` with their
+    /// `liquidityNet`, lowest first.
+    ///
+    /// One `TickLens` call when a lens is set; otherwise the word itself and
+    /// then `ticks()` of every initialized tick in it, issued together.
+    pub async fn get_populated_ticks_in_word(
+        &self,
+        block: Option<BlockId>,
+        word: i16,
+        tick_spacing: i32,
+    ) -> Result<Vec<(i32, i128)>> {
+        if let Some(lens) = &self.tick_lens {
+            let mut populated: Vec<(i32, i128)> = pinned(
+                lens.get_populated_ticks_in_word(self.pool.address(), word),
+                block,
+            )
+            .call()
+            .await?
+            .into_iter()
+            .map(|(tick, liquidity_net, _)| (tick, liquidity_net))
+            .collect();
+            // The lens lists the word from its highest tick down
+            populated.sort_unstable_by_key(|&(tick, _)| tick);
+            return Ok(populated);
+        }
+
+        let bitmap = pinned(self.pool.tick_bitmap(word), block).call().await?;
+        let ticks = initialized_ticks_in_word(word, bitmap, tick_spacing);
+        let nets = futures::future::try_join_all(ticks.iter().map(|&tick| async move {
+            let (_, liquidity_net, ..) = pinned(self.pool.ticks(tick), block).call().await?;
+            Ok::<_, AppError>(liquidity_net)
+        }))
+        .await?;
+        Ok(ticks.into_iter().zip(nets).collect())
+    }
+
+    /// Reads the Uniswap V3 pool fee in basis points (e.g. `fee()` 500 = 5 bps = 0.05%).
+    pub async fn get_pool_fee_bps(&self) -> Result<f64> {
+        let fee_pips: u32 = self.pool.fee().call().await?;
+        fee_pips_to_bps(fee_pips)
+    }
+
+    /// Dry-run an exact-input swap of `amount_in_raw` of the pool's own
+    /// token0 or token1 (per `direction`) against the pool with `eth_call`,
+    /// as a ground truth for the internal swap math.
+    ///
+    /// The call is made from [`SWAP_SIMULATOR`], whose code is supplied by a
+    /// state override: its `uniswapV3SwapCallback` reverts with
+    /// `abi.encode(amount0Delta, amount1Delta)` (the quoter revert pattern),
+    /// so nothing executes and nothing has to be deployed. The node must
+    /// support state overrides, as geth, erigon, reth and anvil do.
+    pub async fn simulate_swap(
+        &self,
+        direction: SwapDirection,
+        amount_in_raw: ethers::types::U256,
+    ) -> Result<SwapDeltas> {
+        let (tx, state) = self.swap_simulation(direction, amount_in_raw)?;
+        let client = self.pool.client();
+        match client.provider().call_raw(&tx).state(&state).await {
+            Ok(_) => Err(AppError::Contract(
+                "simulated swap did not revert; does the node apply state overrides?".to_string(),
+            )),
+            Err(e) => match e.as_error_response().and_then(|e| e.as_revert_data()) {
+                Some(data) => decode_swap_revert(&data),
+                None => Err(AppError::Contract(format!("simulated swap failed: {}", e))),
+            },
+        }
+    }
+
+    /// Output of the DEX leg of an opportunity, simulated with
+    /// [`simulate_swap`](Self::simulate_swap): `amount_in` of the quote when
+    /// `buys_eth`, of ETH otherwise, in and out in human units.
+    pub async fn simulate_leg(&self, buys_eth: bool, amount_in: f64) -> Result<f64> {
+        let (quote_decimals, base_decimals) = self.token_decimals();
+        let (in_decimals, out_decimals) = if buys_eth {
+            (quote_decimals, base_decimals)
+        } else {
+            (base_decimals, quote_decimals)
+        };
+        // The quote is the pool's token0 unless it sits on the other side
+        let quote_is_token0 = self.quote_side == QuoteSide::Token0;
+        let direction = if buys_eth == quote_is_token0 {
+            SwapDirection::Token0ToToken1
+        } else {
+            SwapDirection::Token1ToToken0
+        };
+        let amount_in_raw = amount_in * 10f64.powi(in_decimals as i32);
+        if !(amount_in_raw >= 1.0 && amount_in_raw < u128::MAX as f64) {
+            return Err(AppError::Contract(format!(
+                "swap amount {} out of range",
+                amount_in
+            )));
+        }
+        let deltas = self
+            .simulate_swap(direction, ethers::types::U256::from(amount_in_raw as u128))
+            .await?;
+        Ok(deltas.amount_out_raw(direction) / 10f64.powi(out_decimals as i32))
+    }
+
+    /// `swap` call of [`simulate_swap`](Self::simulate_swap) and the state
+    /// override placing the simulator's code.
+    fn swap_simulation(
+        &self,
+        direction: SwapDirection,
+        amount_in_raw: ethers::types::U256,
+    ) -> Result<(
+        ethers::types::transaction::eip2718::TypedTransaction,
+        spoof::State,
+    )> {
+        let zero_for_one = direction == SwapDirection::Token0ToToken1;
+        let sqrt_price_limit = if zero_for_one {
+            ethers::types::U256::from(MIN_SQRT_RATIO_PLUS_ONE)
+        } else {
+            ethers::types::U256::from_dec_str(MAX_SQRT_RATIO_MINUS_ONE).unwrap_or_default()
+        };
+        let amount_specified = ethers::types::I256::try_from(amount_in_raw)
+            .map_err(|e| AppError::Contract(format!("swap amount out of range: {}", e)))?;
+        let call = self
+            .pool
+            .swap(
+                SWAP_SIMULATOR,
+                zero_for_one,
+                amount_specified,
+                sqrt_price_limit,
+                ethers::types::Bytes::default(),
+            )
+            .from(SWAP_SIMULATOR);
+        let state = spoof::code(SWAP_SIMULATOR, SWAP_SIMULATOR_CODE.to_vec().into());
+        Ok((call.tx, state))
     }
 
     /// Fetch current ETH price in USDC
@@ -94,28 +538,141 @@ impl Dex {
         let sqrt_price_x96 = self.pool.slot_0().call().await?.0;
         let sqrt_price_x96_alloy =
             U256::from_str_radix(&sqrt_price_x96.to_string(), 10).unwrap_or_default();
-        Ok(price_usdc_per_eth(sqrt_price_x96_alloy))
+        Ok(price_usdc_per_eth(
+            self.quote_sqrt_price_x96(sqrt_price_x96_alloy),
+        ))
+    }
+
+    /// Price of `token` in the pool's other token from the current slot0,
+    /// e.g. USDC in USDT for a stablecoin pool.
+    pub async fn fetch_token_price(&self, token: Address) -> Result<f64> {
+        let meta = self
+            .metadata
+            .as_ref()
+            .ok_or_else(|| AppError::Other("pool metadata not loaded".to_string()))?;
+        let token_is_token0 = if meta.token0.address == token {
+            true
+        } else if meta.token1.address == token {
+            false
+        } else {
+            return Err(AppError::Config(format!(
+                "pool {:?} on chain {} does not contain token {:?} ({} / {})",
+                self.pool.address(),
+                self.chain_id,
+                token,
+                meta.token0.symbol,
+                meta.token1.symbol
+            )));
+        };
+        let sqrt_price_x96 = self.pool.slot_0().call().await?.0;
+        let sqrt_price_x96 =
+            U256::from_str_radix(&sqrt_price_x96.to_string(), 10).unwrap_or_default();
+        // Human token0 per token1
+        let price = calculate_human_price_from_sqrt_x96(
+            sqrt_price_x96,
+            meta.token0.decimals,
+            meta.token1.decimals,
+        );
+        Ok(if token_is_token0 { 1.0 / price } else { price })
+    }
+
+    /// Time-weighted average price in USDC per ETH over the last
+    /// `window_secs`, from the pool's own oracle (`observe`).
+    ///
+    /// Fails when the pool's observation history does not reach back that far.
+    pub async fn fetch_twap_price(&self, window_secs: u32) -> Result<f64> {
+        if window_secs == 0 {
+            return Err(AppError::Config(
+                "TWAP window must be at least one second".to_string(),
+            ));
+        }
+        let (tick_cumulatives, _) = self.pool.observe(vec![window_secs, 0]).call().await?;
+        let [ago, now] = tick_cumulatives[..] else {
+            return Err(AppError::Other(format!(
+                "observe returned {} tick cumulatives, expected 2",
+                tick_cumulatives.len()
+            )));
+        };
+        let tick = match self.quote_side {
+            QuoteSide::Token0 => mean_tick(ago, now, window_secs),
+            QuoteSide::Token1 => -mean_tick(ago, now, window_secs),
+        };
+        Ok(price_usdc_per_eth(approx_sqrt_price_x96_at_tick(tick)))
+    }
+
+    /// Startup check that the current sqrtPriceX96 survives a round trip
+    /// through the human price within `tolerance_bps`.
+    ///
+    /// See [`check_sqrt_price_round_trip`]; skipped when `tolerance_bps` is 0.
+    pub async fn check_price_round_trip(&self, tolerance_bps: f64) -> Result<()> {
+        if tolerance_bps <= 0.0 {
+            return Ok(());
+        }
+        let sqrt_price_x96 = self.pool.slot_0().call().await?.0;
+        let sqrt_price_x96 =
+            U256::from_str_radix(&sqrt_price_x96.to_string(), 10).unwrap_or_default();
+        check_sqrt_price_round_trip(
+            self.quote_sqrt_price_x96(sqrt_price_x96),
+            self.token_decimals(),
+            tolerance_bps,
+        )
+    }
+}
+
+/// `tickBitmap` word and bit holding initialized-tick `tick`, as Uniswap V3
+/// lays the bitmap out: one bit per tick spacing, 256 per word.
+pub fn bitmap_position(tick: i32, tick_spacing: i32) -> (i16, u8) {
+    let compressed = tick.div_euclid(tick_spacing.max(1));
+    ((compressed >> 8) as i16, (compressed & 0xff) as u8)
+}
+
+/// Ticks flagged initialized in `bitmap`, the `tickBitmap` word at `word`,
+/// lowest first.
+pub fn initialized_ticks_in_word(
+    word: i16,
+    bitmap: ethers::types::U256,
+    tick_spacing: i32,
+) -> Vec<i32> {
+    if bitmap.is_zero() {
+        return Vec::new();
+    }
+    (0..256)
+        .filter(|&bit| bitmap.bit(bit))
+        .map(|bit| ((word as i32) * 256 + bit as i32) * tick_spacing.max(1))
+        .collect()
+}
+
+/// Pin a contract read to `block` when given, otherwise read the latest state.
+fn pinned<M: Middleware, D: Detokenize>(
+    call: ContractCall<M, D>,
+    block: Option<BlockId>,
+) -> ContractCall<M, D> {
+    match block {
+        Some(block) => call.block(block),
+        None => call,
     }
 }
 
 /// Initialize pool state watcher
+///
+/// Refreshes the pool every 5 seconds and publishes each snapshot on `pool_tx`.
 pub async fn init_pool_state_watcher(
     dex: &Dex,
-    _pool_tx: watch::Sender<PoolState>,
-) -> Result<watch::Receiver<PoolState>> {
-    // Get initial pool state
-    let initial_state = dex.get_pool_state(6, 18, None, None).await?;
-    let (tx, rx) = watch::channel(initial_state);
-
+    pool_tx: watch::Sender<PoolState>,
+) -> Result<tokio::task::JoinHandle<()>> {
     // Spawn background task to update pool state
     let dex_clone = dex.clone();
-    tokio::spawn(async move {
+    let handle = tokio::spawn(async move {
         let mut ticker = tokio::time::interval(std::time::Duration::from_secs(5));
         loop {
             ticker.tick().await;
-            match dex_clone.get_pool_state(6, 18, None, None).await {
+            let (decimals0, decimals1) = dex_clone.token_decimals();
+            match dex_clone
+                .get_pool_state(decimals0, decimals1, None, None)
+                .await
+            {
                 Ok(state) => {
-                    let _ = tx.send(state);
+                    let _ = pool_tx.send(state);
                 }
                 Err(e) => {
                     warn!(error = %e, "[DEX] failed to refresh pool state");
@@ -124,10 +681,309 @@ pub async fn init_pool_state_watcher(
         }
     });
 
-    Ok(rx)
+    Ok(handle)
 }
 
-fn price_usdc_per_eth(sqrt_price_x96: U256) -> f64 {
+/// Spawn a watcher that re-reads the pool on every new block.
+///
+/// Subscribes to `newHeads` over `ws_url`; for each block the pool state is
+/// read pinned to that block and published on `pool_tx` before the block
+/// number goes out on `block_tx`, so a consumer woken by `block_tx` always
+/// sees the matching pool snapshot. The subscription is re-established if it
+/// drops, or if it goes silent for `max_silence` and a forced read shows the
+/// pool moved without it (see [`follow_heads`]).
+pub async fn spawn_block_pool_watcher(
+    dex: &Dex,
+    ws_url: &str,
+    pool_tx: watch::Sender<PoolState>,
+    block_tx: watch::Sender<u64>,
+    max_silence: Option<std::time::Duration>,
+) -> Result<tokio::task::JoinHandle<()>> {
+    // Fail fast on a bad endpoint; later drops are retried in the task
+    let mut provider = Provider::<Ws>::connect(ws_url).await?;
+    let ws_url = ws_url.to_string();
+    let dex = dex.clone();
+    let handle = tokio::spawn(async move {
+        loop {
+            match provider.subscribe_blocks().await {
+                Ok(blocks) => {
+                    let heads = blocks.filter_map(|block| {
+                        futures::future::ready(block.number.map(|number| number.as_u64()))
+                    });
+                    match follow_heads(&dex, heads, &pool_tx, &block_tx, max_silence).await {
+                        HeadsEnd::Closed => {
+                            warn!("[DEX] newHeads subscription ended; resubscribing")
+                        }
+                        HeadsEnd::Stale => {
+                            warn!(
+                                "[DEX] newHeads subscription went silent while the pool moved; resubscribing"
+                            )
+                        }
+                    }
+                }
+                Err(e) => warn!(error = %e, "[DEX] newHeads subscription failed"),
+            }
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            match Provider::<Ws>::connect(&ws_url).await {
+                Ok(reconnected) => provider = reconnected,
+                Err(e) => warn!(error = %e, "[DEX] websocket reconnect failed"),
+            }
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Why [`follow_heads`] gave up on a head subscription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadsEnd {
+    /// The subscription stream ended
+    Closed,
+    /// No head arrived for the silence limit, yet the pool had moved
+    Stale,
+}
+
+/// Publish the pool state of every block number `heads` yields, until the
+/// stream ends or is found stale.
+///
+/// When no head arrives for `max_silence`, the pool is read at the latest
+/// block to tell a quiet chain from a silently dead subscription: unchanged
+/// state keeps waiting, while state that drifted from the last published
+/// snapshot is published (waking `block_tx` consumers) and the subscription
+/// is reported [`HeadsEnd::Stale`] so the caller re-establishes it.
+pub async fn follow_heads<M, S>(
+    dex: &Dex<M>,
+    mut heads: S,
+    pool_tx: &watch::Sender<PoolState>,
+    block_tx: &watch::Sender<u64>,
+    max_silence: Option<std::time::Duration>,
+) -> HeadsEnd
+where
+    M: Middleware + 'static,
+    S: futures::Stream<Item = u64> + Unpin,
+{
+    loop {
+        let next = match max_silence {
+            Some(limit) => tokio::time::timeout(limit, heads.next()).await,
+            None => Ok(heads.next().await),
+        };
+        let (decimals0, decimals1) = dex.token_decimals();
+        match next {
+            Ok(Some(number)) => {
+                match dex
+                    .get_pool_state_at_block(number, decimals0, decimals1, None, None)
+                    .await
+                {
+                    Ok(state) => {
+                        let _ = pool_tx.send(state);
+                        let _ = block_tx.send(number);
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "[DEX] failed to read pool state for block");
+                    }
+                }
+            }
+            Ok(None) => return HeadsEnd::Closed,
+            Err(_) => match dex.get_pool_state(decimals0, decimals1, None, None).await {
+                Ok(state) => {
+                    let drifted = {
+                        let last = pool_tx.borrow();
+                        (state.sqrt_price_x96, state.liquidity, state.tick)
+                            != (last.sqrt_price_x96, last.liquidity, last.tick)
+                    };
+                    if drifted {
+                        let _ = pool_tx.send(state);
+                        block_tx.send_modify(|_| {});
+                        return HeadsEnd::Stale;
+                    }
+                }
+                Err(e) => warn!(error = %e, "[DEX] forced pool resync failed"),
+            },
+        }
+    }
+}
+
+/// Spawn a watcher publishing the pool's TWAP over `window_secs` on
+/// `twap_tx` every `interval`; the last TWAP is kept while a read fails.
+pub fn spawn_twap_watcher<M: Middleware + 'static>(
+    dex: Dex<M>,
+    window_secs: u32,
+    twap_tx: watch::Sender<f64>,
+    interval: std::time::Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match dex.fetch_twap_price(window_secs).await {
+                Ok(price) if price > 0.0 && price.is_finite() => {
+                    let _ = twap_tx.send(price);
+                }
+                Ok(price) => warn!(price, "[DEX] ignoring unusable TWAP"),
+                Err(e) => warn!(error = %e, "[DEX] failed to refresh TWAP"),
+            }
+        }
+    })
+}
+
+/// Arithmetic mean tick between two `observe` tick cumulatives `window_secs`
+/// apart, rounded towards negative infinity like `OracleLibrary.consult`.
+pub fn mean_tick(cumulative_ago: i64, cumulative_now: i64, window_secs: u32) -> i32 {
+    let delta = cumulative_now - cumulative_ago;
+    let window = i64::from(window_secs.max(1));
+    delta.div_euclid(window) as i32
+}
+
+/// Spawn a watcher publishing the price of `token` in the other token of
+/// `dex` on `price_tx` every `interval`; the last price is kept while a read
+/// fails.
+pub fn spawn_token_price_watcher<M: Middleware + 'static>(
+    dex: Dex<M>,
+    token: Address,
+    price_tx: watch::Sender<f64>,
+    interval: std::time::Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match dex.fetch_token_price(token).await {
+                Ok(price) if price > 0.0 && price.is_finite() => {
+                    let _ = price_tx.send(price);
+                }
+                Ok(price) => warn!(price, "[DEX] ignoring unusable reference price"),
+                Err(e) => warn!(error = %e, "[DEX] failed to refresh reference price"),
+            }
+        }
+    })
+}
+
+const RESUBSCRIBE_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Address the swap simulator's code is placed at by a state override; no
+/// contract lives there on any chain.
+pub const SWAP_SIMULATOR: Address = Address::repeat_byte(0x5e);
+
+/// Runtime code of the swap simulator: any call reverts with its first two
+/// arguments, i.e. `(amount0Delta, amount1Delta)` of the swap callback.
+/// `CALLDATACOPY(0, 4, 64)` then `REVERT(0, 64)`.
+const SWAP_SIMULATOR_CODE: [u8; 12] = [
+    0x60, 0x40, 0x60, 0x04, 0x60, 0x00, 0x37, 0x60, 0x40, 0x60, 0x00, 0xfd,
+];
+
+/// `TickMath.MIN_SQRT_RATIO + 1`, the loosest limit for a zeroForOne swap.
+const MIN_SQRT_RATIO_PLUS_ONE: u64 = 4_295_128_740;
+/// `TickMath.MAX_SQRT_RATIO - 1`, the loosest limit for a oneForZero swap.
+const MAX_SQRT_RATIO_MINUS_ONE: &str = "1461446703485210103287273052203988822378723970341";
+
+/// Pool balance changes of a swap, signed from the pool's point of view:
+/// positive is paid in, negative is paid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapDeltas {
+    pub amount0: ethers::types::I256,
+    pub amount1: ethers::types::I256,
+}
+
+impl SwapDeltas {
+    /// Raw amount the swapper receives in `direction`.
+    pub fn amount_out_raw(&self, direction: SwapDirection) -> f64 {
+        let out = match direction {
+            SwapDirection::Token0ToToken1 => self.amount1,
+            SwapDirection::Token1ToToken0 => self.amount0,
+        };
+        out.unsigned_abs().to_string().parse().unwrap_or(0.0)
+    }
+
+    /// Deviation of the internal math's output from this ground truth, in bps.
+    pub fn amount_out_deviation_bps(
+        &self,
+        direction: SwapDirection,
+        internal: &SwapResult,
+        (decimals0, decimals1): (u8, u8),
+    ) -> f64 {
+        let out_decimals = match direction {
+            SwapDirection::Token0ToToken1 => decimals1,
+            SwapDirection::Token1ToToken0 => decimals0,
+        };
+        let actual = self.amount_out_raw(direction) / 10f64.powi(out_decimals as i32);
+        if actual <= 0.0 {
+            return f64::INFINITY;
+        }
+        (internal.amount_out - actual) / actual * 10_000.0
+    }
+}
+
+/// Decode the `(int256 amount0Delta, int256 amount1Delta)` revert payload of
+/// a simulator callback.
+pub fn decode_swap_revert(data: &[u8]) -> Result<SwapDeltas> {
+    let tokens = ethers::abi::decode(
+        &[
+            ethers::abi::ParamType::Int(256),
+            ethers::abi::ParamType::Int(256),
+        ],
+        data,
+    )
+    .map_err(|e| AppError::Contract(format!("unexpected swap revert data: {}", e)))?;
+    match tokens.as_slice() {
+        [
+            ethers::abi::Token::Int(amount0),
+            ethers::abi::Token::Int(amount1),
+        ] => Ok(SwapDeltas {
+            amount0: ethers::types::I256::from_raw(*amount0),
+            amount1: ethers::types::I256::from_raw(*amount1),
+        }),
+        _ => Err(AppError::Contract(
+            "unexpected swap revert data".to_string(),
+        )),
+    }
+}
+
+/// Convert a Uniswap V3 `fee()` value, in hundredths of a bip, to bps.
+///
+/// A fee of 100% or more cannot come from a real pool and is rejected rather
+/// than silently producing a nonsensical `dex_fee_bps`.
+pub fn fee_pips_to_bps(fee_pips: u32) -> Result<f64> {
+    if fee_pips >= 1_000_000 {
+        return Err(AppError::Config(format!(
+            "pool fee() returned {} pips (>= 100%)",
+            fee_pips
+        )));
+    }
+    Ok(fee_pips as f64 / 100.0)
+}
+
+/// Convert `sqrt_price_x96` to the human price the way the pipeline does
+/// (assuming [`EXPECTED_DECIMALS`]), recompute sqrtPriceX96 from it with the
+/// pool's actual `decimals` and require both to agree within `tolerance_bps`.
+///
+/// Consistent decimals only leave rounding noise; a pool whose decimals differ
+/// from the assumed ones misses by orders of magnitude and fails fast here
+/// instead of mispricing every swap.
+pub fn check_sqrt_price_round_trip(
+    sqrt_price_x96: U256,
+    decimals: (u8, u8),
+    tolerance_bps: f64,
+) -> Result<()> {
+    let (assumed0, assumed1) = EXPECTED_DECIMALS;
+    let price = calculate_human_price_from_sqrt_x96(sqrt_price_x96, assumed0, assumed1);
+    let gap_bps = sqrt_price_round_trip_bps(sqrt_price_x96, price, decimals.0, decimals.1)?;
+    if gap_bps > tolerance_bps {
+        return Err(AppError::Config(format!(
+            "sqrtPriceX96 {} does not round-trip: price {} recomputed with decimals {}/{} is {:.2} bps off (tolerance {} bps); pool decimals likely differ from the assumed {}/{}",
+            sqrt_price_x96,
+            price,
+            decimals.0,
+            decimals.1,
+            gap_bps,
+            tolerance_bps,
+            assumed0,
+            assumed1
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn price_usdc_per_eth(sqrt_price_x96: U256) -> f64 {
     // sqrtPriceX96 = sqrt(token1/token0) * 2^96 where token1/token0 are in nominal units
     // For WETH/USDC: sqrtPriceX96 = sqrt(USDC/WETH) * 2^96 where both are in nominal units
     let s = sqrt_price_x96.to_string();
@@ -139,12 +995,437 @@ fn price_usdc_per_eth(sqrt_price_x96: U256) -> f64 {
     let ratio_raw = sqrt_q96 * sqrt_q96; // token1_raw / token0_raw
 
     // Convert raw ratio to human price (USDC per 1 ETH)
-    (1.0 / ratio_raw) * 10_f64.powi(18 - 6)
+    let (decimals0, decimals1) = EXPECTED_DECIMALS;
+    (1.0 / ratio_raw) * 10_f64.powi(decimals1 as i32 - decimals0 as i32)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dex::calc::calculate_sqrt_price_with_precision_per_eth;
+    use ethers::{
+        abi::{Token, encode},
+        providers::{JsonRpcError, MockProvider, MockResponse},
+        types::{Bytes, U256 as EU256},
+        utils,
+    };
+
+    fn mocked_dex() -> (Dex<Provider<MockProvider>>, MockProvider) {
+        let (provider, mock) = Provider::mocked();
+        let dex = Dex::from_client(Arc::new(provider), Address::repeat_byte(0x11));
+        (dex, mock)
+    }
+
+    fn eth_call_params<D>(call: ContractCall<Provider<MockProvider>, D>) -> [serde_json::Value; 2] {
+        [
+            utils::serialize(&call.tx),
+            utils::serialize(&call.block.expect("call should be pinned")),
+        ]
+    }
+
+    #[tokio::test]
+    async fn pool_state_at_block_pins_every_call() {
+        let (dex, mock) = mocked_dex();
+        let sqrt_price = EU256::from(1u128 << 96) * EU256::from(16_000u64);
+        let slot0 = encode(&[
+            Token::Uint(sqrt_price),
+            Token::Int(EU256::from(193_000u64)),
+            Token::Uint(0.into()),
+            Token::Uint(1.into()),
+            Token::Uint(1.into()),
+            Token::Uint(0.into()),
+            Token::Bool(true),
+        ]);
+        // Responses are served last-in first-out
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Int(10.into())])))
+            .unwrap();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Uint(
+            1_000_000_000_000u64.into(),
+        )])))
+        .unwrap();
+        mock.push::<Bytes, _>(Bytes::from(slot0)).unwrap();
+
+        let block = 19_000_000u64;
+        let state = dex
+            .get_pool_state_at_block(block, 6, 18, None, None)
+            .await
+            .unwrap();
+        assert_eq!(state.liquidity, 1_000_000_000_000);
+        assert_eq!(state.tick, 193_000);
+
+        mock.assert_request("eth_call", eth_call_params(dex.pool.slot_0().block(block)))
+            .unwrap();
+        mock.assert_request(
+            "eth_call",
+            eth_call_params(dex.pool.liquidity().block(block)),
+        )
+        .unwrap();
+        mock.assert_request(
+            "eth_call",
+            eth_call_params(dex.pool.tick_spacing().block(block)),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn token1_quote_pool_is_priced_inverted() {
+        // slot0 of the mainnet WETH/USDT pool (token0 WETH, token1 USDT) at
+        // 3000 USDT per ETH: sqrt(3000 * 1e6 / 1e18) * 2^96
+        let (mut dex, mock) = mocked_dex();
+        dex.quote_side = QuoteSide::Token1;
+        let sqrt_price = EU256::from_dec_str("4339505179874779662909440").unwrap();
+        let slot0 = encode(&[
+            Token::Uint(sqrt_price),
+            Token::Int(EU256::from(-196_257i64 as u64)),
+            Token::Uint(0.into()),
+            Token::Uint(1.into()),
+            Token::Uint(1.into()),
+            Token::Uint(0.into()),
+            Token::Bool(true),
+        ]);
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Int(10.into())])))
+            .unwrap();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Uint(
+            1_000_000_000_000u64.into(),
+        )])))
+        .unwrap();
+        mock.push::<Bytes, _>(Bytes::from(slot0)).unwrap();
+
+        // Decimals are passed quote first, as `token_decimals` returns them
+        let state = dex
+            .get_pool_state_at_block(19_000_000, 6, 18, None, None)
+            .await
+            .unwrap();
+        assert!(
+            (state.price_usdc_per_eth - 3_000.0).abs() < 1e-6,
+            "{}",
+            state.price_usdc_per_eth
+        );
+        assert_eq!((state.token0_decimals, state.token1_decimals), (6, 18));
+        assert_eq!(state.tick, 196_257);
+        // The inverted active range still brackets the inverted price
+        let (lower, upper) = (
+            state.limit_lower_sqrt_price_x96.unwrap(),
+            state.limit_upper_sqrt_price_x96.unwrap(),
+        );
+        assert!(lower <= state.sqrt_price_x96 && state.sqrt_price_x96 <= upper);
+        // A USDC/WETH pool at the same sqrt price would read ~3.3e-4
+        assert!((price_usdc_per_eth(state.sqrt_price_x96) - 3_000.0).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn pool_state_at_block_reports_missing_archive_state() {
+        let (dex, mock) = mocked_dex();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: -32000,
+            message: "missing trie node".to_string(),
+            data: None,
+        }));
+
+        let err = dex
+            .get_pool_state_at_block(1, 6, 18, None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::HistoricalState { block: 1, .. }));
+        assert!(err.to_string().contains("archive"));
+    }
+
+    #[tokio::test]
+    async fn simulated_swap_decodes_revert_deltas() {
+        let (dex, mock) = mocked_dex();
+        // 1000 USDC in, 0.25 WETH out, reverted back by the simulator callback
+        let deltas = encode(&[
+            Token::Int(EU256::from(1_000_000_000u64)),
+            Token::Int(ethers::types::I256::from(-250_000_000_000_000_000i64).into_raw()),
+        ]);
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: Some(serde_json::Value::String(format!(
+                "0x{}",
+                utils::hex::encode(&deltas)
+            ))),
+        }));
+
+        let sim = dex
+            .simulate_swap(SwapDirection::Token0ToToken1, EU256::from(1_000_000_000u64))
+            .await
+            .unwrap();
+        // The simulator's code is supplied with the call, not deployed
+        let (tx, state) = dex
+            .swap_simulation(SwapDirection::Token0ToToken1, EU256::from(1_000_000_000u64))
+            .unwrap();
+        assert_eq!(
+            utils::serialize(&state),
+            serde_json::json!({
+                format!("{:#x}", SWAP_SIMULATOR): { "code": "0x6040600460003760406000fd" }
+            })
+        );
+        mock.assert_request(
+            "eth_call",
+            (utils::serialize(&tx), "latest", utils::serialize(&state)),
+        )
+        .unwrap();
+        assert_eq!(sim.amount0, ethers::types::I256::from(1_000_000_000i64));
+        assert_eq!(
+            sim.amount1,
+            ethers::types::I256::from(-250_000_000_000_000_000i64)
+        );
+        assert_eq!(
+            sim.amount_out_raw(SwapDirection::Token0ToToken1),
+            250_000_000_000_000_000.0
+        );
+
+        let internal = SwapResult {
+            amount_in: 1_000.0,
+            amount_out: 0.2505,
+            hit_boundary: false,
+            avg_price: 1_000.0 / 0.2505,
+            ticks_crossed: 0,
+        };
+        let deviation =
+            sim.amount_out_deviation_bps(SwapDirection::Token0ToToken1, &internal, (6, 18));
+        assert!((deviation - 20.0).abs() < 1e-6, "{deviation}");
+
+        assert!(decode_swap_revert(&[0u8; 12]).is_err());
+    }
+
+    #[tokio::test]
+    async fn simulated_leg_swaps_the_quote_side_and_scales_amounts() {
+        let revert = |amount0: i64, amount1: i64| {
+            let deltas = encode(&[
+                Token::Int(ethers::types::I256::from(amount0).into_raw()),
+                Token::Int(ethers::types::I256::from(amount1).into_raw()),
+            ]);
+            MockResponse::Error(JsonRpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: Some(serde_json::Value::String(format!(
+                    "0x{}",
+                    utils::hex::encode(&deltas)
+                ))),
+            })
+        };
+
+        // USDC as token0: buying ETH pays token0 in
+        let (mut dex, mock) = mocked_dex();
+        mock.push_response(revert(1_000_000_000, -250_000_000_000_000_000));
+        let eth_out = dex.simulate_leg(true, 1_000.0).await.unwrap();
+        assert!((eth_out - 0.25).abs() < 1e-12, "{eth_out}");
+        let (tx, state) = dex
+            .swap_simulation(SwapDirection::Token0ToToken1, EU256::from(1_000_000_000u64))
+            .unwrap();
+        mock.assert_request(
+            "eth_call",
+            (utils::serialize(&tx), "latest", utils::serialize(&state)),
+        )
+        .unwrap();
+
+        // USDC as token1: selling ETH pays token0 in and takes token1 out
+        dex.quote_side = QuoteSide::Token1;
+        mock.push_response(revert(250_000_000_000_000_000, -999_000_000));
+        let usdc_out = dex.simulate_leg(false, 0.25).await.unwrap();
+        assert!((usdc_out - 999.0).abs() < 1e-9, "{usdc_out}");
+        assert!(dex.simulate_leg(false, 0.0).await.is_err());
+    }
+
+    /// Transport that counts requests before handing them to a mock.
+    #[derive(Debug, Clone)]
+    struct CountingClient {
+        inner: MockProvider,
+        calls: Arc<std::sync::atomic::AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl ethers::providers::JsonRpcClient for CountingClient {
+        type Error = <MockProvider as ethers::providers::JsonRpcClient>::Error;
+
+        async fn request<T, R>(
+            &self,
+            method: &str,
+            params: T,
+        ) -> std::result::Result<R, Self::Error>
+        where
+            T: std::fmt::Debug + serde::Serialize + Send + Sync,
+            R: serde::de::DeserializeOwned + Send,
+        {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.request(method, params).await
+        }
+    }
+
+    fn counting_client() -> (
+        Arc<Provider<CountingClient>>,
+        MockProvider,
+        Arc<std::sync::atomic::AtomicUsize>,
+    ) {
+        let mock = MockProvider::new();
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let client = CountingClient {
+            inner: mock.clone(),
+            calls: calls.clone(),
+        };
+        (Arc::new(Provider::new(client)), mock, calls)
+    }
+
+    /// Queue the eight reads of a metadata fetch for a 0.05% pool.
+    fn push_metadata_responses(
+        mock: &MockProvider,
+        token0: (Address, &str, u8),
+        token1: (Address, &str, u8),
+    ) {
+        // Responses are served last-in first-out
+        let responses = [
+            Token::String(token1.1.to_string()),
+            Token::Uint(token1.2.into()),
+            Token::String(token0.1.to_string()),
+            Token::Uint(token0.2.into()),
+            Token::Int(10.into()),
+            Token::Uint(500.into()),
+            Token::Address(token1.0),
+            Token::Address(token0.0),
+        ];
+        for response in responses {
+            mock.push::<Bytes, _>(Bytes::from(encode(&[response])))
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn second_construction_reads_metadata_from_cache() {
+        let path = std::env::temp_dir().join(format!(
+            "arbitrage-detector-metadata-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let pool_addr = Address::repeat_byte(0x11);
+        let usdc = Address::repeat_byte(0xa0);
+        let weth = Address::repeat_byte(0xc0);
+
+        let (client, mock, calls) = counting_client();
+        push_metadata_responses(&mock, (usdc, "USDC", 6), (weth, "WETH", 18));
+        let cache = MetadataCache::open(&path).unwrap();
+        let first = Dex::connect(client, pool_addr, 1, Some(&cache))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 8);
+        assert_eq!(first.token_decimals(), (6, 18));
+        assert_eq!(first.metadata().unwrap().token1.symbol, "WETH");
+
+        // A fresh process reopens the file; no responses are queued, so any RPC would fail
+        let (client, _mock, calls) = counting_client();
+        let cache = MetadataCache::open(&path).unwrap();
+        let second = Dex::connect(client, pool_addr, 1, Some(&cache))
+            .await
+            .unwrap();
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 0);
+        assert_eq!(second.metadata(), first.metadata());
+
+        // Entries are per chain, and invalidating a chain forgets them
+        assert!(cache.pool(42_161, pool_addr).is_none());
+        cache.invalidate_chain(1).unwrap();
+        assert!(
+            MetadataCache::open(&path)
+                .unwrap()
+                .pool(1, pool_addr)
+                .is_none()
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn same_pool_address_on_two_chains_is_two_pools() {
+        let path = std::env::temp_dir().join(format!(
+            "arbitrage-detector-multichain-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let cache = MetadataCache::open(&path).unwrap();
+        let pool_addr = Address::repeat_byte(0x22);
+
+        let (client, mock, _) = counting_client();
+        push_metadata_responses(
+            &mock,
+            (Address::repeat_byte(0xa0), "USDC", 6),
+            (Address::repeat_byte(0xc0), "WETH", 18),
+        );
+        let mainnet = Dex::connect(client, pool_addr, 1, Some(&cache))
+            .await
+            .unwrap();
+
+        // Same pool address on Arbitrum, with different tokens behind it
+        let (client, mock, calls) = counting_client();
+        push_metadata_responses(
+            &mock,
+            (Address::repeat_byte(0xa1), "USDC.e", 6),
+            (Address::repeat_byte(0xc1), "WETH", 18),
+        );
+        let arbitrum = Dex::connect(client, pool_addr, 42_161, Some(&cache))
+            .await
+            .unwrap();
+
+        // The mainnet cache entry must not satisfy the Arbitrum lookup
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 8);
+        assert_ne!(mainnet.key(), arbitrum.key());
+        assert_eq!(mainnet.metadata().unwrap().token0.symbol, "USDC");
+        assert_eq!(arbitrum.metadata().unwrap().token0.symbol, "USDC.e");
+        assert_eq!(cache.pool(1, pool_addr).unwrap().chain_id, 1);
+        assert_eq!(cache.pool(42_161, pool_addr).unwrap().chain_id, 42_161);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn sqrt_price_round_trip_rejects_wrong_decimals() {
+        let sqrt_price_x96 = calculate_sqrt_price_with_precision_per_eth(4_000.0, 6, 18).unwrap();
+        assert!(check_sqrt_price_round_trip(sqrt_price_x96, (6, 18), 1.0).is_ok());
+
+        // An 18/18 pool priced as if it were USDC/WETH is off by 10^6 in price
+        let err = check_sqrt_price_round_trip(sqrt_price_x96, (18, 18), 1.0).unwrap_err();
+        assert!(err.to_string().contains("does not round-trip"), "{err}");
+        assert!(check_sqrt_price_round_trip(sqrt_price_x96, (6, 6), 1.0).is_err());
+    }
+
+    #[test]
+    fn bitmap_word_decodes_to_initialized_ticks() {
+        // Word -1 covers compressed ticks -256..=-1: bits 0, 5 and 255 set
+        let bitmap = (EU256::one() << 255) | (EU256::one() << 5) | EU256::one();
+        assert_eq!(
+            initialized_ticks_in_word(-1, bitmap, 10),
+            vec![-2_560, -2_510, -10]
+        );
+        for tick in [-2_560, -2_510, -10] {
+            let (word, bit) = bitmap_position(tick, 10);
+            assert_eq!(word, -1);
+            assert!(bitmap.bit(bit as usize));
+        }
+
+        // Word 13 of a 60-spacing pool, as `tickBitmap(13)` returns it: bits 4, 130, 250
+        let bitmap = EU256::from_dec_str(
+            "1809251394333065553493296640760748561568472978084387666970023179850715496464",
+        )
+        .unwrap();
+        let ticks = initialized_ticks_in_word(13, bitmap, 60);
+        assert_eq!(ticks, vec![199_920, 207_480, 214_680]);
+        assert!(ticks.iter().all(|&tick| bitmap_position(tick, 60).0 == 13));
+        assert!(initialized_ticks_in_word(13, EU256::zero(), 60).is_empty());
+    }
+
+    #[test]
+    fn fee_pips_convert_to_bps() {
+        assert_eq!(fee_pips_to_bps(100).unwrap(), 1.0);
+        assert_eq!(fee_pips_to_bps(500).unwrap(), 5.0);
+        assert_eq!(fee_pips_to_bps(3000).unwrap(), 30.0);
+        assert_eq!(fee_pips_to_bps(10_000).unwrap(), 100.0);
+        assert!(fee_pips_to_bps(1_000_000).is_err());
+    }
+
+    #[tokio::test]
+    async fn pool_fee_is_reported_in_bps() {
+        let (dex, mock) = mocked_dex();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Uint(3000.into())])))
+            .unwrap();
+        assert_eq!(dex.get_pool_fee_bps().await.unwrap(), 30.0);
+    }
 
     #[test]
     fn price_zero_when_sqrt_is_zero() {
@@ -163,4 +1444,93 @@ mod tests {
         assert!(p_small >= 0.0);
         assert!(p_large >= 0.0);
     }
+
+    /// Queue the three reads of a pool state with price `sqrt_price`.
+    fn push_pool_state(mock: &MockProvider, sqrt_price: EU256) {
+        let slot0 = encode(&[
+            Token::Uint(sqrt_price),
+            Token::Int(EU256::from(193_000u64)),
+            Token::Uint(0.into()),
+            Token::Uint(1.into()),
+            Token::Uint(1.into()),
+            Token::Uint(0.into()),
+            Token::Bool(true),
+        ]);
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Int(10.into())])))
+            .unwrap();
+        mock.push::<Bytes, _>(Bytes::from(encode(&[Token::Uint(
+            1_000_000_000_000u64.into(),
+        )])))
+        .unwrap();
+        mock.push::<Bytes, _>(Bytes::from(slot0)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn silent_subscription_forces_a_resync_and_resubscribe() {
+        let (dex, mock) = mocked_dex();
+        let quoted = EU256::from(1u128 << 96) * EU256::from(16_000u64);
+        let moved = EU256::from(1u128 << 96) * EU256::from(16_100u64);
+        // Served last pushed first: block 7, a forced read showing a quiet
+        // pool, then one showing it moved while no head arrived
+        push_pool_state(&mock, moved);
+        push_pool_state(&mock, quoted);
+        push_pool_state(&mock, quoted);
+
+        let initial = PoolState::new(U256::ZERO, 0, 0, 6, 18, None, None, 0.0);
+        let (pool_tx, pool_rx) = watch::channel(initial);
+        let (block_tx, mut block_rx) = watch::channel(0u64);
+        // One head, then the subscription goes silent without closing
+        let heads = futures::stream::iter([7u64]).chain(futures::stream::pending());
+        let silence = std::time::Duration::from_millis(20);
+        let end = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            follow_heads(&dex, heads, &pool_tx, &block_tx, Some(silence)),
+        )
+        .await
+        .expect("a silent subscription is given up");
+        assert_eq!(end, HeadsEnd::Stale);
+
+        // The forced read reached consumers, waking the block trigger
+        let expected = U256::from_str_radix(&moved.to_string(), 10).unwrap();
+        assert_eq!(pool_rx.borrow().sqrt_price_x96, expected);
+        assert!(block_rx.has_changed().unwrap());
+        assert_eq!(*block_rx.borrow_and_update(), 7);
+        mock.assert_request("eth_call", eth_call_params(dex.pool.slot_0().block(7u64)))
+            .unwrap();
+
+        // A closed stream is reported as such, and no silence limit waits forever
+        let closed = follow_heads(&dex, futures::stream::empty(), &pool_tx, &block_tx, None).await;
+        assert_eq!(closed, HeadsEnd::Closed);
+    }
+
+    #[tokio::test]
+    async fn twap_is_read_from_the_pool_oracle() {
+        let (dex, mock) = mocked_dex();
+        // Ten minutes at tick 193_000 on average, i.e. ~4160 USDC per ETH
+        let window = 600u32;
+        let ago = -50_000_000i64;
+        let now = ago + 193_000 * i64::from(window);
+        let signed = |value: i64| Token::Int(ethers::types::I256::from(value).into_raw());
+        mock.push::<Bytes, _>(Bytes::from(encode(&[
+            Token::Array(vec![signed(ago), signed(now)]),
+            Token::Array(vec![Token::Uint(0.into()), Token::Uint(0.into())]),
+        ])))
+        .unwrap();
+
+        let twap = dex.fetch_twap_price(window).await.unwrap();
+        let expected = price_usdc_per_eth(approx_sqrt_price_x96_at_tick(193_000));
+        assert_eq!(twap, expected);
+        assert!((4_000.0..4_300.0).contains(&twap), "{twap}");
+
+        // Partial ticks round down like OracleLibrary, negative ones included
+        assert_eq!(mean_tick(0, 1_199, 600), 1);
+        assert_eq!(mean_tick(0, -1, 600), -1);
+        assert_eq!(mean_tick(100, -1_100, 600), -2);
+
+        // The divergence gate compares spot against it
+        let spot = twap * 1.006;
+        let divergence = crate::oracle::twap_divergence_bps(spot, twap).unwrap();
+        assert!((divergence - 60.0).abs() < 1e-6);
+        assert_eq!(crate::oracle::twap_divergence_bps(spot, 0.0), None);
+    }
 }
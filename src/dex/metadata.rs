@@ -0,0 +1,274 @@
+//! Immutable pool and token metadata, with an optional on-disk cache.
+//!
+//! Decimals, symbols, fee tier, tick spacing and token ordering never change
+//! for a deployed pool, so they are fetched once per chain and reused across
+//! runs.
+
+use crate::errors::{AppError, Result};
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::warn;
+
+/// Decimals of (quote, base) the pricing math assumes: a 6-decimal quote
+/// token (USDC, USDT) against the 18-decimal base token (WETH).
+pub const EXPECTED_DECIMALS: (u8, u8) = (6, 18);
+
+/// Which of the pool's tokens is the quote token.
+///
+/// The pricing math treats token0 as the quote; a pool with the quote as
+/// token1 (e.g. mainnet WETH/USDT) is priced as its mirror image, see
+/// [`PoolState::invert_tokens`](crate::dex::PoolState::invert_tokens).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuoteSide {
+    /// token0 is the quote, as in USDC/WETH
+    #[default]
+    Token0,
+    /// token1 is the quote, as in WETH/USDT
+    Token1,
+}
+
+/// ERC-20 metadata needed for pricing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// Immutable parameters of a Uniswap V3 pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolMetadata {
+    pub chain_id: u64,
+    pub token0: TokenMetadata,
+    pub token1: TokenMetadata,
+    /// Fee tier in hundredths of a basis point (500 = 0.05%)
+    pub fee: u32,
+    pub tick_spacing: i32,
+}
+
+impl PoolMetadata {
+    /// Fee tier in basis points (500 → 5 bps).
+    pub fn fee_bps(&self) -> f64 {
+        self.fee as f64 / 100.0
+    }
+
+    /// Startup invariant: the pool pairs a quote and a base token with the
+    /// decimals the pricing math assumes ([`EXPECTED_DECIMALS`]), and the
+    /// quote is `quote_token` when given; returns which side the quote is on.
+    ///
+    /// Without `quote_token` the side is told from the decimals. A violation
+    /// would not fail later, it would silently misprice every swap, so it is
+    /// reported as a config error naming both tokens.
+    pub fn check_orientation(&self, quote_token: Option<Address>) -> Result<QuoteSide> {
+        let (token0, token1) = (&self.token0, &self.token1);
+        let pair = format!(
+            "token0 {} ({:?}, {} decimals), token1 {} ({:?}, {} decimals)",
+            token0.symbol,
+            token0.address,
+            token0.decimals,
+            token1.symbol,
+            token1.address,
+            token1.decimals
+        );
+        let (expected_quote, expected_base) = EXPECTED_DECIMALS;
+        let side = match quote_token {
+            Some(quote) if token0.address == quote => QuoteSide::Token0,
+            Some(quote) if token1.address == quote => QuoteSide::Token1,
+            Some(quote) => {
+                return Err(AppError::Config(format!(
+                    "pool on chain {} does not contain the configured quote token (quote {:?}; {})",
+                    self.chain_id, quote, pair
+                )));
+            }
+            None if (token0.decimals, token1.decimals) == (expected_base, expected_quote) => {
+                QuoteSide::Token1
+            }
+            None => QuoteSide::Token0,
+        };
+        let (quote, base) = match side {
+            QuoteSide::Token0 => (token0, token1),
+            QuoteSide::Token1 => (token1, token0),
+        };
+        if (quote.decimals, base.decimals) != EXPECTED_DECIMALS {
+            return Err(AppError::Config(format!(
+                "pool on chain {} has quote {} with {} decimals and base {} with {}, pricing expects ({}, {}); {}",
+                self.chain_id,
+                quote.symbol,
+                quote.decimals,
+                base.symbol,
+                base.decimals,
+                expected_quote,
+                expected_base,
+                pair
+            )));
+        }
+        Ok(side)
+    }
+
+    /// The quote token, on `side`.
+    pub fn quote_token(&self, side: QuoteSide) -> &TokenMetadata {
+        match side {
+            QuoteSide::Token0 => &self.token0,
+            QuoteSide::Token1 => &self.token1,
+        }
+    }
+
+    /// The base token, opposite the quote on `side`.
+    pub fn base_token(&self, side: QuoteSide) -> &TokenMetadata {
+        match side {
+            QuoteSide::Token0 => &self.token1,
+            QuoteSide::Token1 => &self.token0,
+        }
+    }
+}
+
+/// Cached entries of a single chain.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct ChainEntries {
+    pools: BTreeMap<Address, PoolMetadata>,
+    tokens: BTreeMap<Address, TokenMetadata>,
+}
+
+/// JSON file cache of pool/token metadata keyed by chain id and address.
+///
+/// Entries of one chain never answer lookups for another, so the same address
+/// deployed on two chains stays distinct.
+#[derive(Debug)]
+pub struct MetadataCache {
+    path: PathBuf,
+    chains: Mutex<BTreeMap<u64, ChainEntries>>,
+}
+
+impl MetadataCache {
+    /// Load the cache at `path`; a missing or unreadable file starts empty.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let chains = match std::fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                warn!(error = %e, path = %path.display(), "[CACHE] ignoring corrupt metadata cache");
+                BTreeMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(AppError::Io(e)),
+        };
+        Ok(Self {
+            path,
+            chains: Mutex::new(chains),
+        })
+    }
+
+    pub fn pool(&self, chain_id: u64, pool: Address) -> Option<PoolMetadata> {
+        self.chains()
+            .get(&chain_id)
+            .and_then(|entries| entries.pools.get(&pool).cloned())
+    }
+
+    pub fn token(&self, chain_id: u64, token: Address) -> Option<TokenMetadata> {
+        self.chains()
+            .get(&chain_id)
+            .and_then(|entries| entries.tokens.get(&token).cloned())
+    }
+
+    /// Record a pool (and both of its tokens) and persist the cache.
+    pub fn insert_pool(&self, pool: Address, metadata: &PoolMetadata) -> Result<()> {
+        let mut chains = self.chains();
+        let entries = chains.entry(metadata.chain_id).or_default();
+        for token in [&metadata.token0, &metadata.token1] {
+            entries.tokens.insert(token.address, token.clone());
+        }
+        entries.pools.insert(pool, metadata.clone());
+        self.persist(&chains)
+    }
+
+    /// Drop every cached entry of `chain_id`.
+    pub fn invalidate_chain(&self, chain_id: u64) -> Result<()> {
+        let mut chains = self.chains();
+        if chains.remove(&chain_id).is_some() {
+            self.persist(&chains)?;
+        }
+        Ok(())
+    }
+
+    fn chains(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, ChainEntries>> {
+        self.chains
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn persist(&self, chains: &BTreeMap<u64, ChainEntries>) -> Result<()> {
+        std::fs::write(&self.path, serde_json::to_string_pretty(chains)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(byte: u8, symbol: &str, decimals: u8) -> TokenMetadata {
+        TokenMetadata {
+            address: Address::repeat_byte(byte),
+            symbol: symbol.to_string(),
+            decimals,
+        }
+    }
+
+    fn pool(token0: TokenMetadata, token1: TokenMetadata) -> PoolMetadata {
+        PoolMetadata {
+            chain_id: 1,
+            token0,
+            token1,
+            fee: 500,
+            tick_spacing: 10,
+        }
+    }
+
+    #[test]
+    fn usdc_weth_pool_passes_orientation_check() {
+        let usdc = token(0xaa, "USDC", 6);
+        let meta = pool(usdc.clone(), token(0xbb, "WETH", 18));
+        assert_eq!(meta.check_orientation(None).unwrap(), QuoteSide::Token0);
+        assert_eq!(
+            meta.check_orientation(Some(usdc.address)).unwrap(),
+            QuoteSide::Token0
+        );
+    }
+
+    #[test]
+    fn weth_usdt_pool_quotes_in_token1() {
+        // Mainnet WETH/USDT: WETH sorts first
+        let usdt = token(0xdd, "USDT", 6);
+        let meta = pool(token(0xbb, "WETH", 18), usdt.clone());
+        assert_eq!(
+            meta.check_orientation(Some(usdt.address)).unwrap(),
+            QuoteSide::Token1
+        );
+        // Without a configured quote token the decimals tell the side
+        assert_eq!(meta.check_orientation(None).unwrap(), QuoteSide::Token1);
+        assert_eq!(meta.quote_token(QuoteSide::Token1).symbol, "USDT");
+    }
+
+    #[test]
+    fn mismatched_pool_fails_fast() {
+        let usdc = token(0xaa, "USDC", 6);
+        let weth = token(0xbb, "WETH", 18);
+
+        let other = pool(token(0xcc, "DAI", 18), weth.clone());
+        let err = other.check_orientation(Some(usdc.address)).unwrap_err();
+        assert!(err.to_string().contains("does not contain"), "{err}");
+        let err = other.check_orientation(None).unwrap_err();
+        assert!(err.to_string().contains("pricing expects (6, 18)"), "{err}");
+
+        // The configured quote token is the 18-decimal one
+        let err = pool(usdc.clone(), weth.clone())
+            .check_orientation(Some(weth.address))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("quote WETH with 18 decimals"),
+            "{err}"
+        );
+    }
+}
@@ -1,9 +1,22 @@
 //! DEX integration for Uniswap V3 pools.
 
 pub mod calc;
-pub mod state;
 pub mod client;
+pub mod metadata;
+pub mod route;
+pub mod split;
+pub mod state;
 
-pub use calc::calculate_swap_with_library;
-pub use state::PoolState;
-pub use client::{Dex, init_pool_state_watcher};
+pub use calc::{
+    FeeFn, FeeModel, SwapOptions, calculate_swap_with_library, calculate_swap_with_options,
+    fill_eth, solve_for_size,
+};
+pub use client::{
+    Dex, HeadsEnd, SwapDeltas, check_sqrt_price_round_trip, decode_swap_revert, follow_heads,
+    init_pool_state_watcher, mean_tick, spawn_block_pool_watcher, spawn_token_price_watcher,
+    spawn_twap_watcher,
+};
+pub use metadata::{EXPECTED_DECIMALS, MetadataCache, PoolMetadata, QuoteSide, TokenMetadata};
+pub use route::{Route, RouteHop, RouteQuote};
+pub use split::{SplitQuote, split_at_price, split_swap};
+pub use state::{PoolState, PriceSegment};
@@ -0,0 +1,318 @@
+//! Multi-hop DEX routes between the base and the quote asset.
+//!
+//! Without a direct pool the base can still be reached through an
+//! intermediate asset (e.g. ETH → USDT → USDC). A [`Route`] is the ordered
+//! list of pools the trade crosses; each hop's output is the next hop's input,
+//! so fees and price impact compound along the way.
+
+use crate::dex::calc::{
+    SwapOptions, calculate_human_price_from_sqrt_x96, calculate_swap_with_options,
+};
+use crate::dex::state::PoolState;
+use crate::errors::SwapMathError;
+use crate::models::{SwapDirection, SwapResult};
+
+/// Target-price doublings tried before a hop is treated as unfillable
+const MAX_BRACKET_STEPS: usize = 64;
+/// Bisection steps solving a hop's target price for an exact input
+const MAX_BISECTION_STEPS: usize = 100;
+
+/// One pool of a route and the side it is swapped through.
+#[derive(Debug, Clone)]
+pub struct RouteHop {
+    pub pool: PoolState,
+    pub direction: SwapDirection,
+    /// Fee tier of the pool, resolved through `SwapOptions::fee_model`
+    pub fee_bps: f64,
+}
+
+impl RouteHop {
+    /// Output per unit of input at the current price, before fees.
+    pub fn spot_rate(&self) -> f64 {
+        let price = calculate_human_price_from_sqrt_x96(
+            self.pool.sqrt_price_x96,
+            self.pool.token0_decimals,
+            self.pool.token1_decimals,
+        );
+        match self.direction {
+            SwapDirection::Token0ToToken1 => 1.0 / price,
+            SwapDirection::Token1ToToken0 => price,
+        }
+    }
+
+    /// Swap exactly `amount_in` (human units, fee included) through this pool.
+    ///
+    /// The swap math solves for a target price, so the target whose fill
+    /// uses `amount_in` is bracketed and bisected. When the loaded liquidity
+    /// runs out first the fill stops there with `hit_boundary` set.
+    pub fn swap_exact_in(
+        &self,
+        amount_in: f64,
+        options: &SwapOptions,
+    ) -> Result<SwapResult, SwapMathError> {
+        let swap = |target: f64, max_amount: f64| {
+            calculate_swap_with_options(
+                &self.pool,
+                target,
+                self.direction,
+                self.fee_bps,
+                max_amount,
+                options,
+            )
+        };
+        // Token0 in raises the price, token1 in lowers it
+        let step = match self.direction {
+            SwapDirection::Token0ToToken1 => 2.0,
+            SwapDirection::Token1ToToken0 => 0.5,
+        };
+        let mut near = calculate_human_price_from_sqrt_x96(
+            self.pool.sqrt_price_x96,
+            self.pool.token0_decimals,
+            self.pool.token1_decimals,
+        );
+        let mut far = near * step;
+        for _ in 0..MAX_BRACKET_STEPS {
+            let res = swap(far, f64::INFINITY)?;
+            if res.amount_in >= amount_in {
+                break;
+            }
+            if res.hit_boundary {
+                return Ok(res);
+            }
+            near = far;
+            far *= step;
+        }
+        for _ in 0..MAX_BISECTION_STEPS {
+            let mid = (near * far).sqrt();
+            if mid == near || mid == far {
+                break;
+            }
+            if swap(mid, f64::INFINITY)?.amount_in >= amount_in {
+                far = mid;
+            } else {
+                near = mid;
+            }
+        }
+        swap(far, amount_in)
+    }
+}
+
+/// Ordered pools a trade crosses, input of the first to output of the last.
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub hops: Vec<RouteHop>,
+}
+
+/// Composed fill of a route.
+#[derive(Debug, Clone)]
+pub struct RouteQuote {
+    /// Input of the first hop actually swapped
+    pub amount_in: f64,
+    /// Output of the last hop
+    pub amount_out: f64,
+    /// Fill of each hop, in its own input and output tokens
+    pub fills: Vec<SwapResult>,
+    /// LP fee paid on each hop, in that hop's input token
+    pub fees: Vec<f64>,
+    /// Compounded LP fee of all hops, in bps of the routed value
+    pub fee_bps: f64,
+    /// Shortfall of the fill against the spot rate after fees, in bps
+    pub impact_bps: f64,
+    /// Some hop ran out of loaded liquidity before using its whole input
+    pub hit_boundary: bool,
+}
+
+impl RouteQuote {
+    /// Initialized ticks crossed on all hops, which gas is charged for.
+    pub fn ticks_crossed(&self) -> usize {
+        self.fills.iter().map(|fill| fill.ticks_crossed).sum()
+    }
+}
+
+impl Route {
+    pub fn new(hops: Vec<RouteHop>) -> Self {
+        Self { hops }
+    }
+
+    /// The same pools crossed the other way, from the last hop's output
+    /// token back to the first hop's input token.
+    pub fn reversed(&self) -> Self {
+        let hops = self
+            .hops
+            .iter()
+            .rev()
+            .map(|hop| RouteHop {
+                direction: match hop.direction {
+                    SwapDirection::Token0ToToken1 => SwapDirection::Token1ToToken0,
+                    SwapDirection::Token1ToToken0 => SwapDirection::Token0ToToken1,
+                },
+                ..hop.clone()
+            })
+            .collect();
+        Self { hops }
+    }
+
+    /// Route exactly `amount_in` of the first hop's input token, feeding
+    /// each hop's output into the next.
+    pub fn quote_exact_in(
+        &self,
+        amount_in: f64,
+        options: &SwapOptions,
+    ) -> Result<RouteQuote, SwapMathError> {
+        if self.hops.is_empty() {
+            return Err(SwapMathError::EmptyRoute);
+        }
+        let mut fills = Vec::with_capacity(self.hops.len());
+        let mut fees = Vec::with_capacity(self.hops.len());
+        let mut keep_after_fees = 1.0;
+        let mut spot_rate = 1.0;
+        let mut amount = amount_in;
+        for hop in &self.hops {
+            let fee_bps = options
+                .fee_model
+                .fee_bps(&hop.pool, hop.direction, hop.fee_bps);
+            let fill = hop.swap_exact_in(amount, options)?;
+            fees.push(fill.amount_in * fee_bps / 10_000.0);
+            keep_after_fees *= 1.0 - fee_bps / 10_000.0;
+            spot_rate *= hop.spot_rate();
+            amount = fill.amount_out;
+            fills.push(fill);
+        }
+
+        // A hop short of liquidity leaves part of the previous output unrouted
+        let hit_boundary = fills.iter().any(|fill| fill.hit_boundary);
+        let routed_in = fills.iter().zip(fills.iter().skip(1)).fold(
+            fills[0].amount_in,
+            |routed, (prev, next)| {
+                if prev.amount_out > 0.0 {
+                    routed * (next.amount_in / prev.amount_out).min(1.0)
+                } else {
+                    0.0
+                }
+            },
+        );
+        let expected_out = routed_in * spot_rate * keep_after_fees;
+        let impact_bps = if expected_out > 0.0 {
+            (1.0 - amount / expected_out) * 10_000.0
+        } else {
+            0.0
+        };
+        Ok(RouteQuote {
+            amount_in: routed_in,
+            amount_out: amount,
+            fills,
+            fees,
+            fee_bps: (1.0 - keep_after_fees) * 10_000.0,
+            impact_bps,
+            hit_boundary,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::calc::calculate_sqrt_price_with_precision_per_eth;
+
+    fn pool(price: f64, liquidity: u128, token0_decimals: u8, token1_decimals: u8) -> PoolState {
+        let sqrt_price_x96 =
+            calculate_sqrt_price_with_precision_per_eth(price, token0_decimals, token1_decimals)
+                .unwrap();
+        PoolState::new(
+            sqrt_price_x96,
+            liquidity,
+            0,
+            token0_decimals,
+            token1_decimals,
+            None,
+            None,
+            price,
+        )
+    }
+
+    fn sqrt_price(pool: &PoolState) -> f64 {
+        pool.sqrt_price_x96.to_string().parse::<f64>().unwrap() / 2f64.powi(96)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        let tolerance = expected.abs() * 1e-8;
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn two_hop_route_composes_amounts_fees_and_impact() {
+        // ETH/USDT (token0 USDT, 5 bps) and USDT/USDC (token0 USDC, 1 bp)
+        let eth_usdt = pool(4_000.0, 1_800_000_000_000_000_000, 6, 18);
+        let usdt_usdc = pool(1.0, 10_000_000_000_000, 6, 6);
+        let (f1, f2) = (5.0 / 10_000.0, 1.0 / 10_000.0);
+        let options = SwapOptions::default();
+
+        // ETH → USDT → USDC: token1 in on both pools, raising sqrtP by
+        // net_in / L and paying out L × (1/s0 − 1/s1) of token0
+        let sell = Route::new(vec![
+            RouteHop {
+                pool: eth_usdt.clone(),
+                direction: SwapDirection::Token1ToToken0,
+                fee_bps: 5.0,
+            },
+            RouteHop {
+                pool: usdt_usdc.clone(),
+                direction: SwapDirection::Token1ToToken0,
+                fee_bps: 1.0,
+            },
+        ]);
+        let token1_in = |pool: &PoolState, raw_in: f64, fee: f64| {
+            let (l, s0) = (pool.liquidity as f64, sqrt_price(pool));
+            let s1 = s0 + raw_in * (1.0 - fee) / l;
+            l * (1.0 / s0 - 1.0 / s1)
+        };
+        let usdt = token1_in(&eth_usdt, 1e18, f1) / 1e6;
+        let usdc = token1_in(&usdt_usdc, usdt * 1e6, f2) / 1e6;
+        let quote = sell.quote_exact_in(1.0, &options).unwrap();
+        assert_close(quote.amount_in, 1.0);
+        assert_close(quote.fills[0].amount_out, usdt);
+        assert_close(quote.amount_out, usdc);
+        assert_close(quote.fees[0], f1);
+        assert_close(quote.fees[1], usdt * f2);
+        assert_close(quote.fee_bps, (1.0 - (1.0 - f1) * (1.0 - f2)) * 10_000.0);
+        let spot = 1e12 / sqrt_price(&eth_usdt).powi(2) / sqrt_price(&usdt_usdc).powi(2);
+        let expected_impact = (1.0 - usdc / (spot * (1.0 - f1) * (1.0 - f2))) * 10_000.0;
+        assert!((quote.impact_bps - expected_impact).abs() < 1e-4);
+        assert!(quote.impact_bps > 0.0 && !quote.hit_boundary);
+
+        // USDC → USDT → ETH: token0 in, raising 1/sqrtP by net_in / L and
+        // paying out L × (s0 − s1) of token1
+        let buy = Route::new(vec![
+            RouteHop {
+                pool: usdt_usdc.clone(),
+                direction: SwapDirection::Token0ToToken1,
+                fee_bps: 1.0,
+            },
+            RouteHop {
+                pool: eth_usdt.clone(),
+                direction: SwapDirection::Token0ToToken1,
+                fee_bps: 5.0,
+            },
+        ]);
+        let token0_in = |pool: &PoolState, raw_in: f64, fee: f64| {
+            let (l, s0) = (pool.liquidity as f64, sqrt_price(pool));
+            let s1 = 1.0 / (1.0 / s0 + raw_in * (1.0 - fee) / l);
+            l * (s0 - s1)
+        };
+        let usdt = token0_in(&usdt_usdc, 4_000e6, f2) / 1e6;
+        let eth = token0_in(&eth_usdt, usdt * 1e6, f1) / 1e18;
+        let quote = buy.quote_exact_in(4_000.0, &options).unwrap();
+        assert_close(quote.fills[0].amount_out, usdt);
+        assert_close(quote.amount_out, eth);
+        assert_close(quote.fees[0], 4_000.0 * f2);
+        assert_close(quote.fees[1], usdt * f1);
+
+        assert!(matches!(
+            Route::new(Vec::new()).quote_exact_in(1.0, &options),
+            Err(SwapMathError::EmptyRoute)
+        ));
+    }
+}
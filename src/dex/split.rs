@@ -0,0 +1,166 @@
+//! Splitting one swap across several pools of the same pair.
+//!
+//! A large trade moves a single pool far; spread over several pools of the
+//! pair it moves each less. The cheapest split walks every pool to the same
+//! marginal price (LP fee included): while one pool is cheaper at the margin,
+//! shifting size to it lowers the total cost. This is the DEX-side analog of
+//! a consolidated CEX book.
+
+use crate::dex::calc::{SwapOptions, calculate_swap_with_options, fill_eth, solve_for_size};
+use crate::dex::state::PoolState;
+use crate::errors::SwapMathError;
+use crate::models::{SwapDirection, SwapResult};
+
+/// Composed fill of a swap split across pools.
+#[derive(Debug, Clone)]
+pub struct SplitQuote {
+    /// Fill of each pool, in the order the pools were given; pools whose
+    /// marginal price is past the common one get an empty fill
+    pub fills: Vec<SwapResult>,
+    /// Input across all pools (USDC buying ETH, ETH selling it)
+    pub amount_in: f64,
+    /// Output across all pools
+    pub amount_out: f64,
+    /// ETH traded across all pools
+    pub size_eth: f64,
+    /// Marginal price in USDC per ETH, LP fee included, every filled pool is
+    /// walked to
+    pub marginal_price: f64,
+    /// Some pool ran out of loaded liquidity before the common price
+    pub hit_boundary: bool,
+}
+
+impl SplitQuote {
+    /// ETH traded by each pool, in the order the pools were given.
+    pub fn allocation_eth(&self, direction: SwapDirection) -> Vec<f64> {
+        self.fills
+            .iter()
+            .map(|fill| fill_eth(direction, fill))
+            .collect()
+    }
+}
+
+/// Walk every pool in `direction` to the marginal price `target_price` (in
+/// USDC per ETH, LP fee included), uncapped.
+pub fn split_at_price(
+    pools: &[PoolState],
+    target_price: f64,
+    direction: SwapDirection,
+    fee_bps: f64,
+    options: &SwapOptions,
+) -> Result<SplitQuote, SwapMathError> {
+    if pools.is_empty() {
+        return Err(SwapMathError::NoPools);
+    }
+    let fills = pools
+        .iter()
+        .map(|pool| {
+            calculate_swap_with_options(
+                pool,
+                target_price,
+                direction,
+                fee_bps,
+                f64::INFINITY,
+                options,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(SplitQuote {
+        amount_in: fills.iter().map(|fill| fill.amount_in).sum(),
+        amount_out: fills.iter().map(|fill| fill.amount_out).sum(),
+        size_eth: fills.iter().map(|fill| fill_eth(direction, fill)).sum(),
+        marginal_price: target_price,
+        hit_boundary: fills.iter().any(|fill| fill.hit_boundary),
+        fills,
+    })
+}
+
+/// Trade `size_eth` in `direction` across `pools` at the least total cost,
+/// by bisecting the common marginal price the pools are walked to.
+///
+/// When the loaded liquidity of all pools runs out first, the split stops
+/// there with `hit_boundary` set.
+pub fn split_swap(
+    pools: &[PoolState],
+    direction: SwapDirection,
+    fee_bps: f64,
+    size_eth: f64,
+    options: &SwapOptions,
+) -> Result<SplitQuote, SwapMathError> {
+    if pools.is_empty() {
+        return Err(SwapMathError::NoPools);
+    }
+    let prices = pools.iter().map(|pool| pool.price_usdc_per_eth);
+    // Buying ETH walks the price up from the cheapest pool, selling walks it
+    // down from the dearest
+    let start = match direction {
+        SwapDirection::Token0ToToken1 => prices.fold(f64::INFINITY, f64::min),
+        SwapDirection::Token1ToToken0 => prices.fold(0.0, f64::max),
+    };
+    solve_for_size(
+        start,
+        direction,
+        size_eth,
+        |target| split_at_price(pools, target, direction, fee_bps, options),
+        |quote| (quote.size_eth, quote.hit_boundary),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::calc::calculate_sqrt_price_with_precision_per_eth;
+
+    fn pool(price: f64, liquidity: u128) -> PoolState {
+        let sqrt_price_x96 = calculate_sqrt_price_with_precision_per_eth(price, 6, 18).unwrap();
+        PoolState::new(sqrt_price_x96, liquidity, 0, 6, 18, None, None, price)
+    }
+
+    #[test]
+    fn split_equalizes_marginal_prices_and_beats_either_pool() {
+        // The shallow pool quotes ETH cheaper, the deep one holds 4x the liquidity
+        let pools = vec![
+            pool(4_000.0, 450_000_000_000_000_000),
+            pool(4_002.0, 1_800_000_000_000_000_000),
+        ];
+        let options = SwapOptions::default();
+        let buy = SwapDirection::Token0ToToken1;
+        let size = 20.0;
+        let split = split_swap(&pools, buy, 5.0, size, &options).unwrap();
+        assert!((split.size_eth - size).abs() < size * 1e-9);
+        let allocation = split.allocation_eth(buy);
+        assert!(allocation.iter().all(|eth| *eth > 0.0));
+        assert!(allocation[1] > allocation[0]);
+
+        // The cost of one more sliver of ETH is the same in either pool
+        let marginal = |index: usize| {
+            let alone = std::slice::from_ref(&pools[index]);
+            let sliver = 1e-4;
+            let more = split_swap(alone, buy, 5.0, allocation[index] + sliver, &options).unwrap();
+            (more.amount_in - split.fills[index].amount_in) / (more.size_eth - allocation[index])
+        };
+        let (shallow, deep) = (marginal(0), marginal(1));
+        assert!((shallow - deep).abs() < deep * 1e-5, "{shallow} vs {deep}");
+        assert!((deep - split.marginal_price).abs() < deep * 1e-5);
+
+        // Either pool alone pays more for the same ETH
+        for single in &pools {
+            let alone = split_swap(std::slice::from_ref(single), buy, 5.0, size, &options).unwrap();
+            assert!((alone.size_eth - size).abs() < size * 1e-9);
+            assert!(split.amount_in < alone.amount_in);
+        }
+
+        // Selling splits the same way, for more USDC than either pool alone
+        let sell = SwapDirection::Token1ToToken0;
+        let split = split_swap(&pools, sell, 5.0, size, &options).unwrap();
+        for single in &pools {
+            let alone =
+                split_swap(std::slice::from_ref(single), sell, 5.0, size, &options).unwrap();
+            assert!(split.amount_out > alone.amount_out);
+        }
+        assert!(matches!(
+            split_swap(&[], buy, 5.0, size, &options),
+            Err(SwapMathError::NoPools)
+        ));
+    }
+}
@@ -1,8 +1,17 @@
+use crate::dex::calc::calculate_human_price_from_sqrt_x96;
+use crate::errors::SwapMathError;
+use crate::models::SwapDirection;
 use alloy_primitives::U256;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uniswap_v3_math::sqrt_price_math::{
+    _get_amount_0_delta, _get_amount_1_delta, get_next_sqrt_price_from_input,
+};
+use uniswap_v3_math::tick_math::get_tick_at_sqrt_ratio;
 
 /// Minimal immutable snapshot of a Uniswap V3 pool state needed for pricing
 /// and swap sizing within a single tick.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PoolState {
     /// Current sqrt(price1/price0) in Q96 (Uniswap V3 `slot0.sqrtPriceX96`).
     pub sqrt_price_x96: U256,
@@ -19,6 +28,47 @@ pub struct PoolState {
     pub limit_upper_sqrt_price_x96: Option<U256>,
     /// Current price in USDC per ETH
     pub price_usdc_per_eth: f64,
+    /// Initialized ranges below the active one, nearest first (walked by token0 → token1 swaps).
+    pub segments_down: Vec<PriceSegment>,
+    /// Initialized ranges above the active one, nearest first (walked by token1 → token0 swaps).
+    pub segments_up: Vec<PriceSegment>,
+}
+
+/// A contiguous sqrt-price range with constant active liquidity.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceSegment {
+    pub sqrt_lower_x96: U256,
+    pub sqrt_upper_x96: U256,
+    /// Active liquidity L while the price is inside this range.
+    pub liquidity: u128,
+}
+
+impl PriceSegment {
+    pub fn new(sqrt_lower_x96: U256, sqrt_upper_x96: U256, liquidity: u128) -> Self {
+        Self {
+            sqrt_lower_x96,
+            sqrt_upper_x96,
+            liquidity,
+        }
+    }
+
+    /// Edge a swap enters this segment from.
+    pub fn near_edge(&self, moving_down: bool) -> U256 {
+        if moving_down {
+            self.sqrt_upper_x96
+        } else {
+            self.sqrt_lower_x96
+        }
+    }
+
+    /// Edge a swap leaves this segment through.
+    pub fn far_edge(&self, moving_down: bool) -> U256 {
+        if moving_down {
+            self.sqrt_lower_x96
+        } else {
+            self.sqrt_upper_x96
+        }
+    }
 }
 
 impl PoolState {
@@ -41,8 +91,168 @@ impl PoolState {
             limit_lower_sqrt_price_x96,
             limit_upper_sqrt_price_x96,
             price_usdc_per_eth,
+            segments_down: Vec::new(),
+            segments_up: Vec::new(),
+        }
+    }
+
+    /// Attach loaded segments on either side of the active range.
+    pub fn with_segments(
+        mut self,
+        segments_down: Vec<PriceSegment>,
+        segments_up: Vec<PriceSegment>,
+    ) -> Self {
+        self.segments_down = segments_down;
+        self.segments_up = segments_up;
+        self
+    }
+
+    /// The same pool seen with token0 and token1 swapped, for a pool whose
+    /// quote token is token1: sqrt prices invert (2^192 / √P), ticks negate,
+    /// each side's segments move to the other and the decimals trade places.
+    /// The USDC-per-ETH price is recomputed from the inverted sqrt price.
+    pub fn invert_tokens(self) -> Self {
+        let q192 = U256::from(1u8) << 192;
+        let invert = |sqrt: U256| {
+            if sqrt.is_zero() {
+                U256::ZERO
+            } else {
+                q192 / sqrt
+            }
+        };
+        let invert_segment = |seg: PriceSegment| {
+            PriceSegment::new(
+                invert(seg.sqrt_upper_x96),
+                invert(seg.sqrt_lower_x96),
+                seg.liquidity,
+            )
+        };
+        let sqrt_price_x96 = invert(self.sqrt_price_x96);
+        Self {
+            sqrt_price_x96,
+            liquidity: self.liquidity,
+            tick: -self.tick,
+            token0_decimals: self.token1_decimals,
+            token1_decimals: self.token0_decimals,
+            limit_lower_sqrt_price_x96: self.limit_upper_sqrt_price_x96.map(invert),
+            limit_upper_sqrt_price_x96: self.limit_lower_sqrt_price_x96.map(invert),
+            price_usdc_per_eth: if sqrt_price_x96.is_zero() {
+                0.0
+            } else {
+                calculate_human_price_from_sqrt_x96(
+                    sqrt_price_x96,
+                    self.token1_decimals,
+                    self.token0_decimals,
+                )
+            },
+            segments_down: self.segments_up.into_iter().map(invert_segment).collect(),
+            segments_up: self.segments_down.into_iter().map(invert_segment).collect(),
         }
     }
+
+    /// This pool after a swap of `amount_in` (human units of the input token,
+    /// LP fee included) in `direction`, for pricing a later trade of the same
+    /// block against the state an earlier one leaves behind.
+    ///
+    /// The price advances through the active range and the loaded segments;
+    /// crossed ranges move to the other side and the active liquidity becomes
+    /// that of the range the price ends in. The old active range is only kept
+    /// when the other side is loaded (otherwise its far end is unknown), and
+    /// input beyond the last loaded segment is dropped at its edge.
+    pub fn apply_swap(
+        &self,
+        direction: SwapDirection,
+        amount_in: f64,
+        fee_bps: f64,
+    ) -> Result<PoolState, SwapMathError> {
+        if !(0.0..10_000.0).contains(&fee_bps) {
+            return Err(SwapMathError::InvalidFee(fee_bps));
+        }
+        let moving_down = direction == SwapDirection::Token0ToToken1;
+        let (segments, opposite, in_decimals) = if moving_down {
+            (&self.segments_down, &self.segments_up, self.token0_decimals)
+        } else {
+            (&self.segments_up, &self.segments_down, self.token1_decimals)
+        };
+        // The LP fee never reaches the curve
+        let net_raw =
+            amount_in.max(0.0) * (1.0 - fee_bps / 10_000.0) * 10f64.powi(in_decimals as i32);
+        let net_str = format!("{:.0}", net_raw);
+        let mut remaining =
+            U256::from_str_radix(&net_str, 10).map_err(|_| SwapMathError::Overflow(net_str))?;
+
+        // (liquidity, far edge) of the active range, then of each loaded segment
+        let ranges = std::iter::once((
+            self.liquidity,
+            segments.first().map(|seg| seg.near_edge(moving_down)),
+        ))
+        .chain(
+            segments
+                .iter()
+                .map(|seg| (seg.liquidity, Some(seg.far_edge(moving_down)))),
+        );
+        let mut cursor = self.sqrt_price_x96;
+        let mut entered = 0;
+        for (index, (liquidity, far_edge)) in ranges.enumerate() {
+            entered = index;
+            let to_edge = match far_edge {
+                _ if liquidity == 0 => U256::ZERO,
+                Some(edge) if moving_down => _get_amount_0_delta(edge, cursor, liquidity, true)?,
+                Some(edge) => _get_amount_1_delta(cursor, edge, liquidity, true)?,
+                None => U256::MAX,
+            };
+            if remaining < to_edge {
+                cursor = get_next_sqrt_price_from_input(cursor, liquidity, remaining, moving_down)?;
+                break;
+            }
+            // Only an empty range without a far edge gets here unbounded
+            let Some(edge) = far_edge else { break };
+            remaining -= to_edge;
+            cursor = edge;
+        }
+
+        // Ranges left behind, nearest the new price first
+        let mut behind: Vec<PriceSegment> = (1..entered)
+            .rev()
+            .map(|index| segments[index - 1].clone())
+            .collect();
+        if entered > 0
+            && let Some(other) = opposite.first()
+        {
+            let (a, b) = (
+                segments[0].near_edge(moving_down),
+                other.near_edge(!moving_down),
+            );
+            behind.push(PriceSegment::new(a.min(b), a.max(b), self.liquidity));
+        }
+        behind.extend(opposite.iter().cloned());
+        let ahead = segments[entered.min(segments.len())..].to_vec();
+
+        let mut state = self.clone();
+        if entered > 0 {
+            state.liquidity = segments[entered - 1].liquidity;
+            let (down, up) = if moving_down {
+                (ahead, behind)
+            } else {
+                (behind, ahead)
+            };
+            state.segments_down = down;
+            state.segments_up = up;
+        }
+        state.sqrt_price_x96 = cursor;
+        state.tick = get_tick_at_sqrt_ratio(cursor)?;
+        state.price_usdc_per_eth =
+            calculate_human_price_from_sqrt_x96(cursor, self.token0_decimals, self.token1_decimals);
+        // The tick limits only hold while the price stays inside them
+        if !matches!(
+            (self.limit_lower_sqrt_price_x96, self.limit_upper_sqrt_price_x96),
+            (Some(lower), Some(upper)) if lower <= cursor && cursor <= upper
+        ) {
+            state.limit_lower_sqrt_price_x96 = None;
+            state.limit_upper_sqrt_price_x96 = None;
+        }
+        Ok(state)
+    }
 }
 
 /// Approximate sqrtPriceX96 at a given tick using f64 math.
@@ -62,6 +272,140 @@ pub fn approx_sqrt_price_x96_at_tick(tick: i32) -> U256 {
     U256::from_str_radix(&s, 10).unwrap_or_else(|_| U256::ZERO)
 }
 
+/// Build segments on both sides of `tick` from the pool's initialized ticks
+/// (tick → `liquidityNet`), looking at most `window_ticks` away.
+///
+/// Each initialized tick inside the window starts a new segment, with the
+/// active liquidity after crossing it; the last segment on each side ends at
+/// the window edge, so a swap running past it reports `hit_boundary`. A side
+/// with no initialized ticks in the window gets a single zero-width segment
+/// at the edge for the same reason.
+pub fn segments_from_ticks(
+    tick: i32,
+    liquidity: u128,
+    initialized: &BTreeMap<i32, i128>,
+    window_ticks: u32,
+) -> (Vec<PriceSegment>, Vec<PriceSegment>) {
+    let window = window_ticks as i32;
+    let lower_edge = tick.saturating_sub(window);
+    let upper_edge = tick.saturating_add(window);
+
+    // Moving down, the active range ends at the nearest initialized tick at or
+    // below the current one; crossing it removes its liquidityNet
+    let down = initialized
+        .range(lower_edge + 1..=tick)
+        .rev()
+        .map(|(&t, &net)| (t, -net));
+    let segments_down = build_side(liquidity, down, lower_edge, true);
+
+    // Moving up, crossing an initialized tick adds its liquidityNet
+    let up = initialized
+        .range(tick + 1..upper_edge)
+        .map(|(&t, &net)| (t, net));
+    let segments_up = build_side(liquidity, up, upper_edge, false);
+
+    (segments_down, segments_up)
+}
+
+/// Turn (tick, liquidity delta) crossings in traversal order into segments
+/// ending at `edge`.
+fn build_side(
+    liquidity: u128,
+    crossings: impl Iterator<Item = (i32, i128)>,
+    edge: i32,
+    moving_down: bool,
+) -> Vec<PriceSegment> {
+    let mut boundaries: Vec<(i32, i128)> = crossings.collect();
+    let mut segments = Vec::with_capacity(boundaries.len());
+    if boundaries.is_empty() {
+        let edge_sqrt = approx_sqrt_price_x96_at_tick(edge);
+        segments.push(PriceSegment::new(edge_sqrt, edge_sqrt, liquidity));
+        return segments;
+    }
+    boundaries.push((edge, 0));
+
+    let mut active = liquidity as i128;
+    for pair in boundaries.windows(2) {
+        let ((near, delta), (far, _)) = (pair[0], pair[1]);
+        active = active.saturating_add(delta).max(0);
+        let (near_sqrt, far_sqrt) = (
+            approx_sqrt_price_x96_at_tick(near),
+            approx_sqrt_price_x96_at_tick(far),
+        );
+        let (lower, upper) = if moving_down {
+            (far_sqrt, near_sqrt)
+        } else {
+            (near_sqrt, far_sqrt)
+        };
+        segments.push(PriceSegment::new(lower, upper, active as u128));
+    }
+    segments
+}
+
+/// Deterministic pool fixtures with explicit segments, for multi-segment swap tests.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::{PoolState, PriceSegment};
+    use alloy_primitives::U256;
+
+    /// `units * 2^96`, i.e. a sqrt price of exactly `units` in Q96.
+    pub(crate) fn q96(units: u64) -> U256 {
+        U256::from(units) << 96
+    }
+
+    /// Build a USDC/WETH (6/18) pool at `sqrt_price_x96` with active `liquidity`
+    /// and the given segments, nearest first on each side.
+    pub(crate) fn pool_with_segments(
+        sqrt_price_x96: U256,
+        liquidity: u128,
+        segments_down: Vec<PriceSegment>,
+        segments_up: Vec<PriceSegment>,
+    ) -> PoolState {
+        let lower = segments_down.first().map(|seg| seg.sqrt_upper_x96);
+        let upper = segments_up.first().map(|seg| seg.sqrt_lower_x96);
+        PoolState::new(
+            sqrt_price_x96,
+            liquidity,
+            0,
+            6,
+            18,
+            lower,
+            upper,
+            crate::dex::client::price_usdc_per_eth(sqrt_price_x96),
+        )
+        .with_segments(segments_down, segments_up)
+    }
+
+    /// Pool at sqrt 16000 (≈ 3906.25 USDC/ETH) with an active range down to
+    /// 15990 and two further segments below it: [15980, 15990] and [15970, 15980].
+    pub(crate) fn three_segments_down() -> PoolState {
+        pool_with_segments(
+            q96(16_000),
+            1_000_000_000_000_000_000,
+            vec![
+                PriceSegment::new(q96(15_980), q96(15_990), 2_000_000_000_000_000_000),
+                PriceSegment::new(q96(15_970), q96(15_980), 500_000_000_000_000_000),
+            ],
+            vec![],
+        )
+    }
+
+    /// Pool at sqrt 16000 with an active range up to 16010 and one segment
+    /// [16010, 16020] above it.
+    pub(crate) fn two_segments_up() -> PoolState {
+        pool_with_segments(
+            q96(16_000),
+            1_000_000_000_000_000_000,
+            vec![],
+            vec![PriceSegment::new(
+                q96(16_010),
+                q96(16_020),
+                3_000_000_000_000_000_000,
+            )],
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,4 +419,98 @@ mod tests {
         assert!(low < mid);
         assert!(mid < high);
     }
+
+    #[test]
+    fn window_size_controls_segment_count() {
+        // Initialized ticks every 10 ticks around tick 5, none adding liquidity
+        let initialized: BTreeMap<i32, i128> = (-20..=20).map(|i| (i * 10, 0)).collect();
+        let liquidity = 1_000_000u128;
+
+        // Down: ticks 0..=-40 within 50; up: ticks 10..=50 within 50
+        let (down, up) = segments_from_ticks(5, liquidity, &initialized, 50);
+        assert_eq!((down.len(), up.len()), (5, 5));
+
+        let (down, up) = segments_from_ticks(5, liquidity, &initialized, 100);
+        assert_eq!((down.len(), up.len()), (10, 10));
+
+        // Segments chain nearest first and end at the window edge
+        assert_eq!(down[0].sqrt_upper_x96, approx_sqrt_price_x96_at_tick(0));
+        assert!(
+            down.windows(2)
+                .all(|w| w[0].sqrt_lower_x96 == w[1].sqrt_upper_x96)
+        );
+        assert_eq!(down[9].sqrt_lower_x96, approx_sqrt_price_x96_at_tick(-95));
+        assert_eq!(up[9].sqrt_upper_x96, approx_sqrt_price_x96_at_tick(105));
+    }
+
+    #[test]
+    fn crossing_ticks_applies_liquidity_net() {
+        // A position spanning [-10, 10) around the current tick
+        let initialized = BTreeMap::from([(-10, 400i128), (10, -400i128)]);
+        let (down, up) = segments_from_ticks(0, 1_000, &initialized, 30);
+        assert_eq!(down[0].liquidity, 600);
+        assert_eq!(up[0].liquidity, 600);
+
+        // Nothing initialized in range: a zero-width marker at the window edge
+        let (down, _) = segments_from_ticks(0, 1_000, &BTreeMap::new(), 30);
+        assert_eq!(down.len(), 1);
+        assert_eq!(down[0].sqrt_lower_x96, down[0].sqrt_upper_x96);
+    }
+
+    #[test]
+    fn apply_swap_advances_price_and_crosses_segments() {
+        use super::fixtures::{pool_with_segments, q96, three_segments_down};
+
+        // Within the active range: the price moves, nothing is crossed
+        let pool = three_segments_down();
+        let small = pool
+            .apply_swap(SwapDirection::Token0ToToken1, 1.0, 0.0)
+            .unwrap();
+        assert!(small.sqrt_price_x96 < pool.sqrt_price_x96);
+        assert!(small.sqrt_price_x96 > q96(15_990));
+        assert!(small.price_usdc_per_eth > pool.price_usdc_per_eth);
+        assert_eq!(small.liquidity, pool.liquidity);
+        assert_eq!(small.segments_down, pool.segments_down);
+
+        // USDC reaching past 15990 ends in the first segment, with its liquidity
+        let to_edge = _get_amount_0_delta(q96(15_990), q96(16_000), pool.liquidity, true).unwrap();
+        let usdc = (to_edge.to::<u128>() as f64 + 1e3) / 1e6;
+        let moved = pool
+            .apply_swap(SwapDirection::Token0ToToken1, usdc, 0.0)
+            .unwrap();
+        assert!(moved.sqrt_price_x96 < q96(15_990) && moved.sqrt_price_x96 > q96(15_980));
+        assert_eq!(moved.liquidity, 2_000_000_000_000_000_000);
+        assert_eq!(moved.segments_down, pool.segments_down[1..]);
+
+        // With the LP fee taken out first the same input falls short of the edge
+        let fee = pool
+            .apply_swap(SwapDirection::Token0ToToken1, usdc, 30.0)
+            .unwrap();
+        assert!(fee.sqrt_price_x96 > q96(15_990));
+
+        // Loaded on both sides, the crossed active range becomes the nearest one above
+        let two_sided = pool_with_segments(
+            q96(16_000),
+            1_000_000_000_000_000_000,
+            pool.segments_down.clone(),
+            vec![PriceSegment::new(q96(16_010), q96(16_020), 7)],
+        );
+        let moved = two_sided
+            .apply_swap(SwapDirection::Token0ToToken1, usdc, 0.0)
+            .unwrap();
+        assert_eq!(
+            moved.segments_up,
+            vec![
+                PriceSegment::new(q96(15_990), q96(16_010), 1_000_000_000_000_000_000),
+                PriceSegment::new(q96(16_010), q96(16_020), 7),
+            ]
+        );
+        assert_eq!(
+            (
+                moved.limit_lower_sqrt_price_x96,
+                moved.limit_upper_sqrt_price_x96
+            ),
+            (None, None)
+        );
+    }
 }
@@ -13,23 +13,30 @@ pub enum AppError {
     #[error("Parse float error: {0}")]
     ParseFloat(#[from] std::num::ParseFloatError),
 
+    #[error("Parse int error: {0}")]
+    ParseInt(#[from] std::num::ParseIntError),
+
+    #[error("Parse bool error: {0}")]
+    ParseBool(#[from] std::str::ParseBoolError),
+
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
 
     #[error("URL parse error: {0}")]
     UrlParse(#[from] url::ParseError),
 
+    /// Boxed, being several times the size of every other variant
     #[error("WebSocket error: {0}")]
-    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
 
     #[error("Provider error: {0}")]
     Provider(#[from] ethers::providers::ProviderError),
 
     #[error("Contract error: {0}")]
-    Contract(
-        #[from]
-        ethers::contract::ContractError<ethers::providers::Provider<ethers::providers::Http>>,
-    ),
+    Contract(String),
+
+    #[error("pool state at block {block} unavailable (requires an archive RPC): {reason}")]
+    HistoricalState { block: u64, reason: String },
 
     #[error("Serialization error: {0}")]
     SerdeJson(#[from] serde_json::Error),
@@ -37,6 +44,49 @@ pub enum AppError {
     #[error("Math error: {0}")]
     Math(#[from] uniswap_v3_math::error::UniswapV3MathError),
 
+    #[error("Swap math error: {0}")]
+    SwapMath(#[from] SwapMathError),
+
     #[error("Other: {0}")]
     Other(String),
 }
+
+impl From<tokio_tungstenite::tungstenite::Error> for AppError {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        AppError::WebSocket(Box::new(e))
+    }
+}
+
+impl<M: ethers::providers::Middleware> From<ethers::contract::ContractError<M>> for AppError {
+    fn from(e: ethers::contract::ContractError<M>) -> Self {
+        AppError::Contract(e.to_string())
+    }
+}
+
+/// Distinct failure modes of the swap / sqrt-price math in `dex::calc`.
+#[derive(Debug, Error)]
+pub enum SwapMathError {
+    #[error("price must be positive and finite, got {0}")]
+    InvalidPrice(f64),
+
+    #[error("swap fee must be in [0, 10000) bps, got {0}")]
+    InvalidFee(f64),
+
+    #[error("BigDecimal conversion failed for {0}")]
+    DecimalConversion(&'static str),
+
+    #[error("sqrt of price ratio is not a positive number ({0})")]
+    NonPositiveSqrt(f64),
+
+    #[error("route has no hops")]
+    EmptyRoute,
+
+    #[error("split has no pools")]
+    NoPools,
+
+    #[error("sqrtPriceX96 {0} does not fit in U256")]
+    Overflow(String),
+
+    #[error("Uniswap V3 math: {0}")]
+    Library(#[from] uniswap_v3_math::error::UniswapV3MathError),
+}
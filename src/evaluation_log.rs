@@ -0,0 +1,256 @@
+//! Replayable log of every evaluation pass, inputs and outputs.
+//!
+//! A book capture reproduces the market but not the decision: the config,
+//! the gas estimate and the pool it was taken against are gone. Each
+//! [`EvaluationRecord`] holds all of them next to the opportunities the pass
+//! produced, so [`EvaluationRecord::replay`] re-runs the exact evaluation
+//! offline and a production decision can be compared against it. What the
+//! pass went on to emit, after confirmation and the emission filters, is
+//! recorded beside it.
+//!
+//! A `Dynamic` DEX fee model is code, so the fee it charged on the pool in
+//! each direction is recorded in its place. The decimal levels kept in exact
+//! precision mode are not recorded, only their f64 view.
+
+use crate::arbitrage::{
+    ArbitrageConfig, ArbitrageOpportunity, GasEstimate, evaluate_across_venues,
+};
+use crate::dex::{FeeModel, PoolState};
+use crate::errors::Result;
+use crate::models::{BookDepth, SwapDirection};
+use crate::sink::{JsonlSink, OpportunitySink};
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Everything one evaluation pass decided on, and what it decided.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvaluationRecord {
+    pub chain_id: u64,
+    /// Clock of the pass, which book freshness is judged against
+    pub now_ms: u64,
+    pub pool: PoolState,
+    /// Book of every venue, fresh or not
+    pub books: Vec<(String, BookDepth)>,
+    pub gas: GasEstimate,
+    /// Config the pass evaluated under (degraded thresholds, funding and
+    /// bias already applied)
+    pub config: ArbitrageConfig,
+    /// Opportunities the evaluation returned, before hysteresis, throttling
+    /// and confirmation
+    pub opportunities: Vec<ArbitrageOpportunity>,
+    /// Opportunities the pass emitted: `opportunities` after confirmation,
+    /// hysteresis, time in profit, throttling, the notional budget and hooks
+    #[serde(default)]
+    pub emitted: Vec<ArbitrageOpportunity>,
+    /// DEX fee the config's fee model charged on `pool`, which replay uses
+    /// in place of the model (absent in older logs)
+    #[serde(default)]
+    pub dex_fees: Option<PoolFees>,
+}
+
+/// Fee of a swap on one pool state in each direction, in bps.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PoolFees {
+    pub token0_in_bps: f64,
+    pub token1_in_bps: f64,
+}
+
+impl PoolFees {
+    /// Fees `config.dex_fee_model` charges on `pool`.
+    pub fn of(pool: &PoolState, config: &ArbitrageConfig) -> Self {
+        let fee_bps = |direction| {
+            config
+                .dex_fee_model
+                .fee_bps(pool, direction, config.dex_fee_bps)
+        };
+        Self {
+            token0_in_bps: fee_bps(SwapDirection::Token0ToToken1),
+            token1_in_bps: fee_bps(SwapDirection::Token1ToToken0),
+        }
+    }
+
+    /// A fee model charging these fees whatever the pool state.
+    fn model(self) -> FeeModel {
+        FeeModel::Dynamic(Arc::new(move |_, direction| match direction {
+            SwapDirection::Token0ToToken1 => self.token0_in_bps,
+            SwapDirection::Token1ToToken0 => self.token1_in_bps,
+        }))
+    }
+}
+
+impl EvaluationRecord {
+    /// Evaluate `pool` against `books` and record the result, with nothing
+    /// emitted.
+    pub fn evaluate(
+        chain_id: u64,
+        now_ms: u64,
+        pool: &PoolState,
+        books: &[(String, BookDepth)],
+        gas: GasEstimate,
+        config: &ArbitrageConfig,
+    ) -> Self {
+        let mut record = Self {
+            chain_id,
+            now_ms,
+            pool: pool.clone(),
+            books: books.to_vec(),
+            gas,
+            config: config.clone(),
+            opportunities: Vec::new(),
+            emitted: Vec::new(),
+            dex_fees: Some(PoolFees::of(pool, config)),
+        };
+        record.opportunities = record.replay();
+        record
+    }
+
+    /// Run the recorded evaluation again.
+    pub fn replay(&self) -> Vec<ArbitrageOpportunity> {
+        let config = match self.dex_fees {
+            Some(fees) => ArbitrageConfig {
+                dex_fee_model: fees.model(),
+                ..self.config.clone()
+            },
+            None => self.config.clone(),
+        };
+        evaluate_across_venues(&self.pool, &self.books, &config, self.gas, self.now_ms)
+    }
+}
+
+/// Destination for evaluation records.
+pub trait EvaluationLogSink: Send + Sync {
+    fn record(&self, record: &EvaluationRecord) -> Result<()>;
+}
+
+impl EvaluationLogSink for JsonlSink {
+    fn record(&self, record: &EvaluationRecord) -> Result<()> {
+        // The log is for post-mortems, so a crash must not lose the last passes
+        self.append(record)?;
+        OpportunitySink::flush(self)
+    }
+}
+
+/// Load an evaluation log, in pass order.
+pub fn load_evaluation_log(path: impl AsRef<Path>) -> Result<Vec<EvaluationRecord>> {
+    let file = std::fs::File::open(path)?;
+    std::io::BufReader::new(file)
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::PriceSegment;
+    use crate::dex::calc::calculate_sqrt_price_with_precision_per_eth;
+
+    #[test]
+    fn logged_evaluation_replays_to_the_same_opportunities() {
+        let sqrt_price_x96 = calculate_sqrt_price_with_precision_per_eth(4_000.0, 6, 18).unwrap();
+        let mut pool = PoolState::new(
+            sqrt_price_x96,
+            1_800_000_000_000_000_000,
+            0,
+            6,
+            18,
+            None,
+            None,
+            4_000.0,
+        );
+        pool.segments_down.push(PriceSegment::new(
+            sqrt_price_x96 / alloy_primitives::U256::from(2),
+            sqrt_price_x96,
+            900_000_000_000_000_000,
+        ));
+        let book = |bid: f64, ask: f64| BookDepth {
+            timestamp: 7,
+            bids: vec![(bid, 3.0), (bid - 1.0, 5.0)],
+            asks: vec![(ask, 3.0), (ask + 1.0, 5.0)],
+            received_at_ms: 9_900,
+            ..Default::default()
+        };
+        let books = vec![
+            ("binance".to_string(), book(4_030.0, 4_031.0)),
+            ("okx".to_string(), book(4_025.0, 4_026.0)),
+        ];
+        let config = ArbitrageConfig {
+            min_pnl_usdc: 0.01,
+            dex_fee_bps: 5.0,
+            cex_fee_bps: 10.0,
+            max_book_age_ms: 500,
+            candidate_sizes_eth: vec![0.5, 1.0, 2.0],
+            ..Default::default()
+        };
+        let gas = GasEstimate {
+            base_usdc: 0.5,
+            per_tick_usdc: 0.1,
+        };
+        let record = EvaluationRecord::evaluate(1, 10_000, &pool, &books, gas, &config);
+        assert!(!record.opportunities.is_empty());
+
+        let path = std::env::temp_dir().join(format!(
+            "evaluation-log-{}-{}.jsonl",
+            std::process::id(),
+            crate::utils::now_ms()
+        ));
+        let log = JsonlSink::open(&path).unwrap();
+        EvaluationLogSink::record(&log, &record).unwrap();
+        let loaded = load_evaluation_log(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The decision is reproduced exactly, down to the bit
+        assert_eq!(loaded.len(), 1);
+        let replayed = loaded[0].replay();
+        assert_eq!(
+            serde_json::to_value(&replayed).unwrap(),
+            serde_json::to_value(&record.opportunities).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&loaded[0].opportunities).unwrap(),
+            serde_json::to_value(&record.opportunities).unwrap()
+        );
+
+        // A dynamic fee is not serialized but the fee it charged is, and a
+        // replay charges that rather than the fixed tier
+        let dynamic = ArbitrageConfig {
+            dex_fee_model: FeeModel::Dynamic(Arc::new(|pool, direction| {
+                let base = if pool.liquidity > 1_000_000_000_000_000_000 {
+                    30.0
+                } else {
+                    1.0
+                };
+                match direction {
+                    SwapDirection::Token0ToToken1 => base,
+                    SwapDirection::Token1ToToken0 => base * 2.0,
+                }
+            })),
+            ..config
+        };
+        let record = EvaluationRecord::evaluate(1, 10_000, &pool, &books, gas, &dynamic);
+        assert_eq!(
+            record.dex_fees,
+            Some(PoolFees {
+                token0_in_bps: 30.0,
+                token1_in_bps: 60.0
+            })
+        );
+        let loaded: EvaluationRecord =
+            serde_json::from_str(&serde_json::to_string(&record).unwrap()).unwrap();
+        assert_eq!(
+            serde_json::to_value(loaded.replay()).unwrap(),
+            serde_json::to_value(&record.opportunities).unwrap()
+        );
+        let fixed = EvaluationRecord {
+            dex_fees: None,
+            ..loaded
+        };
+        assert_ne!(
+            serde_json::to_value(fixed.replay()).unwrap(),
+            serde_json::to_value(&record.opportunities).unwrap()
+        );
+    }
+}
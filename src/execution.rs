@@ -0,0 +1,338 @@
+//! Hand-off of high-confidence opportunities to an external executor.
+//!
+//! Detection never trades itself: opportunities clearing the execution bar
+//! are passed to an [`ExecutionClient`], by default one that drops them. With
+//! the `execution` feature, [`HttpExecutionClient`] POSTs each one as JSON
+//! signed with HMAC-SHA256 in the `X-Signature` header (lowercase hex of the
+//! body's MAC), so the executor can reject payloads not sent by us.
+//! [`SimulatedExecutionClient`] first simulates the DEX leg against the
+//! pool's current state and holds back opportunities whose swap would fill
+//! short of the evaluated price.
+
+use crate::arbitrage::ArbitrageOpportunity;
+use crate::dex::Dex;
+use crate::errors::{AppError, Result};
+use async_trait::async_trait;
+use std::sync::Arc;
+
+/// Receiver of opportunities to execute.
+#[async_trait]
+pub trait ExecutionClient: Send + Sync {
+    async fn submit(&self, opportunity: &ArbitrageOpportunity) -> Result<()>;
+}
+
+/// Drops every opportunity; used when no executor is configured.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopExecutionClient;
+
+#[async_trait]
+impl ExecutionClient for NoopExecutionClient {
+    async fn submit(&self, _opportunity: &ArbitrageOpportunity) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Simulator of an opportunity's DEX leg.
+#[async_trait]
+pub trait LegSimulator: Send + Sync {
+    /// Output of swapping `amount_in` of the quote for ETH when `buys_eth`,
+    /// of ETH for the quote otherwise, in human units.
+    async fn simulate_leg(&self, buys_eth: bool, amount_in: f64) -> Result<f64>;
+}
+
+#[async_trait]
+impl LegSimulator for Dex {
+    async fn simulate_leg(&self, buys_eth: bool, amount_in: f64) -> Result<f64> {
+        Dex::simulate_leg(self, buys_eth, amount_in).await
+    }
+}
+
+/// Simulates the DEX leg of each opportunity of one pool before passing it
+/// on, rejecting it when the simulated output falls more than `tolerance_bps`
+/// short of what the evaluation priced in. Opportunities whose legs name
+/// another pool are passed on unsimulated.
+pub struct SimulatedExecutionClient {
+    pub simulator: Arc<dyn LegSimulator>,
+    pub inner: Arc<dyn ExecutionClient>,
+    /// Pool the simulator swaps in, as named in [`ExecutionLegs::pool`](crate::arbitrage::ExecutionLegs::pool)
+    pub pool: String,
+    pub tolerance_bps: f64,
+}
+
+#[async_trait]
+impl ExecutionClient for SimulatedExecutionClient {
+    async fn submit(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        let own_pool = opportunity
+            .legs
+            .as_ref()
+            .is_none_or(|legs| legs.pool == self.pool);
+        if own_pool {
+            let buys_eth = opportunity.direction == "A";
+            let (_, amount_in) = opportunity.dex_swap();
+            let expected_out = if buys_eth {
+                opportunity.size_eth
+            } else {
+                amount_in * opportunity.dex_vwap
+            };
+            let simulated_out = self.simulator.simulate_leg(buys_eth, amount_in).await?;
+            let shortfall_bps = (expected_out - simulated_out) / expected_out * 10_000.0;
+            if shortfall_bps.is_nan() || shortfall_bps > self.tolerance_bps {
+                return Err(AppError::Other(format!(
+                    "simulated DEX leg returns {} against {} expected ({:.1} bps short)",
+                    simulated_out, expected_out, shortfall_bps
+                )));
+            }
+        }
+        self.inner.submit(opportunity).await
+    }
+}
+
+/// An execution client and the bar an opportunity must clear to reach it.
+#[derive(Clone)]
+pub struct Execution {
+    pub client: Arc<dyn ExecutionClient>,
+    /// Minimum PnL in quote units for an opportunity to be submitted
+    pub min_pnl_usdc: f64,
+}
+
+impl Default for Execution {
+    fn default() -> Self {
+        Self {
+            client: Arc::new(NoopExecutionClient),
+            min_pnl_usdc: f64::INFINITY,
+        }
+    }
+}
+
+impl Execution {
+    /// Submit every opportunity clearing the bar in the background, so a
+    /// slow executor never holds up evaluation.
+    pub fn submit_qualifying(&self, opportunities: &[ArbitrageOpportunity]) {
+        for opp in opportunities
+            .iter()
+            .filter(|opp| opp.pnl >= self.min_pnl_usdc)
+        {
+            let client = self.client.clone();
+            let opp = opp.clone();
+            tokio::spawn(async move {
+                match client.submit(&opp).await {
+                    Ok(()) => tracing::info!(id = %opp.id, pnl = opp.pnl, "[EXEC] submitted"),
+                    Err(e) => tracing::warn!(id = %opp.id, error = %e, "[EXEC] submit failed"),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arbitrage::ExecutionLegs;
+    use std::sync::Mutex;
+
+    /// Fills every swap at a fixed price, in quote per ETH.
+    struct FixedPrice(f64);
+
+    #[async_trait]
+    impl LegSimulator for FixedPrice {
+        async fn simulate_leg(&self, buys_eth: bool, amount_in: f64) -> Result<f64> {
+            Ok(if buys_eth {
+                amount_in / self.0
+            } else {
+                amount_in * self.0
+            })
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl ExecutionClient for Recorder {
+        async fn submit(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+            self.0.lock().unwrap().push(opportunity.id.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn simulated_shortfall_holds_back_the_opportunity() {
+        let recorder = Arc::new(Recorder::default());
+        let client = |price: f64| SimulatedExecutionClient {
+            simulator: Arc::new(FixedPrice(price)),
+            inner: recorder.clone(),
+            pool: "0xpool".to_string(),
+            tolerance_bps: 10.0,
+        };
+        let buy = ArbitrageOpportunity {
+            direction: "A".to_string(),
+            id: "buy".to_string(),
+            size_eth: 2.0,
+            dex_vwap: 4000.0,
+            ..Default::default()
+        };
+        let sell = ArbitrageOpportunity {
+            direction: "B".to_string(),
+            id: "sell".to_string(),
+            ..buy.clone()
+        };
+
+        // Within 10 bps of the evaluated price either way
+        client(4002.0).submit(&buy).await.unwrap();
+        client(3998.0).submit(&sell).await.unwrap();
+        // 25 bps worse than evaluated
+        assert!(client(4010.0).submit(&buy).await.is_err());
+        assert!(client(3990.0).submit(&sell).await.is_err());
+        // Another pool's leg is not this simulator's to check
+        let elsewhere = ArbitrageOpportunity {
+            id: "elsewhere".to_string(),
+            legs: Some(ExecutionLegs {
+                pool: "0xother".to_string(),
+                ..Default::default()
+            }),
+            ..buy.clone()
+        };
+        client(4010.0).submit(&elsewhere).await.unwrap();
+
+        assert_eq!(*recorder.0.lock().unwrap(), ["buy", "sell", "elsewhere"]);
+    }
+}
+
+#[cfg(feature = "execution")]
+pub use http::{HttpExecutionClient, sign};
+
+#[cfg(feature = "execution")]
+mod http {
+    use super::*;
+    use crate::errors::AppError;
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    /// Lowercase hex HMAC-SHA256 of `body` under `key`.
+    pub fn sign(key: &[u8], body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    /// POSTs signed opportunities to an executor endpoint.
+    pub struct HttpExecutionClient {
+        endpoint: url::Url,
+        key: Vec<u8>,
+        client: reqwest::Client,
+    }
+
+    impl HttpExecutionClient {
+        pub fn new(endpoint: &str, key: impl Into<Vec<u8>>) -> Result<Self> {
+            let key = key.into();
+            if key.is_empty() {
+                return Err(AppError::Config(
+                    "execution HMAC key must not be empty".to_string(),
+                ));
+            }
+            Ok(Self {
+                endpoint: endpoint.parse()?,
+                key,
+                client: reqwest::Client::new(),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl ExecutionClient for HttpExecutionClient {
+        async fn submit(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+            let body = serde_json::to_vec(opportunity)?;
+            let signature = sign(&self.key, &body);
+            let response = self
+                .client
+                .post(self.endpoint.clone())
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header("X-Signature", signature)
+                .body(body)
+                .send()
+                .await
+                .map_err(|e| AppError::Other(format!("execution endpoint: {}", e)))?;
+            if !response.status().is_success() {
+                return Err(AppError::Other(format!(
+                    "execution endpoint answered {}",
+                    response.status()
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        use tokio::net::TcpListener;
+
+        /// Accept one request and return its headers (lowercased names) and body.
+        async fn accept_one(listener: TcpListener) -> (Vec<(String, String)>, Vec<u8>) {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut raw = Vec::new();
+            let mut buf = [0u8; 4096];
+            let header_end = loop {
+                let n = socket.read(&mut buf).await.unwrap();
+                raw.extend_from_slice(&buf[..n]);
+                if let Some(pos) = raw.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+            };
+            let head = String::from_utf8(raw[..header_end].to_vec()).unwrap();
+            let headers: Vec<(String, String)> = head
+                .lines()
+                .skip(1)
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+                .collect();
+            let length: usize = headers
+                .iter()
+                .find(|(name, _)| name == "content-length")
+                .map(|(_, value)| value.parse().unwrap())
+                .unwrap();
+            while raw.len() < header_end + length {
+                let n = socket.read(&mut buf).await.unwrap();
+                raw.extend_from_slice(&buf[..n]);
+            }
+            socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            (headers, raw[header_end..header_end + length].to_vec())
+        }
+
+        #[tokio::test]
+        async fn signed_payload_reaches_the_endpoint_and_verifies() {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let endpoint = format!("http://{}/execute", listener.local_addr().unwrap());
+            let server = tokio::spawn(accept_one(listener));
+
+            let opp = ArbitrageOpportunity {
+                direction: "A".to_string(),
+                venue: "binance".to_string(),
+                id: "run-1".to_string(),
+                pnl: 42.5,
+                ..Default::default()
+            };
+            let client = HttpExecutionClient::new(&endpoint, b"secret".to_vec()).unwrap();
+            client.submit(&opp).await.unwrap();
+
+            let (headers, body) = server.await.unwrap();
+            let signature = headers
+                .iter()
+                .find(|(name, _)| name == "x-signature")
+                .map(|(_, value)| value.clone())
+                .unwrap();
+            let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+            mac.update(&body);
+            mac.verify_slice(&hex::decode(&signature).unwrap())
+                .expect("HMAC verifies against the received body");
+            assert_ne!(signature, sign(b"other", &body));
+
+            let received: ArbitrageOpportunity = serde_json::from_slice(&body).unwrap();
+            assert_eq!((received.id.as_str(), received.pnl), ("run-1", 42.5));
+        }
+    }
+}
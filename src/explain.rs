@@ -0,0 +1,302 @@
+//! Dry-run walkthrough of one evaluation over a hypothetical market state.
+//!
+//! Builds a single-range pool and a one-level book from the given prices,
+//! evaluates each direction with the evaluator the live loop uses, and
+//! renders what it found as text: both legs, fees, gas, break-even, PnL
+//! against the threshold and the final decision. Nothing touches the network.
+
+use crate::arbitrage::{
+    ArbitrageConfig, ArbitrageOpportunity, evaluate_direction, evaluate_opportunities,
+};
+use crate::dex::PoolState;
+use crate::dex::calc::calculate_sqrt_price_with_precision_per_eth;
+use crate::errors::{AppError, Result};
+use crate::models::BookDepth;
+use std::fmt::Write;
+
+pub const USAGE: &str = "usage: explain --cex-bid X --cex-ask Y --dex-price Z --liquidity L \
+[--cex-qty ETH] [--gas-usdc USDC] [--dex-fee-bps BPS] [--cex-fee-bps BPS] [--min-pnl USDC]";
+
+/// Hypothetical market state and thresholds to explain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplainInputs {
+    pub cex_bid: f64,
+    pub cex_ask: f64,
+    /// DEX price in USDC per ETH of a USDC/WETH (6/18 decimals) pool
+    pub dex_price: f64,
+    /// Active liquidity of the pool, raw
+    pub liquidity: u128,
+    /// Quantity at each CEX touch (default 10)
+    pub cex_qty: f64,
+    /// Gas cost per trade in USDC (default 0)
+    pub gas_usdc: f64,
+    /// Pool fee tier (default 5)
+    pub dex_fee_bps: f64,
+    /// CEX taker fee (default 10)
+    pub cex_fee_bps: f64,
+    /// Smallest PnL worth emitting (default 0)
+    pub min_pnl_usdc: f64,
+}
+
+impl ExplainInputs {
+    /// Parse `--flag value` pairs; see [`USAGE`].
+    pub fn from_args(args: &[String]) -> Result<Self> {
+        let (mut cex_bid, mut cex_ask, mut dex_price, mut liquidity) = (None, None, None, None);
+        let mut inputs = Self {
+            cex_bid: 0.0,
+            cex_ask: 0.0,
+            dex_price: 0.0,
+            liquidity: 0,
+            cex_qty: 10.0,
+            gas_usdc: 0.0,
+            dex_fee_bps: 5.0,
+            cex_fee_bps: 10.0,
+            min_pnl_usdc: 0.0,
+        };
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| AppError::Config(format!("{} needs a value", flag)))?;
+            match flag.as_str() {
+                "--cex-bid" => cex_bid = Some(value.parse()?),
+                "--cex-ask" => cex_ask = Some(value.parse()?),
+                "--dex-price" => dex_price = Some(value.parse()?),
+                "--liquidity" => liquidity = Some(value.parse()?),
+                "--cex-qty" => inputs.cex_qty = value.parse()?,
+                "--gas-usdc" => inputs.gas_usdc = value.parse()?,
+                "--dex-fee-bps" => inputs.dex_fee_bps = value.parse()?,
+                "--cex-fee-bps" => inputs.cex_fee_bps = value.parse()?,
+                "--min-pnl" => inputs.min_pnl_usdc = value.parse()?,
+                other => return Err(AppError::Config(format!("unknown flag `{}`", other))),
+            }
+        }
+        match (cex_bid, cex_ask, dex_price, liquidity) {
+            (Some(cex_bid), Some(cex_ask), Some(dex_price), Some(liquidity)) => Ok(Self {
+                cex_bid,
+                cex_ask,
+                dex_price,
+                liquidity,
+                ..inputs
+            }),
+            _ => Err(AppError::Config(USAGE.to_string())),
+        }
+    }
+
+    fn config(&self) -> ArbitrageConfig {
+        ArbitrageConfig {
+            min_pnl_usdc: self.min_pnl_usdc,
+            dex_fee_bps: self.dex_fee_bps,
+            cex_fee_bps: self.cex_fee_bps,
+            ..Default::default()
+        }
+    }
+}
+
+/// Render the evaluation of `inputs` step by step.
+pub fn explain(inputs: &ExplainInputs) -> Result<String> {
+    let config = inputs.config();
+    config.validate()?;
+    let sqrt_price_x96 = calculate_sqrt_price_with_precision_per_eth(inputs.dex_price, 6, 18)?;
+    let pool = PoolState::new(
+        sqrt_price_x96,
+        inputs.liquidity,
+        0,
+        6,
+        18,
+        None,
+        None,
+        inputs.dex_price,
+    );
+    let book = BookDepth {
+        bids: vec![(inputs.cex_bid, inputs.cex_qty)],
+        asks: vec![(inputs.cex_ask, inputs.cex_qty)],
+        ..Default::default()
+    };
+
+    let mut out = String::new();
+    let _ = writeln!(out, "== Inputs ==");
+    let _ = writeln!(
+        out,
+        "CEX bid {:.2} / ask {:.2} ({} ETH each), DEX price {:.2}, liquidity {}",
+        inputs.cex_bid, inputs.cex_ask, inputs.cex_qty, inputs.dex_price, inputs.liquidity
+    );
+    let _ = writeln!(
+        out,
+        "Fees: DEX {} bps, CEX {} bps; gas {:.4} USDC per trade; min PnL {:.4} USDC",
+        inputs.dex_fee_bps, inputs.cex_fee_bps, inputs.gas_usdc, inputs.min_pnl_usdc
+    );
+
+    // Each direction as the evaluator sizes and prices it, whatever its PnL
+    let unfiltered = ArbitrageConfig {
+        min_pnl_usdc: f64::NEG_INFINITY,
+        ..config.clone()
+    };
+    for (direction, title) in [
+        ("A", "Direction A: buy ETH on the DEX, sell on the CEX"),
+        ("B", "Direction B: buy ETH on the CEX, sell on the DEX"),
+    ] {
+        let _ = writeln!(out, "\n== {} ==", title);
+        match evaluate_direction(&pool, &book, &unfiltered, inputs.gas_usdc, direction) {
+            Some(opp) => write_direction(&mut out, &opp, &config),
+            None => {
+                let _ = writeln!(
+                    out,
+                    "No trade: after fees the CEX price does not cross the pool's"
+                );
+            }
+        }
+    }
+
+    let _ = writeln!(out, "\n== Decision ==");
+    let opportunities = evaluate_opportunities(&pool, &book, &config, inputs.gas_usdc);
+    if opportunities.is_empty() {
+        let _ = writeln!(
+            out,
+            "No opportunity: neither direction clears {:.4} USDC after fees and gas",
+            inputs.min_pnl_usdc
+        );
+    }
+    for opp in &opportunities {
+        let _ = writeln!(
+            out,
+            "Emit {}: PnL {:.4} USDC ({})",
+            opp.direction, opp.pnl, opp.description
+        );
+    }
+    Ok(out)
+}
+
+/// The legs, costs and PnL of one direction's opportunity, and whether it
+/// clears `config`'s threshold.
+fn write_direction(out: &mut String, opp: &ArbitrageOpportunity, config: &ArbitrageConfig) {
+    let dex_usdc = opp.size_eth * opp.dex_vwap;
+    if opp.direction == "A" {
+        let _ = writeln!(
+            out,
+            "DEX leg: pay {:.6} USDC for {:.6} ETH at VWAP {:.2}",
+            dex_usdc, opp.size_eth, opp.dex_vwap
+        );
+        let _ = writeln!(
+            out,
+            "CEX leg: sell {:.6} ETH for {:.6} USDC before fees",
+            opp.size_eth, opp.notional_usdc
+        );
+    } else {
+        let _ = writeln!(
+            out,
+            "CEX leg: buy {:.6} ETH for {:.6} USDC before fees",
+            opp.size_eth, opp.notional_usdc
+        );
+        let _ = writeln!(
+            out,
+            "DEX leg: sell {:.6} ETH for {:.6} USDC at VWAP {:.2}",
+            opp.size_eth, dex_usdc, opp.dex_vwap
+        );
+    }
+    let _ = writeln!(out, "Fees: {:.4} USDC, DEX LP and CEX", opp.fees_usdc);
+    let _ = writeln!(out, "Gas: {:.4} USDC", opp.gas_cost_usdc);
+    let _ = writeln!(
+        out,
+        "PnL: {:.4} USDC before fees and gas, {:.4} after",
+        opp.gross_pnl_usdc, opp.pnl
+    );
+    // Net PnL is gross less fees and gas, so either cost alone can grow
+    // until it eats the rest
+    let _ = writeln!(
+        out,
+        "Break-even: net PnL reaches zero at {:.4} USDC of gas or {:.4} USDC of fees",
+        opp.gross_pnl_usdc - opp.fees_usdc,
+        opp.gross_pnl_usdc - opp.gas_cost_usdc
+    );
+    let verdict = if config.clears_net_edge(opp.pnl, opp.notional_usdc) {
+        "clears"
+    } else {
+        "misses"
+    };
+    let _ = writeln!(
+        out,
+        "Threshold: {} the {:.4} USDC minimum",
+        verdict, config.min_pnl_usdc
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(raw: &str) -> Vec<String> {
+        raw.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn explain_walks_through_every_step() {
+        let inputs = ExplainInputs::from_args(&args(
+            "--cex-bid 4225 --cex-ask 4230 --dex-price 4200 --liquidity 1800000000000000000 \
+             --gas-usdc 0.001",
+        ))
+        .unwrap();
+        assert_eq!((inputs.dex_fee_bps, inputs.cex_qty), (5.0, 10.0));
+        let text = explain(&inputs).unwrap();
+        for section in [
+            "== Inputs ==",
+            "== Direction A: buy ETH on the DEX, sell on the CEX ==",
+            "== Direction B: buy ETH on the CEX, sell on the DEX ==",
+            "DEX leg: pay",
+            "CEX leg: sell",
+            "Gas: 0.0010 USDC",
+            "Threshold: clears",
+            "== Decision ==",
+            "Emit A: PnL",
+        ] {
+            assert!(text.contains(section), "missing `{section}` in\n{text}");
+        }
+        assert!(!text.contains("Emit B"));
+
+        // The walkthrough shows the evaluator's own numbers for what it emits
+        let config = inputs.config();
+        let pool = PoolState::new(
+            calculate_sqrt_price_with_precision_per_eth(4200.0, 6, 18).unwrap(),
+            inputs.liquidity,
+            0,
+            6,
+            18,
+            None,
+            None,
+            4200.0,
+        );
+        let book = BookDepth {
+            bids: vec![(4225.0, 10.0)],
+            asks: vec![(4230.0, 10.0)],
+            ..Default::default()
+        };
+        let emitted = &evaluate_opportunities(&pool, &book, &config, 0.001)[0];
+        for line in [
+            format!(
+                "for {:.6} ETH at VWAP {:.2}",
+                emitted.size_eth, emitted.dex_vwap
+            ),
+            format!("Fees: {:.4} USDC", emitted.fees_usdc),
+            format!(
+                "PnL: {:.4} USDC before fees and gas, {:.4} after",
+                emitted.gross_pnl_usdc, emitted.pnl
+            ),
+            format!(
+                "Break-even: net PnL reaches zero at {:.4} USDC of gas or {:.4} USDC of fees",
+                emitted.pnl + emitted.gas_cost_usdc,
+                emitted.pnl + emitted.fees_usdc
+            ),
+        ] {
+            assert!(text.contains(&line), "missing `{line}` in\n{text}");
+        }
+
+        // A bid below break-even explains why nothing is emitted
+        let quiet = ExplainInputs {
+            cex_bid: 4199.0,
+            ..inputs
+        };
+        assert!(explain(&quiet).unwrap().contains("No opportunity"));
+        assert!(ExplainInputs::from_args(&args("--cex-bid 4225")).is_err());
+        assert!(ExplainInputs::from_args(&args("--cex-bid")).is_err());
+    }
+}
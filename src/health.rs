@@ -0,0 +1,507 @@
+//! One 0..1 system health score across every subsystem, for operators to
+//! alert on.
+//!
+//! Each subsystem is degraded by a fraction in [0, 1]: the CEX book, pool and
+//! gas by their data age over a limit, RPC by its error rate over a limit and
+//! the CEX feeds by their reconnects per minute over a limit. The score is one
+//! minus the weighted mean of those fractions, so 1 is fully healthy.
+
+use crate::cex::{FeedEvent, FeedUpdate};
+use crate::clock::Clock;
+use crate::dex::PoolState;
+use crate::errors::{AppError, Result};
+use crate::models::BookDepth;
+use crate::rate_limit::RateLimiter;
+use crate::sink::{JsonlSink, OpportunitySink};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
+
+/// Trailing window the reconnect frequency is counted over
+const RECONNECT_WINDOW_MS: u64 = 60_000;
+
+/// Weight of each subsystem in the score; only their ratios matter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HealthWeights {
+    pub cex: f64,
+    pub pool: f64,
+    pub gas: f64,
+    pub rpc_errors: f64,
+    pub reconnects: f64,
+}
+
+impl Default for HealthWeights {
+    fn default() -> Self {
+        Self {
+            cex: 0.3,
+            pool: 0.2,
+            gas: 0.1,
+            rpc_errors: 0.2,
+            reconnects: 0.2,
+        }
+    }
+}
+
+impl FromStr for HealthWeights {
+    type Err = AppError;
+
+    /// Weights from comma-separated `name=weight` entries, names being `cex`,
+    /// `pool`, `gas`, `rpc` and `reconnects`; subsystems left out keep their
+    /// default weight.
+    fn from_str(raw: &str) -> Result<Self> {
+        let mut weights = Self::default();
+        for entry in raw
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (name, weight) = entry.split_once('=').ok_or_else(|| {
+                AppError::Config(format!(
+                    "HEALTH_WEIGHTS entries must be name=weight, got `{}`",
+                    entry
+                ))
+            })?;
+            let (name, weight) = (name.trim().to_lowercase(), weight.trim());
+            let weight: f64 = weight.parse().map_err(|_| {
+                AppError::Config(format!(
+                    "HEALTH_WEIGHTS weight of `{}` must be a number, got `{}`",
+                    name, weight
+                ))
+            })?;
+            if weight < 0.0 || weight.is_nan() {
+                return Err(AppError::Config(format!(
+                    "HEALTH_WEIGHTS must be non-negative, got {}={}",
+                    name, weight
+                )));
+            }
+            let slot = match name.as_str() {
+                "cex" => &mut weights.cex,
+                "pool" => &mut weights.pool,
+                "gas" => &mut weights.gas,
+                "rpc" => &mut weights.rpc_errors,
+                "reconnects" => &mut weights.reconnects,
+                other => {
+                    return Err(AppError::Config(format!(
+                        "HEALTH_WEIGHTS names must be `cex`, `pool`, `gas`, `rpc` or `reconnects`, got `{}`",
+                        other
+                    )));
+                }
+            };
+            *slot = weight;
+        }
+        Ok(weights)
+    }
+}
+
+/// How the health score is computed and reported.
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    pub weights: HealthWeights,
+    /// Book age at which the CEX feed counts as fully degraded (0 = never)
+    pub cex_max_age_ms: u64,
+    /// Time without a pool update at which the pool counts as fully degraded
+    /// (0 = never)
+    pub pool_max_age_ms: u64,
+    /// Time without a gas update at which gas counts as fully degraded (0 = never)
+    pub gas_max_age_ms: u64,
+    /// Share of failed RPC requests at which RPC counts as fully degraded (0 = never)
+    pub max_rpc_error_rate: f64,
+    /// CEX reconnects per minute at which the feeds count as fully degraded
+    /// (0 = never)
+    pub max_reconnects_per_min: f64,
+    /// Warn while the score is below this
+    pub warn_below: f64,
+}
+
+impl HealthConfig {
+    /// Health in [0, 1] of subsystems in `states`; 1 when every weight is 0.
+    pub fn score(&self, states: &SubsystemStates) -> f64 {
+        let w = &self.weights;
+        let parts = [
+            (
+                w.cex,
+                degradation(states.cex_age_ms as f64, self.cex_max_age_ms as f64),
+            ),
+            (
+                w.pool,
+                degradation(states.pool_age_ms as f64, self.pool_max_age_ms as f64),
+            ),
+            (
+                w.gas,
+                degradation(states.gas_age_ms as f64, self.gas_max_age_ms as f64),
+            ),
+            (
+                w.rpc_errors,
+                degradation(states.rpc_error_rate, self.max_rpc_error_rate),
+            ),
+            (
+                w.reconnects,
+                degradation(states.reconnects_per_min, self.max_reconnects_per_min),
+            ),
+        ];
+        let total: f64 = parts.iter().map(|(weight, _)| weight).sum();
+        if total <= 0.0 {
+            return 1.0;
+        }
+        1.0 - parts
+            .iter()
+            .map(|(weight, degraded)| weight * degraded)
+            .sum::<f64>()
+            / total
+    }
+}
+
+/// `value` as a fraction of `limit`, at most 1; a limit of 0 never degrades.
+fn degradation(value: f64, limit: f64) -> f64 {
+    if limit > 0.0 {
+        (value / limit).clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+/// What each subsystem looks like at one instant.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct SubsystemStates {
+    /// Age of the oldest CEX book
+    pub cex_age_ms: u64,
+    /// Time since the least recently updated pool last updated
+    pub pool_age_ms: u64,
+    /// Time since the least recently updated gas price last updated
+    pub gas_age_ms: u64,
+    /// Share of RPC requests that failed since the previous observation
+    pub rpc_error_rate: f64,
+    /// CEX reconnects over the last minute
+    pub reconnects_per_min: f64,
+}
+
+/// One published health score and the states it was computed from.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HealthReport {
+    pub at_ms: u64,
+    pub score: f64,
+    pub states: SubsystemStates,
+}
+
+impl HealthReport {
+    /// Report of a fully healthy system, before the first observation.
+    pub fn healthy(at_ms: u64) -> Self {
+        Self {
+            at_ms,
+            score: 1.0,
+            states: SubsystemStates::default(),
+        }
+    }
+}
+
+/// The channels a [`HealthMonitor`] observes.
+#[derive(Debug)]
+pub struct HealthInputs {
+    pub cex_rxs: Vec<watch::Receiver<BookDepth>>,
+    pub pool_rxs: Vec<watch::Receiver<PoolState>>,
+    pub gas_rxs: Vec<watch::Receiver<f64>>,
+    /// Limiters of every RPC endpoint, which count request outcomes
+    pub rpc_limiters: Vec<Arc<RateLimiter>>,
+    pub feed_events: broadcast::Receiver<FeedUpdate>,
+}
+
+/// Turns [`HealthInputs`] into [`SubsystemStates`] and a score.
+#[derive(Debug)]
+pub struct HealthMonitor {
+    config: HealthConfig,
+    inputs: HealthInputs,
+    pool_updated_ms: Vec<u64>,
+    gas_updated_ms: Vec<u64>,
+    rpc_outcomes: (u64, u64),
+    reconnects_ms: VecDeque<u64>,
+}
+
+impl HealthMonitor {
+    /// Monitor of `inputs`, whose current pool and gas values count as
+    /// received at `now_ms`.
+    pub fn new(config: HealthConfig, inputs: HealthInputs, now_ms: u64) -> Self {
+        let rpc_outcomes = total_outcomes(&inputs.rpc_limiters);
+        Self {
+            config,
+            pool_updated_ms: vec![now_ms; inputs.pool_rxs.len()],
+            gas_updated_ms: vec![now_ms; inputs.gas_rxs.len()],
+            inputs,
+            rpc_outcomes,
+            reconnects_ms: VecDeque::new(),
+        }
+    }
+
+    /// Subsystem states at `now_ms`, folding in everything since the
+    /// previous observation.
+    pub fn observe(&mut self, now_ms: u64) -> SubsystemStates {
+        let cex_age_ms = self
+            .inputs
+            .cex_rxs
+            .iter()
+            .map(|rx| rx.borrow().age_ms(now_ms))
+            .max()
+            .unwrap_or(0);
+        let pool_age_ms = update_age(&mut self.inputs.pool_rxs, &mut self.pool_updated_ms, now_ms);
+        let gas_age_ms = update_age(&mut self.inputs.gas_rxs, &mut self.gas_updated_ms, now_ms);
+
+        let (requests, errors) = total_outcomes(&self.inputs.rpc_limiters);
+        let (new_requests, new_errors) = (
+            requests.saturating_sub(self.rpc_outcomes.0),
+            errors.saturating_sub(self.rpc_outcomes.1),
+        );
+        self.rpc_outcomes = (requests, errors);
+        let rpc_error_rate = if new_requests > 0 {
+            new_errors as f64 / new_requests as f64
+        } else {
+            0.0
+        };
+
+        loop {
+            match self.inputs.feed_events.try_recv() {
+                Ok(update) => {
+                    if matches!(update.event, FeedEvent::Reconnecting { .. }) {
+                        self.reconnects_ms.push_back(now_ms);
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
+        }
+        while self
+            .reconnects_ms
+            .front()
+            .is_some_and(|&at_ms| now_ms.saturating_sub(at_ms) >= RECONNECT_WINDOW_MS)
+        {
+            self.reconnects_ms.pop_front();
+        }
+
+        SubsystemStates {
+            cex_age_ms,
+            pool_age_ms,
+            gas_age_ms,
+            rpc_error_rate,
+            reconnects_per_min: self.reconnects_ms.len() as f64 * 60_000.0
+                / RECONNECT_WINDOW_MS as f64,
+        }
+    }
+
+    /// Observe at `now_ms` and score the result.
+    pub fn score(&mut self, now_ms: u64) -> (f64, SubsystemStates) {
+        let states = self.observe(now_ms);
+        (self.config.score(&states), states)
+    }
+}
+
+/// Requests and failures across `limiters`.
+fn total_outcomes(limiters: &[Arc<RateLimiter>]) -> (u64, u64) {
+    limiters.iter().fold((0, 0), |(requests, errors), limiter| {
+        let (r, e) = limiter.outcomes();
+        (requests + r, errors + e)
+    })
+}
+
+/// Time since the least recently updated of `rxs` was sent a value, marking
+/// those that were at `now_ms`.
+fn update_age<T>(rxs: &mut [watch::Receiver<T>], updated_ms: &mut [u64], now_ms: u64) -> u64 {
+    rxs.iter_mut()
+        .zip(updated_ms.iter_mut())
+        .map(|(rx, updated_ms)| {
+            if rx.has_changed().unwrap_or(false) {
+                rx.mark_unchanged();
+                *updated_ms = now_ms;
+            }
+            now_ms.saturating_sub(*updated_ms)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Publish the health report on `tx` every `interval`, warning as `[HEALTH]`
+/// when the score falls below `warn_below` and when it recovers.
+pub fn spawn_health_monitor(
+    mut monitor: HealthMonitor,
+    tx: watch::Sender<HealthReport>,
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        let mut unhealthy = false;
+        loop {
+            ticker.tick().await;
+            let at_ms = clock.now_ms();
+            let (score, states) = monitor.score(at_ms);
+            tracing::debug!(score, ?states, "[HEALTH] system health");
+            let below = score < monitor.config.warn_below;
+            if below != unhealthy {
+                unhealthy = below;
+                if below {
+                    tracing::warn!(score, ?states, "[HEALTH] system health degraded");
+                } else {
+                    tracing::info!(score, "[HEALTH] system health recovered");
+                }
+            }
+            tx.send_replace(HealthReport {
+                at_ms,
+                score,
+                states,
+            });
+        }
+    })
+}
+
+/// Append every health report published on `rx` to the JSONL file at
+/// `path`, one line per report, for dashboards and alerting.
+pub fn spawn_health_log(
+    mut rx: watch::Receiver<HealthReport>,
+    path: impl AsRef<Path>,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let log = JsonlSink::open(path)?;
+    Ok(tokio::spawn(async move {
+        while rx.changed().await.is_ok() {
+            let report = *rx.borrow_and_update();
+            if let Err(e) = log.append(&report).and_then(|()| log.flush()) {
+                tracing::warn!(error = %e, "[HEALTH] failed to write health report");
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cex::FeedEvents;
+    use crate::dex::state::fixtures::two_segments_up;
+
+    #[test]
+    fn score_falls_with_each_degraded_subsystem_and_recovers() {
+        let config = HealthConfig {
+            weights: HealthWeights::default(),
+            cex_max_age_ms: 5_000,
+            pool_max_age_ms: 30_000,
+            gas_max_age_ms: 60_000,
+            max_rpc_error_rate: 0.5,
+            max_reconnects_per_min: 3.0,
+            warn_below: 0.5,
+        };
+        let now = 1_000_000;
+        let book = |received_at_ms| BookDepth {
+            bids: vec![(4_000.0, 1.0)],
+            asks: vec![(4_001.0, 1.0)],
+            received_at_ms,
+            ..Default::default()
+        };
+        let (cex_tx, cex_rx) = watch::channel(book(now));
+        let (pool_tx, pool_rx) = watch::channel(two_segments_up());
+        let (gas_tx, gas_rx) = watch::channel(10.0);
+        let limiter = Arc::new(RateLimiter::unlimited());
+        let (events_tx, feed_events) = broadcast::channel(16);
+        let events = FeedEvents::new("ethusdc", events_tx);
+        let mut monitor = HealthMonitor::new(
+            config,
+            HealthInputs {
+                cex_rxs: vec![cex_rx],
+                pool_rxs: vec![pool_rx],
+                gas_rxs: vec![gas_rx],
+                rpc_limiters: vec![limiter.clone()],
+                feed_events,
+            },
+            now,
+        );
+
+        // Everything fresh and nothing failing
+        assert_eq!(monitor.score(now).0, 1.0);
+
+        // A 2.5 s old book is half way to its limit: 0.3 × 0.5 off
+        pool_tx.send(two_segments_up()).unwrap();
+        gas_tx.send(11.0).unwrap();
+        let (score, states) = monitor.score(now + 2_500);
+        assert_eq!(states.cex_age_ms, 2_500);
+        assert!((score - 0.85).abs() < 1e-9, "{score}");
+
+        // The pool and gas keep updating, the book goes fully stale, and a
+        // quarter of the RPC requests fail: 0.3 + 0.2 × 0.5 off
+        let t = now + 10_000;
+        pool_tx.send(two_segments_up()).unwrap();
+        gas_tx.send(12.0).unwrap();
+        for ok in [true, true, true, false] {
+            limiter.record(ok);
+        }
+        let (score, states) = monitor.score(t);
+        assert_eq!((states.pool_age_ms, states.gas_age_ms), (0, 0));
+        assert_eq!(states.rpc_error_rate, 0.25);
+        assert!((score - 0.6).abs() < 1e-9, "{score}");
+
+        // Reconnect storm and silent pool and gas on top: only RPC is healthy
+        for attempt in 1..=3 {
+            events.publish(FeedEvent::Reconnecting { attempt });
+        }
+        let (score, states) = monitor.score(t + 60_000);
+        assert_eq!(states.reconnects_per_min, 3.0);
+        assert_eq!(states.rpc_error_rate, 0.0);
+        assert!((score - 0.2).abs() < 1e-9, "{score}");
+
+        // Everything recovers once the feeds update and the storm ages out
+        let t = t + 130_000;
+        cex_tx.send(book(t)).unwrap();
+        pool_tx.send(two_segments_up()).unwrap();
+        gas_tx.send(11.0).unwrap();
+        let (score, states) = monitor.score(t);
+        assert_eq!(states.reconnects_per_min, 0.0);
+        assert_eq!(score, 1.0);
+    }
+
+    #[tokio::test]
+    async fn health_log_appends_every_published_report() {
+        let path = std::env::temp_dir().join(format!(
+            "health-{}-{}.jsonl",
+            std::process::id(),
+            crate::utils::now_ms()
+        ));
+        let (tx, rx) = watch::channel(HealthReport::healthy(0));
+        let handle = spawn_health_log(rx, &path).unwrap();
+        for (at_ms, score) in [(5_000, 0.85), (10_000, 0.6)] {
+            tx.send(HealthReport {
+                at_ms,
+                score,
+                states: SubsystemStates {
+                    cex_age_ms: at_ms,
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        drop(tx);
+        handle.await.unwrap();
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["at_ms"], 10_000);
+        assert_eq!(lines[1]["score"], 0.6);
+        assert_eq!(lines[1]["states"]["cex_age_ms"], 10_000);
+    }
+
+    #[test]
+    fn weights_parse_by_subsystem_name() {
+        let weights: HealthWeights = " rpc=1, CEX=0 ".parse().unwrap();
+        assert_eq!(weights.rpc_errors, 1.0);
+        assert_eq!(weights.cex, 0.0);
+        assert_eq!(weights.pool, HealthWeights::default().pool);
+
+        let error = |raw: &str| raw.parse::<HealthWeights>().unwrap_err().to_string();
+        assert!(error("disk=1").contains("HEALTH_WEIGHTS names"));
+        assert!(error("rpc").contains("name=weight"));
+        assert!(error("rpc=high").contains("must be a number"));
+        assert!(error("rpc=-1").contains("non-negative"));
+    }
+}
@@ -0,0 +1,44 @@
+//! Custom logic run after every evaluation pass.
+//!
+//! An [`EvaluationHook`] sees what a pass evaluated and the opportunities it
+//! is about to emit, and may filter, annotate or act on them, so extra rules
+//! or side effects do not need a fork of the aggregator. Hooks are registered
+//! with [`EvaluatorInputs::with_hook`](crate::aggregator::EvaluatorInputs::with_hook)
+//! and run in registration order, each seeing what the previous one left.
+
+use crate::arbitrage::{ArbitrageConfig, ArbitrageOpportunity, GasEstimate};
+use crate::dex::PoolState;
+use crate::models::BookDepth;
+
+/// What one evaluation pass ran on.
+#[derive(Debug, Clone, Copy)]
+pub struct EvaluationPass<'a> {
+    pub chain_id: u64,
+    /// Address of the evaluated pool
+    pub pool: &'a str,
+    /// Trigger count of the evaluator, skipped passes included
+    pub tick: u64,
+    /// Clock of the pass, which book freshness is judged against
+    pub now_ms: u64,
+    pub pool_state: &'a PoolState,
+    /// Book of every venue, fresh or not
+    pub books: &'a [(String, BookDepth)],
+    pub gas: GasEstimate,
+    /// Config the pass evaluated under (degraded thresholds, funding and
+    /// bias already applied)
+    pub config: &'a ArbitrageConfig,
+}
+
+/// Called after each evaluation pass that got as far as evaluating.
+pub trait EvaluationHook: Send + Sync {
+    /// Inspect or edit `opportunities`, the pass's output after hysteresis,
+    /// throttling and the notional budget, stamped with ids; whatever is left
+    /// is emitted. Throttle intervals and budget are only charged for what is
+    /// left, and a dropped opportunity has to clear the hysteresis entry
+    /// threshold again. Runs on the evaluator task, so it must not block.
+    fn after_evaluation(
+        &self,
+        pass: &EvaluationPass<'_>,
+        opportunities: &mut Vec<ArbitrageOpportunity>,
+    );
+}
@@ -1,9 +1,28 @@
 pub mod aggregator;
 pub mod arbitrage;
+pub mod backtest;
+pub mod bias;
+pub mod blackout;
 pub mod cex;
 pub mod cli;
+pub mod clock;
 pub mod config;
+pub mod cross_quote;
 pub mod dex;
 pub mod errors;
+pub mod evaluation_log;
+pub mod execution;
+pub mod explain;
+pub mod health;
+pub mod hooks;
+pub mod load_shed;
 pub mod models;
+pub mod oracle;
+pub mod rate_limit;
+pub mod reconcile;
+pub mod rng;
+pub mod shadow;
+pub mod sink;
+#[cfg(feature = "otel")]
+pub mod telemetry;
 pub mod utils;
@@ -0,0 +1,198 @@
+//! Shedding evaluation work when ticks run over a latency budget.
+//!
+//! Every pool's evaluator reports how long its pass took and whether it found
+//! an edge. Once a pass takes longer than the budget, pools in the less
+//! edge-prone half are skipped until the latest pass of every pool fits the
+//! budget again, so the hottest pools stay responsive instead of all of them
+//! falling behind.
+//! Venues of one pool are evaluated together and are never shed separately.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Weight of the latest pass in a pool's edge frequency
+const EDGE_RATE_ALPHA: f64 = 0.2;
+/// A shed pool is evaluated anyway after this many skipped ticks in a row
+const MAX_CONSECUTIVE_SKIPS: u32 = 10;
+
+#[derive(Debug, Default)]
+struct PoolLoad {
+    /// Moving average of passes that found an edge
+    edge_rate: f64,
+    skipped: u32,
+    /// Whether the pool's latest pass ran over the budget
+    over_budget: bool,
+}
+
+#[derive(Debug, Default)]
+struct ShedState {
+    pools: HashMap<String, PoolLoad>,
+}
+
+impl ShedState {
+    /// Whether any pool's latest pass ran over the budget, so one fast pool
+    /// does not lift the overload a slow one is still in.
+    fn overloaded(&self) -> bool {
+        self.pools.values().any(|load| load.over_budget)
+    }
+}
+
+/// Latency budget shared by the evaluators of all pools.
+#[derive(Debug, Clone)]
+pub struct LoadShedder {
+    budget: Duration,
+    state: Arc<Mutex<ShedState>>,
+}
+
+impl LoadShedder {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            state: Arc::default(),
+        }
+    }
+
+    /// Handle through which the evaluator of `pool` is scheduled.
+    pub fn for_pool(&self, pool: impl Into<String>) -> PoolShed {
+        let pool = pool.into();
+        self.state().pools.entry(pool.clone()).or_default();
+        PoolShed {
+            pool,
+            shedder: self.clone(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ShedState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// One pool's view of a [`LoadShedder`].
+#[derive(Debug, Clone)]
+pub struct PoolShed {
+    pool: String,
+    shedder: LoadShedder,
+}
+
+impl PoolShed {
+    /// Whether this pool is evaluated this tick; counts the skip when not.
+    pub fn should_evaluate(&self) -> bool {
+        let mut state = self.shedder.state();
+        if !state.overloaded() {
+            return true;
+        }
+        // Rank by edge frequency, ties broken by name so every pool agrees
+        let mut ranked: Vec<(&String, f64)> = state
+            .pools
+            .iter()
+            .map(|(pool, load)| (pool, load.edge_rate))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let keep = ranked.len().div_ceil(2);
+        let high_priority = ranked[..keep].iter().any(|(pool, _)| **pool == self.pool);
+
+        let load = state.pools.entry(self.pool.clone()).or_default();
+        if high_priority || load.skipped >= MAX_CONSECUTIVE_SKIPS {
+            load.skipped = 0;
+            return true;
+        }
+        load.skipped += 1;
+        false
+    }
+
+    /// Record a finished pass; returns `true` when it ran over the budget.
+    pub fn record(&self, elapsed: Duration, found_edge: bool) -> bool {
+        let mut state = self.shedder.state();
+        let over = elapsed > self.shedder.budget;
+        let was_overloaded = state.overloaded();
+        let load = state.pools.entry(self.pool.clone()).or_default();
+        load.over_budget = over;
+        let hit = if found_edge { 1.0 } else { 0.0 };
+        load.edge_rate += EDGE_RATE_ALPHA * (hit - load.edge_rate);
+        let overloaded = state.overloaded();
+        if overloaded != was_overloaded {
+            tracing::warn!(
+                pool = %self.pool,
+                elapsed_ms = elapsed.as_millis() as u64,
+                budget_ms = self.shedder.budget.as_millis() as u64,
+                overloaded,
+                "[SHED] evaluation budget state changed"
+            );
+        }
+        over
+    }
+
+    /// Run `evaluate` unless this pool is shed this tick, timing the pass and
+    /// counting a non-empty result as an edge.
+    pub fn run<T>(&self, evaluate: impl FnOnce() -> Vec<T>) -> Option<Vec<T>> {
+        if !self.should_evaluate() {
+            return None;
+        }
+        let started = Instant::now();
+        let found = evaluate();
+        self.record(started.elapsed(), !found.is_empty());
+        Some(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_pass_sheds_the_less_edge_prone_pools() {
+        let shedder = LoadShedder::new(Duration::from_millis(5));
+        let hot = shedder.for_pool("hot");
+        let cold = shedder.for_pool("cold");
+
+        // Within budget everything runs; only the hot pool finds edges
+        for _ in 0..5 {
+            assert_eq!(hot.run(|| vec![()]), Some(vec![()]));
+            assert_eq!(cold.run(Vec::<()>::new), Some(vec![]));
+        }
+
+        // An artificially slow pass puts the shedder over budget
+        let slow = hot.run(|| {
+            std::thread::sleep(Duration::from_millis(20));
+            vec![()]
+        });
+        assert!(slow.is_some());
+        assert_eq!(cold.run(|| vec![()]), None);
+        assert_eq!(cold.run(|| vec![()]), None);
+
+        // The hot pool keeps running and its fast pass lifts the shedding
+        assert!(hot.run(|| vec![()]).is_some());
+        assert!(cold.run(Vec::<()>::new).is_some());
+
+        // A pool shed for too long is evaluated anyway
+        assert!(hot.record(Duration::from_millis(20), true));
+        let runs = (0..=MAX_CONSECUTIVE_SKIPS)
+            .filter(|_| cold.should_evaluate())
+            .count();
+        assert_eq!(runs, 1);
+    }
+
+    #[test]
+    fn fast_pool_does_not_lift_another_pools_overrun() {
+        let shedder = LoadShedder::new(Duration::from_millis(5));
+        let (a, b, c) = (
+            shedder.for_pool("a"),
+            shedder.for_pool("b"),
+            shedder.for_pool("c"),
+        );
+        a.record(Duration::ZERO, true);
+        b.record(Duration::ZERO, true);
+
+        // The hot pool `a` runs over budget, then `b` runs fast: still overloaded
+        assert!(a.record(Duration::from_millis(20), true));
+        assert!(!b.record(Duration::ZERO, true));
+        assert!(!c.should_evaluate());
+
+        // Only once `a` fits the budget again is nothing shed
+        assert!(!a.record(Duration::ZERO, true));
+        assert!(c.should_evaluate());
+    }
+}
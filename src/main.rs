@@ -1,54 +1,760 @@
 use anyhow::Result;
+#[cfg(feature = "execution")]
+use arbitrage_detector::execution::HttpExecutionClient;
 use arbitrage_detector::{
-    aggregator::spawn_arbitrage_evaluator,
-    cex::spawn_cex_stream_watcher,
-    config::AppConfig,
-    dex::{Dex, init_pool_state_watcher},
-    utils::{init_logging, spawn_gas_price_watcher},
+    aggregator::{
+        EvalTrigger, EvaluatorInputs, NotionalBudget, OpportunityIds, spawn_arbitrage_evaluator,
+        spawn_pause_signal_listener,
+    },
+    backtest::{
+        ReplayChannels, ReplayClock, load_capture, load_channel_capture, merge_replay,
+        spawn_channel_capture, spawn_replay,
+    },
+    bias::spawn_direction_bias_watcher,
+    cex::{
+        BINANCE_FUTURES_SERVER_TIME_URL, ClockSkewEstimator, ConsolidatedBook, FeedEvents,
+        MockBookGenerator, spawn_feed_event_log, spawn_feed_watcher, spawn_mock_book_feed,
+        spawn_perp_mark_watcher, spawn_server_time_calibration,
+    },
+    cli::stdout_jsonl_requested,
+    clock::{Clock, SystemClock},
+    config::{AppConfig, CexMarket, EvalTriggerMode},
+    cross_quote::{QuoteFeeds, spawn_cross_quote_monitor},
+    dex::{
+        Dex, MetadataCache, PoolState, init_pool_state_watcher, spawn_block_pool_watcher,
+        spawn_token_price_watcher, spawn_twap_watcher,
+    },
+    errors::AppError,
+    evaluation_log::EvaluationLogSink,
+    execution::{Execution, SimulatedExecutionClient},
+    health::{HealthInputs, HealthMonitor, HealthReport, spawn_health_log, spawn_health_monitor},
+    load_shed::LoadShedder,
+    models::BookDepth,
+    oracle::spawn_oracle_divergence_monitor,
+    rate_limit::{RateLimiter, rate_limited_provider},
+    shadow::ShadowEvaluation,
+    sink::{
+        HourlyCapSink, JsonlSink, MarketSnapshotLog, MultiSink, OpportunitySink, StdoutJsonlSink,
+    },
+    utils::{GasSenders, GasSmoother, init_logging, spawn_gas_price_watcher_or_fallback},
 };
-use ethers::types::Address;
-use std::str::FromStr;
-use tokio::sync::watch;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
-    init_logging();
+    // Opportunities on stdout for pipelines; logs move to stderr
+    let stdout_jsonl = stdout_jsonl_requested(std::env::args().skip(1));
+    #[cfg(feature = "otel")]
+    let otel_provider = match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) => Some(arbitrage_detector::telemetry::init_logging_with_otlp(
+            &endpoint,
+            stdout_jsonl,
+        )?),
+        Err(_) => {
+            init_logging(stdout_jsonl);
+            None
+        }
+    };
+    #[cfg(not(feature = "otel"))]
+    init_logging(stdout_jsonl);
 
     // Configuration
     let config = AppConfig::try_load()?;
     let gas_config = config.gas_config;
     let arbitrage_config = config.arbitrage_config;
 
-    tracing::info!("[INIT] arbitrage-detector starting");
+    tracing::info!(seed = config.seed, "[INIT] arbitrage-detector starting");
 
     // Shared state channels
     let (cex_tx, cex_rx) = watch::channel::<arbitrage_detector::models::BookDepth>(
         arbitrage_detector::models::BookDepth::default(),
     );
 
-    // Initialize DEX
-    let dex = Dex::new(&config.rpc_url, Address::from_str(&config.pool_address)?).await?;
+    let (degraded_tx, degraded_rx) = watch::channel(false);
+    let (funding_tx, funding_rx) = watch::channel(0.0);
 
-    // Initialize pool state watcher
-    let initial_pool_state = dex.get_pool_state(6, 18, None, None).await?;
-    let (pool_tx, pool_rx) =
-        watch::channel::<arbitrage_detector::dex::PoolState>(initial_pool_state);
-    let _pool_handle = init_pool_state_watcher(&dex, pool_tx).await?;
+    // Direction bias from an inventory system, re-read from file when configured
+    let bias_rx = config.direction_bias_file.as_deref().map(|path| {
+        let (bias_tx, bias_rx) = watch::channel(arbitrage_config.direction_bias);
+        let _bias_handle =
+            spawn_direction_bias_watcher(path, std::time::Duration::from_secs(5), bias_tx);
+        tracing::info!(path, "[INIT] reading the direction bias from file");
+        bias_rx
+    });
 
-    // Initialize gas price watcher
-    let (gas_tx, gas_rx) = watch::channel::<f64>(0.0);
-    let _gas_handle = spawn_gas_price_watcher(&config.rpc_url, gas_tx.clone(), 10).await?;
-    tracing::info!("[INIT] gas watcher started (10s interval)");
+    // Structured feed health transitions, for dashboards
+    let (feed_events_tx, feed_events_rx) = broadcast::channel(256);
+    if let Some(path) = config.feed_event_log_path.as_deref() {
+        let _feed_event_handle = spawn_feed_event_log(feed_events_rx, path)?;
+        tracing::info!(path, "[INIT] writing feed events to JSONL");
+    }
+
+    // Operator pause, toggled with SIGUSR1 (`kill -USR1 <pid>`)
+    let (pause_tx, paused_rx) = watch::channel(config.start_paused);
+    #[cfg(unix)]
+    let _pause_handle = spawn_pause_signal_listener(pause_tx)?;
+    let perp = config.cex_market == CexMarket::Perp && !config.mock_cex_feed;
+
+    // A capture replay runs the pipeline on the capture's clock
+    let replay = match config.replay_capture_path.as_deref() {
+        Some(path) => {
+            let books = load_capture(path)?;
+            let start_ms = books.first().map_or(0, |book| book.received_at_ms);
+            let clock = Arc::new(ReplayClock::new(start_ms, config.replay_speed));
+            tracing::info!(path, books = books.len(), speed = ?config.replay_speed, "[INIT] replaying CEX capture");
+            let pools = match config.replay_pool_path.as_deref() {
+                Some(path) => {
+                    let pools = load_channel_capture::<PoolState>(path)?;
+                    tracing::info!(path, states = pools.len(), "[INIT] replaying pool capture");
+                    Some(pools)
+                }
+                None => None,
+            };
+            let gas = match config.replay_gas_path.as_deref() {
+                Some(path) => {
+                    let gas = load_channel_capture::<f64>(path)?;
+                    tracing::info!(path, prices = gas.len(), "[INIT] replaying gas capture");
+                    Some(gas)
+                }
+                None => None,
+            };
+            Some((books, pools, gas, clock))
+        }
+        None => None,
+    };
+    let clock: Arc<dyn Clock> = match &replay {
+        Some((_, _, _, replay_clock)) => replay_clock.clone(),
+        None => Arc::new(SystemClock),
+    };
+
+    // Opportunity sinks
+    let mut sinks = MultiSink::default();
+    let capped = |sink: Box<dyn OpportunitySink>| -> Box<dyn OpportunitySink> {
+        match config.sink_max_per_hour {
+            0 => sink,
+            cap => Box::new(HourlyCapSink::new(sink, cap, clock.clone())),
+        }
+    };
+    if let Some(path) = config.opportunity_log_path.as_deref() {
+        sinks.push(capped(Box::new(
+            JsonlSink::open(path)?.with_schema_version(config.opportunity_schema_version),
+        )));
+        tracing::info!(path, "[INIT] writing opportunities to JSONL");
+    }
+    if stdout_jsonl {
+        sinks.push(capped(Box::new(
+            StdoutJsonlSink::new().with_schema_version(config.opportunity_schema_version),
+        )));
+        tracing::info!("[INIT] streaming opportunities to stdout as JSONL");
+    }
+    match config.market_snapshot_log_path.as_deref() {
+        Some(path) => {
+            sinks.push(Box::new(MarketSnapshotLog::open(path)?));
+            tracing::info!(path, "[INIT] writing market snapshots to JSONL");
+        }
+        None if arbitrage_config.snapshot_interval_ms > 0 => {
+            tracing::warn!(
+                "[INIT] MARKET_SNAPSHOT_INTERVAL_MS is set but MARKET_SNAPSHOT_LOG_PATH is not; snapshots go nowhere"
+            );
+        }
+        None => {}
+    }
+    let sink: Arc<dyn OpportunitySink> = Arc::new(sinks);
+    #[cfg(feature = "execution")]
+    let execution = match (&config.execution_endpoint, &config.execution_hmac_key) {
+        (Some(endpoint), Some(key)) => {
+            let min_pnl_usdc = config.execution_min_pnl_usdc;
+            tracing::info!(%endpoint, min_pnl_usdc, "[INIT] submitting opportunities for execution");
+            Execution {
+                client: Arc::new(HttpExecutionClient::new(endpoint, key.as_bytes())?),
+                min_pnl_usdc,
+            }
+        }
+        _ => Execution::default(),
+    };
+    #[cfg(not(feature = "execution"))]
+    let execution = Execution::default();
+    let evaluation_log = match config.evaluation_log_path.as_deref() {
+        Some(path) => {
+            tracing::info!(path, "[INIT] logging every evaluation for replay");
+            let log: Arc<dyn EvaluationLogSink> = Arc::new(JsonlSink::open(path)?);
+            Some(log)
+        }
+        None => None,
+    };
+    let shadow = match (&config.shadow_overrides, config.shadow_log_path.as_deref()) {
+        (Some(overrides), Some(path)) => {
+            tracing::info!(path, ?overrides, "[INIT] shadow evaluation enabled");
+            Some(ShadowEvaluation {
+                overrides: overrides.clone(),
+                sink: Arc::new(JsonlSink::open(path)?),
+            })
+        }
+        _ => None,
+    };
+
+    // Venue books the evaluators read, optionally merged into one virtual venue
+    let feed = config.cex_exchange.feed(config.cex_stream);
+    let venue = if perp { "binance-perp" } else { feed.venue() };
+
+    // Live feeds measure book ages from the venue's clock, skew corrected
+    let server_time_url = if perp {
+        Some(BINANCE_FUTURES_SERVER_TIME_URL)
+    } else {
+        feed.server_time_url()
+    };
+    let calibrate = |url: Option<&'static str>| match url {
+        Some(url)
+            if config.clock_skew_server_time_secs > 0
+                && replay.is_none()
+                && !config.mock_cex_feed =>
+        {
+            tracing::info!(
+                url,
+                every_secs = config.clock_skew_server_time_secs,
+                "[INIT] calibrating the CEX clock skew against its server time"
+            );
+            let (skew_rx, _calibration_handle) = spawn_server_time_calibration(
+                url,
+                std::time::Duration::from_secs(config.clock_skew_server_time_secs),
+            );
+            Some(skew_rx)
+        }
+        _ => None,
+    };
+    let skew_with = |server_skew_rx: &Option<watch::Receiver<Option<i64>>>| {
+        (config.clock_skew_window > 0 || server_skew_rx.is_some()).then(|| {
+            let skew = ClockSkewEstimator::new(config.clock_skew_window);
+            match server_skew_rx {
+                Some(skew_rx) => skew.with_server_time(skew_rx.clone()),
+                None => skew,
+            }
+        })
+    };
+    let server_skew_rx = calibrate(server_time_url);
+    let skew_estimator = || skew_with(&server_skew_rx);
+    let primary_cex_rx = cex_rx.clone();
+    let mut venue_rxs = BTreeMap::from([(venue.to_string(), cex_rx)]);
+
+    // Every further exchange streams the first pool's pair as a venue of its
+    // own; its staleness is caught by the per-venue freshness check
+    if !config.cex_extra_exchanges.is_empty() && (replay.is_some() || config.mock_cex_feed) {
+        return Err(AppError::Config(
+            "CEX_EXTRA_EXCHANGES needs the live spot feed (no replay or mock)".to_string(),
+        )
+        .into());
+    }
+    for exchange in &config.cex_extra_exchanges {
+        let extra_feed = exchange.feed(config.cex_stream);
+        let (extra_cex_tx, extra_cex_rx) = watch::channel(BookDepth::default());
+        let (extra_degraded_tx, _extra_degraded_rx) = watch::channel(false);
+        let symbol = extra_feed.symbol("eth", &config.pools[0].quote.symbol);
+        let extra_skew_rx = calibrate(extra_feed.server_time_url());
+        let _extra_cex_handle = spawn_feed_watcher(
+            extra_feed.clone(),
+            &symbol,
+            extra_cex_tx,
+            config.feed_health_config.monitor(),
+            extra_degraded_tx,
+            FeedEvents::new(&symbol, feed_events_tx.clone()),
+            skew_with(&extra_skew_rx),
+        )
+        .await?;
+        tracing::info!(exchange = extra_feed.venue(), %symbol, "[INIT] streaming an extra CEX venue");
+        venue_rxs.insert(extra_feed.venue().to_string(), extra_cex_rx);
+    }
+    if config.consolidated_book {
+        let (merged_rx, _merge_handle) =
+            ConsolidatedBook::new(arbitrage_config.max_book_age_ms).spawn(venue_rxs);
+        venue_rxs = BTreeMap::from([("consolidated".to_string(), merged_rx)]);
+        tracing::info!("[INIT] evaluating against the consolidated CEX book");
+    }
+
+    // Pools quoted in another asset than the first pool each get their own
+    // ETH<quote> spot stream
+    let primary_quote = config.pools[0].quote.symbol.clone();
+    let mut quote_feeds = BTreeMap::from([(
+        primary_quote.clone(),
+        (venue_rxs, degraded_rx, primary_cex_rx),
+    )]);
+    for pool in &config.pools {
+        if quote_feeds.contains_key(&pool.quote.symbol) {
+            continue;
+        }
+        if replay.is_some() || config.mock_cex_feed || perp {
+            return Err(AppError::Config(format!(
+                "pools quoted in {} need the live spot feed (no replay, mock or perp)",
+                pool.quote.symbol
+            ))
+            .into());
+        }
+        let (quote_cex_tx, quote_cex_rx) = watch::channel(BookDepth::default());
+        let (quote_degraded_tx, quote_degraded_rx) = watch::channel(false);
+        let symbol = feed.symbol("eth", &pool.quote.symbol);
+        let _quote_cex_handle = spawn_feed_watcher(
+            feed.clone(),
+            &symbol,
+            quote_cex_tx,
+            config.feed_health_config.monitor(),
+            quote_degraded_tx,
+            FeedEvents::new(&symbol, feed_events_tx.clone()),
+            skew_estimator(),
+        )
+        .await?;
+        tracing::info!(quote = %pool.quote.symbol, "[INIT] streaming the CEX pair for an extra quote");
+        quote_feeds.insert(
+            pool.quote.symbol.clone(),
+            (
+                BTreeMap::from([(feed.venue().to_string(), quote_cex_rx.clone())]),
+                quote_degraded_rx,
+                quote_cex_rx,
+            ),
+        );
+    }
+    let mut quote_pool_rxs = BTreeMap::new();
+
+    let metadata_cache = config
+        .metadata_cache_path
+        .as_deref()
+        .map(MetadataCache::open)
+        .transpose()?;
+
+    // One DEX and gas pipeline per pool, all sharing the CEX feed, and one
+    // evaluator per pair: pools trading the same pair on one chain are
+    // evaluated together, per POOL_SELECTION, by the first one's evaluator
+    let mut evaluators: Vec<((u64, String), EvalTrigger, EvaluatorInputs, _)> = Vec::new();
+    // Ids are labelled with the start time so restarts never reuse one
+    let opportunity_ids = OpportunityIds::new(format!("{:x}", clock.now_ms()));
+    let mut anchor_price = None;
+    let mut quote_usd_rx = None;
+    let (mut replay_pool_tx, mut replay_gas_tx) = (None, None);
+    let mut replay_steps = Vec::new();
+    let mut captures = Vec::new();
+    let (mut health_pool_rxs, mut health_gas_rxs) = (Vec::new(), Vec::new());
+    // One request budget per RPC endpoint, shared by every pool and gas
+    // watcher on it
+    let mut rpc_limiters: HashMap<String, Arc<RateLimiter>> = HashMap::new();
+    let notional_budget = (config.max_inflight_notional_usdc > 0.0)
+        .then(|| NotionalBudget::new(config.max_inflight_notional_usdc, config.inflight_window_ms));
+    let load_shedder = (config.eval_budget_ms > 0)
+        .then(|| LoadShedder::new(std::time::Duration::from_millis(config.eval_budget_ms)));
+    let tick_lens = config
+        .tick_lens_address
+        .as_deref()
+        .map(|lens| {
+            lens.parse()
+                .map_err(|e| AppError::Config(format!("invalid TICK_LENS_ADDRESS {}: {}", lens, e)))
+        })
+        .transpose()?;
+    for (index, pool) in config.pools.iter().enumerate() {
+        let rpc_limiter = rpc_limiters
+            .entry(pool.rpc_url.clone())
+            .or_insert_with(|| Arc::new(RateLimiter::new(config.rpc_max_rps)))
+            .clone();
+        let dex = Dex::new(pool, rpc_limiter.clone(), metadata_cache.as_ref())
+            .await?
+            .with_segment_window_ticks(config.segment_window_ticks)
+            .with_tick_lens(tick_lens);
+        dex.check_price_round_trip(config.sqrt_round_trip_tolerance_bps)
+            .await?;
+        let (decimals0, decimals1) = dex.token_decimals();
+        if let Some(pool_fee_bps) = dex.metadata().map(|m| m.fee_bps())
+            && pool_fee_bps != arbitrage_config.dex_fee_bps
+        {
+            tracing::warn!(
+                pool_fee_bps,
+                dex_fee_bps = arbitrage_config.dex_fee_bps,
+                "[INIT] DEX_FEE_BPS differs from the pool's fee tier"
+            );
+        }
+
+        // USD price of the first pool's quote token, read from a stablecoin pool
+        if let (None, Some(reference)) = (&quote_usd_rx, config.usdc_reference_pool.as_deref()) {
+            let quote_token = dex
+                .metadata()
+                .map(|m| m.quote_token(dex.quote_side()).address)
+                .ok_or_else(|| AppError::Config("pool metadata not loaded".to_string()))?;
+            let reference_addr = reference.parse().map_err(|e| {
+                AppError::Config(format!("invalid USDC_REFERENCE_POOL {}: {}", reference, e))
+            })?;
+            let provider = Arc::new(rate_limited_provider(&pool.rpc_url, rpc_limiter.clone())?);
+            let reference_dex = Dex::connect(
+                provider,
+                reference_addr,
+                pool.chain_id,
+                metadata_cache.as_ref(),
+            )
+            .await?;
+            let usd_price = reference_dex.fetch_token_price(quote_token).await?;
+            tracing::info!(
+                reference,
+                usd_price,
+                "[INIT] pricing the quote token in USD"
+            );
+            let (usd_tx, usd_rx) = watch::channel(usd_price);
+            let _reference_handle = spawn_token_price_watcher(
+                reference_dex,
+                quote_token,
+                usd_tx,
+                std::time::Duration::from_secs(15),
+            );
+            quote_usd_rx = Some((pool.chain_id, pool.quote.symbol.clone(), usd_rx));
+        }
+
+        // Initialize pool state watcher, or the replay of its capture
+        let replayed_pool = replay
+            .as_ref()
+            .and_then(|(_, pools, _, _)| pools.as_ref())
+            .map(|pools| pools.first().map(|first| first.value.clone()));
+        let initial_pool_state = match replayed_pool.clone().flatten() {
+            Some(first) => first,
+            None => dex.get_pool_state(decimals0, decimals1, None, None).await?,
+        };
+        anchor_price.get_or_insert(initial_pool_state.price_usdc_per_eth);
+        let (pool_tx, pool_rx) = watch::channel::<PoolState>(initial_pool_state);
+        health_pool_rxs.push(pool_rx.clone());
+        quote_pool_rxs
+            .entry(pool.quote.symbol.clone())
+            .or_insert_with(|| pool_rx.clone());
+        if let Some(path) = config.capture_pool_path.as_deref()
+            && index == 0
+        {
+            tracing::info!(path, "[INIT] capturing pool states");
+            captures.push(spawn_channel_capture(
+                pool_rx.clone(),
+                JsonlSink::open(path)?,
+                clock.clone(),
+            ));
+        }
+
+        // Persistent divergence of the first pool and its CEX feed from a reference price
+        if let Some(oracle) = config.oracle_pool.as_deref()
+            && index == 0
+            && replayed_pool.is_none()
+        {
+            let base_token = dex
+                .metadata()
+                .map(|m| m.base_token(dex.quote_side()).address)
+                .ok_or_else(|| AppError::Config("pool metadata not loaded".to_string()))?;
+            let oracle_addr = oracle
+                .parse()
+                .map_err(|e| AppError::Config(format!("invalid ORACLE_POOL {}: {}", oracle, e)))?;
+            let provider = Arc::new(rate_limited_provider(&pool.rpc_url, rpc_limiter.clone())?);
+            let oracle_dex = Dex::connect(
+                provider,
+                oracle_addr,
+                pool.chain_id,
+                metadata_cache.as_ref(),
+            )
+            .await?;
+            let oracle_price = oracle_dex.fetch_token_price(base_token).await?;
+            tracing::info!(
+                oracle,
+                oracle_price,
+                tolerance_bps = config.oracle_divergence.tolerance_bps,
+                "[INIT] checking prices against the oracle pool"
+            );
+            let (oracle_tx, oracle_rx) = watch::channel(oracle_price);
+            let _oracle_price_handle = spawn_token_price_watcher(
+                oracle_dex,
+                base_token,
+                oracle_tx,
+                std::time::Duration::from_secs(15),
+            );
+            let _oracle_handle = spawn_oracle_divergence_monitor(
+                oracle_rx,
+                pool_rx.clone(),
+                quote_feeds[&pool.quote.symbol].2.clone(),
+                config.oracle_divergence,
+                clock.clone(),
+                std::time::Duration::from_secs(5),
+            );
+        }
+        let live_trigger = match (config.eval_trigger, pool.ws_rpc_url.as_deref()) {
+            _ if replayed_pool.is_some() => {
+                replay_pool_tx = Some(pool_tx);
+                None
+            }
+            (EvalTriggerMode::NewBlock, Some(ws_rpc_url)) => {
+                let (block_tx, block_rx) = watch::channel::<u64>(0);
+                let max_silence = (config.max_head_silence_ms > 0)
+                    .then(|| std::time::Duration::from_millis(config.max_head_silence_ms));
+                let _pool_handle =
+                    spawn_block_pool_watcher(&dex, ws_rpc_url, pool_tx, block_tx, max_silence)
+                        .await?;
+                Some(EvalTrigger::NewBlock(block_rx))
+            }
+            _ => {
+                let _pool_handle = init_pool_state_watcher(&dex, pool_tx).await?;
+                Some(EvalTrigger::every(std::time::Duration::from_secs(1)))
+            }
+        };
+        // A replay evaluates once per replayed event instead
+        let trigger = match live_trigger {
+            Some(trigger) if replay.is_none() => trigger,
+            _ => {
+                let (step_tx, step_rx) = tokio::sync::mpsc::channel(1);
+                replay_steps.push(step_tx);
+                EvalTrigger::replay_steps(step_rx)
+            }
+        };
+
+        // Initialize gas price watcher on the pool's own chain, or the replay
+        // of its capture
+        let replayed_gas = replay
+            .as_ref()
+            .and_then(|(_, _, gas, _)| gas.as_ref())
+            .map(|gas| gas.first().map_or(0.0, |first| first.value));
+        let (gas_tx, gas_rx) = watch::channel::<f64>(replayed_gas.unwrap_or(0.0));
+        health_gas_rxs.push(gas_rx.clone());
+        let raw_gas_rx = if replayed_gas.is_some() {
+            replay_gas_tx = Some(gas_tx);
+            None
+        } else {
+            let (raw_gas_tx, raw_gas_rx) = watch::channel(0.0);
+            let _gas_handle = spawn_gas_price_watcher_or_fallback(
+                &pool.rpc_url,
+                rpc_limiter,
+                GasSenders {
+                    smoothed: gas_tx.clone(),
+                    raw: raw_gas_tx,
+                },
+                10,
+                config.gas_missing_base_fee,
+                GasSmoother::new(config.gas_smoothing, config.gas_smoothing_blocks),
+                config.gas_fallback_gwei,
+            )
+            .await?;
+            Some(raw_gas_rx)
+        };
+        if let Some(path) = config.capture_gas_path.as_deref()
+            && index == 0
+        {
+            tracing::info!(path, "[INIT] capturing gas prices");
+            captures.push(spawn_channel_capture(
+                gas_rx.clone(),
+                JsonlSink::open(path)?,
+                clock.clone(),
+            ));
+        }
+
+        // The pool's own TWAP, when the spot price is gated on it
+        let twap_rx = if arbitrage_config.max_twap_divergence_bps > 0.0 && replayed_pool.is_none() {
+            // A pool with less observation history than the window has no TWAP
+            // yet; the gate stays open until the watcher reads one
+            let twap = match dex.fetch_twap_price(config.twap_window_secs).await {
+                Ok(twap) => {
+                    tracing::info!(
+                        twap,
+                        window_secs = config.twap_window_secs,
+                        "[INIT] gating on the pool TWAP"
+                    );
+                    twap
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        error = %e,
+                        window_secs = config.twap_window_secs,
+                        "[INIT] pool TWAP unavailable; not gating on it until it can be read"
+                    );
+                    0.0
+                }
+            };
+            let (twap_tx, twap_rx) = watch::channel(twap);
+            let _twap_handle = spawn_twap_watcher(
+                dex.clone(),
+                config.twap_window_secs,
+                twap_tx,
+                std::time::Duration::from_secs(15),
+            );
+            Some(twap_rx)
+        } else {
+            None
+        };
+
+        // Arbitrage evaluator against the CEX pair in the pool's quote
+        let (venue_rxs, degraded_rx, _) = &quote_feeds[&pool.quote.symbol];
+        let cex_rxs = venue_rxs.clone();
+        // Named by its address, as opportunity legs and evaluation logs carry it
+        let pool_name = format!("{:#x}", dex.address());
+        // Check the DEX leg against the pool's own state before it is executed
+        let pool_execution = if config.simulate_execution && replay.is_none() {
+            Execution {
+                client: Arc::new(SimulatedExecutionClient {
+                    simulator: Arc::new(dex.clone()),
+                    inner: execution.client.clone(),
+                    pool: pool_name.clone(),
+                    tolerance_bps: config.simulation_tolerance_bps,
+                }),
+                ..execution.clone()
+            }
+        } else {
+            execution.clone()
+        };
+        let inputs = EvaluatorInputs {
+            chain_id: pool.chain_id,
+            pool: pool_name.clone(),
+            cex_rxs,
+            pool_rx,
+            gas_rx,
+            raw_gas_rx,
+            degraded_rx: degraded_rx.clone(),
+            paused_rx: paused_rx.clone(),
+            funding_rx: perp.then(|| funding_rx.clone()),
+            bias_rx: bias_rx.clone(),
+            clock: clock.clone(),
+            ids: opportunity_ids.clone(),
+            quote_usd_rx: quote_usd_rx
+                .as_ref()
+                .filter(|(chain_id, quote, _)| {
+                    *chain_id == pool.chain_id && *quote == pool.quote.symbol
+                })
+                .map(|(_, _, rx)| rx.clone()),
+            twap_rx,
+            shadow: shadow.clone(),
+            evaluation_log: evaluation_log.clone(),
+            execution: pool_execution,
+            notional_budget: notional_budget.clone(),
+            // Library users register theirs with `EvaluatorInputs::with_hook`
+            hooks: Vec::new(),
+            load_shed: None,
+            sibling_pools: Vec::new(),
+        };
+        let pair = (pool.chain_id, pool.quote.symbol.clone());
+        match evaluators
+            .iter_mut()
+            .find(|(leader_pair, ..)| *leader_pair == pair)
+        {
+            Some((_, _, leader, _)) => {
+                tracing::info!(
+                    pool = %inputs.pool,
+                    evaluated_with = %leader.pool,
+                    selection = ?arbitrage_config.pool_selection,
+                    "[INIT] pool shares the evaluator of its pair"
+                );
+                leader.sibling_pools.push((inputs.pool, inputs.pool_rx));
+            }
+            None => {
+                let load_shed = load_shedder
+                    .as_ref()
+                    .map(|shedder| shedder.for_pool(format!("{}:{}", pool.chain_id, pool_name)));
+                let inputs = EvaluatorInputs {
+                    load_shed,
+                    ..inputs
+                };
+                evaluators.push((pair, trigger, inputs, gas_config.for_chain(pool.chain_id)));
+            }
+        }
+        tracing::info!(
+            chain_id = pool.chain_id,
+            pool = %pool.pool_address,
+            trigger = ?config.eval_trigger,
+            "[INIT] pool pipeline started (gas every 10s)"
+        );
+    }
+
+    let mut evaluator_tasks = Vec::new();
+    for (_, trigger, inputs, gas_config) in evaluators {
+        evaluator_tasks.push(
+            spawn_arbitrage_evaluator(
+                trigger,
+                inputs,
+                gas_config,
+                arbitrage_config.clone(),
+                sink.clone(),
+            )
+            .await,
+        );
+    }
+
+    // One health score over every feed, pool, gas price and RPC endpoint
+    let health_inputs = HealthInputs {
+        cex_rxs: quote_feeds.values().map(|(_, _, rx)| rx.clone()).collect(),
+        pool_rxs: health_pool_rxs,
+        gas_rxs: health_gas_rxs,
+        rpc_limiters: rpc_limiters.into_values().collect(),
+        feed_events: feed_events_tx.subscribe(),
+    };
+    let (health_tx, health_rx) = watch::channel(HealthReport::healthy(clock.now_ms()));
+    if let Some(path) = config.health_log_path.as_deref() {
+        let _health_log_handle = spawn_health_log(health_rx, path)?;
+        tracing::info!(path, "[INIT] writing health reports to JSONL");
+    }
+    let _health_handle = spawn_health_monitor(
+        HealthMonitor::new(config.health_config.clone(), health_inputs, clock.now_ms()),
+        health_tx,
+        clock.clone(),
+        std::time::Duration::from_secs(5),
+    );
+
+    if config.cross_quote_basis {
+        let feeds = |quote: &String| QuoteFeeds {
+            quote: quote.clone(),
+            pool_rx: quote_pool_rxs[quote].clone(),
+            cex_rx: quote_feeds[quote].2.clone(),
+        };
+        for quote in quote_feeds.keys().filter(|quote| **quote != primary_quote) {
+            let _basis_handle = spawn_cross_quote_monitor(
+                feeds(&primary_quote),
+                feeds(quote),
+                std::time::Duration::from_secs(10),
+            );
+        }
+    }
 
     // Spawn producer tasks
-    let cex_task = spawn_cex_stream_watcher("ethusdc", cex_tx).await?;
+    let cex_task = if let Some((books, pools, gas, replay_clock)) = replay {
+        let channels = ReplayChannels {
+            cex_tx,
+            pool_tx: replay_pool_tx,
+            gas_tx: replay_gas_tx,
+            steps: replay_steps,
+        };
+        let events = merge_replay(books, pools.unwrap_or_default(), gas.unwrap_or_default());
+        spawn_replay(events, channels, replay_clock)
+    } else if config.mock_cex_feed {
+        let generator = MockBookGenerator::new(config.seed, anchor_price.unwrap_or(4_000.0));
+        tracing::info!("[INIT] using the mock CEX feed");
+        spawn_mock_book_feed(generator, cex_tx, std::time::Duration::from_millis(100))
+    } else if perp {
+        tracing::info!("[INIT] evaluating against the ETHUSDT perpetual mark price");
+        let events = FeedEvents::new("ethusdt-perp", feed_events_tx);
+        spawn_perp_mark_watcher("ethusdt", cex_tx, funding_tx, skew_estimator(), events).await?
+    } else {
+        let symbol = feed.symbol("eth", &primary_quote);
+        let events = FeedEvents::new(&symbol, feed_events_tx);
+        tracing::info!(exchange = feed.venue(), %symbol, "[INIT] streaming the CEX book");
+        spawn_feed_watcher(
+            feed,
+            &symbol,
+            cex_tx,
+            config.feed_health_config.monitor(),
+            degraded_tx,
+            events,
+            skew_estimator(),
+        )
+        .await?
+    };
 
-    // Spawn arbitrage evaluator
-    let _evaluator_task =
-        spawn_arbitrage_evaluator(cex_rx, pool_rx, gas_rx, gas_config, arbitrage_config).await;
+    // Run until the producers stop (they never finish) or Ctrl-C arrives
+    tokio::select! {
+        _ = cex_task => {}
+        _ = tokio::signal::ctrl_c() => tracing::info!("[SHUTDOWN] interrupt received"),
+    }
 
-    // Wait indefinitely for producer tasks (they never finish)
-    let _ = futures::join!(cex_task);
+    // Stop emitting before flushing so no record lands after the sinks close
+    for task in evaluator_tasks.into_iter().chain(captures) {
+        task.abort();
+        let _ = task.await;
+    }
+    sink.close()?;
+    tracing::info!("[SHUTDOWN] sinks flushed");
+    #[cfg(feature = "otel")]
+    if let Some(provider) = otel_provider {
+        provider.shutdown()?;
+    }
     Ok(())
 }
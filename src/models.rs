@@ -1,10 +1,32 @@
+use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
 /// Depth snapshot (top N levels per side).
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BookDepth {
     pub timestamp: u64,
     /// (price, qty) pairs best → worst
     pub bids: Vec<(f64, f64)>,
     pub asks: Vec<(f64, f64)>,
+    /// Local receive time in unix milliseconds (0 if never received)
+    pub received_at_ms: u64,
+    /// When the venue produced the update, in its own clock, unix ms (0 when
+    /// the stream carries no such timestamp)
+    #[serde(default)]
+    pub event_time_ms: u64,
+    /// Estimated venue clock minus local clock; once set, the book's age is
+    /// measured from `event_time_ms` corrected by it
+    #[serde(default)]
+    pub clock_skew_ms: Option<i64>,
+    /// Set by the feed while it is reconnecting; the levels are the last
+    /// pre-disconnect snapshot and must not be traded on
+    #[serde(default)]
+    pub stale: bool,
+    /// The levels exactly as the exchange sent them, kept in exact precision
+    /// mode; `bids`/`asks` are then their f64 view
+    #[serde(skip)]
+    pub exact: Option<Arc<DecimalLevels>>,
 }
 
 impl Default for BookDepth {
@@ -13,15 +35,187 @@ impl Default for BookDepth {
             timestamp: 0,
             bids: Vec::new(),
             asks: Vec::new(),
+            received_at_ms: 0,
+            event_time_ms: 0,
+            clock_skew_ms: None,
+            stale: false,
+            exact: None,
+        }
+    }
+}
+
+impl BookDepth {
+    /// Milliseconds since the venue produced this book, in local time, when
+    /// its clock skew is known; otherwise since it was received.
+    pub fn age_ms(&self, now_ms: u64) -> u64 {
+        match self.clock_skew_ms {
+            Some(skew_ms) if self.event_time_ms > 0 => {
+                let produced_ms = self.event_time_ms as i64 - skew_ms;
+                (now_ms as i64).saturating_sub(produced_ms).max(0) as u64
+            }
+            _ => now_ms.saturating_sub(self.received_at_ms),
+        }
+    }
+
+    /// Quote value of `size_eth` at the best bid (`bid`) or best ask, gross
+    /// and with `fee_bps` taken off the bid or added to the ask.
+    ///
+    /// With exact levels the products are taken in BigDecimal and rounded to
+    /// f64 once, so the decimal price carries no representation error.
+    pub fn top_value(&self, bid: bool, size_eth: f64, fee_bps: f64) -> (f64, f64) {
+        let fee_sign = if bid { -1.0 } else { 1.0 };
+        let exact = self
+            .exact
+            .as_ref()
+            .and_then(|levels| {
+                if bid {
+                    levels.bids.first()
+                } else {
+                    levels.asks.first()
+                }
+            })
+            .and_then(|(price, _)| {
+                let size = BigDecimal::from_f64(size_eth)?;
+                let fee = BigDecimal::from_f64(fee_sign * fee_bps)? / BigDecimal::from(10_000);
+                let gross = price * size;
+                let net = &gross * (BigDecimal::from(1) + fee);
+                Some((gross.to_f64()?, net.to_f64()?))
+            });
+        exact.unwrap_or_else(|| {
+            let price = if bid { self.bids[0].0 } else { self.asks[0].0 };
+            let adjusted = price * (1.0 + fee_sign * fee_bps / 10_000.0);
+            (price * size_eth, adjusted * size_eth)
+        })
+    }
+
+    /// [`Self::top_value`] walking the bid (`bid`) or ask levels best first,
+    /// for a size that may run past the top level; whatever exceeds the
+    /// listed depth is priced at the last level.
+    pub fn depth_value(&self, bid: bool, size_eth: f64, fee_bps: f64) -> (f64, f64) {
+        let levels = if bid { &self.bids } else { &self.asks };
+        if levels.first().is_none_or(|&(_, qty)| size_eth <= qty) {
+            return self.top_value(bid, size_eth, fee_bps);
+        }
+        let mut remaining = size_eth;
+        let mut gross = 0.0;
+        for (i, &(price, qty)) in levels.iter().enumerate() {
+            let take = if i + 1 == levels.len() {
+                remaining
+            } else {
+                remaining.min(qty)
+            };
+            gross += price * take;
+            remaining -= take;
+            if remaining <= 0.0 {
+                break;
+            }
+        }
+        let fee_sign = if bid { -1.0 } else { 1.0 };
+        (gross, gross * (1.0 + fee_sign * fee_bps / 10_000.0))
+    }
+}
+
+/// A (price, qty) level as sent by the exchange.
+pub type DecimalLevel = (BigDecimal, BigDecimal);
+
+/// Decimal levels, best → worst.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecimalLevels {
+    pub bids: Vec<DecimalLevel>,
+    pub asks: Vec<DecimalLevel>,
+}
+
+/// Per-tick statistics derived from the received CEX depth.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BookStats {
+    /// Best bid / best ask midpoint
+    pub mid: f64,
+    /// Best ask minus best bid, in bps of the mid
+    pub spread_bps: f64,
+    /// Total bid quantity over the considered levels
+    pub bid_volume: f64,
+    /// Total ask quantity over the considered levels
+    pub ask_volume: f64,
+    /// (bid − ask) / (bid + ask) volume in [-1, 1]; positive means buying pressure
+    pub imbalance: f64,
+}
+
+impl BookStats {
+    /// Compute stats over the top `levels` of each side (0 means all levels).
+    ///
+    /// Returns `None` when either side of the book is empty.
+    pub fn from_books<'a>(
+        books: impl IntoIterator<Item = &'a BookDepth>,
+        levels: usize,
+    ) -> Option<Self> {
+        let take = if levels == 0 { usize::MAX } else { levels };
+        let mut best_bid = f64::MIN;
+        let mut best_ask = f64::MAX;
+        let mut bid_volume = 0.0;
+        let mut ask_volume = 0.0;
+        for book in books {
+            let (Some(&(bid, _)), Some(&(ask, _))) = (book.bids.first(), book.asks.first()) else {
+                continue;
+            };
+            best_bid = best_bid.max(bid);
+            best_ask = best_ask.min(ask);
+            bid_volume += book.bids.iter().take(take).map(|(_, qty)| qty).sum::<f64>();
+            ask_volume += book.asks.iter().take(take).map(|(_, qty)| qty).sum::<f64>();
+        }
+        if best_bid == f64::MIN || best_ask == f64::MAX {
+            return None;
         }
+
+        let mid = (best_bid + best_ask) / 2.0;
+        let total = bid_volume + ask_volume;
+        Some(Self {
+            mid,
+            spread_bps: (best_ask - best_bid) / mid * 10_000.0,
+            bid_volume,
+            ask_volume,
+            imbalance: if total > 0.0 {
+                (bid_volume - ask_volume) / total
+            } else {
+                0.0
+            },
+        })
     }
 }
 
+/// Periodic view of one pool's market, recorded whether or not an edge
+/// exists so the basis can be studied as a time series.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "record", rename = "market_snapshot")]
+pub struct MarketSnapshot {
+    pub chain_id: u64,
+    /// When the snapshot was taken, unix milliseconds
+    pub taken_at_ms: u64,
+    /// DEX marginal price, USDC per ETH
+    pub dex_price: f64,
+    /// Best bid and ask across the fresh CEX books
+    pub cex_bid: f64,
+    pub cex_ask: f64,
+    pub cex_mid: f64,
+    /// CEX mid over the DEX price in bps; positive when the CEX is richer
+    pub basis_bps: f64,
+    pub gas_gwei: f64,
+    /// Latest block's gas price before smoothing; `gas_gwei` when unsmoothed
+    #[serde(default)]
+    pub raw_gas_gwei: f64,
+    /// Active liquidity of the pool, raw
+    pub liquidity: u128,
+}
+
 #[derive(Debug, Clone)]
 pub struct SwapResult {
     pub amount_in: f64,
     pub amount_out: f64,
     pub hit_boundary: bool,
+    /// Volume-weighted execution price of the whole fill in USDC per ETH,
+    /// LP fee included (0 for an empty fill)
+    pub avg_price: f64,
+    /// Segment boundaries (initialized ticks) the fill walked across
+    pub ticks_crossed: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -33,3 +227,33 @@ pub enum SwapDirection {
     /// When CEX price < DEX price, sell ETH on DEX (ETH→USDC) to profit
     Token1ToToken0,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imbalance_reflects_skewed_book() {
+        let book = BookDepth {
+            bids: vec![(100.0, 6.0), (99.9, 3.0), (99.8, 50.0)],
+            asks: vec![(100.1, 1.0), (100.2, 2.0), (100.3, 50.0)],
+            ..Default::default()
+        };
+
+        // Top two levels: 9 bid vs 3 ask => (9 - 3) / 12 = 0.5
+        let stats = BookStats::from_books([&book], 2).unwrap();
+        assert!((stats.imbalance - 0.5).abs() < 1e-12);
+        assert!((stats.mid - 100.05).abs() < 1e-12);
+
+        // Mirror the book: same magnitude, opposite sign
+        let mirrored = BookDepth {
+            bids: book.asks.clone(),
+            asks: book.bids.clone(),
+            ..Default::default()
+        };
+        let stats = BookStats::from_books([&mirrored], 2).unwrap();
+        assert!((stats.imbalance + 0.5).abs() < 1e-12);
+
+        assert!(BookStats::from_books([&BookDepth::default()], 2).is_none());
+    }
+}
@@ -0,0 +1,182 @@
+//! Cross-checks of live DEX/CEX prices against a reference (oracle) price.
+//!
+//! A brief gap between a venue and the oracle is what arbitrage looks like; a
+//! gap that persists usually means something is broken (a depeg, a
+//! manipulated pool, a misconfigured feed), so it raises a distinct alert.
+
+use crate::clock::Clock;
+use crate::dex::PoolState;
+use crate::models::BookDepth;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Tolerance and persistence required before a divergence is alerted on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DivergenceConfig {
+    /// Largest tolerated gap to the oracle, in bps of the oracle price
+    pub tolerance_bps: f64,
+    /// How long the gap must persist before alerting
+    pub min_duration_ms: u64,
+}
+
+/// A price that has stayed away from the oracle for too long.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DivergenceAlert {
+    pub source: &'static str,
+    pub price: f64,
+    pub oracle_price: f64,
+    pub divergence_bps: f64,
+    /// How long the divergence has lasted when the alert fired
+    pub duration_ms: u64,
+}
+
+/// Tracks one price source against the oracle.
+///
+/// Alerts once per divergence episode, when the gap has exceeded the
+/// tolerance continuously for `min_duration_ms`; coming back within tolerance
+/// resets the episode.
+#[derive(Debug, Clone)]
+pub struct DivergenceMonitor {
+    source: &'static str,
+    config: DivergenceConfig,
+    diverged_since_ms: Option<u64>,
+    alerted: bool,
+}
+
+impl DivergenceMonitor {
+    pub fn new(source: &'static str, config: DivergenceConfig) -> Self {
+        Self {
+            source,
+            config,
+            diverged_since_ms: None,
+            alerted: false,
+        }
+    }
+
+    /// Feed one observation; returns an alert when the divergence has just
+    /// become persistent. Unusable prices (zero, negative, NaN) are ignored.
+    pub fn observe(
+        &mut self,
+        price: f64,
+        oracle_price: f64,
+        now_ms: u64,
+    ) -> Option<DivergenceAlert> {
+        if !(price > 0.0 && oracle_price > 0.0) {
+            return None;
+        }
+        let divergence_bps = (price - oracle_price).abs() / oracle_price * 10_000.0;
+        if divergence_bps <= self.config.tolerance_bps {
+            self.diverged_since_ms = None;
+            self.alerted = false;
+            return None;
+        }
+
+        let since = *self.diverged_since_ms.get_or_insert(now_ms);
+        let duration_ms = now_ms.saturating_sub(since);
+        if self.alerted || duration_ms < self.config.min_duration_ms {
+            return None;
+        }
+        self.alerted = true;
+        Some(DivergenceAlert {
+            source: self.source,
+            price,
+            oracle_price,
+            divergence_bps,
+            duration_ms,
+        })
+    }
+}
+
+/// Distance of a stablecoin's USD price from its $1 peg, in bps.
+pub fn depeg_bps(usd_price: f64) -> f64 {
+    (usd_price - 1.0).abs() * 10_000.0
+}
+
+/// Gap between the pool's spot price and its own TWAP, in bps of the TWAP;
+/// `None` while either price is unusable.
+///
+/// Spot far from the TWAP means the pool moved a lot within the window,
+/// which is what a manipulated spot price looks like.
+pub fn twap_divergence_bps(spot_price: f64, twap_price: f64) -> Option<f64> {
+    (spot_price > 0.0 && twap_price > 0.0 && spot_price.is_finite() && twap_price.is_finite())
+        .then(|| (spot_price - twap_price).abs() / twap_price * 10_000.0)
+}
+
+/// Check the pool price and the CEX mid against `oracle_rx` every `interval`,
+/// logging an `[ORACLE]` error for each persistent divergence.
+pub fn spawn_oracle_divergence_monitor(
+    oracle_rx: watch::Receiver<f64>,
+    pool_rx: watch::Receiver<PoolState>,
+    cex_rx: watch::Receiver<BookDepth>,
+    config: DivergenceConfig,
+    clock: Arc<dyn Clock>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut dex = DivergenceMonitor::new("dex", config);
+        let mut cex = DivergenceMonitor::new("cex", config);
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let now = clock.now_ms();
+            let oracle_price = *oracle_rx.borrow();
+            let dex_price = pool_rx.borrow().price_usdc_per_eth;
+            let cex_mid = {
+                let book = cex_rx.borrow();
+                match (book.bids.first(), book.asks.first()) {
+                    (Some(&(bid, _)), Some(&(ask, _))) => (bid + ask) / 2.0,
+                    _ => 0.0,
+                }
+            };
+            let alerts = [
+                dex.observe(dex_price, oracle_price, now),
+                cex.observe(cex_mid, oracle_price, now),
+            ];
+            for alert in alerts.into_iter().flatten() {
+                tracing::error!(
+                    source = alert.source,
+                    price = alert.price,
+                    oracle_price = alert.oracle_price,
+                    divergence_bps = alert.divergence_bps,
+                    duration_ms = alert.duration_ms,
+                    "[ORACLE] persistent divergence from oracle price"
+                );
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sustained_divergence_alerts_only_after_duration() {
+        let mut monitor = DivergenceMonitor::new(
+            "dex",
+            DivergenceConfig {
+                tolerance_bps: 50.0,
+                min_duration_ms: 10_000,
+            },
+        );
+
+        // 1% off the oracle, but only briefly
+        assert_eq!(monitor.observe(4_040.0, 4_000.0, 0), None);
+        assert_eq!(monitor.observe(4_040.0, 4_000.0, 5_000), None);
+        assert_eq!(monitor.observe(4_001.0, 4_000.0, 6_000), None);
+
+        // Sustained from t=7s: nothing until 10s have elapsed, then one alert
+        assert_eq!(monitor.observe(4_040.0, 4_000.0, 7_000), None);
+        assert_eq!(monitor.observe(4_040.0, 4_000.0, 16_999), None);
+        let alert = monitor.observe(4_040.0, 4_000.0, 17_000).expect("alert");
+        assert_eq!(alert.duration_ms, 10_000);
+        assert!((alert.divergence_bps - 100.0).abs() < 1e-9);
+        assert_eq!(monitor.observe(4_040.0, 4_000.0, 30_000), None);
+
+        // Recovering re-arms it for the next episode
+        assert_eq!(monitor.observe(4_000.0, 4_000.0, 31_000), None);
+        assert_eq!(monitor.observe(3_900.0, 4_000.0, 32_000), None);
+        assert!(monitor.observe(3_900.0, 4_000.0, 42_000).is_some());
+    }
+}
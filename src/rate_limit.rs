@@ -0,0 +1,196 @@
+//! Outbound RPC rate limiting.
+//!
+//! Every HTTP JSON-RPC request goes through a shared [`RateLimiter`] so pool
+//! reads, gas polling and any other calls together stay under the node
+//! provider's requests-per-second budget.
+
+use crate::clock::{Clock, SystemClock};
+use async_trait::async_trait;
+use ethers::providers::{Http, JsonRpcClient, Provider};
+use serde::{Serialize, de::DeserializeOwned};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// HTTP provider whose requests share a [`RateLimiter`].
+pub type RpcProvider = Provider<RateLimitedClient<Http>>;
+
+/// Build an HTTP provider for `rpc_url` that draws from `limiter`.
+pub fn rate_limited_provider(
+    rpc_url: &str,
+    limiter: Arc<RateLimiter>,
+) -> crate::errors::Result<RpcProvider> {
+    let http = Http::new(url::Url::parse(rpc_url)?);
+    Ok(Provider::new(RateLimitedClient::new(http, limiter)))
+}
+
+/// Token bucket allowing bursts of up to `rps` requests, refilled at `rps` per second.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rps: f64,
+    tokens: f64,
+    last_ms: Option<u64>,
+}
+
+impl TokenBucket {
+    pub fn new(rps: f64) -> Self {
+        Self {
+            rps,
+            tokens: rps,
+            last_ms: None,
+        }
+    }
+
+    /// Take a token at `now_ms`, or return how long to wait until one is available.
+    pub fn try_acquire(&mut self, now_ms: u64) -> Result<(), Duration> {
+        if let Some(last_ms) = self.last_ms {
+            let elapsed_ms = now_ms.saturating_sub(last_ms) as f64;
+            self.tokens = (self.tokens + elapsed_ms * self.rps / 1000.0).min(self.rps);
+        }
+        self.last_ms = Some(now_ms);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait_ms = ((1.0 - self.tokens) * 1000.0 / self.rps).ceil();
+            Err(Duration::from_millis(wait_ms as u64))
+        }
+    }
+}
+
+/// Shared requests-per-second limiter; a limit of 0 disables throttling.
+///
+/// It also counts the outcomes of the requests it admitted, for the RPC
+/// error rate of the system health score.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Option<Mutex<TokenBucket>>,
+    clock: Arc<dyn Clock>,
+    requests: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl RateLimiter {
+    pub fn new(max_rps: f64) -> Self {
+        Self::with_clock(max_rps, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(max_rps: f64, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            bucket: (max_rps > 0.0).then(|| Mutex::new(TokenBucket::new(max_rps))),
+            clock,
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// Limiter that never throttles.
+    pub fn unlimited() -> Self {
+        Self::new(0.0)
+    }
+
+    /// Take a permit now, or return how long until the next one frees up.
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        match &self.bucket {
+            Some(bucket) => bucket
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .try_acquire(self.clock.now_ms()),
+            None => Ok(()),
+        }
+    }
+
+    /// Count a request made under this limiter, failed unless `ok`.
+    pub fn record(&self, ok: bool) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        if !ok {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Requests and failed requests recorded so far.
+    pub fn outcomes(&self) -> (u64, u64) {
+        (
+            self.requests.load(Ordering::Relaxed),
+            self.errors.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Wait until a permit is available.
+    pub async fn acquire(&self) {
+        while let Err(wait) = self.try_acquire() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// JSON-RPC transport that waits on a [`RateLimiter`] before every request.
+#[derive(Debug, Clone)]
+pub struct RateLimitedClient<C> {
+    inner: C,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<C> RateLimitedClient<C> {
+    pub fn new(inner: C, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+#[async_trait]
+impl<C: JsonRpcClient> JsonRpcClient for RateLimitedClient<C> {
+    type Error = C::Error;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: std::fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        self.limiter.acquire().await;
+        let result = self.inner.request(method, params).await;
+        self.limiter.record(result.is_ok());
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn bursts_beyond_rate_are_throttled() {
+        let clock = Arc::new(MockClock::new(1_000));
+        let limiter = RateLimiter::with_clock(5.0, clock.clone());
+
+        // A burst of the configured size goes straight through
+        for _ in 0..5 {
+            assert!(limiter.try_acquire().is_ok());
+        }
+        // The next one must wait one refill period (1s / 5)
+        assert_eq!(limiter.try_acquire(), Err(Duration::from_millis(200)));
+
+        clock.advance(100);
+        assert_eq!(limiter.try_acquire(), Err(Duration::from_millis(100)));
+
+        clock.advance(100);
+        assert!(limiter.try_acquire().is_ok());
+        assert!(limiter.try_acquire().is_err());
+
+        // An idle period refills the bucket, but never beyond the burst size
+        clock.advance(10_000);
+        for _ in 0..5 {
+            assert!(limiter.try_acquire().is_ok());
+        }
+        assert!(limiter.try_acquire().is_err());
+    }
+
+    #[test]
+    fn zero_limit_never_throttles() {
+        let limiter = RateLimiter::unlimited();
+        for _ in 0..1_000 {
+            assert!(limiter.try_acquire().is_ok());
+        }
+    }
+}
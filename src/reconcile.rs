@@ -0,0 +1,276 @@
+//! Reconciliation of detected opportunities against actual fills.
+//!
+//! Takes the JSONL opportunity log written by the sink and a JSONL file of
+//! fills (one [`Fill`] per line, keyed by opportunity id) and reports which
+//! opportunities were acted on, realized against predicted PnL, and how the
+//! gap splits into DEX slippage, CEX slippage and a smaller traded size.
+
+use crate::arbitrage::ArbitrageOpportunity;
+use crate::errors::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::Path;
+
+/// One executed trade of a detected opportunity; several fills may share an id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fill {
+    /// `id` of the opportunity acted on
+    pub opportunity_id: String,
+    /// ETH traded on each leg
+    pub size_eth: f64,
+    /// Average USDC-per-ETH price of the DEX leg, LP fee included
+    pub dex_price: f64,
+    /// Average USDC-per-ETH price of the CEX leg
+    pub cex_price: f64,
+    /// PnL after all fees and gas, in quote units
+    pub realized_pnl: f64,
+}
+
+/// Predicted against realized outcome of one acted-on opportunity.
+///
+/// Every cost is positive when it made the trade worse than predicted; what
+/// the three do not explain (fees, gas, rounding) is left in `unexplained`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reconciliation {
+    pub opportunity_id: String,
+    pub direction: String,
+    pub venue: String,
+    pub predicted_pnl: f64,
+    pub realized_pnl: f64,
+    /// Worse DEX price than the predicted `dex_vwap`, over the filled size
+    pub dex_slippage: f64,
+    /// Worse CEX price than quoted at detection, over the filled size
+    pub cex_slippage: f64,
+    /// Predicted PnL given up by filling less than the detected size
+    pub size_shortfall: f64,
+    pub unexplained: f64,
+}
+
+/// Outcome of reconciling a whole opportunity log.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ReconciliationReport {
+    pub acted_on: Vec<Reconciliation>,
+    /// Detected opportunities without any fill
+    pub not_acted_on: Vec<String>,
+    /// Fill ids matching no detected opportunity
+    pub unknown_fills: Vec<String>,
+    /// Predicted PnL of the acted-on opportunities only
+    pub predicted_pnl: f64,
+    pub realized_pnl: f64,
+}
+
+/// Load a JSONL file, skipping blank lines.
+pub fn load_jsonl<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<Vec<T>> {
+    let file = std::fs::File::open(path)?;
+    std::io::BufReader::new(file)
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+/// Match `fills` to `opportunities` by id and attribute the PnL gap.
+pub fn reconcile(opportunities: &[ArbitrageOpportunity], fills: &[Fill]) -> ReconciliationReport {
+    // Several partial fills of one opportunity count as one size-weighted fill
+    let mut merged: BTreeMap<&str, Fill> = BTreeMap::new();
+    for fill in fills {
+        let entry = merged
+            .entry(fill.opportunity_id.as_str())
+            .or_insert_with(|| Fill {
+                opportunity_id: fill.opportunity_id.clone(),
+                size_eth: 0.0,
+                dex_price: 0.0,
+                cex_price: 0.0,
+                realized_pnl: 0.0,
+            });
+        let size = entry.size_eth + fill.size_eth;
+        if size > 0.0 {
+            entry.dex_price =
+                (entry.dex_price * entry.size_eth + fill.dex_price * fill.size_eth) / size;
+            entry.cex_price =
+                (entry.cex_price * entry.size_eth + fill.cex_price * fill.size_eth) / size;
+        }
+        entry.size_eth = size;
+        entry.realized_pnl += fill.realized_pnl;
+    }
+
+    let mut report = ReconciliationReport::default();
+    for opp in opportunities {
+        let Some(fill) = merged.remove(opp.id.as_str()) else {
+            report.not_acted_on.push(opp.id.clone());
+            continue;
+        };
+        let quoted_cex_price = if opp.size_eth > 0.0 {
+            opp.notional_usdc / opp.size_eth
+        } else {
+            0.0
+        };
+        // Direction A buys on the DEX and sells on the CEX, B the reverse
+        let sign = if opp.direction == "A" { 1.0 } else { -1.0 };
+        let dex_slippage = sign * (fill.dex_price - opp.dex_vwap) * fill.size_eth;
+        let cex_slippage = sign * (quoted_cex_price - fill.cex_price) * fill.size_eth;
+        let size_shortfall = if opp.size_eth > 0.0 {
+            opp.pnl * (1.0 - fill.size_eth / opp.size_eth)
+        } else {
+            0.0
+        };
+        let unexplained =
+            opp.pnl - fill.realized_pnl - dex_slippage - cex_slippage - size_shortfall;
+
+        report.predicted_pnl += opp.pnl;
+        report.realized_pnl += fill.realized_pnl;
+        report.acted_on.push(Reconciliation {
+            opportunity_id: opp.id.clone(),
+            direction: opp.direction.clone(),
+            venue: opp.venue.clone(),
+            predicted_pnl: opp.pnl,
+            realized_pnl: fill.realized_pnl,
+            dex_slippage,
+            cex_slippage,
+            size_shortfall,
+            unexplained,
+        });
+    }
+    report.unknown_fills = merged.into_keys().map(str::to_string).collect();
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn fills_file_reconciles_against_opportunity_log() {
+        let dir = std::env::temp_dir();
+        let opps_path = dir.join(format!("reconcile-opps-{}.jsonl", std::process::id()));
+        let fills_path = dir.join(format!("reconcile-fills-{}.jsonl", std::process::id()));
+        let opps = [
+            // Buy 2 ETH on the DEX at 4000, sell on the CEX at 4010
+            ArbitrageOpportunity {
+                id: "run-1".to_string(),
+                direction: "A".to_string(),
+                venue: "binance".to_string(),
+                pnl: 20.0,
+                size_eth: 2.0,
+                notional_usdc: 8_020.0,
+                dex_vwap: 4_000.0,
+                ..Default::default()
+            },
+            // Buy 1 ETH on the CEX at 3990, sell on the DEX at 4000
+            ArbitrageOpportunity {
+                id: "run-2".to_string(),
+                direction: "B".to_string(),
+                venue: "binance".to_string(),
+                pnl: 10.0,
+                size_eth: 1.0,
+                notional_usdc: 3_990.0,
+                dex_vwap: 4_000.0,
+                ..Default::default()
+            },
+            ArbitrageOpportunity {
+                id: "run-3".to_string(),
+                direction: "A".to_string(),
+                pnl: 5.0,
+                ..Default::default()
+            },
+        ];
+        let opps_log: Vec<String> = opps
+            .iter()
+            .map(|opp| serde_json::to_string(opp).unwrap())
+            .collect();
+        std::fs::write(&opps_path, opps_log.join("\n")).unwrap();
+        std::fs::write(
+            &fills_path,
+            r#"{"opportunity_id":"run-1","size_eth":1.0,"dex_price":4001.0,"cex_price":4009.0,"realized_pnl":7.0}
+{"opportunity_id":"run-1","size_eth":1.0,"dex_price":4003.0,"cex_price":4009.0,"realized_pnl":5.0}
+
+{"opportunity_id":"run-2","size_eth":0.5,"dex_price":3998.0,"cex_price":3990.0,"realized_pnl":3.5}
+{"opportunity_id":"manual-7","size_eth":1.0,"dex_price":4000.0,"cex_price":4000.0,"realized_pnl":0.0}
+"#,
+        )
+        .unwrap();
+
+        let opportunities: Vec<ArbitrageOpportunity> = load_jsonl(&opps_path).unwrap();
+        let fills: Vec<Fill> = load_jsonl(&fills_path).unwrap();
+        std::fs::remove_file(&opps_path).unwrap();
+        std::fs::remove_file(&fills_path).unwrap();
+        let report = reconcile(&opportunities, &fills);
+
+        assert_eq!(report.not_acted_on, vec!["run-3".to_string()]);
+        assert_eq!(report.unknown_fills, vec!["manual-7".to_string()]);
+        assert_close(report.predicted_pnl, 30.0);
+        assert_close(report.realized_pnl, 15.5);
+
+        // run-1: DEX filled 2 USDC/ETH dearer on average, CEX 1 cheaper, full size
+        let first = &report.acted_on[0];
+        assert_eq!(first.opportunity_id, "run-1");
+        assert_close(first.realized_pnl, 12.0);
+        assert_close(first.dex_slippage, 4.0);
+        assert_close(first.cex_slippage, 2.0);
+        assert_close(first.size_shortfall, 0.0);
+        assert_close(first.unexplained, 2.0);
+
+        // run-2: sold on the DEX 2 lower at half the size, CEX as quoted
+        let second = &report.acted_on[1];
+        assert_close(second.dex_slippage, 1.0);
+        assert_close(second.cex_slippage, 0.0);
+        assert_close(second.size_shortfall, 5.0);
+        assert_close(second.unexplained, 0.5);
+    }
+
+    #[test]
+    fn opportunity_log_reconciles_with_snapshots_enabled() {
+        use crate::models::MarketSnapshot;
+        use crate::sink::{JsonlSink, MarketSnapshotLog, MultiSink, OpportunitySink};
+
+        let dir = std::env::temp_dir();
+        let opps_path = dir.join(format!("reconcile-snap-opps-{}.jsonl", std::process::id()));
+        let snaps_path = dir.join(format!("reconcile-snap-snaps-{}.jsonl", std::process::id()));
+        for path in [&opps_path, &snaps_path] {
+            let _ = std::fs::remove_file(path);
+        }
+        // The sinks main wires up with both logs configured
+        let sink = MultiSink::new(vec![
+            Box::new(JsonlSink::open(&opps_path).unwrap()),
+            Box::new(MarketSnapshotLog::open(&snaps_path).unwrap()),
+        ]);
+        let snapshot = MarketSnapshot {
+            chain_id: 1,
+            taken_at_ms: 0,
+            dex_price: 4_000.0,
+            cex_bid: 4_010.0,
+            cex_ask: 4_011.0,
+            cex_mid: 4_010.5,
+            basis_bps: 26.0,
+            gas_gwei: 10.0,
+            raw_gas_gwei: 12.0,
+            liquidity: 1,
+        };
+        for i in 1..=2 {
+            sink.record_snapshot(&snapshot).unwrap();
+            sink.record(&ArbitrageOpportunity {
+                id: format!("run-{}", i),
+                direction: "A".to_string(),
+                pnl: 10.0,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        sink.close().unwrap();
+
+        let opportunities: Vec<ArbitrageOpportunity> = load_jsonl(&opps_path).unwrap();
+        let snapshots: Vec<MarketSnapshot> = load_jsonl(&snaps_path).unwrap();
+        std::fs::remove_file(&opps_path).unwrap();
+        std::fs::remove_file(&snaps_path).unwrap();
+        assert_eq!(snapshots, vec![snapshot.clone(), snapshot]);
+
+        let report = reconcile(&opportunities, &[]);
+        assert_eq!(report.not_acted_on, vec!["run-1", "run-2"]);
+    }
+}
@@ -0,0 +1,255 @@
+//! Shadow evaluation of a candidate config next to the live one.
+//!
+//! Every pass is evaluated a second time under the live config with a few
+//! settings overridden (e.g. `min_pnl_usdc=5,cex_fee_bps=8`), on the very same
+//! pool, books and gas. Passes where the two disagree on what to emit are
+//! recorded as [`ShadowDiff`]s, so a change can be judged before going live.
+
+use crate::arbitrage::{
+    ArbitrageConfig, ArbitrageOpportunity, GasEstimate, evaluate_across_venues,
+};
+use crate::dex::PoolState;
+use crate::errors::{AppError, Result};
+use crate::models::BookDepth;
+use crate::sink::{JsonlSink, OpportunitySink};
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Settings a candidate config changes relative to the live one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides(pub Vec<(String, f64)>);
+
+impl ConfigOverrides {
+    const KEYS: [&'static str; 7] = [
+        "min_pnl_usdc",
+        "min_pnl_bps",
+        "dex_fee_bps",
+        "cex_fee_bps",
+        "min_basis_bps",
+        "max_target_move_bps",
+        "max_ticks_traversed",
+    ];
+
+    /// `live` with the overrides applied.
+    pub fn apply(&self, live: &ArbitrageConfig) -> ArbitrageConfig {
+        let mut config = live.clone();
+        for (key, value) in &self.0 {
+            match key.as_str() {
+                "min_pnl_usdc" => config.min_pnl_usdc = *value,
+                "min_pnl_bps" => config.min_pnl_bps = *value,
+                "dex_fee_bps" => config.dex_fee_bps = *value,
+                "cex_fee_bps" => config.cex_fee_bps = *value,
+                "min_basis_bps" => config.min_basis_bps = *value,
+                "max_target_move_bps" => config.max_target_move_bps = *value,
+                "max_ticks_traversed" => config.max_ticks_traversed = *value as usize,
+                _ => unreachable!("override keys are checked when parsed"),
+            }
+        }
+        config
+    }
+}
+
+impl FromStr for ConfigOverrides {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> Result<Self> {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (key, value) = entry.split_once('=').ok_or_else(|| {
+                    AppError::Config(format!(
+                        "shadow override must be `key=value`, got `{}`",
+                        entry
+                    ))
+                })?;
+                let key = key.trim().to_lowercase();
+                if !Self::KEYS.contains(&key.as_str()) {
+                    return Err(AppError::Config(format!(
+                        "unknown shadow override `{}` (expected one of {})",
+                        key,
+                        Self::KEYS.join(", ")
+                    )));
+                }
+                Ok((key, value.trim().parse()?))
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+/// Which config alone emitted an opportunity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Divergence {
+    /// The live config emits it, the candidate would not
+    LiveOnly,
+    /// The candidate would emit it, the live config filters it
+    ShadowOnly,
+}
+
+/// One opportunity the live and candidate configs disagree on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShadowDiff {
+    pub at_ms: u64,
+    pub chain_id: u64,
+    pub direction: String,
+    pub venue: String,
+    pub divergence: Divergence,
+    /// PnL of the config that emitted it
+    pub pnl: f64,
+    pub size_eth: f64,
+}
+
+/// Destination for shadow diffs, kept apart from real opportunities.
+pub trait ShadowSink: Send + Sync {
+    fn record(&self, diff: &ShadowDiff) -> Result<()>;
+}
+
+impl ShadowSink for JsonlSink {
+    fn record(&self, diff: &ShadowDiff) -> Result<()> {
+        // Diffs are rare and nothing flushes this sink on shutdown
+        self.append(diff)?;
+        OpportunitySink::flush(self)
+    }
+}
+
+/// The candidate config of a shadow evaluation and where its diffs go.
+#[derive(Clone)]
+pub struct ShadowEvaluation {
+    pub overrides: ConfigOverrides,
+    pub sink: Arc<dyn ShadowSink>,
+}
+
+impl ShadowEvaluation {
+    /// Evaluate one pass under `live` and under the candidate, record every
+    /// opportunity only one of them emits, and return those diffs.
+    pub fn compare(
+        &self,
+        pool_state: &PoolState,
+        books: &[(String, BookDepth)],
+        live: &ArbitrageConfig,
+        gas: GasEstimate,
+        now_ms: u64,
+        chain_id: u64,
+    ) -> Vec<ShadowDiff> {
+        let candidate = self.overrides.apply(live);
+        let live_opps = evaluate_across_venues(pool_state, books, live, gas, now_ms);
+        let shadow_opps = evaluate_across_venues(pool_state, books, &candidate, gas, now_ms);
+
+        let only_in = |opps: &[ArbitrageOpportunity],
+                       others: &[ArbitrageOpportunity],
+                       divergence: Divergence| {
+            opps.iter()
+                .filter(|opp| {
+                    !others
+                        .iter()
+                        .any(|other| other.signature() == opp.signature())
+                })
+                .map(|opp| ShadowDiff {
+                    at_ms: now_ms,
+                    chain_id,
+                    direction: opp.direction.clone(),
+                    venue: opp.venue.clone(),
+                    divergence,
+                    pnl: opp.pnl,
+                    size_eth: opp.size_eth,
+                })
+                .collect::<Vec<_>>()
+        };
+        let mut diffs = only_in(&live_opps, &shadow_opps, Divergence::LiveOnly);
+        diffs.extend(only_in(&shadow_opps, &live_opps, Divergence::ShadowOnly));
+
+        for diff in &diffs {
+            if let Err(e) = self.sink.record(diff) {
+                tracing::warn!(error = %e, "[SHADOW] failed to record diff");
+            }
+        }
+        diffs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dex::calc::calculate_sqrt_price_with_precision_per_eth;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemorySink(Mutex<Vec<ShadowDiff>>);
+
+    impl ShadowSink for MemorySink {
+        fn record(&self, diff: &ShadowDiff) -> Result<()> {
+            self.0.lock().unwrap().push(diff.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn min_pnl_difference_is_recorded_as_divergence() {
+        let sqrt_price_x96 = calculate_sqrt_price_with_precision_per_eth(4_000.0, 6, 18).unwrap();
+        let pool = PoolState::new(
+            sqrt_price_x96,
+            20_000_000_000_000_000_000,
+            0,
+            6,
+            18,
+            None,
+            None,
+            4_000.0,
+        );
+        let books = vec![(
+            "binance".to_string(),
+            BookDepth {
+                bids: vec![(4_020.0, 1e9)],
+                asks: vec![(4_030.0, 1e9)],
+                ..Default::default()
+            },
+        )];
+        let live = ArbitrageConfig::default();
+        let pnl = evaluate_across_venues(&pool, &books, &live, GasEstimate::default(), 0)[0].pnl;
+        assert!(pnl > 0.0);
+
+        // A candidate demanding more than the edge pays would have stayed quiet
+        let sink = Arc::new(MemorySink::default());
+        let stricter = ShadowEvaluation {
+            overrides: format!("min_pnl_usdc={}", pnl * 2.0).parse().unwrap(),
+            sink: sink.clone(),
+        };
+        let diffs = stricter.compare(&pool, &books, &live, GasEstimate::default(), 7, 1);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].divergence, Divergence::LiveOnly);
+        assert_eq!(diffs[0].direction, "A");
+        assert_eq!(diffs[0].venue, "binance");
+        assert_eq!(diffs[0].pnl, pnl);
+        assert_eq!((diffs[0].at_ms, diffs[0].chain_id), (7, 1));
+
+        // ...and the other way round when the live config is the stricter one
+        let strict_live = ArbitrageConfig {
+            min_pnl_usdc: pnl * 2.0,
+            ..Default::default()
+        };
+        let looser = ShadowEvaluation {
+            overrides: "min_pnl_usdc=0".parse().unwrap(),
+            sink: sink.clone(),
+        };
+        let diffs = looser.compare(&pool, &books, &strict_live, GasEstimate::default(), 8, 1);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].divergence, Divergence::ShadowOnly);
+
+        // Identical configs agree, and only the diffs reached the sink
+        let same = ShadowEvaluation {
+            overrides: ConfigOverrides::default(),
+            sink: sink.clone(),
+        };
+        assert!(
+            same.compare(&pool, &books, &live, GasEstimate::default(), 9, 1)
+                .is_empty()
+        );
+        assert_eq!(sink.0.lock().unwrap().len(), 2);
+
+        assert!("min_pnl_usdc".parse::<ConfigOverrides>().is_err());
+        assert!("gas_units=1".parse::<ConfigOverrides>().is_err());
+    }
+}
@@ -0,0 +1,432 @@
+//! Destinations for emitted arbitrage opportunities and market snapshots.
+
+use crate::arbitrage::{ArbitrageOpportunity, OPPORTUNITY_SCHEMA_VERSION};
+use crate::clock::Clock;
+use crate::errors::Result;
+use crate::models::MarketSnapshot;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Something that records emitted opportunities.
+///
+/// Sinks may buffer; [`OpportunitySink::close`] must be called on shutdown so
+/// the last records are not lost.
+pub trait OpportunitySink: Send + Sync {
+    fn record(&self, opportunity: &ArbitrageOpportunity) -> Result<()>;
+
+    /// Record a periodic market snapshot; sinks that only keep
+    /// opportunities ignore it.
+    fn record_snapshot(&self, _snapshot: &MarketSnapshot) -> Result<()> {
+        Ok(())
+    }
+
+    /// Push buffered records to durable storage.
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Flush and release resources; the sink should not be used afterwards.
+    fn close(&self) -> Result<()> {
+        self.flush()
+    }
+}
+
+/// Appends one JSON object per opportunity to a file, through a buffered writer.
+pub struct JsonlSink {
+    writer: Mutex<BufWriter<File>>,
+    /// Schema opportunities are written in
+    schema_version: u32,
+}
+
+impl JsonlSink {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+            schema_version: OPPORTUNITY_SCHEMA_VERSION,
+        })
+    }
+
+    /// Write opportunities in an older schema for consumers pinned to it.
+    pub fn with_schema_version(mut self, version: u32) -> Self {
+        self.schema_version = version;
+        self
+    }
+
+    /// Append any serializable record as one line.
+    pub fn append<T: serde::Serialize>(&self, record: &T) -> Result<()> {
+        let mut writer = self.writer();
+        serde_json::to_writer(&mut *writer, record)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn writer(&self) -> std::sync::MutexGuard<'_, BufWriter<File>> {
+        self.writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl OpportunitySink for JsonlSink {
+    fn record(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.append(&opportunity.to_schema(self.schema_version)?)
+    }
+
+    fn flush(&self) -> Result<()> {
+        let mut writer = self.writer();
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+        Ok(())
+    }
+}
+
+/// Appends one JSON object per market snapshot to a file of its own, so the
+/// opportunity log stays a stream of opportunities only.
+pub struct MarketSnapshotLog {
+    log: JsonlSink,
+}
+
+impl MarketSnapshotLog {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self {
+            log: JsonlSink::open(path)?,
+        })
+    }
+}
+
+impl OpportunitySink for MarketSnapshotLog {
+    fn record(&self, _opportunity: &ArbitrageOpportunity) -> Result<()> {
+        Ok(())
+    }
+
+    fn record_snapshot(&self, snapshot: &MarketSnapshot) -> Result<()> {
+        self.log.append(snapshot)
+    }
+
+    fn flush(&self) -> Result<()> {
+        OpportunitySink::flush(&self.log)
+    }
+}
+
+/// Prints one JSON object per opportunity to stdout (or any writer), for
+/// piping into `jq` or another consumer. Each line is flushed as it is
+/// written so readers see opportunities as they are detected.
+pub struct StdoutJsonlSink {
+    writer: Mutex<Box<dyn Write + Send>>,
+    /// Schema opportunities are written in
+    schema_version: u32,
+}
+
+impl StdoutJsonlSink {
+    pub fn new() -> Self {
+        Self::with_writer(Box::new(std::io::stdout()))
+    }
+
+    pub fn with_writer(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            schema_version: OPPORTUNITY_SCHEMA_VERSION,
+        }
+    }
+
+    /// Write opportunities in an older schema for consumers pinned to it.
+    pub fn with_schema_version(mut self, version: u32) -> Self {
+        self.schema_version = version;
+        self
+    }
+}
+
+impl Default for StdoutJsonlSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpportunitySink for StdoutJsonlSink {
+    fn record(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        let mut line = serde_json::to_vec(&opportunity.to_schema(self.schema_version)?)?;
+        line.push(b'\n');
+        let mut writer = self
+            .writer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        writer.write_all(&line)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Fans every call out to all child sinks.
+///
+/// A failing child does not stop the others; the first error is returned.
+#[derive(Default)]
+pub struct MultiSink {
+    sinks: Vec<Box<dyn OpportunitySink>>,
+}
+
+impl MultiSink {
+    pub fn new(sinks: Vec<Box<dyn OpportunitySink>>) -> Self {
+        Self { sinks }
+    }
+
+    pub fn push(&mut self, sink: Box<dyn OpportunitySink>) {
+        self.sinks.push(sink);
+    }
+
+    fn for_each(&self, f: impl Fn(&dyn OpportunitySink) -> Result<()>) -> Result<()> {
+        let mut first_err = None;
+        for sink in &self.sinks {
+            if let Err(e) = f(sink.as_ref()) {
+                first_err.get_or_insert(e);
+            }
+        }
+        first_err.map_or(Ok(()), Err)
+    }
+}
+
+impl OpportunitySink for MultiSink {
+    fn record(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        self.for_each(|sink| sink.record(opportunity))
+    }
+
+    fn record_snapshot(&self, snapshot: &MarketSnapshot) -> Result<()> {
+        self.for_each(|sink| sink.record_snapshot(snapshot))
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.for_each(|sink| sink.flush())
+    }
+
+    fn close(&self) -> Result<()> {
+        self.for_each(|sink| sink.close())
+    }
+}
+
+const HOUR_MS: u64 = 3_600_000;
+
+/// Caps the records passed to `inner` per clock hour.
+///
+/// Records over the cap are dropped and counted; the count is logged when the
+/// next hour opens and recording resumes.
+pub struct HourlyCapSink {
+    inner: Box<dyn OpportunitySink>,
+    max_per_hour: usize,
+    clock: Arc<dyn Clock>,
+    window: Mutex<HourWindow>,
+}
+
+#[derive(Default)]
+struct HourWindow {
+    hour: u64,
+    recorded: usize,
+    suppressed: usize,
+}
+
+impl HourlyCapSink {
+    pub fn new(
+        inner: Box<dyn OpportunitySink>,
+        max_per_hour: usize,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            inner,
+            max_per_hour,
+            clock,
+            window: Mutex::new(HourWindow::default()),
+        }
+    }
+}
+
+impl OpportunitySink for HourlyCapSink {
+    fn record(&self, opportunity: &ArbitrageOpportunity) -> Result<()> {
+        {
+            let mut window = self
+                .window
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            let hour = self.clock.now_ms() / HOUR_MS;
+            if hour != window.hour {
+                if window.suppressed > 0 {
+                    tracing::warn!(
+                        suppressed = window.suppressed,
+                        cap = self.max_per_hour,
+                        "[SINK] hourly cap reached; records were suppressed"
+                    );
+                }
+                *window = HourWindow {
+                    hour,
+                    ..Default::default()
+                };
+            }
+            if window.recorded >= self.max_per_hour {
+                window.suppressed += 1;
+                return Ok(());
+            }
+            window.recorded += 1;
+        }
+        self.inner.record(opportunity)
+    }
+
+    /// Snapshots are periodic by construction and not counted against the cap.
+    fn record_snapshot(&self, snapshot: &MarketSnapshot) -> Result<()> {
+        self.inner.record_snapshot(snapshot)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.inner.flush()
+    }
+
+    fn close(&self) -> Result<()> {
+        self.inner.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_flushes_every_buffered_record() {
+        let dir = std::env::temp_dir();
+        let paths = [
+            dir.join(format!(
+                "arbitrage-detector-sink-a-{}.jsonl",
+                std::process::id()
+            )),
+            dir.join(format!(
+                "arbitrage-detector-sink-b-{}.jsonl",
+                std::process::id()
+            )),
+        ];
+        for path in &paths {
+            let _ = std::fs::remove_file(path);
+        }
+        let sink = MultiSink::new(
+            paths
+                .iter()
+                .map(|path| Box::new(JsonlSink::open(path).unwrap()) as Box<dyn OpportunitySink>)
+                .collect(),
+        );
+
+        for i in 0..100 {
+            let opp = ArbitrageOpportunity {
+                direction: "A".to_string(),
+                venue: "binance".to_string(),
+                pnl: i as f64,
+                ..Default::default()
+            };
+            sink.record(&opp).unwrap();
+        }
+        sink.close().unwrap();
+
+        for path in &paths {
+            let contents = std::fs::read_to_string(path).unwrap();
+            let pnls: Vec<f64> = contents
+                .lines()
+                .map(|line| {
+                    serde_json::from_str::<ArbitrageOpportunity>(line)
+                        .unwrap()
+                        .pnl
+                })
+                .collect();
+            assert_eq!(pnls, (0..100).map(|i| i as f64).collect::<Vec<_>>());
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    /// Writer appending into a buffer the test keeps a handle on.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn stdout_jsonl_prints_one_opportunity_per_line() {
+        let stdout = SharedBuffer::default();
+        let sink = StdoutJsonlSink::with_writer(Box::new(stdout.clone()));
+        let snapshot = MarketSnapshot {
+            chain_id: 1,
+            taken_at_ms: 0,
+            dex_price: 4_000.0,
+            cex_bid: 4_020.0,
+            cex_ask: 4_030.0,
+            cex_mid: 4_025.0,
+            basis_bps: 62.5,
+            gas_gwei: 0.0,
+            raw_gas_gwei: 0.0,
+            liquidity: 0,
+        };
+        for i in 0..3 {
+            let opp = ArbitrageOpportunity {
+                direction: "B".to_string(),
+                id: format!("run-{}", i),
+                description: "multi\nline".to_string(),
+                pnl: i as f64,
+                ..Default::default()
+            };
+            sink.record(&opp).unwrap();
+            // Snapshots are not opportunities and stay off the stream
+            sink.record_snapshot(&snapshot).unwrap();
+        }
+
+        let output = String::from_utf8(stdout.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        for (i, line) in lines.iter().enumerate() {
+            let opp: ArbitrageOpportunity = serde_json::from_str(line).unwrap();
+            assert_eq!((opp.id, opp.pnl), (format!("run-{}", i), i as f64));
+            assert_eq!(opp.schema_version, OPPORTUNITY_SCHEMA_VERSION);
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingSink(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl OpportunitySink for CountingSink {
+        fn record(&self, _: &ArbitrageOpportunity) -> Result<()> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn hourly_cap_suppresses_then_resumes_next_hour() {
+        use crate::clock::MockClock;
+        use std::sync::atomic::Ordering;
+
+        let counter = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let clock = Arc::new(MockClock::new(10 * HOUR_MS));
+        let sink = HourlyCapSink::new(Box::new(CountingSink(counter.clone())), 3, clock.clone());
+        let opp = ArbitrageOpportunity::default();
+
+        for _ in 0..10 {
+            sink.record(&opp).unwrap();
+            clock.advance(60_000);
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+
+        // Still the same hour: nothing more gets through
+        clock.set(11 * HOUR_MS - 1);
+        sink.record(&opp).unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+
+        // The next hour opens a fresh budget
+        clock.set(11 * HOUR_MS);
+        for _ in 0..5 {
+            sink.record(&opp).unwrap();
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 6);
+    }
+}
@@ -0,0 +1,57 @@
+//! OpenTelemetry export of the evaluation spans (feature `otel`).
+//!
+//! The evaluator always opens an `eval_tick` span per pass, with `cex_read`,
+//! `pool_read` and `swap_math` children; this module ships them to an OTLP
+//! collector alongside the regular log output.
+
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{EnvFilter, Layer, fmt, prelude::*};
+
+/// Service name the spans are reported under.
+const SERVICE_NAME: &str = "arbitrage-detector";
+
+/// Tracer provider exporting over OTLP/HTTP to `endpoint`
+/// (e.g. `http://localhost:4318/v1/traces`), in batches.
+pub fn otlp_tracer_provider(endpoint: &str) -> anyhow::Result<SdkTracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint)
+        .build()?;
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .build())
+}
+
+/// `tracing` layer forwarding spans to `provider`.
+pub fn otel_layer<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME))
+}
+
+/// [`crate::utils::init_logging`] plus span export to `endpoint`.
+///
+/// The returned provider must be shut down on exit to flush pending spans.
+pub fn init_logging_with_otlp(
+    endpoint: &str,
+    to_stderr: bool,
+) -> anyhow::Result<SdkTracerProvider> {
+    let provider = otlp_tracer_provider(endpoint)?;
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            fmt::layer()
+                .with_writer(crate::utils::log_writer(to_stderr))
+                .with_target(false)
+                .without_time(),
+        )
+        .with(otel_layer(&provider))
+        .init();
+    Ok(provider)
+}
@@ -1,47 +1,525 @@
 //! Miscellaneous helper utilities.
 
+use crate::errors::AppError;
+use crate::rate_limit::{RateLimiter, rate_limited_provider};
 use anyhow::Result;
-use ethers::providers::{Http, Middleware, Provider};
+use ethers::providers::Middleware;
 use std::sync::Arc;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::{EnvFilter, fmt};
 
 /// Initialize `tracing` subscriber with env-based filter.
 ///
-/// If `RUST_LOG` is not set, defaults to `info` level.
-pub fn init_logging() {
+/// If `RUST_LOG` is not set, defaults to `info` level. Logs go to stdout, or
+/// to stderr with `to_stderr` when stdout carries data.
+pub fn init_logging(to_stderr: bool) {
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
     fmt::Subscriber::builder()
         .with_env_filter(filter)
+        .with_writer(log_writer(to_stderr))
         .with_target(false)
         .without_time()
         .init();
 }
 
+/// Stream log lines are written to.
+pub fn log_writer(to_stderr: bool) -> BoxMakeWriter {
+    if to_stderr {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    }
+}
+
+/// Current wall-clock time in unix milliseconds.
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// What the gas watcher reports for a block without a base fee (pre-1559
+/// chains, some L2s).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingBaseFee {
+    /// Ask the node for the legacy `eth_gasPrice`
+    #[default]
+    LegacyGasPrice,
+    /// Keep reporting the last known gas price
+    KeepLast,
+    /// Report 0 gwei, i.e. treat gas as free
+    Zero,
+}
+
+impl std::str::FromStr for MissingBaseFee {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, AppError> {
+        match raw.trim().to_lowercase().as_str() {
+            "legacy" => Ok(Self::LegacyGasPrice),
+            "keep" => Ok(Self::KeepLast),
+            "zero" => Ok(Self::Zero),
+            other => Err(AppError::Config(format!(
+                "GAS_MISSING_BASE_FEE must be `legacy`, `keep` or `zero`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// How the gas watcher smooths the per-block gas price before reporting it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GasSmoothing {
+    /// Report each block's price as is
+    #[default]
+    Raw,
+    /// Exponential moving average with the span of the window
+    Ema,
+    /// Median of the last window of blocks, which ignores isolated spikes
+    Median,
+}
+
+impl std::str::FromStr for GasSmoothing {
+    type Err = AppError;
+
+    fn from_str(raw: &str) -> std::result::Result<Self, AppError> {
+        match raw.trim().to_lowercase().as_str() {
+            "raw" | "off" => Ok(Self::Raw),
+            "ema" => Ok(Self::Ema),
+            "median" => Ok(Self::Median),
+            other => Err(AppError::Config(format!(
+                "GAS_SMOOTHING must be `raw`, `ema` or `median`, got `{}`",
+                other
+            ))),
+        }
+    }
+}
+
+/// Gas price smoothed over the last `window` blocks, so a single-block spike
+/// barely moves the figure PnL is computed with while a sustained change
+/// still comes through within about a window.
+#[derive(Debug, Clone)]
+pub struct GasSmoother {
+    kind: GasSmoothing,
+    window: usize,
+    history: std::collections::VecDeque<f64>,
+    raw: Option<f64>,
+    smoothed: Option<f64>,
+}
+
+impl GasSmoother {
+    /// Smooth per `kind` over `window` blocks (at least one).
+    pub fn new(kind: GasSmoothing, window: usize) -> Self {
+        Self {
+            kind,
+            window: window.max(1),
+            history: std::collections::VecDeque::with_capacity(window.max(1)),
+            raw: None,
+            smoothed: None,
+        }
+    }
+
+    /// Record the next block's raw price and return the smoothed one.
+    pub fn push(&mut self, gwei: f64) -> f64 {
+        let smoothed = match self.kind {
+            GasSmoothing::Raw => gwei,
+            GasSmoothing::Ema => {
+                let alpha = 2.0 / (self.window as f64 + 1.0);
+                self.smoothed
+                    .map_or(gwei, |last| last + alpha * (gwei - last))
+            }
+            GasSmoothing::Median => {
+                if self.history.len() == self.window {
+                    self.history.pop_front();
+                }
+                self.history.push_back(gwei);
+                let mut sorted: Vec<f64> = self.history.iter().copied().collect();
+                sorted.sort_by(f64::total_cmp);
+                let mid = sorted.len() / 2;
+                if sorted.len().is_multiple_of(2) {
+                    (sorted[mid - 1] + sorted[mid]) / 2.0
+                } else {
+                    sorted[mid]
+                }
+            }
+        };
+        self.raw = Some(gwei);
+        self.smoothed = Some(smoothed);
+        smoothed
+    }
+
+    /// Latest block's price as reported by the node.
+    pub fn raw(&self) -> Option<f64> {
+        self.raw
+    }
+
+    /// Latest smoothed price.
+    pub fn smoothed(&self) -> Option<f64> {
+        self.smoothed
+    }
+}
+
+fn wei_to_gwei(wei: ethers::types::U256) -> f64 {
+    (wei.as_u128() as f64) / 1_000_000_000.0
+}
+
+/// Gas price in gwei from the latest block's base fee, or per `missing` when
+/// the block has none; `Ok(None)` leaves the last reported price in place and
+/// an error means the RPC could not be read.
+pub async fn fetch_gas_gwei<M: Middleware>(
+    provider: &M,
+    missing: MissingBaseFee,
+) -> std::result::Result<Option<f64>, M::Error> {
+    let block = provider
+        .get_block(ethers::types::BlockNumber::Latest)
+        .await?;
+    match block.and_then(|b| b.base_fee_per_gas) {
+        Some(base_fee) => Ok(Some(wei_to_gwei(base_fee))),
+        None => match missing {
+            MissingBaseFee::LegacyGasPrice => {
+                Ok(Some(wei_to_gwei(provider.get_gas_price().await?)))
+            }
+            MissingBaseFee::KeepLast => Ok(None),
+            MissingBaseFee::Zero => Ok(Some(0.0)),
+        },
+    }
+}
+
+/// Where the gas watcher publishes each price, in gwei: the smoothed one PnL
+/// is computed with, and the latest block's raw one beside it.
+#[derive(Debug, Clone)]
+pub struct GasSenders {
+    pub smoothed: tokio::sync::watch::Sender<f64>,
+    pub raw: tokio::sync::watch::Sender<f64>,
+}
+
+impl GasSenders {
+    fn send(&self, raw_gwei: f64, smoothed_gwei: f64) {
+        let _ = self.raw.send(raw_gwei);
+        let _ = self.smoothed.send(smoothed_gwei);
+    }
+}
+
+/// Consecutive failed polls after which the gas watcher reports its static
+/// fallback price, when it has one.
+const GAS_FAILURES_BEFORE_FALLBACK: u32 = 3;
+
 /// Spawns a background task that periodically fetches EIP-1559 base fee and
-/// updates a provided `tokio::sync::watch::Sender<f64>` with an average gas
-/// price estimate in gwei. Caller decides the interval.
+/// updates the provided [`GasSenders`] with the gas price in gwei, handling
+/// blocks without a base fee per `missing`, smoothed per `smoother` and raw.
+/// Caller decides the interval; requests draw from the shared `limiter`.
 pub async fn spawn_gas_price_watcher(
     rpc_url: &str,
-    tx: tokio::sync::watch::Sender<f64>,
+    limiter: Arc<RateLimiter>,
+    tx: GasSenders,
     interval_secs: u64,
+    missing: MissingBaseFee,
+    smoother: GasSmoother,
 ) -> Result<tokio::task::JoinHandle<()>> {
-    let provider = Arc::new(Provider::<Http>::try_from(rpc_url)?);
-    let handle = tokio::spawn(async move {
+    let provider = rate_limited_provider(rpc_url, limiter)?;
+    Ok(spawn_gas_poller(
+        provider,
+        tx,
+        interval_secs,
+        missing,
+        smoother,
+        None,
+        0,
+    ))
+}
+
+/// Poll loop of the gas watcher. After [`GAS_FAILURES_BEFORE_FALLBACK`]
+/// consecutive failures, `fallback_gwei` is reported until a poll succeeds
+/// again; `failures` is the count it starts from.
+fn spawn_gas_poller<M: Middleware + 'static>(
+    provider: M,
+    tx: GasSenders,
+    interval_secs: u64,
+    missing: MissingBaseFee,
+    mut smoother: GasSmoother,
+    fallback_gwei: Option<f64>,
+    mut failures: u32,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
         let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
         loop {
             ticker.tick().await;
-            let mut gwei = 0.0f64;
-            if let Ok(block) = provider.get_block(ethers::types::BlockNumber::Latest).await {
-                if let Some(b) = block {
-                    if let Some(base_fee) = b.base_fee_per_gas {
-                        // Convert wei to gwei
-                        let wei: u128 = base_fee.as_u128();
-                        gwei = (wei as f64) / 1_000_000_000.0;
+            match fetch_gas_gwei(&provider, missing).await {
+                Ok(Some(gwei)) => {
+                    if failures >= GAS_FAILURES_BEFORE_FALLBACK && fallback_gwei.is_some() {
+                        tracing::info!(
+                            "[GAS] RPC recovered, leaving the static fallback gas price"
+                        );
+                    }
+                    failures = 0;
+                    let smoothed = smoother.push(gwei);
+                    tracing::debug!(raw_gwei = gwei, smoothed_gwei = smoothed, "[GAS] price");
+                    tx.send(gwei, smoothed);
+                }
+                Ok(None) => failures = 0,
+                Err(e) => {
+                    failures += 1;
+                    tracing::warn!(error = %e, failures, "[GAS] failed to fetch the gas price");
+                    if failures == GAS_FAILURES_BEFORE_FALLBACK
+                        && let Some(gwei) = fallback_gwei
+                    {
+                        tracing::warn!(
+                            gwei,
+                            "[GAS] RPC keeps failing, using the static fallback gas price"
+                        );
+                        tx.send(gwei, gwei);
                     }
                 }
             }
-            let _ = tx.send(gwei);
         }
-    });
-    Ok(handle)
+    })
+}
+
+/// [`spawn_gas_price_watcher`], degrading to a static `fallback_gwei` when one
+/// is configured and the gas RPC is unusable: when the watcher cannot be built
+/// or its first read fails at startup, and whenever polls keep failing later.
+/// Returns `None` when the watcher could not be built and the fallback stays
+/// in place for good.
+pub async fn spawn_gas_price_watcher_or_fallback(
+    rpc_url: &str,
+    limiter: Arc<RateLimiter>,
+    tx: GasSenders,
+    interval_secs: u64,
+    missing: MissingBaseFee,
+    smoother: GasSmoother,
+    fallback_gwei: Option<f64>,
+) -> Result<Option<tokio::task::JoinHandle<()>>> {
+    let use_fallback = |e: anyhow::Error| match fallback_gwei {
+        Some(gwei) => {
+            tracing::warn!(error = %e, gwei, "[GAS] watcher unavailable, using the static fallback gas price");
+            tx.send(gwei, gwei);
+            Ok(())
+        }
+        None => Err(e),
+    };
+    let provider = match rate_limited_provider(rpc_url, limiter) {
+        Ok(provider) => provider,
+        Err(e) => return use_fallback(e.into()).map(|()| None),
+    };
+    // Probe the RPC so a dead endpoint is caught now rather than at the first poll
+    let failures = match fetch_gas_gwei(&provider, missing).await {
+        Ok(_) => 0,
+        Err(e) => {
+            use_fallback(e.into())?;
+            GAS_FAILURES_BEFORE_FALLBACK
+        }
+    };
+    Ok(Some(spawn_gas_poller(
+        provider,
+        tx.clone(),
+        interval_secs,
+        missing,
+        smoother,
+        fallback_gwei,
+        failures,
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gas senders and the receiver of their smoothed price.
+    fn gas_channels() -> (GasSenders, tokio::sync::watch::Receiver<f64>) {
+        let (smoothed, rx) = tokio::sync::watch::channel(0.0);
+        let (raw, _) = tokio::sync::watch::channel(0.0);
+        (GasSenders { smoothed, raw }, rx)
+    }
+
+    #[tokio::test]
+    async fn gas_watcher_failure_falls_back_to_static_price() {
+        let limiter = Arc::new(RateLimiter::new(0.0));
+        let (tx, rx) = gas_channels();
+
+        let handle = spawn_gas_price_watcher_or_fallback(
+            "not a url",
+            limiter.clone(),
+            tx.clone(),
+            10,
+            MissingBaseFee::default(),
+            GasSmoother::new(GasSmoothing::Raw, 1),
+            Some(25.0),
+        )
+        .await
+        .expect("starts on the fallback");
+        assert!(handle.is_none());
+        assert_eq!(*rx.borrow(), 25.0);
+
+        assert!(
+            spawn_gas_price_watcher_or_fallback(
+                "not a url",
+                limiter,
+                tx,
+                10,
+                MissingBaseFee::default(),
+                GasSmoother::new(GasSmoothing::Raw, 1),
+                None
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn unreachable_gas_rpc_falls_back_at_startup() {
+        let limiter = Arc::new(RateLimiter::unlimited());
+        let (tx, rx) = gas_channels();
+        // Nothing listens on port 1
+        let unreachable = "http://127.0.0.1:1";
+
+        let handle = spawn_gas_price_watcher_or_fallback(
+            unreachable,
+            limiter.clone(),
+            tx.clone(),
+            10,
+            MissingBaseFee::default(),
+            GasSmoother::new(GasSmoothing::Raw, 1),
+            Some(25.0),
+        )
+        .await
+        .expect("starts on the fallback");
+        assert_eq!(*rx.borrow(), 25.0);
+        // Still polling, to pick the RPC back up once it answers
+        handle.expect("watcher keeps polling").abort();
+
+        assert!(
+            spawn_gas_price_watcher_or_fallback(
+                unreachable,
+                limiter,
+                tx,
+                10,
+                MissingBaseFee::default(),
+                GasSmoother::new(GasSmoothing::Raw, 1),
+                None
+            )
+            .await
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn gas_watcher_publishes_raw_and_smoothed_prices() {
+        use ethers::providers::Provider;
+        use ethers::types::{Block, H256, U256};
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(Block::<H256> {
+            base_fee_per_gas: Some(U256::from(100_000_000_000u64)),
+            ..Default::default()
+        })
+        .unwrap();
+        // Two quiet blocks already seen, so the median ignores the spike
+        let mut smoother = GasSmoother::new(GasSmoothing::Median, 3);
+        smoother.push(10.0);
+        smoother.push(10.0);
+        let (smoothed, mut smoothed_rx) = tokio::sync::watch::channel(0.0);
+        let (raw, raw_rx) = tokio::sync::watch::channel(0.0);
+
+        let handle = spawn_gas_poller(
+            provider,
+            GasSenders { smoothed, raw },
+            10,
+            MissingBaseFee::default(),
+            smoother,
+            None,
+            0,
+        );
+        smoothed_rx.changed().await.unwrap();
+        handle.abort();
+        assert_eq!(*smoothed_rx.borrow(), 10.0);
+        assert_eq!(*raw_rx.borrow(), 100.0);
+    }
+
+    #[tokio::test]
+    async fn block_without_base_fee_uses_legacy_gas_price() {
+        use ethers::providers::Provider;
+        use ethers::types::{Block, H256, U256};
+
+        let (provider, mock) = Provider::mocked();
+        let pre_1559: Block<H256> = Block::default();
+        let london = Block::<H256> {
+            base_fee_per_gas: Some(U256::from(12_000_000_000u64)),
+            ..Default::default()
+        };
+        // Responses are served last pushed first
+        mock.push(pre_1559.clone()).unwrap();
+        mock.push(pre_1559.clone()).unwrap();
+        mock.push(U256::from(31_500_000_000u64)).unwrap();
+        mock.push(pre_1559).unwrap();
+        mock.push(london).unwrap();
+
+        let gwei = |missing| fetch_gas_gwei(&provider, missing);
+        assert_eq!(
+            gwei(MissingBaseFee::LegacyGasPrice).await.unwrap(),
+            Some(12.0)
+        );
+        assert_eq!(
+            gwei(MissingBaseFee::LegacyGasPrice).await.unwrap(),
+            Some(31.5)
+        );
+        mock.assert_request("eth_getBlockByNumber", ("latest", false))
+            .unwrap();
+        mock.assert_request("eth_getBlockByNumber", ("latest", false))
+            .unwrap();
+        mock.assert_request("eth_gasPrice", ()).unwrap();
+
+        assert_eq!(gwei(MissingBaseFee::KeepLast).await.unwrap(), None);
+        assert_eq!(gwei(MissingBaseFee::Zero).await.unwrap(), Some(0.0));
+        assert_eq!(
+            "keep".parse::<MissingBaseFee>().unwrap(),
+            MissingBaseFee::KeepLast
+        );
+        assert!("eip1559".parse::<MissingBaseFee>().is_err());
+    }
+
+    #[test]
+    fn smoothed_gas_is_less_volatile_and_converges_to_the_mean() {
+        // 30 gwei with a 100 gwei spike every seventh block
+        let series: Vec<f64> = (0..700)
+            .map(|block| if block % 7 == 0 { 100.0 } else { 30.0 })
+            .collect();
+        let mean = series.iter().sum::<f64>() / series.len() as f64;
+        let stdev = |values: &[f64]| {
+            let m = values.iter().sum::<f64>() / values.len() as f64;
+            (values.iter().map(|v| (v - m).powi(2)).sum::<f64>() / values.len() as f64).sqrt()
+        };
+
+        let mut ema = GasSmoother::new(GasSmoothing::Ema, 20);
+        let smoothed: Vec<f64> = series.iter().map(|gwei| ema.push(*gwei)).collect();
+        let (raw_tail, smoothed_tail) = (&series[100..], &smoothed[100..]);
+        assert!(stdev(smoothed_tail) < stdev(raw_tail) / 4.0);
+        let smoothed_mean = smoothed_tail.iter().sum::<f64>() / smoothed_tail.len() as f64;
+        assert!(
+            (smoothed_mean - mean).abs() < mean * 0.02,
+            "{smoothed_mean} vs {mean}"
+        );
+        assert_eq!(ema.raw(), series.last().copied());
+
+        // The median drops the isolated spikes altogether
+        let mut median = GasSmoother::new(GasSmoothing::Median, 5);
+        let filtered: Vec<f64> = series.iter().map(|gwei| median.push(*gwei)).collect();
+        assert!(filtered[5..].iter().all(|gwei| *gwei == 30.0));
+
+        // A sustained change still comes through within a few windows
+        for _ in 0..60 {
+            ema.push(60.0);
+            median.push(60.0);
+        }
+        assert!((ema.smoothed().unwrap() - 60.0).abs() < 1.0);
+        assert_eq!(median.smoothed(), Some(60.0));
+
+        let mut raw = GasSmoother::new(GasSmoothing::default(), 20);
+        assert_eq!(raw.push(100.0), 100.0);
+        assert_eq!(
+            "median".parse::<GasSmoothing>().unwrap(),
+            GasSmoothing::Median
+        );
+        assert!("sma".parse::<GasSmoothing>().is_err());
+    }
 }